@@ -0,0 +1,29 @@
+package com.itsvks.code.input
+
+import android.view.View
+import androidx.test.ext.junit.runners.AndroidJUnit4
+import androidx.test.platform.app.InstrumentationRegistry
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import org.junit.Assert.assertEquals
+import org.junit.Test
+import org.junit.runner.RunWith
+
+@RunWith(AndroidJUnit4::class)
+class EditorInputConnectionTest {
+    private fun connection(state: CodeEditorState): EditorInputConnection {
+        val context = InstrumentationRegistry.getInstrumentation().targetContext
+        return EditorInputConnection(View(context), state) { false }
+    }
+
+    @Test
+    fun testTextAroundCursorForAnyLength() {
+        val state = CodeEditorState("hello world")
+        state.setCursor(CursorPosition(0, 5))
+        val connection = connection(state)
+
+        assertEquals(" world", connection.getTextAfterCursor(Int.MAX_VALUE, 0).toString())
+        assertEquals("hello", connection.getTextBeforeCursor(Int.MAX_VALUE, 0).toString())
+        assertEquals(" w", connection.getTextAfterCursor(2, 0).toString())
+    }
+}
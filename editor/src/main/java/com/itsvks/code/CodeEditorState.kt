@@ -7,28 +7,57 @@ import androidx.compose.runtime.getValue
 import androidx.compose.runtime.mutableStateOf
 import androidx.compose.runtime.remember
 import androidx.compose.runtime.setValue
+import com.itsvks.code.completion.EditorCompletion
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.Direction
+import com.itsvks.code.core.DocumentListener
 import com.itsvks.code.core.FoldableRange
-import com.itsvks.code.core.findBraceFoldableRanges
-// import com.itsvks.code.core.emptyStringBuilder // Not used anymore
-// import com.itsvks.code.core.getLine // Still used via CodeEditorState.getLine()
-// import com.itsvks.code.core.insertLineAt // Still used via CodeEditorState.insertLine()
-// import com.itsvks.code.core.lineCount // Still used via CodeEditorState.lineCount
-// import com.itsvks.code.core.removeLineAt // Still used via CodeEditorState.removeLine()
-// import com.itsvks.code.core.updateLine // Still used via CodeEditorState.updateLine()
+import com.itsvks.code.core.Rope
+import com.itsvks.code.core.TextChange
+import com.itsvks.code.core.TextEdit
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.core.bracketPairs
+import com.itsvks.code.core.isWordChar
+import com.itsvks.code.diagnostics.EditorDiagnostics
+import com.itsvks.code.folding.FoldState
+import com.itsvks.code.folding.FoldingContext
+import com.itsvks.code.folding.SavedFold
+import com.itsvks.code.history.UndoManager
+import com.itsvks.code.hover.EditorHover
 import com.itsvks.code.language.Language
 import com.itsvks.code.language.LanguageRegistry
 import com.itsvks.code.language.PlainTextLanguage
+import com.itsvks.code.marker.EditorMarkers
+import com.itsvks.code.marker.Marker
+import com.itsvks.code.marker.MarkerGravity
+import com.itsvks.code.navigation.EditorNavigation
+import com.itsvks.code.parser.DocumentSyntax
+import com.itsvks.code.parser.OutlineItem
+import com.itsvks.code.parser.SyntaxTree
+import com.itsvks.code.search.EditorSearch
+import com.itsvks.code.signature.EditorSignatureHelp
+import com.itsvks.code.snippet.EditorSnippets
+import com.itsvks.code.syntax.DocumentHighlighter
+import com.itsvks.code.syntax.DocumentSemanticTokens
+import com.itsvks.code.syntax.LexerState
+import com.itsvks.code.syntax.SyntaxHighlighter
+import com.itsvks.code.syntax.SyntaxHighlighterFactory
 import com.itsvks.code.theme.AtomOneDarkTheme
 import com.itsvks.code.theme.EditorTheme
 import com.itsvks.code.theme.VsCodeDarkTheme
+import com.itsvks.code.theme.findBracketPairIndices
 import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.flow.MutableStateFlow
 import kotlinx.coroutines.flow.StateFlow
 import kotlinx.coroutines.flow.asStateFlow
 import kotlinx.coroutines.flow.update
+import kotlinx.coroutines.withContext
 import java.io.File
 import java.io.IOException
 import java.io.InputStream
+import java.util.concurrent.CopyOnWriteArrayList
+import kotlin.math.abs
 
 @Composable
 fun rememberCodeEditorState(
@@ -38,7 +67,7 @@ fun rememberCodeEditorState(
 ): CodeEditorState {
     val state = remember {
         CodeEditorState(
-            initialLines = emptyList(),
+            initialText = "",
             initialLanguage = initialLanguage,
             initialTheme = initialTheme
         )
@@ -65,7 +94,7 @@ fun rememberCodeEditorState(
 ): CodeEditorState {
     val state = remember {
         CodeEditorState(
-            initialLines = emptyList(),
+            initialText = "",
             initialLanguage = initialLanguage,
             initialTheme = initialTheme
         )
@@ -91,125 +120,1204 @@ fun rememberCodeEditorState(
     theme: EditorTheme = VsCodeDarkTheme
 ): CodeEditorState {
     return remember(text, language, theme) {
-        CodeEditorState(text.lines(), language, theme)
+        CodeEditorState(text, language, theme)
     }
 }
 
 class CodeEditorState(
-    initialLines: List<String> = emptyList(),
+    initialText: String = "",
     initialLanguage: Language = PlainTextLanguage,
     initialTheme: EditorTheme = AtomOneDarkTheme
 ) {
-    private var _lines: List<String> = initialLines
-        set(value) {
-            field = value
-            _foldableRanges.value = findBraceFoldableRanges(field)
-            // Decide on _foldedLines reconciliation strategy for direct _lines updates if any outside setText/setFile etc.
-        }
+    constructor(
+        initialLines: List<String>,
+        initialLanguage: Language = PlainTextLanguage,
+        initialTheme: EditorTheme = AtomOneDarkTheme
+    ) : this(initialLines.joinToString("\n"), initialLanguage, initialTheme)
+
+    private var rope: Rope = Rope.fromString(initialText.normalizeLineEndings())
+    private var version = 0L
 
-    private val _content = MutableStateFlow<List<String>>(initialLines)
-    val content: StateFlow<List<String>> = _content.asStateFlow()
+    private val _content = MutableStateFlow(TextSnapshot(rope, version))
+    val content: StateFlow<TextSnapshot> = _content.asStateFlow()
 
-    private val _foldableRanges = MutableStateFlow<List<FoldableRange>>(emptyList())
+    /**
+     * Syntax tree of the document for the current [language], if it has a parser. Parsed in the
+     * background and again only where edits change it.
+     */
+    val syntax = DocumentSyntax(initialLanguage.parser)
+
+    // Found by lexing alone at first, so creating a state never parses on the calling thread; the syntax
+    // tree refines them in refreshFoldableRanges
+    private val _foldableRanges = MutableStateFlow(foldableRangesOf(_content.value, initialLanguage, tree = null))
     val foldableRanges: StateFlow<List<FoldableRange>> = _foldableRanges.asStateFlow()
 
     private val _foldedLines = MutableStateFlow<Set<Int>>(emptySet())
+
+    /**
+     * Start lines of the folded ranges. Folds move with the text as it's edited, so they survive
+     * changes above, inside and below them.
+     */
     val foldedLines: StateFlow<Set<Int>> = _foldedLines.asStateFlow()
 
+    // A marker at the end of each folded range's first line, and the line it was on before the last edit
+    private var foldMarkers = emptyMap<Marker, Int>()
+
     private val _isLoading = MutableStateFlow(false)
     val isLoading: StateFlow<Boolean> = _isLoading.asStateFlow()
 
-    var language by mutableStateOf(initialLanguage)
+    private val _selections = MutableStateFlow(listOf(CursorRange.Zero))
+
+    /**
+     * Every caret and selection, sorted by position and never overlapping. There is always at least one.
+     */
+    val selections: StateFlow<List<CursorRange>> = _selections.asStateFlow()
+
+    private val _selection = MutableStateFlow(CursorRange.Zero)
+
+    /**
+     * The primary selection in document coordinates; a collapsed range is a plain caret.
+     * Together with [selections] this is the single source of truth for where the carets are.
+     */
+    val selection: StateFlow<CursorRange> = _selection.asStateFlow()
+
+    val cursor: CursorPosition get() = _selection.value.end
+
+    val hasMultipleSelections: Boolean get() = _selections.value.size > 1
+
+    val selectedText: String get() = snapshot.getText(_selection.value)
+
+    private val _composition = MutableStateFlow<CursorRange?>(null)
+
+    /**
+     * Text the input method is still composing at the primary caret, or null. It moves with edits
+     * like the selections do and is dropped once an edit swallows it.
+     */
+    val composition: StateFlow<CursorRange?> = _composition.asStateFlow()
+
+    // Columns kept per caret while moving up and down through shorter lines
+    private var preferredColumns: IntArray? = null
+
+    private val documentListeners = CopyOnWriteArrayList<DocumentListener>()
+
+    // Selections before and after each expandSelection, so shrinkSelection can step back
+    private var selectionExpansions = ArrayList<Pair<List<CursorRange>, List<CursorRange>>>()
+
+    private var languageState by mutableStateOf(initialLanguage)
+
+    /**
+     * Language of the document. Changing it highlights and parses the document again from the start.
+     */
+    var language: Language
+        get() = languageState
+        set(value) {
+            languageState = value
+            highlighting.reset(SyntaxHighlighterFactory.createHighlighter(value), snapshot)
+            syntax.reset(value.parser)
+            semanticTokens.clear()
+        }
+
     var theme by mutableStateOf(initialTheme)
 
-    val lineCount get() = _lines.size
+    /**
+     * Tokens of every line for the current [language], lexed in the background and again only where edits change them.
+     */
+    val highlighting = DocumentHighlighter(SyntaxHighlighterFactory.createHighlighter(initialLanguage), snapshot)
 
-    init {
-        // Initial calculation for foldable ranges when state is created with initialLines
-        _foldableRanges.value = findBraceFoldableRanges(_lines)
-    }
+    /**
+     * Semantic tokens pushed by the host's language backend, drawn over the lexical [highlighting].
+     */
+    val semanticTokens = DocumentSemanticTokens().also { it.reset(version, snapshot.lineCount) }
+
+    val undoManager = UndoManager()
+    val canUndo: StateFlow<Boolean> get() = undoManager.canUndo
+    val canRedo: StateFlow<Boolean> get() = undoManager.canRedo
+
+    /**
+     * Find and replace over the whole document.
+     */
+    val search = EditorSearch(this)
+
+    /**
+     * Code completion at the caret, opened by typing.
+     */
+    val completion = EditorCompletion(this)
+
+    /**
+     * Errors and warnings reported about the document by the host.
+     */
+    val diagnostics = EditorDiagnostics(this)
+
+    /**
+     * Snippets inserted into the document and the tab stop being filled in.
+     */
+    val snippets = EditorSnippets(this)
+
+    /**
+     * Hover cards about the symbol under a long press or the mouse.
+     */
+    val hover = EditorHover(this)
+
+    /**
+     * Signatures of the call the caret is in, opened by typing `(` or `,`.
+     */
+    val signatureHelp = EditorSignatureHelp(this)
+
+    /**
+     * Go to definition, find references and the back/forward history of jumps.
+     */
+    val navigation = EditorNavigation(this)
+
+    /**
+     * Positions and ranges that move with the text as it's edited.
+     */
+    val markers = EditorMarkers(this)
+
+    // Markers the editor keeps for itself, e.g. for folds, apart from the host's so it never sees or clears them
+    internal val internalMarkers = EditorMarkers(this)
+
+    /**
+     * Path of the file being edited, if any. Set by [setFile]; snippet variables like `TM_FILENAME` use it.
+     */
+    var filePath: String? = null
+
+    /**
+     * Current immutable view of the document.
+     */
+    val snapshot: TextSnapshot get() = _content.value
+
+    val lineCount get() = rope.lineCount + 1
 
     fun setText(text: String) {
         _isLoading.update { false }
-        _lines = text.lines()
-        // _foldableRanges.value is updated by _lines setter
-        _foldedLines.value = emptySet()
-        _content.update { _lines }
+        val newRope = Rope.fromString(text.normalizeLineEndings())
+        syntax.reset(language.parser)
+        resetContent(newRope, foldableRangesOf(TextSnapshot(newRope, version + 1), language, tree = null))
     }
 
     suspend fun setFile(file: File) {
+        filePath = file.path
+        load { Rope.fromFile(file) }
+    }
+
+    suspend fun setInputStream(inputStream: InputStream) = load { Rope.fromInputStream(inputStream) }
+
+    private suspend fun load(read: () -> Rope) {
         _isLoading.update { true }
         // Temporary state while loading
-        val loadingLines = listOf("Loading...")
-        _lines = loadingLines
-        // _foldableRanges.value is updated by _lines setter for loadingLines
-        _foldedLines.value = emptySet()
-        _content.update { _lines } // Show "Loading..."
+        syntax.reset(language.parser)
+        resetContent(Rope.fromString("Loading..."), emptyList())
 
         try {
-            val newLines = file.useLines { it.toList() }
-            _lines = newLines
-            // _foldableRanges.value is updated by _lines setter for newLines
-            _foldedLines.value = emptySet() // Reset folds for new file
-            _content.update { _lines }
+            val newRope = withContext(Dispatchers.IO) { read().normalizeLineEndings() }
+            val ranges = withContext(Dispatchers.Default) {
+                val loaded = TextSnapshot(newRope, version + 1)
+                foldableRangesOf(loaded, language, syntax.treeFor(loaded))
+            }
+            resetContent(newRope, ranges)
         } finally {
             _isLoading.update { false }
         }
     }
 
-    suspend fun setInputStream(inputStream: InputStream) {
-        _isLoading.update { true }
-        // Temporary state while loading
-        val loadingLines = listOf("Loading...")
-        _lines = loadingLines
-        // _foldableRanges.value is updated by _lines setter for loadingLines
-        _foldedLines.value = emptySet()
-        _content.update { _lines } // Show "Loading..."
+    private fun resetContent(newRope: Rope, ranges: List<FoldableRange>) {
+        rope = newRope
+        undoManager.clear()
+        _foldableRanges.value = ranges
+        setFoldedLines(emptySet()) // Reset folds for new content
+        publish()
+        markers.onReset()
+        internalMarkers.onReset()
+        highlighting.reset(highlighting.highlighter, snapshot)
+        semanticTokens.reset(version, snapshot.lineCount)
+        documentListeners.forEach { it.onReset(snapshot) }
+        _selections.value = listOf(CursorRange.Zero)
+        _composition.value = null
+        _selection.value = CursorRange.Zero
+        preferredColumns = null
+    }
+
+    // Folds found by the language's folding provider, with the syntax tree if it has a parser and the
+    // line states the highlighter already knows
+    private fun foldableRangesOf(
+        snapshot: TextSnapshot,
+        language: Language,
+        tree: SyntaxTree?,
+        highlighter: SyntaxHighlighter? = null,
+        lineStates: List<LexerState?> = emptyList()
+    ): List<FoldableRange> {
+        val context = FoldingContext(snapshot, language, tree, highlighter, lineStates)
+        return language.foldingProvider.provideFoldingRanges(context)
+    }
+
+    private fun publish() {
+        version++
+        _content.value = TextSnapshot(rope, version)
+    }
+
+    fun addDocumentListener(listener: DocumentListener) {
+        documentListeners.add(listener)
+    }
+
+    fun removeDocumentListener(listener: DocumentListener) {
+        documentListeners.remove(listener)
+    }
+
+    fun getText(): String = rope.toString()
+
+    fun getText(range: CursorRange): String = snapshot.getText(range)
+
+    fun getLine(lineIdx: Int): String = snapshot.getLine(lineIdx)
+
+    fun insert(position: CursorPosition, text: String) {
+        val offset = snapshot.offsetOf(position)
+        replace(offset, offset, text)
+    }
+
+    fun delete(range: CursorRange) = replace(range, "")
+
+    fun replace(range: CursorRange, text: String) {
+        val normalized = range.normalize()
+        val current = snapshot
+        replace(current.offsetOf(normalized.start), current.offsetOf(normalized.end), text)
+    }
+
+    /**
+     * Replaces the chars in `[start, end)` with [text]. This is the single entry point every edit goes
+     * through; its cost depends on the size of the edit, not on the size of the document.
+     */
+    fun replace(start: Int, end: Int, text: String) {
+        require(start in 0..end && end <= rope.length) { "Invalid range [$start, $end) for length ${rope.length}" }
+        val newText = text.normalizeLineEndings()
+        if (start == end && newText.isEmpty()) return
+
+        applyChange(TextChange(start, rope.slice(start, end).toString(), newText))
+    }
+
+    private fun applyChange(change: TextChange) {
+        val before = snapshot
+        val oldSelections = _selections.value
+        val offsets = oldSelections.map { before.offsetOf(it.start) to before.offsetOf(it.end) }
+        val primaryCaret = before.offsetOf(_selection.value.end)
+        val composing = _composition.value?.let { before.offsetOf(it.startInclusive) to before.offsetOf(it.endInclusive) }
+        val start = change.start
+        val end = change.oldEnd
 
+        val startLine = rope.lineOfCharIndex(start)
+        val endLine = rope.lineOfCharIndex(end)
+        // A line whose whole content follows the edit moves with it
+        val shiftFrom = if (end == rope.lineStartIndex(endLine)) endLine else endLine + 1
+        val lineDelta = change.newText.count { it == '\n' } - (endLine - startLine)
+
+        rope = rope.replace(start, end, change.newText)
+        publish()
+        markers.onChange(change)
+        internalMarkers.onChange(change)
+        shiftFolds(startLine, shiftFrom, lineDelta)
+        syntax.onChange(version, change, startLine, endLine - startLine + 1, endLine - startLine + 1 + lineDelta)
+        highlighting.onLinesReplaced(snapshot, startLine, endLine - startLine + 1, endLine - startLine + 1 + lineDelta)
+        semanticTokens.onLinesReplaced(version, startLine, endLine - startLine + 1, endLine - startLine + 1 + lineDelta)
+        documentListeners.forEach { it.onChange(change, snapshot) }
+
+        val after = snapshot
+        val mapped = offsets.map { (anchor, caret) ->
+            CursorRange(after.positionOf(change.mapOffset(anchor)), after.positionOf(change.mapOffset(caret)))
+        }
+        updateSelections(mapped, CursorRange.collapsed(after.positionOf(change.mapOffset(primaryCaret))), breakMerge = false)
+        _composition.value = composing?.let { (from, to) ->
+            val mappedFrom = if (from >= change.oldEnd) change.mapOffset(from) else minOf(from, change.start)
+            val mappedTo = if (to <= change.start) to else change.mapOffset(to)
+            if (mappedFrom < mappedTo) CursorRange(after.positionOf(mappedFrom), after.positionOf(mappedTo)) else null
+        }
+        preferredColumns = null
+        undoManager.record(change, oldSelections, _selections.value)
+    }
+
+    /**
+     * Groups every edit until the matching [endTransaction] into a single undo step.
+     * Transactions can be nested; only the outermost one creates the step.
+     */
+    fun beginTransaction() = undoManager.beginTransaction(_selections.value)
+
+    fun endTransaction() = undoManager.endTransaction(_selections.value)
+
+    inline fun <T> transaction(block: CodeEditorState.() -> T): T {
+        beginTransaction()
         try {
-            val newLines = inputStream.bufferedReader(Charsets.UTF_8).useLines { it.toList() }
-            _lines = newLines
-            // _foldableRanges.value is updated by _lines setter for newLines
-            _foldedLines.value = emptySet() // Reset folds for new stream
-            _content.update { _lines }
+            return block()
         } finally {
-            _isLoading.update { false }
+            endTransaction()
         }
     }
 
-    private fun updateLinesAndFolds(newLines: List<String>) {
-        _lines = newLines
-        // _foldableRanges.value is updated by _lines setter
-        val newFoldedLines = _foldedLines.value.filter { foldedStartLine ->
-            _foldableRanges.value.any { it.startLine == foldedStartLine }
-        }.toSet()
-        _foldedLines.value = newFoldedLines
-        _content.update { _lines }
+    /**
+     * Applies [edits], which all refer to the current text and don't overlap, as one undo step.
+     * Returns where the new text of each edit ended up, in the order given.
+     */
+    fun applyEdits(edits: List<TextEdit>): List<CursorRange> {
+        val current = snapshot
+        val offsets = edits.map { current.offsetOf(it.range.startInclusive) to current.offsetOf(it.range.endInclusive) }
+        val texts = edits.map { it.newText.normalizeLineEndings() }
+        val order = edits.indices.sortedBy { offsets[it].first }
+
+        // Back to front, so the offsets of the remaining edits stay valid
+        transaction {
+            for (i in order.asReversed()) replace(offsets[i].first, offsets[i].second, texts[i])
+        }
+
+        val after = snapshot
+        val ranges = arrayOfNulls<CursorRange>(edits.size)
+        var shift = 0
+        for (i in order) {
+            val (start, end) = offsets[i]
+            val newStart = start + shift
+            ranges[i] = CursorRange(after.positionOf(newStart), after.positionOf(newStart + texts[i].length))
+            shift += texts[i].length - (end - start)
+        }
+        return ranges.requireNoNulls().asList()
+    }
+
+    /**
+     * Reverts the last undo step and restores the selections that were active before it.
+     * Returns the restored primary selection, or null if there was nothing to undo.
+     */
+    fun undo(): CursorRange? {
+        val entry = undoManager.undo(::applyChange) ?: return null
+        return restoreSelections(entry.selectionsBefore)
+    }
+
+    fun redo(): CursorRange? {
+        val entry = undoManager.redo(::applyChange) ?: return null
+        return restoreSelections(entry.selectionsAfter)
+    }
+
+    private fun restoreSelections(ranges: List<CursorRange>): CursorRange {
+        updateSelections(ranges, ranges.last(), breakMerge = false)
+        preferredColumns = null
+        return _selection.value
+    }
+
+    /**
+     * Replaces all carets with a single [range].
+     */
+    fun setSelection(range: CursorRange) = setSelections(listOf(range))
+
+    fun setSelection(start: CursorPosition, end: CursorPosition) = setSelection(CursorRange(start, end))
+
+    fun setCursor(position: CursorPosition) = setSelection(CursorRange.collapsed(position))
+
+    /**
+     * Replaces all carets with [ranges]; overlapping ranges are merged. The range at [primaryIndex]
+     * becomes the primary [selection].
+     */
+    fun setSelections(ranges: List<CursorRange>, primaryIndex: Int = ranges.lastIndex) {
+        require(ranges.isNotEmpty()) { "There must be at least one selection" }
+        updateSelections(ranges, ranges[primaryIndex])
+        preferredColumns = null
+    }
+
+    /**
+     * Moves the primary caret to [position] while keeping its anchor where it is.
+     */
+    fun extendSelection(position: CursorPosition) = setSelection(CursorRange(_selection.value.start, position))
+
+    fun selectAll() = setSelection(CursorPosition.Zero, snapshot.lastPosition)
+
+    /**
+     * Collapses every selection to its caret.
+     */
+    fun clearSelection() {
+        updateSelections(_selections.value.map { CursorRange.collapsed(it.end) }, CursorRange.collapsed(cursor))
+    }
+
+    /**
+     * Drops every caret except the primary one.
+     */
+    fun clearSecondarySelections() = setSelection(_selection.value)
+
+    fun addCursor(position: CursorPosition) {
+        val caret = CursorRange.collapsed(position)
+        updateSelections(_selections.value + caret, caret)
+        preferredColumns = null
+    }
+
+    fun addCursorAbove() = addCursorVertically(-1)
+
+    fun addCursorBelow() = addCursorVertically(1)
+
+    private fun addCursorVertically(lineDelta: Int) {
+        val current = snapshot
+        val selections = _selections.value
+        val edge = if (lineDelta < 0) selections.first() else selections.last()
+        val line = edge.end.line + lineDelta
+        if (line !in 0 until current.lineCount) return
+
+        addCursor(CursorPosition(line, minOf(edge.end.column, current.getLineLength(line))))
+    }
+
+    /**
+     * Selects the word at the primary caret, or if the primary selection is not empty, adds a selection
+     * at the next occurrence of its text, wrapping around at the end of the document.
+     */
+    fun addNextOccurrence() {
+        val current = snapshot
+        val selections = _selections.value
+        val primary = _selection.value
+
+        if (primary.isCollapsed) {
+            val word = current.wordRangeAt(primary.end) ?: return
+            updateSelections(selections - primary + word, word)
+            return
+        }
+
+        val needle = current.getText(primary)
+        var from = current.offsetOf(primary.endInclusive)
+        repeat(selections.size + 1) {
+            var index = current.rope.indexOf(needle, from)
+            if (index < 0) index = current.rope.indexOf(needle, 0)
+            if (index < 0) return
+
+            val occurrence = CursorRange(current.positionOf(index), current.positionOf(index + needle.length))
+            if (selections.none { it.startInclusive == occurrence.start && it.endInclusive == occurrence.end }) {
+                updateSelections(selections + occurrence, occurrence)
+                return
+            }
+            from = index + needle.length
+        }
+    }
+
+    /**
+     * Grows every selection to the next larger syntactic unit around it: the word, the contents of the
+     * enclosing brackets, the brackets themselves, and so on up to the whole document.
+     */
+    fun expandSelection() {
+        val current = snapshot
+        val selections = _selections.value
+        val tree = syntax.treeFor(current)
+        val expanded = selections.map { selection ->
+            val start = current.offsetOf(selection.startInclusive)
+            val end = current.offsetOf(selection.endInclusive)
+            val word = current.wordRangeAt(selection.end)?.takeIf { selection.isCollapsed }
+            val (from, to) = when {
+                word != null -> current.offsetOf(word.start) to current.offsetOf(word.end)
+                tree != null -> tree.expandSelection(start, end) ?: (0 to current.length)
+                else -> 0 to current.length
+            }
+            CursorRange(current.positionOf(from), current.positionOf(to))
+        }
+        if (expanded == selections) return
+
+        val history = selectionExpansions.takeIf { it.lastOrNull()?.second == selections } ?: ArrayList()
+        setSelections(expanded, selections.indexOf(_selection.value).coerceAtLeast(0))
+        history.add(selections to _selections.value)
+        selectionExpansions = history
+    }
+
+    /**
+     * Undoes the last [expandSelection], as long as the selections haven't changed since.
+     */
+    fun shrinkSelection() {
+        val history = selectionExpansions
+        val (previous, expanded) = history.lastOrNull() ?: return
+        if (expanded != _selections.value) {
+            selectionExpansions = ArrayList()
+            return
+        }
+        history.removeAt(history.lastIndex)
+        val primary = _selection.value
+        val primaryIndex = previous.indexOfFirst { it.startInclusive >= primary.startInclusive && it.endInclusive <= primary.endInclusive }
+        setSelections(previous, primaryIndex.coerceAtLeast(0))
+        selectionExpansions = history
+    }
+
+    /**
+     * Turns every selection spanning several lines into one selection per line.
+     */
+    fun splitSelectionIntoLines() {
+        val current = snapshot
+        val result = mutableListOf<CursorRange>()
+        for (selection in _selections.value) {
+            val start = selection.startInclusive
+            val end = selection.endInclusive
+            if (start.line == end.line) {
+                result.add(selection)
+                continue
+            }
+            for (line in start.line..end.line) {
+                val from = if (line == start.line) start.column else 0
+                val to = if (line == end.line) end.column else current.getLineLength(line)
+                result.add(CursorRange(CursorPosition(line, from), CursorPosition(line, to)))
+            }
+        }
+        updateSelections(result, result.last())
+        preferredColumns = null
+    }
+
+    /**
+     * Moves every caret one char or one line in [direction]. With [extend] the selection anchors stay put,
+     * otherwise a non-empty selection collapses towards [direction].
+     */
+    fun moveCursor(direction: Direction, extend: Boolean = false) {
+        when (direction) {
+            Direction.UP -> moveVertically(-1, extend)
+            Direction.DOWN -> moveVertically(1, extend)
+            else -> moveCarets(extend, keepColumn = false) { selection, _, current ->
+                when {
+                    !extend && !selection.isCollapsed -> {
+                        if (direction == Direction.LEFT) selection.startInclusive else selection.endInclusive
+                    }
+
+                    direction == Direction.LEFT -> current.positionOf(current.offsetOf(selection.end) - 1)
+                    else -> current.positionOf(current.offsetOf(selection.end) + 1)
+                }
+            }
+        }
+    }
+
+    /**
+     * Moves every caret to the start of the previous word ([Direction.LEFT]) or the end of the next one ([Direction.RIGHT]).
+     */
+    fun moveWord(direction: Direction, extend: Boolean = false) {
+        require(direction == Direction.LEFT || direction == Direction.RIGHT) { "Words are only crossed horizontally" }
+        moveCarets(extend, keepColumn = false) { selection, _, current ->
+            val offset = current.offsetOf(selection.end)
+            current.positionOf(current.wordBoundary(offset, forward = direction == Direction.RIGHT))
+        }
+    }
+
+    /**
+     * Moves every caret a page of [lines] lines up or down, keeping its column.
+     */
+    fun movePage(direction: Direction, lines: Int, extend: Boolean = false) {
+        require(direction == Direction.UP || direction == Direction.DOWN) { "Pages are only turned vertically" }
+        val delta = lines.coerceAtLeast(1)
+        moveVertically(if (direction == Direction.UP) -delta else delta, extend)
+    }
+
+    /**
+     * Moves every caret to the start of its line. With [smart] the first press stops at the first
+     * non-blank char and the next one toggles between that and column 0.
+     */
+    fun moveToLineStart(extend: Boolean = false, smart: Boolean = true) {
+        moveCarets(extend, keepColumn = false) { selection, _, current ->
+            val caret = selection.end
+            val indent = current.getLine(caret.line).indexOfFirst { !it.isWhitespace() }
+            val column = if (smart && indent > 0 && caret.column != indent) indent else 0
+            CursorPosition(caret.line, column)
+        }
+    }
+
+    fun moveToLineEnd(extend: Boolean = false) {
+        moveCarets(extend, keepColumn = false) { selection, _, current ->
+            CursorPosition(selection.end.line, current.getLineLength(selection.end.line))
+        }
+    }
+
+    fun moveToDocumentStart(extend: Boolean = false) {
+        moveCarets(extend, keepColumn = false) { _, _, _ -> CursorPosition.Zero }
+    }
+
+    fun moveToDocumentEnd(extend: Boolean = false) {
+        moveCarets(extend, keepColumn = false) { _, _, current -> current.lastPosition }
+    }
+
+    private fun moveVertically(lineDelta: Int, extend: Boolean) {
+        moveCarets(extend, keepColumn = true) { selection, column, current ->
+            val line = selection.end.line + lineDelta
+            when {
+                line < 0 -> CursorPosition.Zero
+                line >= current.lineCount -> current.lastPosition
+                else -> CursorPosition(line, minOf(column, current.getLineLength(line)))
+            }
+        }
+    }
+
+    /**
+     * Moves every caret to the position returned by [target], which receives the selection, the caret's
+     * preferred column and the current snapshot. With [keepColumn] the preferred columns survive the move.
+     */
+    private fun moveCarets(
+        extend: Boolean,
+        keepColumn: Boolean,
+        target: (CursorRange, Int, TextSnapshot) -> CursorPosition
+    ) {
+        val current = snapshot
+        val selections = _selections.value
+        val primaryIndex = selections.indexOf(_selection.value).coerceAtLeast(0)
+        val columns = preferredColumns?.takeIf { it.size == selections.size }
+            ?: IntArray(selections.size) { selections[it].end.column }
+
+        val moved = selections.mapIndexed { i, selection ->
+            val position = target(selection, columns[i], current)
+            if (extend) CursorRange(selection.start, position) else CursorRange.collapsed(position)
+        }
+        updateSelections(moved, moved[primaryIndex])
+        preferredColumns = if (keepColumn) columns else null
+    }
+
+    // Clamps, sorts and merges the selections. A caret moved by the user ends the current typing group.
+    private fun updateSelections(ranges: List<CursorRange>, primary: CursorRange, breakMerge: Boolean = true) {
+        val current = snapshot
+        val clampedPrimary = current.clamp(primary.end)
+        val merged = mergeSelections(ranges.map { CursorRange(current.clamp(it.start), current.clamp(it.end)) })
+        val primaryIndex = merged.indexOfFirst { clampedPrimary in it }.coerceAtLeast(0)
+
+        if (breakMerge && merged != _selections.value) undoManager.breakMerge()
+        _selections.value = merged
+        _selection.value = merged[primaryIndex]
+    }
+
+    private fun mergeSelections(ranges: List<CursorRange>): List<CursorRange> {
+        val result = ArrayList<CursorRange>(ranges.size)
+        for (range in ranges.sortedBy { it.startInclusive }) {
+            val last = result.lastOrNull()
+            val overlaps = last != null && (range.startInclusive < last.endInclusive ||
+                    range.startInclusive == last.endInclusive && (range.isCollapsed || last.isCollapsed))
+
+            if (last != null && overlaps) {
+                val start = last.startInclusive
+                val end = maxOf(last.endInclusive, range.endInclusive)
+                result[result.lastIndex] = if (last.start <= last.end) CursorRange(start, end) else CursorRange(end, start)
+            } else {
+                result.add(range)
+            }
+        }
+        return result
+    }
+
+    /**
+     * An edit planned for one selection: `[start, end)` is replaced by [text], after which the selection
+     * spans [anchor] to [caret], both relative to [start].
+     */
+    private class SelectionEdit(
+        val start: Int,
+        val end: Int,
+        val text: String,
+        val anchor: Int = text.length,
+        val caret: Int = anchor
+    )
+
+    /**
+     * Applies an edit at every selection as one undo step. [edit] receives the index of the selection,
+     * the selection and the snapshot all edits are planned against; returning null leaves that selection alone.
+     * Returns where the edit of the primary selection starts once the edits before it are applied, or null
+     * if it wasn't edited.
+     */
+    private fun editSelections(
+        mergeable: Boolean,
+        composing: Boolean = false,
+        edit: (Int, CursorRange, TextSnapshot) -> SelectionEdit?
+    ): Int? {
+        val current = snapshot
+        val selections = _selections.value
+        val primaryIndex = selections.indexOf(_selection.value).coerceAtLeast(0)
+
+        val planned = mutableListOf<SelectionEdit>()
+        val finalOffsets = ArrayList<Pair<Int, Int>>(selections.size)
+        var delta = 0
+        var lastEnd = -1
+        var primaryStart: Int? = null
+
+        selections.forEachIndexed { i, selection ->
+            val selectionEdit = edit(i, selection, current)?.takeIf { it.start >= lastEnd }
+            if (selectionEdit == null) {
+                finalOffsets.add(current.offsetOf(selection.start) + delta to current.offsetOf(selection.end) + delta)
+                return@forEachIndexed
+            }
+
+            val finalStart = selectionEdit.start + delta
+            if (i == primaryIndex) primaryStart = finalStart
+            finalOffsets.add(finalStart + selectionEdit.anchor to finalStart + selectionEdit.caret)
+            if (selectionEdit.start != selectionEdit.end || selectionEdit.text.isNotEmpty()) {
+                planned.add(selectionEdit)
+                delta += selectionEdit.text.length - (selectionEdit.end - selectionEdit.start)
+                lastEnd = selectionEdit.end
+            }
+        }
+
+        if (planned.isEmpty()) {
+            moveToOffsets(finalOffsets, primaryIndex)
+            return primaryStart
+        }
+
+        undoManager.beginTransaction(selections)
+        try {
+            // Back to front, so the offsets of the remaining edits stay valid
+            for (selectionEdit in planned.asReversed()) {
+                val oldText = rope.slice(selectionEdit.start, selectionEdit.end).toString()
+                applyChange(TextChange(selectionEdit.start, oldText, selectionEdit.text))
+            }
+            moveToOffsets(finalOffsets, primaryIndex)
+        } finally {
+            undoManager.endTransaction(_selections.value, mergeable, composing)
+        }
+        return primaryStart
+    }
+
+    private fun moveToOffsets(offsets: List<Pair<Int, Int>>, primaryIndex: Int) {
+        val current = snapshot
+        val ranges = offsets.map { (anchor, caret) -> CursorRange(current.positionOf(anchor), current.positionOf(caret)) }
+        updateSelections(ranges, ranges[primaryIndex], breakMerge = false)
+        preferredColumns = null
+    }
+
+    /**
+     * Replaces every selection with [text] and leaves the carets after it, like pasting a word.
+     */
+    fun insertText(text: String) {
+        val newText = text.normalizeLineEndings()
+        editSelections(mergeable = true) { _, selection, current ->
+            SelectionEdit(current.offsetOf(selection.startInclusive), current.offsetOf(selection.endInclusive), newText)
+        }
+    }
+
+    /**
+     * Types [text] at every caret. A single bracket or quote is auto-closed (or wraps the selection),
+     * and typing a closing bracket right before the same one just steps over it. A single char may
+     * also open or close [completion].
+     */
+    fun typeText(text: String) {
+        val typed = text.singleOrNull() ?: return insertText(text)
+        val closing = bracketPairs[typed]
+
+        editSelections(mergeable = true) { _, selection, current ->
+            val start = current.offsetOf(selection.startInclusive)
+            val end = current.offsetOf(selection.endInclusive)
+            val next = if (end < current.length) current.charAt(end) else null
+            val previous = if (start > 0) current.charAt(start - 1) else null
+            val isQuote = typed == '"' || typed == '\''
+
+            when {
+                start == end && next == typed && typed in bracketPairs.values -> SelectionEdit(start, start, "", 1)
+
+                closing != null && start != end && !isQuote -> {
+                    val selected = current.getText(start, end)
+                    SelectionEdit(start, end, "$typed$selected$closing", 1, 1 + selected.length)
+                }
+
+                closing != null && start == end &&
+                        (next == null || next.isWhitespace() || next in bracketPairs.values) &&
+                        !(isQuote && previous?.isWordChar() == true) -> {
+                    SelectionEdit(start, end, "$typed$closing", 1)
+                }
+
+                else -> SelectionEdit(start, end, text)
+            }
+        }
+        completion.onTyped(typed)
+        signatureHelp.onTyped(typed)
+    }
+
+    /**
+     * Breaks the line at every caret, keeping the caret line's indentation and indenting one level
+     * deeper after an opening bracket. Between a bracket pair the closing half gets its own line.
+     */
+    fun newLine() {
+        editSelections(mergeable = false) { _, selection, current ->
+            val position = selection.startInclusive
+            val start = current.offsetOf(position)
+            val end = current.offsetOf(selection.endInclusive)
+            val before = current.getLine(position.line).substring(0, position.column)
+            val indent = before.takeWhile { it == ' ' || it == '\t' }
+            val opening = before.trimEnd().lastOrNull()?.takeIf { it == '{' || it == '[' || it == '(' }
+            val next = if (end < current.length) current.charAt(end) else null
+
+            when {
+                opening == null -> SelectionEdit(start, end, "\n$indent")
+                next == bracketPairs[opening] -> {
+                    SelectionEdit(start, end, "\n$indent$INDENT\n$indent", 1 + indent.length + INDENT.length)
+                }
+
+                else -> SelectionEdit(start, end, "\n$indent$INDENT")
+            }
+        }
+    }
+
+    /**
+     * Commits [text] from an input method, replacing the composing text if there is any. Android's
+     * [newCursorPosition] is relative to the end of the text when positive, to its start otherwise.
+     */
+    fun commitText(text: String, newCursorPosition: Int = 1) {
+        val composing = _composition.value
+        if (composing == null && newCursorPosition == 1) {
+            if (text == "\n") newLine() else typeText(text)
+            return
+        }
+
+        replaceComposition(text, newCursorPosition)
+        _composition.value = null
+        undoManager.breakMerge()
+    }
+
+    /**
+     * Replaces the composing text, or the selections when nothing is being composed, with [text] and marks
+     * it as composing. Every caret gets the text; the whole composition is a single undo step.
+     */
+    fun setComposingText(text: String, newCursorPosition: Int = 1) {
+        val start = replaceComposition(text, newCursorPosition)
+        val current = snapshot
+        val newText = text.normalizeLineEndings()
+        _composition.value = if (newText.isEmpty()) null else {
+            CursorRange(current.positionOf(start), current.positionOf(start + newText.length))
+        }
+    }
+
+    /**
+     * Marks the existing text in `[start, end)` as composing, e.g. when the input method re-opens a word.
+     */
+    fun setComposingRegion(start: Int, end: Int) {
+        val current = snapshot
+        val from = minOf(start, end).coerceIn(0, current.length)
+        val to = maxOf(start, end).coerceIn(0, current.length)
+        _composition.value = if (from == to) null else CursorRange(current.positionOf(from), current.positionOf(to))
+    }
+
+    /**
+     * Keeps the composing text as it is and stops composing.
+     */
+    fun finishComposingText() {
+        _composition.value = null
+        undoManager.breakMerge()
+    }
+
+    /**
+     * Deletes [beforeLength] chars before and [afterLength] chars after every selection, keeping the
+     * selected text itself, as input methods do for backspace.
+     */
+    fun deleteSurroundingText(beforeLength: Int, afterLength: Int) {
+        editSelections(mergeable = true) { _, selection, current ->
+            val start = current.offsetOf(selection.startInclusive)
+            val end = current.offsetOf(selection.endInclusive)
+            val from = (start - beforeLength).coerceAtLeast(0)
+            val to = (end + afterLength).coerceAtMost(current.length)
+            if (from == start && to == end) return@editSelections null
+
+            val selected = current.getText(start, end)
+            SelectionEdit(from, to, selected, anchor = 0, caret = selected.length)
+        }
+    }
+
+    // Replaces the composing text, or else the selection, at every caret and places the carets like Android's
+    // newCursorPosition. The other carets replace as much text around them as the composing text covers around
+    // the primary one. Returns the start offset of the new text at the primary caret.
+    private fun replaceComposition(text: String, newCursorPosition: Int): Int {
+        val composing = _composition.value
+        val primary = _selection.value
+        val newText = text.normalizeLineEndings()
+        val caret = if (newCursorPosition > 0) newText.length + newCursorPosition - 1 else newCursorPosition
+
+        // Composing text around the primary caret: how far it reaches before and after it
+        val reach = composing?.let {
+            val current = snapshot
+            val primaryCaret = current.offsetOf(primary.end)
+            val before = primaryCaret - current.offsetOf(it.startInclusive)
+            val after = current.offsetOf(it.endInclusive) - primaryCaret
+            if (before >= 0 && after >= 0) before to after else null
+        }
+
+        val primaryStart = editSelections(mergeable = true, composing = true) { _, selection, current ->
+            val (start, end) = when {
+                composing == null -> current.offsetOf(selection.startInclusive) to current.offsetOf(selection.endInclusive)
+                selection == primary -> current.offsetOf(composing.startInclusive) to current.offsetOf(composing.endInclusive)
+                // A composing region away from the caret belongs to the primary caret alone
+                reach == null -> return@editSelections null
+                else -> {
+                    val offset = current.offsetOf(selection.end)
+                    (offset - reach.first).coerceAtLeast(0) to (offset + reach.second).coerceAtMost(current.length)
+                }
+            }
+            SelectionEdit(start, end, newText, caret)
+        }
+        // The caret may have been clamped to the document, the start of the edit never is
+        return primaryStart ?: snapshot.offsetOf(_selection.value.start)
+    }
+
+    /**
+     * Text of the selections for the clipboard, one selection per line, or null if nothing is selected.
+     * [paste] splits it up again when there are as many carets.
+     */
+    fun copySelection(): String? {
+        val current = snapshot
+        val selections = _selections.value
+        if (selections.all { it.isCollapsed }) return null
+        return selections.joinToString("\n") { current.getText(it) }
+    }
+
+    /**
+     * Deletes the selections as one undo step and returns their text like [copySelection].
+     */
+    fun cutSelection(): String? {
+        val text = copySelection() ?: return null
+        editSelections(mergeable = false) { _, selection, current ->
+            SelectionEdit(current.offsetOf(selection.startInclusive), current.offsetOf(selection.endInclusive), "")
+        }
+        return text
+    }
+
+    /**
+     * Pastes [text]. When it has exactly one line per caret, each caret gets its own line.
+     */
+    fun paste(text: String) {
+        val newText = text.normalizeLineEndings()
+        val lines = newText.split('\n')
+        val linePerCaret = hasMultipleSelections && lines.size == _selections.value.size
+
+        // A paste is an undo step of its own, never merged with typing
+        editSelections(mergeable = false) { i, selection, current ->
+            SelectionEdit(current.offsetOf(selection.startInclusive), current.offsetOf(selection.endInclusive), if (linePerCaret) lines[i] else newText)
+        }
+    }
+
+    /**
+     * Deletes every selection, or the char before each caret. Deleting the opening half of an empty
+     * bracket pair removes both halves.
+     */
+    fun deleteBackward() {
+        editSelections(mergeable = true) { _, selection, current ->
+            val start = current.offsetOf(selection.startInclusive)
+            val end = current.offsetOf(selection.endInclusive)
+            when {
+                start != end -> SelectionEdit(start, end, "")
+                start == 0 -> null
+                else -> {
+                    val previous = current.charAt(start - 1)
+                    val next = if (start < current.length) current.charAt(start) else null
+                    val from = if (start >= 2 && previous.isLowSurrogate() && current.charAt(start - 2).isHighSurrogate()) {
+                        start - 2
+                    } else start - 1
+                    val to = if (next != null && bracketPairs[previous] == next) start + 1 else start
+                    SelectionEdit(from, to, "")
+                }
+            }
+        }
+    }
+
+    /**
+     * Deletes every selection, or the char after each caret.
+     */
+    fun deleteForward() {
+        editSelections(mergeable = true) { _, selection, current ->
+            val start = current.offsetOf(selection.startInclusive)
+            val end = current.offsetOf(selection.endInclusive)
+            when {
+                start != end -> SelectionEdit(start, end, "")
+                start == current.length -> null
+                else -> {
+                    val to = if (start + 1 < current.length && current.charAt(start).isHighSurrogate() &&
+                        current.charAt(start + 1).isLowSurrogate()
+                    ) start + 2 else start + 1
+                    SelectionEdit(start, to, "")
+                }
+            }
+        }
+    }
+
+    /**
+     * Deletes every selection, or the word before each caret.
+     */
+    fun deleteWordBackward() = deleteWord(forward = false)
+
+    /**
+     * Deletes every selection, or the word after each caret.
+     */
+    fun deleteWordForward() = deleteWord(forward = true)
+
+    private fun deleteWord(forward: Boolean) {
+        editSelections(mergeable = true) { _, selection, current ->
+            val start = current.offsetOf(selection.startInclusive)
+            val end = current.offsetOf(selection.endInclusive)
+            when {
+                start != end -> SelectionEdit(start, end, "")
+                forward -> SelectionEdit(start, current.wordBoundary(start, forward = true), "")
+                else -> SelectionEdit(current.wordBoundary(start, forward = false), start, "")
+            }
+        }
+    }
+
+    /**
+     * Copies the lines touched by each selection right above them, leaving the selections on the lower copy.
+     */
+    fun duplicateLines() {
+        val blocks = selectedLineBlocks()
+        transaction {
+            for (block in blocks.asReversed()) {
+                val start = rope.lineStartIndex(block.first)
+                val end = rope.lineStartIndex(block.last) + snapshot.getLineLength(block.last)
+                replace(start, start, rope.slice(start, end).toString() + "\n")
+            }
+        }
+    }
+
+    /**
+     * Deletes the lines touched by each selection.
+     */
+    fun deleteLines() {
+        val blocks = selectedLineBlocks()
+        transaction {
+            for (block in blocks.asReversed()) {
+                val start = rope.lineStartIndex(block.first)
+                when {
+                    block.last < lineCount - 1 -> replace(start, rope.lineStartIndex(block.last + 1), "")
+                    block.first > 0 -> replace(start - 1, rope.length, "")
+                    else -> replace(0, rope.length, "")
+                }
+            }
+        }
+    }
+
+    /**
+     * Indents the lines touched by each selection by one level. Empty lines stay empty.
+     */
+    fun indentLines() {
+        val blocks = selectedLineBlocks()
+        transaction {
+            for (line in blocks.flatten().asReversed()) {
+                if (snapshot.getLineLength(line) > 0) insert(CursorPosition(line, 0), INDENT)
+            }
+        }
+    }
+
+    /**
+     * Swaps the lines touched by each selection with the line above ([Direction.UP]) or below
+     * ([Direction.DOWN]). Nothing moves if any of them is already at the edge of the document.
+     */
+    fun moveSelectedLines(direction: Direction) {
+        require(direction == Direction.UP || direction == Direction.DOWN) { "Lines only move vertically" }
+        val up = direction == Direction.UP
+        val blocks = selectedLineBlocks()
+        if (up && blocks.first().first == 0 || !up && blocks.last().last == lineCount - 1) return
+
+        val delta = if (up) -1 else 1
+        fun CursorPosition.moved() = CursorPosition(line + delta, column)
+        val moved = _selections.value.map { CursorRange(it.start.moved(), it.end.moved()) }
+        val primary = _selection.value.let { CursorRange(it.start.moved(), it.end.moved()) }
+
+        transaction {
+            for (block in if (up) blocks else blocks.asReversed()) {
+                // Move the neighbouring line to the other side of the block
+                if (up) {
+                    val text = getLine(block.first - 1)
+                    removeLine(block.first - 1)
+                    insertLine(block.last, text)
+                } else {
+                    val text = getLine(block.last + 1)
+                    removeLine(block.last + 1)
+                    insertLine(block.first, text)
+                }
+            }
+            updateSelections(moved, primary)
+        }
+    }
+
+    // Lines covered by the selections as sorted, merged blocks. A selection ending at column 0 does not cover that line.
+    private fun selectedLineBlocks(): List<IntRange> {
+        val blocks = mutableListOf<IntRange>()
+        for (selection in _selections.value) {
+            val first = selection.startInclusive.line
+            val end = selection.endInclusive
+            val last = if (end.column == 0 && end.line > first) end.line - 1 else end.line
+
+            val previous = blocks.lastOrNull()
+            if (previous != null && first <= previous.last + 1) {
+                blocks[blocks.lastIndex] = previous.first..maxOf(previous.last, last)
+            } else {
+                blocks.add(first..last)
+            }
+        }
+        return blocks
     }
 
     fun removeLine(lineIdx: Int) {
-        if (lineIdx in _lines.indices) {
-            val mutableLines = _lines.toMutableList()
-            mutableLines.removeAt(lineIdx)
-            updateLinesAndFolds(mutableLines.toList())
+        if (lineIdx !in 0 until lineCount) return
+
+        when {
+            lineCount == 1 -> replace(0, rope.length, "")
+            lineIdx < lineCount - 1 -> replace(rope.lineStartIndex(lineIdx), rope.lineEndIndex(lineIdx), "")
+            else -> replace(rope.lineStartIndex(lineIdx) - 1, rope.length, "")
         }
     }
 
     fun updateLine(lineIdx: Int, text: String) {
-        if (lineIdx in _lines.indices) {
-            val mutableLines = _lines.toMutableList()
-            mutableLines[lineIdx] = text
-            updateLinesAndFolds(mutableLines.toList())
+        if (lineIdx in 0 until lineCount) {
+            // Only replace what actually changed so typing is recorded as small, mergeable edits
+            val oldText = snapshot.getLine(lineIdx)
+            val prefix = oldText.commonPrefixWith(text).length
+            val suffix = oldText.commonSuffixWith(text).length.coerceAtMost(minOf(oldText.length, text.length) - prefix)
+            val start = rope.lineStartIndex(lineIdx)
+            replace(start + prefix, start + oldText.length - suffix, text.substring(prefix, text.length - suffix))
         }
     }
 
     fun insertLine(lineIdx: Int, text: String) {
-        val mutableLines = _lines.toMutableList()
-        mutableLines.add(if (lineIdx in 0.._lines.size) lineIdx else _lines.size, text)
-        updateLinesAndFolds(mutableLines.toList())
+        if (lineIdx in 0 until lineCount) {
+            val start = rope.lineStartIndex(lineIdx)
+            replace(start, start, text + "\n")
+        } else {
+            replace(rope.length, rope.length, "\n" + text)
+        }
+    }
+
+    /**
+     * Recomputes the foldable ranges for the current snapshot off the main thread. Edits only shift
+     * the existing ranges, so the editor calls this once typing settles.
+     */
+    suspend fun refreshFoldableRanges() {
+        val current = snapshot
+        val language = language
+        val tree = syntax.parse(current)
+        // Taken together, so the states are those of this highlighter
+        val highlighter = highlighting.highlighter
+        val lineStates = highlighting.statesFor(current)
+        val ranges = withContext(Dispatchers.Default) { foldableRangesOf(current, language, tree, highlighter, lineStates) }
+        if (snapshot.version != current.version) return
+
+        _foldableRanges.value = ranges
+        setFoldedLines(_foldedLines.value.filter { foldedStartLine -> ranges.any { it.startLine == foldedStartLine } }.toSet())
+    }
+
+    /**
+     * Positions of the bracket at [position], or else of the one just before it, and of its partner.
+     * Empty if there's no such pair. Brackets in strings and comments only count without a syntax tree.
+     */
+    fun findMatchingBrackets(position: CursorPosition): List<CursorPosition> {
+        val current = snapshot
+        val tree = syntax.tree.value?.takeIf { it.version == current.version }
+        if (tree != null) {
+            val (open, close) = tree.bracketPairAt(current.offsetOf(position)) ?: return emptyList()
+            return listOf(current.positionOf(open), current.positionOf(close))
+        }
+        val line = current.getLineOrNull(position.line) ?: return emptyList()
+        return findBracketPairIndices(line, position.column).sorted().map { CursorPosition(position.line, it) }
     }
 
-    fun getLine(lineIdx: Int): String = _lines[lineIdx]
+    /**
+     * Classes, functions and other declarations of the document, as far as its language's parser knows them.
+     */
+    suspend fun outline(): List<OutlineItem> = syntax.parse(snapshot)?.outline().orEmpty()
+
+    // Moves the folds with their markers, and shifts ranges below an edit until the next refresh instead
+    // of rescanning the whole file. Lines in [startLine + 1, shiftFrom) were replaced by the edit; lines
+    // from shiftFrom on move by lineDelta, and folded ranges go where their folds went.
+    private fun shiftFolds(startLine: Int, shiftFrom: Int, lineDelta: Int) {
+        val moved = HashMap<Int, Int>()
+        foldMarkers = foldMarkers.filterKeys { it.isValid }.mapValues { (marker, line) -> marker.start.line.also { moved[line] = it } }
+        _foldedLines.value = foldMarkers.values.toSet()
+        if (lineDelta == 0 && moved.all { (from, to) -> from == to }) return
+
+        fun shiftLine(line: Int): Int? = when {
+            line >= shiftFrom -> line + lineDelta
+            line <= startLine -> line
+            else -> null
+        }
+
+        _foldableRanges.update { ranges ->
+            ranges.mapNotNull { range ->
+                val start = moved[range.startLine] ?: shiftLine(range.startLine) ?: return@mapNotNull null
+                val end = shiftLine(range.endLine) ?: return@mapNotNull null
+                if (end > start) range.copy(startLine = start, endLine = end) else null
+            }
+        }
+    }
 
     fun toggleFold(startLineToToggle: Int) {
         val currentFolded = _foldedLines.value.toMutableSet()
@@ -218,7 +1326,7 @@ class CodeEditorState(
         if (!rangeExists) {
             if (currentFolded.contains(startLineToToggle)) {
                 currentFolded.remove(startLineToToggle)
-                _foldedLines.value = currentFolded
+                setFoldedLines(currentFolded)
             }
             return
         }
@@ -228,6 +1336,94 @@ class CodeEditorState(
         } else {
             currentFolded.add(startLineToToggle)
         }
-        _foldedLines.value = currentFolded
+        setFoldedLines(currentFolded)
+    }
+
+    fun foldAll() {
+        setFoldedLines(_foldableRanges.value.mapTo(HashSet()) { it.startLine })
+    }
+
+    fun unfoldAll() {
+        setFoldedLines(emptySet())
+    }
+
+    /**
+     * Folds the ranges nested [level] deep, 1 being the outermost, except those around the caret so
+     * it stays visible. Other folds are kept.
+     */
+    fun foldLevel(level: Int) {
+        val caretLine = cursor.line
+        val open = ArrayDeque<FoldableRange>()
+        val starts = HashSet<Int>()
+        for (range in _foldableRanges.value.sortedWith(compareBy<FoldableRange>({ it.startLine }, { -it.endLine }))) {
+            // Ranges that ended before this one starts don't contain it
+            while (open.isNotEmpty() && open.last().endLine <= range.startLine) open.removeLast()
+            open.addLast(range)
+            if (open.size == level && caretLine !in range.startLine..range.endLine) starts += range.startLine
+        }
+        setFoldedLines(_foldedLines.value + starts)
+    }
+
+    /**
+     * Folds the range starting on [line], or else the innermost one around it, and all ranges inside it.
+     */
+    fun foldRecursively(line: Int = cursor.line) {
+        val starts = rangesWithin(foldableRangeAt(line) ?: return).map { it.startLine }
+        setFoldedLines(_foldedLines.value + starts)
+    }
+
+    fun unfoldRecursively(line: Int = cursor.line) {
+        val starts = rangesWithin(foldableRangeAt(line) ?: return).map { it.startLine }.toSet()
+        setFoldedLines(_foldedLines.value - starts)
+    }
+
+    /**
+     * The folded regions, to [restoreFolds] them when the document is opened again.
+     */
+    fun saveFolds(): FoldState {
+        val current = snapshot
+        return FoldState(_foldedLines.value.sorted().map { SavedFold(it, current.getLine(it).trim()) })
+    }
+
+    /**
+     * Folds the regions of [state] again, once the document is loaded, finding the foldable ranges first.
+     * A region is found by the text of its first line, at the line nearest to where it was; regions that
+     * can't be found stay unfolded.
+     */
+    suspend fun restoreFolds(state: FoldState) {
+        refreshFoldableRanges()
+        val current = snapshot
+        val starts = _foldableRanges.value.map { it.startLine }
+        val folded = state.folds.mapNotNull { fold ->
+            starts.filter { current.getLine(it).trim() == fold.text }.minByOrNull { abs(it - fold.line) }
+        }
+        setFoldedLines(_foldedLines.value + folded)
+    }
+
+    private fun setFoldedLines(lines: Set<Int>) {
+        foldMarkers.keys.forEach(Marker::remove)
+        val current = snapshot
+        // Enter at the end of the first line leaves the fold where it is
+        foldMarkers = lines.filter { it in 0 until current.lineCount }.associateBy(
+            { line -> internalMarkers.create(CursorPosition(line, current.getLineLength(line)), MarkerGravity.LEFT) },
+            { it }
+        )
+        _foldedLines.value = lines
+    }
+
+    private fun foldableRangeAt(line: Int): FoldableRange? {
+        val ranges = _foldableRanges.value
+        return ranges.firstOrNull { it.startLine == line }
+            ?: ranges.filter { line in it.startLine..it.endLine }.minByOrNull { it.endLine - it.startLine }
+    }
+
+    private fun rangesWithin(range: FoldableRange): List<FoldableRange> {
+        return _foldableRanges.value.filter { it.startLine >= range.startLine && it.endLine <= range.endLine }
     }
 }
+
+private const val INDENT = "    "
+
+private fun String.normalizeLineEndings() = if ('\r' in this) replace("\r\n", "\n").replace('\r', '\n') else this
+
+private fun Rope.normalizeLineEndings() = if ('\r' in this) Rope.fromString(toString().normalizeLineEndings()) else this
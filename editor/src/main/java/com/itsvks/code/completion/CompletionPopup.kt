@@ -1,50 +1,173 @@
 package com.itsvks.code.completion
 
 import androidx.compose.foundation.background
+import androidx.compose.foundation.border
 import androidx.compose.foundation.clickable
-import androidx.compose.foundation.layout.Box
-import androidx.compose.foundation.layout.Column
-import androidx.compose.foundation.layout.offset
+import androidx.compose.foundation.layout.Row
+import androidx.compose.foundation.layout.Spacer
+import androidx.compose.foundation.layout.fillMaxWidth
+import androidx.compose.foundation.layout.height
+import androidx.compose.foundation.layout.heightIn
 import androidx.compose.foundation.layout.padding
+import androidx.compose.foundation.layout.width
+import androidx.compose.foundation.layout.widthIn
+import androidx.compose.foundation.lazy.LazyColumn
+import androidx.compose.foundation.lazy.itemsIndexed
+import androidx.compose.foundation.lazy.rememberLazyListState
 import androidx.compose.foundation.shape.RoundedCornerShape
 import androidx.compose.material3.Text
 import androidx.compose.runtime.Composable
+import androidx.compose.runtime.LaunchedEffect
+import androidx.compose.runtime.remember
+import androidx.compose.ui.Alignment
 import androidx.compose.ui.Modifier
-import androidx.compose.ui.geometry.Offset
 import androidx.compose.ui.graphics.Color
+import androidx.compose.ui.text.SpanStyle
+import androidx.compose.ui.text.TextStyle
+import androidx.compose.ui.text.buildAnnotatedString
 import androidx.compose.ui.text.font.FontFamily
+import androidx.compose.ui.text.font.FontWeight
+import androidx.compose.ui.text.style.TextOverflow
 import androidx.compose.ui.unit.IntOffset
 import androidx.compose.ui.unit.dp
 import androidx.compose.ui.unit.sp
 import androidx.compose.ui.window.Popup
+import com.itsvks.code.syntax.TokenType
+import com.itsvks.code.theme.EditorTheme
 
+private const val MAX_VISIBLE_ITEMS = 8
+private val ROW_HEIGHT = 24.dp
+
+/**
+ * The list of completion items, drawn with the colors of [theme]. [position] is the popup's top left
+ * corner relative to its parent, usually just below the start of the word being completed.
+ */
 @Composable
 fun CompletionPopup(
-    items: List<String>,
+    items: List<CompletionItem>,
+    selectedIndex: Int,
+    prefix: String,
+    theme: EditorTheme,
     position: IntOffset,
-    onItemSelected: (String) -> Unit
+    onItemSelected: (Int) -> Unit,
+    textStyle: TextStyle = TextStyle(fontSize = 14.sp, fontFamily = FontFamily.Monospace)
 ) {
-    Popup(
-        offset = position,
-    ) {
-        Box(
+    val listState = rememberLazyListState()
+
+    // Keep the selected item in view while moving through the list with the keyboard
+    LaunchedEffect(selectedIndex, items) {
+        if (selectedIndex < 0) return@LaunchedEffect
+        val first = listState.firstVisibleItemIndex
+        when {
+            selectedIndex < first -> listState.scrollToItem(selectedIndex)
+            selectedIndex >= first + MAX_VISIBLE_ITEMS -> listState.scrollToItem(selectedIndex - MAX_VISIBLE_ITEMS + 1)
+        }
+    }
+
+    Popup(offset = position) {
+        val shape = RoundedCornerShape(4.dp)
+        LazyColumn(
+            state = listState,
             modifier = Modifier
-                .background(Color(0xFF252526), shape = RoundedCornerShape(4.dp))
-                .padding(4.dp)
+                .widthIn(min = 200.dp, max = 360.dp)
+                .heightIn(max = ROW_HEIGHT * MAX_VISIBLE_ITEMS)
+                .background(theme.completionBackgroundColor, shape)
+                .border(1.dp, theme.completionBorderColor, shape)
         ) {
-            Column {
-                items.forEach { item ->
-                    Text(
-                        text = item,
-                        color = Color.White,
-                        fontSize = 14.sp,
-                        fontFamily = FontFamily.Monospace,
-                        modifier = Modifier
-                            .padding(horizontal = 8.dp, vertical = 4.dp)
-                            .clickable { onItemSelected(item) }
-                    )
-                }
+            itemsIndexed(items) { index, item ->
+                CompletionRow(item, index == selectedIndex, prefix, theme, textStyle) { onItemSelected(index) }
+            }
+        }
+    }
+}
+
+@Composable
+private fun CompletionRow(
+    item: CompletionItem,
+    selected: Boolean,
+    prefix: String,
+    theme: EditorTheme,
+    textStyle: TextStyle,
+    onClick: () -> Unit
+) {
+    val label = remember(item.label, prefix, theme) {
+        val matched = FuzzyMatcher.match(prefix, item.label)?.matchedIndices ?: IntArray(0)
+        buildAnnotatedString {
+            append(item.label)
+            for (index in matched) {
+                addStyle(SpanStyle(color = theme.completionHighlightColor, fontWeight = FontWeight.Bold), index, index + 1)
             }
         }
     }
+
+    Row(
+        verticalAlignment = Alignment.CenterVertically,
+        modifier = Modifier
+            .fillMaxWidth()
+            .height(ROW_HEIGHT)
+            .background(if (selected) theme.completionSelectedColor else Color.Transparent)
+            .clickable(onClick = onClick)
+            .padding(horizontal = 6.dp)
+    ) {
+        Text(
+            text = item.kind.badge,
+            style = textStyle.copy(fontSize = textStyle.fontSize * 0.8f),
+            color = kindColor(item.kind, theme),
+            maxLines = 1,
+            modifier = Modifier.width(24.dp)
+        )
+        Text(
+            text = label,
+            style = textStyle,
+            color = theme.completionTextColor,
+            maxLines = 1,
+            overflow = TextOverflow.Ellipsis,
+            modifier = Modifier.weight(1f, fill = false)
+        )
+        val detail = item.detail
+        if (detail != null) {
+            Spacer(Modifier.width(12.dp))
+            Text(
+                text = detail,
+                style = textStyle.copy(fontSize = textStyle.fontSize * 0.85f),
+                color = theme.completionDetailColor,
+                maxLines = 1,
+                overflow = TextOverflow.Ellipsis
+            )
+        }
+    }
+}
+
+private val CompletionItemKind.badge: String
+    get() = when (this) {
+        CompletionItemKind.TEXT -> "ab"
+        CompletionItemKind.KEYWORD -> "kw"
+        CompletionItemKind.TYPE -> "T"
+        CompletionItemKind.CLASS -> "C"
+        CompletionItemKind.INTERFACE -> "I"
+        CompletionItemKind.ENUM -> "E"
+        CompletionItemKind.FUNCTION -> "f"
+        CompletionItemKind.METHOD -> "m"
+        CompletionItemKind.CONSTRUCTOR -> "c"
+        CompletionItemKind.FIELD, CompletionItemKind.PROPERTY -> "p"
+        CompletionItemKind.VARIABLE -> "v"
+        CompletionItemKind.CONSTANT -> "K"
+        CompletionItemKind.MODULE -> "M"
+        CompletionItemKind.SNIPPET -> "{}"
+        CompletionItemKind.FILE -> "F"
+    }
+
+// Kinds take the color of the tokens they name in code
+private fun kindColor(kind: CompletionItemKind, theme: EditorTheme): Color {
+    val type = when (kind) {
+        CompletionItemKind.KEYWORD -> TokenType.KEYWORD
+        CompletionItemKind.TYPE, CompletionItemKind.CLASS, CompletionItemKind.INTERFACE,
+        CompletionItemKind.ENUM, CompletionItemKind.MODULE -> TokenType.TYPE
+        CompletionItemKind.FUNCTION, CompletionItemKind.METHOD, CompletionItemKind.CONSTRUCTOR -> TokenType.FUNCTION
+        CompletionItemKind.FIELD, CompletionItemKind.PROPERTY -> TokenType.STATIC
+        CompletionItemKind.CONSTANT -> TokenType.CONST
+        CompletionItemKind.SNIPPET -> TokenType.ANNOTATION
+        CompletionItemKind.VARIABLE, CompletionItemKind.TEXT, CompletionItemKind.FILE -> return theme.completionDetailColor
+    }
+    return theme.getStyleForToken(type).color
 }
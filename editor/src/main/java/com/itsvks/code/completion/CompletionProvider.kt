@@ -0,0 +1,66 @@
+package com.itsvks.code.completion
+
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextEdit
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.language.Language
+import com.itsvks.code.snippet.Snippet
+
+enum class CompletionItemKind {
+    TEXT, KEYWORD, TYPE, CLASS, INTERFACE, ENUM, FUNCTION, METHOD, CONSTRUCTOR, FIELD, PROPERTY,
+    VARIABLE, CONSTANT, MODULE, SNIPPET, FILE
+}
+
+/**
+ * A suggestion for the text at the caret.
+ *
+ * Accepting it replaces the word being typed with [insertText], or applies [textEdit] instead if
+ * there is one. [additionalEdits] are applied along with it, e.g. to add an import; they must not
+ * overlap the main edit. If [isSnippet], the inserted text is a [Snippet] whose tab stops are then
+ * filled in.
+ */
+data class CompletionItem(
+    val label: String,
+    val kind: CompletionItemKind = CompletionItemKind.TEXT,
+    val detail: String? = null,
+    val documentation: String? = null,
+    val insertText: String = label,
+    val textEdit: TextEdit? = null,
+    val additionalEdits: List<TextEdit> = emptyList(),
+    // Matched against what was typed instead of the label
+    val filterText: String = label,
+    // Orders items that match equally well; the label is used without it
+    val sortText: String? = null,
+    val isSnippet: Boolean = false
+)
+
+/**
+ * What completion was asked for: [prefix] is the part of the word before the caret at [position],
+ * spanning [prefixRange]. [triggerCharacter] is the char that opened the popup, e.g. `.`, or null if
+ * typing a word or the user did.
+ */
+class CompletionContext(
+    val snapshot: TextSnapshot,
+    val position: CursorPosition,
+    val prefix: String,
+    val prefixRange: CursorRange,
+    val language: Language,
+    val triggerCharacter: Char? = null
+)
+
+/**
+ * Suggests completions, e.g. from a language server.
+ *
+ * Providers are asked again as the user keeps typing, and the previous request is cancelled, so
+ * long-running work should suspend or check for cancellation. Items don't need to be filtered by the
+ * prefix: the editor ranks them by how well they match it.
+ */
+interface CompletionProvider {
+    /**
+     * Chars that open completion when typed, besides the chars of a word.
+     */
+    val triggerCharacters: Set<Char> get() = emptySet()
+
+    suspend fun provideCompletions(context: CompletionContext): List<CompletionItem>
+}
@@ -0,0 +1,219 @@
+package com.itsvks.code.completion
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextEdit
+import com.itsvks.code.core.isWordChar
+import kotlinx.coroutines.CancellationException
+import kotlinx.coroutines.async
+import kotlinx.coroutines.awaitAll
+import kotlinx.coroutines.coroutineScope
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+
+/**
+ * Where completion was opened: the word being completed starts at [start]. [triggerCharacter] is the
+ * char that opened it, if any; [isExplicit] if the user asked for it, e.g. with Ctrl+Space.
+ */
+data class CompletionRequest(
+    val start: CursorPosition,
+    val triggerCharacter: Char? = null,
+    val isExplicit: Boolean = false
+)
+
+/**
+ * Code completion for a [CodeEditorState].
+ *
+ * Typing a word or a trigger char opens completion; [refresh] then asks the [providers] off the main
+ * thread and ranks what they offer against the word typed so far. The editor runs it whenever the
+ * document or the caret changes while completion is open, and closes it once the caret leaves the word.
+ */
+class EditorCompletion internal constructor(private val state: CodeEditorState) {
+    /**
+     * Asked for items in this order; items of equal rank keep it.
+     */
+    var providers: List<CompletionProvider> = listOf(WordCompletionProvider(), SnippetCompletionProvider())
+
+    private val _request = MutableStateFlow<CompletionRequest?>(null)
+
+    /**
+     * What completion is open for, or null while it's closed.
+     */
+    val request: StateFlow<CompletionRequest?> = _request.asStateFlow()
+
+    private val _items = MutableStateFlow<List<CompletionItem>>(emptyList())
+
+    /**
+     * Items matching the word typed so far, best first.
+     */
+    val items: StateFlow<List<CompletionItem>> = _items.asStateFlow()
+
+    private val _selectedIndex = MutableStateFlow(-1)
+
+    /**
+     * Index of the item Enter would accept, or -1 if there is none.
+     */
+    val selectedIndex: StateFlow<Int> = _selectedIndex.asStateFlow()
+
+    private val _prefix = MutableStateFlow("")
+
+    /**
+     * The part of the word before the caret that [items] were matched against.
+     */
+    val prefix: StateFlow<String> = _prefix.asStateFlow()
+
+    // Items shown by showItems, which stay until the document or the selection changes
+    private var fixedItems: FixedItems? = null
+
+    private class FixedItems(val version: Long, val selections: List<CursorRange>)
+
+    private val _isLoading = MutableStateFlow(false)
+    val isLoading: StateFlow<Boolean> = _isLoading.asStateFlow()
+
+    /**
+     * Whether there's a list to pick from, so keys like Enter and the arrows go to completion.
+     */
+    val isShowing: Boolean get() = _request.value != null && _items.value.isNotEmpty()
+
+    /**
+     * Opens completion for the word before the caret, or for everything if there's none.
+     */
+    fun show() = open(triggerCharacter = null, isExplicit = true)
+
+    /**
+     * Shows [items] as they are instead of asking the providers, e.g. the choices of a snippet
+     * placeholder. They must have a [CompletionItem.textEdit]. Editing or moving the caret closes them.
+     */
+    fun showItems(items: List<CompletionItem>) {
+        if (items.isEmpty()) return dismiss()
+        fixedItems = FixedItems(state.snapshot.version, state.selections.value)
+        _request.value = CompletionRequest(state.cursor, isExplicit = true)
+        _items.value = items
+        _selectedIndex.value = 0
+        _prefix.value = ""
+    }
+
+    fun dismiss() {
+        fixedItems = null
+        _request.value = null
+        _items.value = emptyList()
+        _selectedIndex.value = -1
+        _prefix.value = ""
+    }
+
+    fun selectNext() = moveSelection(1)
+
+    fun selectPrevious() = moveSelection(-1)
+
+    fun select(index: Int) {
+        if (index in _items.value.indices) _selectedIndex.value = index
+    }
+
+    /**
+     * Replaces the word being typed with the item at [index] and closes completion. Returns false if
+     * there was no such item.
+     */
+    fun accept(index: Int = _selectedIndex.value): Boolean {
+        val request = _request.value ?: return false
+        val item = _items.value.getOrNull(index) ?: return false
+        val caret = state.cursor
+
+        val edit = item.textEdit ?: TextEdit(CursorRange(request.start, caret), item.insertText)
+        if (item.isSnippet) {
+            dismiss()
+            state.snippets.insert(edit.newText, edit.range, item.additionalEdits)
+            return true
+        }
+        val ranges = state.applyEdits(listOf(edit) + item.additionalEdits)
+        state.setCursor(ranges[0].endInclusive)
+        dismiss()
+        return true
+    }
+
+    /**
+     * Asks the providers for items at the caret and ranks them. Cancelling the calling coroutine
+     * cancels the providers too; closes completion if the caret is no longer in the word it was opened for.
+     */
+    suspend fun refresh() {
+        val request = _request.value ?: return
+        fixedItems?.let { fixed ->
+            if (fixed.version != state.snapshot.version || fixed.selections != state.selections.value) dismiss()
+            return
+        }
+        val snapshot = state.snapshot
+        val selection = state.selection.value
+        val caret = selection.end
+        val line = snapshot.getLineOrNull(caret.line)
+        if (line == null || !selection.isCollapsed || state.hasMultipleSelections ||
+            caret.line != request.start.line || caret.column < request.start.column
+        ) return dismiss()
+
+        val prefix = line.substring(request.start.column, caret.column)
+        if (!prefix.all { it.isWordChar() }) return dismiss()
+        // Deleting the whole word closes completion again unless it was asked for
+        if (prefix.isEmpty() && request.triggerCharacter == null && !request.isExplicit) return dismiss()
+
+        val context = CompletionContext(snapshot, caret, prefix, CursorRange(request.start, caret), state.language, request.triggerCharacter)
+        _isLoading.value = true
+        try {
+            val found = coroutineScope {
+                providers.map { provider -> async { provideSafely(provider, context) } }.awaitAll().flatten()
+            }
+            // Plain words found by one provider are left out if another knows more about them
+            val known = found.filter { it.kind != CompletionItemKind.TEXT }.mapTo(HashSet()) { it.label }
+            val items = found
+                .filter { it.kind != CompletionItemKind.TEXT || it.label !in known }
+                .distinctBy { it.label to it.kind }
+
+            val ranked = FuzzyMatcher.rank(items, prefix)
+            _prefix.value = prefix
+            _items.value = ranked
+            _selectedIndex.value = if (ranked.isEmpty()) -1 else 0
+        } finally {
+            _isLoading.value = false
+        }
+    }
+
+    /**
+     * Opens or closes completion after [typed] was typed at the caret.
+     */
+    internal fun onTyped(typed: Char) {
+        val isOpen = _request.value != null
+        when {
+            state.hasMultipleSelections -> if (isOpen) dismiss()
+            typed.isWordChar() -> if (!isOpen) open(triggerCharacter = null, isExplicit = false)
+            providers.any { typed in it.triggerCharacters } -> open(typed, isExplicit = false)
+            isOpen -> dismiss()
+        }
+    }
+
+    private fun open(triggerCharacter: Char?, isExplicit: Boolean) {
+        val caret = state.cursor
+        val line = state.snapshot.getLine(caret.line)
+        var start = caret.column.coerceAtMost(line.length)
+        if (triggerCharacter == null) {
+            while (start > 0 && line[start - 1].isWordChar()) start--
+        }
+        _request.value = CompletionRequest(CursorPosition(caret.line, start), triggerCharacter, isExplicit)
+    }
+
+    private fun moveSelection(step: Int) {
+        val items = _items.value
+        if (items.isEmpty()) return
+        val current = _selectedIndex.value
+        _selectedIndex.value = if (current < 0) 0 else Math.floorMod(current + step, items.size)
+    }
+
+    // A failing provider shouldn't take the others' items with it
+    private suspend fun provideSafely(provider: CompletionProvider, context: CompletionContext): List<CompletionItem> {
+        return try {
+            provider.provideCompletions(context)
+        } catch (e: CancellationException) {
+            throw e
+        } catch (_: Exception) {
+            emptyList()
+        }
+    }
+}
@@ -0,0 +1,116 @@
+package com.itsvks.code.completion
+
+// Longer candidates aren't worth the quadratic matching; they only match by prefix
+private const val MAX_FUZZY_LENGTH = 128
+
+private const val START_BONUS = 8
+private const val WORD_START_BONUS = 6
+private const val CONSECUTIVE_BONUS = 5
+private const val CASE_BONUS = 1
+private const val MAX_GAP_PENALTY = 3
+
+/**
+ * How well a pattern matched a candidate: the higher [score] the better, and which chars of the
+ * candidate matched, e.g. to highlight them.
+ */
+class FuzzyMatch(val score: Int, val matchedIndices: IntArray)
+
+/**
+ * Matches typed text against completion candidates the way editors usually do: the pattern's chars
+ * must appear in order, ignoring case, and matches at the start, at word starts like the `B` of
+ * `fooBar` or the `b` of `foo_bar`, and runs of consecutive chars count the most.
+ */
+object FuzzyMatcher {
+    /**
+     * The best match of [pattern] in [candidate], or null if its chars don't all appear in order.
+     */
+    @JvmStatic
+    fun match(pattern: String, candidate: String): FuzzyMatch? {
+        if (pattern.isEmpty()) return FuzzyMatch(0, IntArray(0))
+        if (pattern.length > candidate.length) return null
+        if (candidate.length > MAX_FUZZY_LENGTH) {
+            if (!candidate.startsWith(pattern, ignoreCase = true)) return null
+            return FuzzyMatch(START_BONUS + pattern.length * (1 + CONSECUTIVE_BONUS), IntArray(pattern.length) { it })
+        }
+
+        val n = pattern.length
+        val m = candidate.length
+        // scores[i][j]: best score with pattern[i] matched at candidate[j]; previous[i][j]: where pattern[i - 1] matched
+        val scores = Array(n) { IntArray(m) { NO_MATCH } }
+        val previous = Array(n) { IntArray(m) { -1 } }
+
+        for (i in 0 until n) {
+            val p = pattern[i]
+            // Leave room for the rest of the pattern
+            for (j in i..m - n + i) {
+                val c = candidate[j]
+                if (!c.equals(p, ignoreCase = true)) continue
+                val charScore = 1 + (if (c == p) CASE_BONUS else 0) + bonusAt(candidate, j)
+
+                if (i == 0) {
+                    scores[0][j] = charScore - minOf(j, MAX_GAP_PENALTY)
+                    continue
+                }
+                var best = NO_MATCH
+                var bestPrevious = -1
+                for (k in i - 1 until j) {
+                    val score = scores[i - 1][k]
+                    if (score == NO_MATCH) continue
+                    val total = score + if (k == j - 1) CONSECUTIVE_BONUS else -minOf(j - k - 1, MAX_GAP_PENALTY)
+                    if (total > best) {
+                        best = total
+                        bestPrevious = k
+                    }
+                }
+                if (best != NO_MATCH) {
+                    scores[i][j] = best + charScore
+                    previous[i][j] = bestPrevious
+                }
+            }
+        }
+
+        var end = -1
+        for (j in 0 until m) {
+            if (scores[n - 1][j] != NO_MATCH && (end < 0 || scores[n - 1][j] > scores[n - 1][end])) end = j
+        }
+        if (end < 0) return null
+
+        val indices = IntArray(n)
+        var j = end
+        for (i in n - 1 downTo 0) {
+            indices[i] = j
+            j = previous[i][j]
+        }
+        return FuzzyMatch(scores[n - 1][end], indices)
+    }
+
+    /**
+     * The [items] that match [prefix], best first. Equally good matches are ordered by their sort text,
+     * then by label.
+     */
+    @JvmStatic
+    fun rank(items: List<CompletionItem>, prefix: String): List<CompletionItem> {
+        return items
+            .mapNotNull { item -> match(prefix, item.filterText)?.let { item to it.score } }
+            .sortedWith(
+                compareByDescending<Pair<CompletionItem, Int>> { it.second }
+                    .thenBy { it.first.sortText ?: it.first.label }
+                    .thenBy { it.first.label.length }
+            )
+            .map { it.first }
+    }
+
+    private fun bonusAt(candidate: String, index: Int): Int {
+        if (index == 0) return START_BONUS
+        val previous = candidate[index - 1]
+        val current = candidate[index]
+        return when {
+            !previous.isLetterOrDigit() && current.isLetterOrDigit() -> WORD_START_BONUS
+            previous.isLowerCase() && current.isUpperCase() -> WORD_START_BONUS
+            previous.isLetter() && current.isDigit() -> WORD_START_BONUS / 2
+            else -> 0
+        }
+    }
+
+    private const val NO_MATCH = Int.MIN_VALUE
+}
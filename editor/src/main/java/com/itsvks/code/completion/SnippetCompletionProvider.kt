@@ -0,0 +1,24 @@
+package com.itsvks.code.completion
+
+import com.itsvks.code.snippet.SnippetRegistry
+
+/**
+ * Completes the prefixes of the snippets registered for the language in [SnippetRegistry].
+ */
+class SnippetCompletionProvider : CompletionProvider {
+    override suspend fun provideCompletions(context: CompletionContext): List<CompletionItem> {
+        // Snippets start a statement or an expression, not a member
+        if (context.triggerCharacter != null) return emptyList()
+        return SnippetRegistry.getSnippets(context.language).flatMap { definition ->
+            definition.prefixes.map { prefix ->
+                CompletionItem(
+                    label = prefix,
+                    kind = CompletionItemKind.SNIPPET,
+                    detail = definition.description ?: definition.name,
+                    insertText = definition.body,
+                    isSnippet = true
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+package com.itsvks.code.completion
+
+import com.itsvks.code.core.isWordChar
+import kotlinx.coroutines.Dispatchers
+import kotlinx.coroutines.ensureActive
+import kotlinx.coroutines.withContext
+
+// Only this much text around the caret is scanned for words in bigger documents
+private const val MAX_SCANNED_LENGTH = 500_000
+
+// Lines scanned between checks for cancellation
+private const val CANCELLATION_CHECK_LINES = 1000
+
+/**
+ * Completes the language's keywords and types, and the words found in the document itself. This is
+ * what the editor offers when the host has no better provider.
+ */
+class WordCompletionProvider(private val minWordLength: Int = 3) : CompletionProvider {
+    override suspend fun provideCompletions(context: CompletionContext): List<CompletionItem> {
+        val language = context.language
+        val items = LinkedHashMap<String, CompletionItem>()
+        for (keyword in language.keywords) items[keyword] = CompletionItem(keyword, CompletionItemKind.KEYWORD)
+        for (type in language.types) items.putIfAbsent(type, CompletionItem(type, CompletionItemKind.TYPE))
+
+        for (word in wordsOf(context)) {
+            items.putIfAbsent(word, CompletionItem(word, CompletionItemKind.TEXT))
+        }
+        return items.values.toList()
+    }
+
+    private suspend fun wordsOf(context: CompletionContext): Set<String> = withContext(Dispatchers.Default) {
+        val snapshot = context.snapshot
+        val caretOffset = snapshot.offsetOf(context.position)
+        val scanStart = (caretOffset - MAX_SCANNED_LENGTH / 2).coerceIn(0, (snapshot.length - MAX_SCANNED_LENGTH).coerceAtLeast(0))
+        val firstLine = snapshot.positionOf(scanStart).line
+        val lastLine = snapshot.positionOf(scanStart + MAX_SCANNED_LENGTH).line
+
+        val words = HashSet<String>()
+        for (line in firstLine..lastLine) {
+            if ((line - firstLine) % CANCELLATION_CHECK_LINES == 0) ensureActive()
+            val text = snapshot.getLine(line)
+            var i = 0
+            while (i < text.length) {
+                if (!text[i].isWordChar()) {
+                    i++
+                    continue
+                }
+                val start = i
+                while (i < text.length && text[i].isWordChar()) i++
+                // Words start with a letter or an underscore, numbers aren't worth completing
+                if (i - start >= minWordLength && !text[start].isDigit()) {
+                    // Skip the word being typed
+                    if (line != context.position.line || start != context.prefixRange.startInclusive.column) {
+                        words.add(text.substring(start, i))
+                    }
+                }
+            }
+        }
+        words
+    }
+}
@@ -2,6 +2,7 @@ package com.itsvks.code.component
 
 import androidx.compose.animation.AnimatedVisibility
 import androidx.compose.animation.core.LinearEasing
+import androidx.compose.animation.core.RepeatMode
 import androidx.compose.animation.core.animateFloat
 import androidx.compose.animation.core.infiniteRepeatable
 import androidx.compose.animation.core.rememberInfiniteTransition
@@ -9,71 +10,112 @@ import androidx.compose.animation.core.tween
 import androidx.compose.animation.fadeIn
 import androidx.compose.animation.fadeOut
 import androidx.compose.foundation.background
+import androidx.compose.foundation.focusable
+import androidx.compose.foundation.gestures.Orientation
+import androidx.compose.foundation.gestures.awaitEachGesture
+import androidx.compose.foundation.gestures.awaitFirstDown
+import androidx.compose.foundation.gestures.detectDragGesturesAfterLongPress
+import androidx.compose.foundation.gestures.detectTapGestures
 import androidx.compose.foundation.gestures.detectTransformGestures
-import androidx.compose.foundation.horizontalScroll
-import androidx.compose.foundation.layout.Arrangement
+import androidx.compose.foundation.gestures.rememberScrollableState
+import androidx.compose.foundation.gestures.scrollable
 import androidx.compose.foundation.layout.Box
-import androidx.compose.foundation.layout.Row
 import androidx.compose.foundation.layout.Spacer
 import androidx.compose.foundation.layout.fillMaxSize
-import androidx.compose.foundation.layout.fillMaxWidth
 import androidx.compose.foundation.layout.padding
 import androidx.compose.foundation.layout.size
-import androidx.compose.foundation.layout.width
-import androidx.compose.foundation.layout.heightIn
-import androidx.compose.foundation.layout.wrapContentHeight
-import androidx.compose.foundation.lazy.LazyColumn
-import androidx.compose.foundation.lazy.itemsIndexed
-import androidx.compose.foundation.lazy.rememberLazyListState
-import androidx.compose.foundation.rememberScrollState
-import androidx.compose.foundation.text.BasicTextField
-import androidx.compose.foundation.text.ClickableText
-import androidx.compose.foundation.text.selection.DisableSelection
-import androidx.compose.foundation.text.selection.LocalTextSelectionColors
-import androidx.compose.foundation.text.selection.SelectionContainer
-import androidx.compose.foundation.text.selection.TextSelectionColors
-import androidx.compose.material3.Text
 import androidx.compose.runtime.Composable
-import androidx.compose.runtime.CompositionLocalProvider
+import androidx.compose.runtime.LaunchedEffect
+import androidx.compose.runtime.SideEffect
 import androidx.compose.runtime.collectAsState
-import androidx.compose.runtime.derivedStateOf
 import androidx.compose.runtime.getValue
-import androidx.compose.runtime.mutableIntStateOf
 import androidx.compose.runtime.mutableStateOf
 import androidx.compose.runtime.remember
+import androidx.compose.runtime.rememberUpdatedState
 import androidx.compose.runtime.setValue
+import androidx.compose.runtime.snapshotFlow
 import androidx.compose.ui.Alignment
 import androidx.compose.ui.Modifier
+import androidx.compose.ui.draw.drawBehind
 import androidx.compose.ui.draw.drawWithCache
+import androidx.compose.ui.focus.FocusRequester
+import androidx.compose.ui.focus.focusRequester
 import androidx.compose.ui.focus.onFocusChanged
 import androidx.compose.ui.geometry.Offset
 import androidx.compose.ui.geometry.Size
-import androidx.compose.ui.graphics.RectangleShape
-import androidx.compose.ui.graphics.SolidColor
+import androidx.compose.ui.graphics.Color
+import androidx.compose.ui.graphics.Path
+import androidx.compose.ui.graphics.PathEffect
 import androidx.compose.ui.graphics.StrokeCap
+import androidx.compose.ui.graphics.drawscope.DrawScope
 import androidx.compose.ui.graphics.drawscope.Stroke
+import androidx.compose.ui.graphics.drawscope.clipRect
+import androidx.compose.ui.graphics.drawscope.translate
+import androidx.compose.ui.input.key.Key
+import androidx.compose.ui.input.key.KeyEvent
+import androidx.compose.ui.input.key.KeyEventType
+import androidx.compose.ui.input.key.isAltPressed
+import androidx.compose.ui.input.key.isCtrlPressed
+import androidx.compose.ui.input.key.isMetaPressed
+import androidx.compose.ui.input.key.isShiftPressed
+import androidx.compose.ui.input.key.key
+import androidx.compose.ui.input.key.onKeyEvent
+import androidx.compose.ui.input.key.type
+import androidx.compose.ui.input.key.utf16CodePoint
+import androidx.compose.ui.input.pointer.PointerEventPass
+import androidx.compose.ui.input.pointer.PointerEventType
+import androidx.compose.ui.input.pointer.PointerType
+import androidx.compose.ui.input.pointer.areAnyPressed
+import androidx.compose.ui.input.pointer.isCtrlPressed
 import androidx.compose.ui.input.pointer.pointerInput
-import androidx.compose.ui.text.TextRange
-import androidx.compose.ui.text.AnnotatedString
+import androidx.compose.ui.layout.onSizeChanged
+import androidx.compose.ui.platform.LocalContext
+import androidx.compose.ui.platform.LocalDensity
+import androidx.compose.ui.platform.LocalSoftwareKeyboardController
 import androidx.compose.ui.text.TextStyle
-import androidx.compose.ui.text.input.TextFieldValue
-import androidx.compose.ui.text.style.TextOverflow
+import androidx.compose.ui.text.drawText
+import androidx.compose.ui.text.rememberTextMeasurer
 import androidx.compose.ui.unit.Dp
+import androidx.compose.ui.unit.IntOffset
+import androidx.compose.ui.unit.IntRect
+import androidx.compose.ui.unit.IntSize
 import androidx.compose.ui.unit.TextUnit
 import androidx.compose.ui.unit.dp
 import androidx.compose.ui.unit.sp
 import com.itsvks.code.CodeEditorState
-import com.itsvks.code.core.FoldableRange // Assuming FoldableRange is in this package
-import com.itsvks.code.core.bracketPairs
+import com.itsvks.code.completion.CompletionPopup
+import com.itsvks.code.completion.EditorCompletion
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextSnapshot
 import com.itsvks.code.core.rememberJetBrainsMonoFontFamily
-import com.itsvks.code.syntax.SyntaxHighlighterFactory
+import com.itsvks.code.diagnostics.Diagnostic
+import com.itsvks.code.diagnostics.DiagnosticPopup
+import com.itsvks.code.diagnostics.DiagnosticSeverity
+import com.itsvks.code.diagnostics.diagnosticColor
+import com.itsvks.code.hover.HoverPopup
+import com.itsvks.code.input.EditorClipboard
+import com.itsvks.code.input.Keymap
+import com.itsvks.code.input.codeEditorTextInput
+import com.itsvks.code.search.SearchMatch
+import com.itsvks.code.signature.SignatureHelpPopup
 import com.itsvks.code.theme.EditorTheme
-import com.itsvks.code.theme.findBracketPairIndices
-import com.itsvks.code.theme.highlight
-import kotlinx.coroutines.Dispatchers
-import my.nanihadesuka.compose.LazyColumnScrollbar
-import my.nanihadesuka.compose.ScrollbarSettings
-import kotlin.math.max
+import com.itsvks.code.util.isPrintable
+import kotlinx.coroutines.Job
+import kotlinx.coroutines.coroutineScope
+import kotlinx.coroutines.delay
+import kotlinx.coroutines.flow.collectLatest
+import kotlinx.coroutines.flow.combine
+import kotlinx.coroutines.flow.drop
+import kotlinx.coroutines.launch
+
+private const val FOLD_REFRESH_DELAY_MILLIS = 300L
+private const val SEARCH_REFRESH_DELAY_MILLIS = 150L
+private const val CARET_BLINK_MILLIS = 500
+private const val COMPLETION_DELAY_MILLIS = 50L
+private const val SIGNATURE_HELP_DELAY_MILLIS = 50L
+
+// How long the mouse rests on a symbol before its hover card is asked for
+private const val HOVER_DELAY_MILLIS = 500L
 
 sealed class DisplayLine {
     abstract val originalLineIndex: Int
@@ -89,6 +131,10 @@ sealed class DisplayLine {
     ) : DisplayLine()
 }
 
+/**
+ * The editor surface: every visible line of [state] is drawn on a single canvas with its own caret,
+ * selection and hit testing, so the whole document behaves as one text field.
+ */
 @Composable
 fun CodeEditor(
     state: CodeEditorState,
@@ -98,67 +144,209 @@ fun CodeEditor(
     gutterWidth: Dp = 48.dp,
     horizontalPadding: Dp = 6.dp,
     editable: Boolean = false,
-    enableZoomGestures: Boolean = false
+    enableZoomGestures: Boolean = false,
+    keymap: Keymap = Keymap.Default
 ) {
     var fontSize by remember { mutableStateOf(initialFontSize) }
     val fontFamily = rememberJetBrainsMonoFontFamily()
-    val listState = rememberLazyListState()
     val theme = state.theme
-    val content by state.content.collectAsState(Dispatchers.IO) // This is List<String>
-    val isLoading by state.isLoading.collectAsState(Dispatchers.IO)
-    val foldableRanges by state.foldableRanges.collectAsState(Dispatchers.IO)
-    val foldedLines by state.foldedLines.collectAsState(Dispatchers.IO)
-
-    val displayLines by remember(content, foldedLines, foldableRanges) {
-        derivedStateOf {
-            val originalContent = content
-            val currentDisplayLines = mutableListOf<DisplayLine>()
-            var currentOriginalLine = 0
-            while (currentOriginalLine < originalContent.size) {
-                val isFoldedStart = foldedLines.contains(currentOriginalLine)
-                if (isFoldedStart) {
-                    val range = foldableRanges.find { it.startLine == currentOriginalLine }
-                    if (range != null) {
-                        currentDisplayLines.add(DisplayLine.FoldedMarker(
-                            originalLineIndex = currentOriginalLine,
-                            endLine = range.endLine,
-                            numberOfHiddenLines = range.endLine - range.startLine - 1
-                        ))
-                        currentOriginalLine = range.endLine + 1
-                    } else {
-                        currentDisplayLines.add(DisplayLine.Code(currentOriginalLine))
-                        currentOriginalLine++
-                    }
-                } else {
-                    currentDisplayLines.add(DisplayLine.Code(currentOriginalLine))
-                    currentOriginalLine++
-                }
-            }
-            currentDisplayLines
+    val content by state.content.collectAsState()
+    val isLoading by state.isLoading.collectAsState()
+    val foldableRanges by state.foldableRanges.collectAsState()
+    val foldedLines by state.foldedLines.collectAsState()
+    val selection by state.selection.collectAsState()
+    val selections by state.selections.collectAsState()
+    val composition by state.composition.collectAsState()
+    val searchMatches by state.search.matches.collectAsState()
+    val currentSearchMatch by state.search.currentIndex.collectAsState()
+    val diagnostics by state.diagnostics.items.collectAsState()
+
+    val displayLines = remember(content.lineCount, foldedLines, foldableRanges) {
+        DisplayLineMapping(content.lineCount, foldedLines, foldableRanges)
+    }
+    val foldStartLines = remember(foldableRanges) { foldableRanges.mapTo(HashSet()) { it.startLine } }
+
+    LaunchedEffect(state) {
+        // The ranges start out lexed only, and edits only shift them; parse again once typing settles
+        state.refreshFoldableRanges()
+        combine(state.content, snapshotFlow { state.language }) { snapshot, _ -> snapshot }.drop(1).collectLatest {
+            delay(FOLD_REFRESH_DELAY_MILLIS)
+            state.refreshFoldableRanges()
         }
     }
 
-    val syntaxHighlighter by remember {
-        derivedStateOf {
-            SyntaxHighlighterFactory.createHighlighter(state.language)
+    LaunchedEffect(state) {
+        // Search again whenever the query or the document changes, dropping a search that is still running
+        combine(state.search.query, state.content) { query, _ -> query }.collectLatest { query ->
+            if (query == null) return@collectLatest
+            delay(SEARCH_REFRESH_DELAY_MILLIS)
+            state.search.refresh()
         }
     }
 
-    val selectionColors by remember {
-        derivedStateOf {
-            TextSelectionColors(
-                handleColor = theme.selectionHandleColor,
-                backgroundColor = theme.selectionColor
-            )
+    LaunchedEffect(state) {
+        // Ask for completions again as the word at the caret changes, dropping a request still running
+        combine(state.completion.request, state.content, state.selection) { request, _, _ -> request }.collectLatest { request ->
+            if (request == null) return@collectLatest
+            delay(COMPLETION_DELAY_MILLIS)
+            state.completion.refresh()
+        }
+    }
+
+    LaunchedEffect(state) {
+        // Follow the arguments being typed; a caret moved without an edit closes signature help
+        combine(state.signatureHelp.request, state.content, state.selection) { request, _, _ -> request }.collectLatest { request ->
+            if (request == null) return@collectLatest
+            delay(SIGNATURE_HELP_DELAY_MILLIS)
+            state.signatureHelp.refresh()
+        }
+    }
+
+    LaunchedEffect(state) {
+        // Ask for the hover card, or close it once the document or the caret changes
+        combine(state.hover.request, state.content, state.selection) { request, _, _ -> request }.collectLatest { request ->
+            if (request == null) return@collectLatest
+            state.hover.refresh()
+        }
+    }
+
+    LaunchedEffect(state) {
+        state.navigation.request.collectLatest { request ->
+            if (request != null) state.navigation.refresh()
+        }
+    }
+
+    val language = state.language
+
+    val textMeasurer = rememberTextMeasurer()
+    val textLayout = remember(textMeasurer) { EditorTextLayout(textMeasurer) }
+    val viewport = remember(state) { EditorViewport() }
+    val focusRequester = remember { FocusRequester() }
+    val keyboardController = LocalSoftwareKeyboardController.current
+    val context = LocalContext.current
+    val clipboard = remember(context) { EditorClipboard.of(context) }
+    var surfaceSize by remember { mutableStateOf(IntSize.Zero) }
+    var isFocused by remember { mutableStateOf(false) }
+
+    val density = LocalDensity.current
+    val gutterWidthPx = with(density) { gutterWidth.toPx() }
+    val paddingPx = with(density) { horizontalPadding.toPx() }
+    val textAreaWidth = surfaceSize.width - gutterWidthPx - paddingPx * 2
+    val visibleLineCount = if (textLayout.lineHeight > 0f) (surfaceSize.height / textLayout.lineHeight).toInt() else 0
+
+    val textStyle = TextStyle(
+        fontSize = fontSize,
+        fontFamily = fontFamily,
+        color = theme.defaultTextColor,
+        lineHeight = fontSize * 1.2f
+    )
+    val gutterStyle = textStyle.copy(color = theme.gutterTextColor)
+    val foldMarkerStyle = textStyle.copy(color = theme.gutterTextColor.copy(alpha = 0.8f), fontSize = fontSize * 0.8f)
+    val foldSuffixStyle = textStyle.copy(color = theme.defaultTextColor.copy(alpha = 0.7f))
+
+    SideEffect {
+        val wrapWidth = if (softWrap) textAreaWidth.toInt() else null
+        textLayout.update(textStyle, theme, language, wrapWidth)
+    }
+
+    val currentContent by rememberUpdatedState(content)
+    val currentDisplayLines by rememberUpdatedState(displayLines)
+    val currentFoldStartLines by rememberUpdatedState(foldStartLines)
+    val currentTextAreaWidth by rememberUpdatedState(textAreaWidth)
+    val currentVisibleLineCount by rememberUpdatedState(visibleLineCount)
+    val rowHeight = { row: Int -> textLayout.rowHeight(currentDisplayLines[row], currentContent) }
+
+    LaunchedEffect(state) {
+        // Lines on screen are lexed first, so even a huge file shows highlighted text right away
+        val visibleLines = snapshotFlow {
+            val rows = currentDisplayLines
+            if (rows.size == 0) return@snapshotFlow IntRange.EMPTY
+            val first = rows[viewport.firstVisibleRow.coerceIn(0, rows.size - 1)]
+            val last = rows[(viewport.firstVisibleRow + currentVisibleLineCount).coerceIn(0, rows.size - 1)]
+            first.originalLineIndex..if (last is DisplayLine.FoldedMarker) last.endLine else last.originalLineIndex
+        }
+        combine(state.content, visibleLines, snapshotFlow { state.language }) { snapshot, lines, _ -> snapshot to lines }
+            .collectLatest { (snapshot, lines) -> state.highlighting.highlight(snapshot, lines) }
+    }
+
+    val highlightUpdate by state.highlighting.updates.collectAsState()
+    val syntaxTree by state.syntax.tree.collectAsState()
+    // Rebuilt whenever the highlighter publishes new tokens, so the surface is redrawn with them.
+    // A syntax tree knows more, e.g. which strings are keys; its tokens stay on the lines edits didn't
+    // touch until the next parse, so colors don't flip back and forth while typing.
+    val treeTokens = remember(state, syntaxTree, content) { state.syntax.currentTokens() }
+    val tokensOf = remember(state, highlightUpdate, treeTokens) {
+        { line: Int -> treeTokens?.tokensOn(line) ?: state.highlighting.tokensFor(line).orEmpty() }
+    }
+    val semanticUpdate by state.semanticTokens.updates.collectAsState()
+    val semanticTokensOf = remember(state, semanticUpdate, content) { { line: Int -> state.semanticTokens.tokensOn(line) } }
+    val bracketPair = remember(state, selection, syntaxTree, content, isFocused) {
+        if (isFocused) state.findMatchingBrackets(selection.end) else emptyList()
+    }
+
+    val verticalScrollState = rememberScrollableState { delta ->
+        -viewport.scrollBy(-delta, currentDisplayLines.size, rowHeight)
+    }
+    val horizontalScrollState = rememberScrollableState { delta ->
+        val maxOffset = currentContent.rope.maxLineLength() * textLayout.charWidth - currentTextAreaWidth / 2
+        -viewport.scrollHorizontallyBy(-delta, maxOffset)
+    }
+
+    LaunchedEffect(state) {
+        // Remembered with each jump, so going back scrolls to where it was
+        snapshotFlow {
+            val rows = currentDisplayLines
+            if (rows.size == 0) 0 else rows[viewport.firstVisibleRow.coerceIn(0, rows.size - 1)].originalLineIndex
+        }.collect { state.navigation.firstVisibleLine = it }
+    }
+
+    LaunchedEffect(state) {
+        state.navigation.scrollRequest.collect { line ->
+            if (line == null) return@collect
+            viewport.scrollToRow(currentDisplayLines.displayIndexOf(line), currentDisplayLines.size)
+            state.navigation.onScrollRestored()
         }
     }
-    // Shared lineHeight for gutter calculation, updated by BasicTextField or default for FoldedMarker
-    var sharedLineHeight by remember { mutableIntStateOf((fontSize.value * 1.2f).toInt()) }
 
+    LaunchedEffect(displayLines.size) {
+        if (viewport.firstVisibleRow >= displayLines.size) viewport.scrollToRow(displayLines.size - 1, displayLines.size)
+    }
+
+    // Keep the primary caret on screen when it moves
+    LaunchedEffect(selection, surfaceSize) {
+        if (surfaceSize == IntSize.Zero) return@LaunchedEffect
+        val caret = selection.end
+        viewport.ensureRowVisible(displayLines.displayIndexOf(caret.line), displayLines.size, surfaceSize.height.toFloat(), rowHeight)
+
+        val line = content.getLineOrNull(caret.line)
+        if (!softWrap && line != null) {
+            val x = textLayout.measureLine(line).getHorizontalPosition(caret.column.coerceAtMost(line.length), true)
+            viewport.ensureHorizontallyVisible(x - textLayout.charWidth, x + textLayout.charWidth * 2, textAreaWidth)
+        }
+    }
+
+    LaunchedEffect(isFocused) {
+        if (!isFocused) {
+            state.completion.dismiss()
+            state.signatureHelp.dismiss()
+        }
+    }
+
+    val caretTransition = rememberInfiniteTransition(label = "caret")
+    val caretAlpha by caretTransition.animateFloat(
+        initialValue = 1f,
+        targetValue = 0f,
+        animationSpec = infiniteRepeatable(
+            animation = tween(durationMillis = CARET_BLINK_MILLIS, easing = LinearEasing),
+            repeatMode = RepeatMode.Reverse
+        ),
+        label = "caretAlpha"
+    )
 
     Box(
         modifier = modifier
             .fillMaxSize()
+            .background(theme.backgroundColor)
             .then(
                 if (enableZoomGestures) Modifier.pointerInput(Unit) {
                     detectTransformGestures(panZoomLock = true) { _, _, zoom, _ ->
@@ -167,192 +355,204 @@ fun CodeEditor(
                 } else Modifier
             )
     ) {
-        SelectionContainer {
-            CompositionLocalProvider(LocalTextSelectionColors provides selectionColors) {
-                LazyColumnScrollbar(
-                    state = listState,
-                    settings = ScrollbarSettings.Default.copy(
-                        hideDelayMillis = 1000,
-                        scrollbarPadding = 0.dp,
-                        thumbThickness = 10.dp,
-                        thumbSelectedColor = theme.scrollBarSelectedColor,
-                        thumbUnselectedColor = theme.scrollBarColor,
-                        thumbShape = RectangleShape
+        Spacer(
+            modifier = Modifier
+                .fillMaxSize()
+                .onSizeChanged { surfaceSize = it }
+                .focusRequester(focusRequester)
+                .then(if (editable) Modifier.codeEditorTextInput(state, keyboardController) { handleKeyEvent(state, it, keymap, visibleLineCount, clipboard) } else Modifier)
+                .onFocusChanged { isFocused = it.isFocused }
+                .focusable()
+                .onKeyEvent { editable && handleKeyEvent(state, it, keymap, visibleLineCount, clipboard) }
+                .scrollable(verticalScrollState, Orientation.Vertical)
+                .then(if (!softWrap) Modifier.scrollable(horizontalScrollState, Orientation.Horizontal) else Modifier)
+                .pointerInput(state, gutterWidthPx) {
+                    // Ctrl-clicking a name goes to its definition; taken before the tap detector sees it
+                    awaitEachGesture {
+                        val down = awaitFirstDown(pass = PointerEventPass.Initial)
+                        if (!currentEvent.keyboardModifiers.isCtrlPressed || down.position.x < gutterWidthPx) return@awaitEachGesture
+                        val position = textLayout.positionAt(down.position) ?: return@awaitEachGesture
+                        if (!state.navigation.isNavigable(position)) return@awaitEachGesture
+                        down.consume()
+                        focusRequester.requestFocus()
+                        state.setCursor(position)
+                        state.navigation.requestDefinition(position)
+                    }
+                }
+                .pointerInput(state, gutterWidthPx) {
+                    detectTapGestures(
+                        onTap = { offset ->
+                            focusRequester.requestFocus()
+                            val position = textLayout.positionAt(offset) ?: return@detectTapGestures
+                            val inGutter = offset.x < gutterWidthPx
+                            val startingHere = state.diagnostics.diagnosticsOnLine(position.line)
+                                .filter { it.range.startInclusive.line == position.line }
+                            // Diagnostic icons are next to the line numbers, fold markers at the gutter's left edge
+                            when {
+                                inGutter && offset.x >= gutterWidthPx / 2 && startingHere.isNotEmpty() -> state.diagnostics.show(startingHere)
+                                inGutter && position.line in currentFoldStartLines -> state.toggleFold(position.line)
+                                else -> {
+                                    state.setCursor(position)
+                                    state.diagnostics.showAt(position)
+                                }
+                            }
+                        },
+                        onDoubleTap = { offset ->
+                            val position = textLayout.positionAt(offset) ?: return@detectTapGestures
+                            state.snapshot.wordRangeAt(position)?.let(state::setSelection)
+                        }
                     )
-                ) {
-                    LazyColumn(
-                        modifier = Modifier
-                            .fillMaxSize()
-                            .then(if (!softWrap) Modifier.horizontalScroll(rememberScrollState()) else Modifier)
-                            .background(theme.backgroundColor),
-                        state = listState,
-                        verticalArrangement = Arrangement.spacedBy(1.dp)
-                    ) {
-                        itemsIndexed(displayLines, key = { _, item -> item.originalLineIndex }) { _, displayLineItem ->
-                            val lineIdx = displayLineItem.originalLineIndex
-                            var isLineFocused by remember(lineIdx) { mutableStateOf(false) }
-
-                            Row(modifier = Modifier.wrapContentHeight()) {
-                                // Gutter Box
-                                Box(
-                                    contentAlignment = Alignment.CenterEnd, // Center content vertically and align text to end
-                                    modifier = Modifier
-                                        .width(gutterWidth)
-                                        .padding(end = horizontalPadding / 2) // Reduced padding for fold marker space
-                                        .heightIn(min = (fontSize.value * 1.2f).dp) // Ensure min height for clickability
-                                        .drawWithCache {
-                                            onDrawBehind {
-                                                val width = size.width + horizontalPadding.toPx() / 2
-                                                val height = max(size.height, sharedLineHeight.toFloat())
-                                                val isActiveLine = isLineFocused && displayLineItem is DisplayLine.Code
-
-                                                drawRect(
-                                                    color = if (isActiveLine) theme.activeLineColor else theme.gutterBgColor,
-                                                    topLeft = Offset(0f, (-0.5f).dp.toPx()),
-                                                    size = Size(width, height + 1.5f.dp.toPx())
-                                                )
-                                                drawLine(
-                                                    color = theme.gutterBorderColor,
-                                                    start = Offset(width, (-0.5f).dp.toPx()),
-                                                    end = Offset(width, height + 1.5f.dp.toPx()),
-                                                    strokeWidth = 1f
-                                                )
-                                            }
-                                        }
-                                ) {
-                                    Row(verticalAlignment = Alignment.CenterVertically) {
-                                        val foldableRange = foldableRanges.find { it.startLine == lineIdx }
-                                        if (foldableRange != null) {
-                                            val isFolded = foldedLines.contains(lineIdx)
-                                            ClickableText(
-                                                text = AnnotatedString(if (isFolded) "[+]" else "[-]"),
-                                                onClick = { state.toggleFold(lineIdx) },
-                                                style = TextStyle(
-                                                    color = theme.gutterTextColor.copy(alpha = 0.8f),
-                                                    fontSize = fontSize * 0.8f // Smaller for marker
-                                                ),
-                                                modifier = Modifier.padding(end = horizontalPadding / 2)
-                                            )
-                                        } else {
-                                            // Spacer to align line numbers when no fold marker
-                                            Spacer(Modifier.width((fontSize.value * 0.8f * 3).dp + horizontalPadding/2))
-                                        }
-                                        DisableSelection {
-                                            Text(
-                                                text = (lineIdx + 1).toString(),
-                                                color = theme.gutterTextColor,
-                                                fontSize = fontSize,
-                                                fontFamily = fontFamily,
-                                                lineHeight = fontSize,
-                                                softWrap = false,
-                                                overflow = TextOverflow.Visible
-                                            )
+                }
+                .pointerInput(state) {
+                    var anchor = CursorRange.Zero
+                    detectDragGesturesAfterLongPress(
+                        onDragStart = { offset ->
+                            val position = textLayout.positionAt(offset) ?: return@detectDragGesturesAfterLongPress
+                            anchor = state.snapshot.wordRangeAt(position) ?: CursorRange.collapsed(position)
+                            state.setSelection(anchor)
+                            state.hover.show(position)
+                        },
+                        onDrag = { change, _ ->
+                            val position = textLayout.positionAt(change.position) ?: return@detectDragGesturesAfterLongPress
+                            // Keep the word picked by the long press selected while dragging either way
+                            state.setSelection(
+                                if (position < anchor.start) CursorRange(anchor.end, position) else CursorRange(anchor.start, position)
+                            )
+                        }
+                    )
+                }
+                .pointerInput(state, gutterWidthPx) {
+                    // Resting the mouse on a symbol shows its hover card, e.g. on Chromebooks
+                    coroutineScope {
+                        var pending: Job? = null
+                        awaitPointerEventScope {
+                            while (true) {
+                                val event = awaitPointerEvent()
+                                val change = event.changes.firstOrNull() ?: continue
+                                if (change.type != PointerType.Mouse) continue
+                                pending?.cancel()
+                                // Not while a button is held, e.g. to select
+                                if (event.type != PointerEventType.Move || event.buttons.areAnyPressed) continue
+
+                                val position = if (change.position.x < gutterWidthPx) null else textLayout.positionAt(change.position)
+                                when {
+                                    position == null -> state.hover.dismiss()
+                                    state.hover.isShowingAt(position) -> {}
+                                    else -> {
+                                        state.hover.dismiss()
+                                        pending = launch {
+                                            delay(HOVER_DELAY_MILLIS)
+                                            state.hover.show(position)
                                         }
                                     }
                                 }
+                            }
+                        }
+                    }
+                }
+                .drawBehind {
+                    val textLeft = gutterWidthPx + paddingPx - viewport.horizontalOffset
+                    val caret = selection.end
+                    val rows = textLayout.layoutRows(
+                        viewport = viewport,
+                        displayLines = displayLines,
+                        snapshot = content,
+                        viewportHeight = size.height,
+                        textLeft = textLeft,
+                        tokensOf = tokensOf,
+                        semanticTokensOf = semanticTokensOf
+                    ) { line, _ ->
+                        bracketPair.filter { it.line == line }.mapTo(HashSet()) { it.column }
+                    }
 
-                                // Line Content Box
-                                when (displayLineItem) {
-                                    is DisplayLine.Code -> {
-                                        var textFieldValue by remember(lineIdx, content.getOrNull(lineIdx)) {
-                                            val currentLineText = content.getOrNull(lineIdx) ?: ""
-                                            mutableStateOf(TextFieldValue(currentLineText.highlight(theme, syntaxHighlighter)))
-                                        }
-
-                                        BasicTextField(
-                                            value = textFieldValue,
-                                            onValueChange = {
-                                                var updated = handleAutoIndent(it, textFieldValue)
-                                                updated = handleBracketPairMatch(updated, textFieldValue)
-
-                                                val newText = updated.text // This is String, from TextFieldValue.text
-                                                // state.updateLine needs a String
-                                                state.updateLine(lineIdx, newText)
-
-                                                // textFieldValue needs an AnnotatedString.
-                                                // newText (String) needs to be highlighted to become AnnotatedString.
-                                                val highlightedAnnotatedString = newText.highlight( // Re-highlight after potential structural changes by auto-indent
-                                                        theme = theme,
-                                                        syntaxHighlighter = syntaxHighlighter,
-                                                        bracketIndices = findBracketIndices(updated)
-                                                    )
-
-                                                textFieldValue = TextFieldValue(
-                                                    annotatedString = highlightedAnnotatedString,
-                                                    selection = updated.selection,
-                                                    composition = updated.composition
-                                                )
-                                            },
-                                            singleLine = !softWrap,
-                                            readOnly = !editable,
-                                            textStyle = TextStyle(
-                                                fontSize = fontSize,
-                                                fontFamily = fontFamily,
-                                                color = theme.defaultTextColor,
-                                                lineHeight = fontSize * 1.2f // Ensure consistent line height
-                                            ),
-                                            onTextLayout = {
-                                                sharedLineHeight = it.size.height
-                                            },
-                                            modifier = Modifier
-                                                .fillMaxWidth()
-                                                .padding(horizontal = horizontalPadding)
-                                                .onFocusChanged { focusState ->
-                                                    isLineFocused = focusState.isFocused
-                                                    if (focusState.isFocused) {
-                                                        // Re-highlight with bracket matching when focused
-                                                        textFieldValue = textFieldValue.copy(
-                                                            annotatedString = textFieldValue.annotatedString.highlight(
-                                                                theme = theme,
-                                                                syntaxHighlighter = syntaxHighlighter,
-                                                                bracketIndices = findBracketIndices(textFieldValue)
-                                                            )
-                                                        )
-                                                    } else {
-                                                        // Re-highlight without specific bracket matching when unfocused
-                                                        textFieldValue = textFieldValue.copy(
-                                                            annotatedString = textFieldValue.annotatedString.highlight(
-                                                                theme = theme,
-                                                                syntaxHighlighter = syntaxHighlighter,
-                                                                bracketIndices = emptySet()
-                                                            )
-                                                        )
-                                                    }
-                                                },
-                                            cursorBrush = SolidColor(theme.cursorColor),
-                                            decorationBox = { innerTextField ->
-                                                Box(
-                                                    modifier = Modifier
-                                                        .fillMaxSize()
-                                                        .drawActiveLineColor(isLineFocused, theme, horizontalPadding)
-                                                ) {
-                                                    innerTextField()
-                                                }
-                                            }
-                                        )
-                                    }
-                                    is DisplayLine.FoldedMarker -> {
-                                        val lineText = content.getOrElse(lineIdx) { "" }
-                                        Text(
-                                            text = "$lineText ... (${displayLineItem.numberOfHiddenLines} lines hidden)",
-                                            fontSize = fontSize,
-                                            fontFamily = fontFamily,
-                                            color = theme.defaultTextColor.copy(alpha = 0.7f),
-                                            modifier = Modifier
-                                                .fillMaxWidth()
-                                                .padding(horizontal = horizontalPadding, vertical = ((fontSize.value * 1.2f - fontSize.value) / 2f).dp) // Align with BasicTextField's effective vertical padding
-                                                .heightIn(min = (fontSize.value * 1.2f).dp), // Match BasicTextField line height
-                                            lineHeight = fontSize * 1.2f
-                                        )
-                                    }
+                    clipRect(left = gutterWidthPx) {
+                        for (row in rows) {
+                            if (row.line == caret.line && row.displayLine is DisplayLine.Code) {
+                                drawRect(
+                                    color = theme.activeLineColor,
+                                    topLeft = Offset(gutterWidthPx, row.top),
+                                    size = Size(size.width - gutterWidthPx, row.height)
+                                )
+                            }
+                            translate(textLeft, row.top) {
+                                drawSearchMatches(row, searchMatches, currentSearchMatch, theme)
+                                drawSelections(row, selections, theme)
+                                drawText(row.layout)
+                                if (diagnostics.isNotEmpty()) drawDiagnostics(row, state.diagnostics.diagnosticsOnLine(row.line), theme)
+                                composition?.let { drawCompositionUnderline(row, it, theme) }
+
+                                val marker = row.displayLine as? DisplayLine.FoldedMarker
+                                if (marker != null) {
+                                    val suffix = textLayout.measureLabel(" ... (${marker.numberOfHiddenLines} lines hidden)", foldSuffixStyle)
+                                    val lastLine = row.layout.lineCount - 1
+                                    drawText(suffix, topLeft = Offset(row.layout.getLineRight(lastLine), row.layout.getLineTop(lastLine)))
                                 }
+
+                                if (editable && isFocused) drawCarets(row, selections, theme, caretAlpha)
                             }
                         }
                     }
+
+                    drawGutter(gutterWidthPx, rows, caret.line, foldStartLines, foldedLines, theme, gutterStyle, foldMarkerStyle, textLayout) { line ->
+                        if (diagnostics.isEmpty()) emptyList() else state.diagnostics.diagnosticsOnLine(line)
+                    }
                 }
-            }
+        )
+
+        if (editable) {
+            EditorCompletionPopup(
+                state = state,
+                textLayout = textLayout,
+                viewport = viewport,
+                displayLines = displayLines,
+                snapshot = content,
+                viewportHeight = surfaceSize.height.toFloat(),
+                textLeft = gutterWidthPx + paddingPx,
+                textStyle = textStyle.copy(lineHeight = TextUnit.Unspecified)
+            )
         }
 
+        if (editable) {
+            EditorSignatureHelpPopup(
+                state = state,
+                textLayout = textLayout,
+                viewport = viewport,
+                displayLines = displayLines,
+                snapshot = content,
+                viewportHeight = surfaceSize.height.toFloat(),
+                textLeft = gutterWidthPx + paddingPx,
+                textStyle = textStyle.copy(lineHeight = TextUnit.Unspecified)
+            )
+        }
+
+        EditorHoverPopup(
+            state = state,
+            textLayout = textLayout,
+            viewport = viewport,
+            displayLines = displayLines,
+            snapshot = content,
+            viewportHeight = surfaceSize.height.toFloat(),
+            textLeft = gutterWidthPx + paddingPx,
+            textStyle = textStyle.copy(lineHeight = TextUnit.Unspecified)
+        )
+
+        EditorDiagnosticPopup(
+            state = state,
+            textLayout = textLayout,
+            viewport = viewport,
+            displayLines = displayLines,
+            snapshot = content,
+            viewportHeight = surfaceSize.height.toFloat(),
+            textLeft = gutterWidthPx + paddingPx,
+            textStyle = textStyle.copy(lineHeight = TextUnit.Unspecified)
+        )
+
+        EditorScrollbar(
+            viewport = viewport,
+            rowCount = displayLines.size,
+            visibleRowCount = visibleLineCount,
+            theme = theme
+        )
 
         AnimatedVisibility(
             visible = isLoading,
@@ -400,139 +600,349 @@ fun CodeEditor(
     }
 }
 
-private fun Modifier.drawActiveLineColor(
-    isLineFocused: Boolean, // This should be specific to DisplayLine.Code
-    theme: EditorTheme,
-    horizontalPadding: Dp
-) = this then if (isLineFocused) Modifier.drawWithCache {
-    onDrawBehind {
-        drawRect(
-            color = theme.activeLineColor,
-            topLeft = Offset(-horizontalPadding.toPx() + 1, 0f) // +1 for gutter border width
-        )
-    }
-} else Modifier
-
-
-private fun handleBracketPairMatch(
-    newValue: TextFieldValue,
-    oldValue: TextFieldValue
-): TextFieldValue {
-    val text = newValue.text
-    val cursor = newValue.selection.start
-    val oldCursor = oldValue.selection.start
-    val oldText = oldValue.text
-
-    val typedChar = if (cursor > oldCursor && oldCursor in oldText.indices) {
-        text.getOrNull(oldCursor)
-    } else null
-
-    val isClosingBracket = typedChar in bracketPairs.values
-    val atCursorChar = text.getOrNull(cursor)
-
-    val skipClosing = isClosingBracket && typedChar == atCursorChar &&
-            atCursorChar !in listOf('"', '\'')
-
-    val updatedTextFieldValue = when {
-        // skip over closing bracket if already present
-        skipClosing -> {
-            TextFieldValue(
-                text = oldText,
-                selection = TextRange(cursor + 1),
-                composition = newValue.composition
+// Its own scope, so following the viewport while completion is open doesn't recompose the whole editor
+@Composable
+private fun EditorCompletionPopup(
+    state: CodeEditorState,
+    textLayout: EditorTextLayout,
+    viewport: EditorViewport,
+    displayLines: DisplayLineMapping,
+    snapshot: TextSnapshot,
+    viewportHeight: Float,
+    textLeft: Float,
+    textStyle: TextStyle
+) {
+    val request by state.completion.request.collectAsState()
+    val items by state.completion.items.collectAsState()
+    val selectedIndex by state.completion.selectedIndex.collectAsState()
+    val prefix by state.completion.prefix.collectAsState()
+
+    val start = request?.start ?: return
+    if (items.isEmpty()) return
+    val anchor = textLayout.caretRect(start, snapshot, displayLines, viewport, viewportHeight, textLeft - viewport.horizontalOffset) ?: return
+
+    CompletionPopup(
+        items = items,
+        selectedIndex = selectedIndex,
+        prefix = prefix,
+        theme = state.theme,
+        position = IntOffset(anchor.left.toInt(), anchor.bottom.toInt()),
+        onItemSelected = { state.completion.accept(it) },
+        textStyle = textStyle
+    )
+}
+
+@Composable
+private fun EditorDiagnosticPopup(
+    state: CodeEditorState,
+    textLayout: EditorTextLayout,
+    viewport: EditorViewport,
+    displayLines: DisplayLineMapping,
+    snapshot: TextSnapshot,
+    viewportHeight: Float,
+    textLeft: Float,
+    textStyle: TextStyle
+) {
+    val shown by state.diagnostics.shown.collectAsState()
+    val start = shown.firstOrNull()?.range?.startInclusive ?: return
+    val anchor = textLayout.caretRect(start, snapshot, displayLines, viewport, viewportHeight, textLeft - viewport.horizontalOffset) ?: return
+
+    DiagnosticPopup(
+        diagnostics = shown,
+        theme = state.theme,
+        position = IntOffset(anchor.left.toInt(), anchor.bottom.toInt()),
+        onDismissRequest = { state.diagnostics.hide() },
+        textStyle = textStyle
+    )
+}
+
+@Composable
+private fun EditorHoverPopup(
+    state: CodeEditorState,
+    textLayout: EditorTextLayout,
+    viewport: EditorViewport,
+    displayLines: DisplayLineMapping,
+    snapshot: TextSnapshot,
+    viewportHeight: Float,
+    textLeft: Float,
+    textStyle: TextStyle
+) {
+    val request by state.hover.request.collectAsState()
+    val hover by state.hover.hover.collectAsState()
+    val shown = hover ?: return
+    val position = request?.position ?: return
+    // Below the start of the symbol, like the diagnostic popup
+    val start = shown.range?.startInclusive ?: snapshot.wordRangeAt(position)?.startInclusive ?: position
+    val anchor = textLayout.caretRect(start, snapshot, displayLines, viewport, viewportHeight, textLeft - viewport.horizontalOffset) ?: return
+
+    HoverPopup(
+        hover = shown,
+        theme = state.theme,
+        position = IntOffset(anchor.left.toInt(), anchor.bottom.toInt()),
+        onDismissRequest = { state.hover.dismiss() },
+        textStyle = textStyle
+    )
+}
+
+@Composable
+private fun EditorSignatureHelpPopup(
+    state: CodeEditorState,
+    textLayout: EditorTextLayout,
+    viewport: EditorViewport,
+    displayLines: DisplayLineMapping,
+    snapshot: TextSnapshot,
+    viewportHeight: Float,
+    textLeft: Float,
+    textStyle: TextStyle
+) {
+    val help by state.signatureHelp.help.collectAsState()
+    val selection by state.selection.collectAsState()
+    val shown = help ?: return
+    val anchor = textLayout.caretRect(selection.end, snapshot, displayLines, viewport, viewportHeight, textLeft - viewport.horizontalOffset) ?: return
+
+    SignatureHelpPopup(
+        help = shown,
+        theme = state.theme,
+        anchor = IntRect(anchor.left.toInt(), anchor.top.toInt(), anchor.right.toInt(), anchor.bottom.toInt()),
+        textStyle = textStyle
+    )
+}
+
+private fun DrawScope.drawSelections(row: VisibleRow, selections: List<CursorRange>, theme: EditorTheme) {
+    val layout = row.layout
+    val length = layout.layoutInput.text.length
+
+    for (selection in selections) {
+        val (start, end, pastLineBreak) = selection.columnsOn(row.line, length) ?: continue
+        if (start < end) drawPath(layout.getPathForRange(start, end), theme.selectionColor)
+        if (pastLineBreak) {
+            // Mark the selected line break with a half-char block after the text
+            val lineEnd = layout.getCursorRect(length)
+            drawRect(
+                color = theme.selectionColor,
+                topLeft = Offset(lineEnd.left, lineEnd.top),
+                size = Size(lineEnd.height / 2, lineEnd.height)
             )
         }
+    }
+}
 
-        // insert closing pair only if a new bracket was just typed
-        cursor > 0 &&
-                text.length == oldText.length + 1 &&
-                cursor > oldCursor &&
-                bracketPairs.containsKey(text.getOrNull(cursor - 1)) &&
-                text.getOrNull(cursor) != bracketPairs[text[cursor - 1]] -> {
+private fun DrawScope.drawSearchMatches(row: VisibleRow, matches: List<SearchMatch>, current: Int, theme: EditorTheme) {
+    if (matches.isEmpty()) return
+    val layout = row.layout
+    val length = layout.layoutInput.text.length
 
-            val open = text[cursor - 1]
-            val close = bracketPairs[open]!!
+    // Matches never overlap, so their ends are sorted like their starts
+    var index = matches.binarySearch { it.range.endInclusive.line.compareTo(row.line) }.let { if (it < 0) -it - 1 else it }
+    while (index > 0 && matches[index - 1].range.endInclusive.line == row.line) index--
 
-            val before = text.substring(0, cursor)
-            val after = text.substring(cursor)
+    while (index < matches.size && matches[index].range.startInclusive.line <= row.line) {
+        val columns = matches[index].range.columnsOn(row.line, length)
+        if (columns != null && columns.first < columns.second) {
+            val color = if (index == current) theme.currentSearchMatchColor else theme.searchMatchColor
+            drawPath(layout.getPathForRange(columns.first, columns.second), color)
+        }
+        index++
+    }
+}
 
-            TextFieldValue(
-                text = before + close + after,
-                selection = TextRange(cursor, cursor),
-                composition = newValue.composition
-            )
+private fun DrawScope.drawCompositionUnderline(row: VisibleRow, composition: CursorRange, theme: EditorTheme) {
+    val layout = row.layout
+    val (start, end) = composition.columnsOn(row.line, layout.layoutInput.text.length) ?: return
+    if (start >= end) return
+
+    // One underline per visual line the composing text covers when the line is wrapped
+    for (visualLine in layout.getLineForOffset(start)..layout.getLineForOffset(end)) {
+        val from = maxOf(start, layout.getLineStart(visualLine))
+        val to = minOf(end, layout.getLineEnd(visualLine))
+        if (from >= to) continue
+
+        val y = layout.getLineBottom(visualLine) - 1.dp.toPx()
+        drawLine(
+            color = theme.defaultTextColor,
+            start = Offset(layout.getHorizontalPosition(from, true), y),
+            end = Offset(layout.getHorizontalPosition(to, false), y),
+            strokeWidth = 1.dp.toPx()
+        )
+    }
+}
+
+// Squiggles under errors, warnings and infos, dashes under hints; the most severe are drawn last, on top
+private fun DrawScope.drawDiagnostics(row: VisibleRow, diagnostics: List<Diagnostic>, theme: EditorTheme) {
+    val layout = row.layout
+    val length = layout.layoutInput.text.length
+
+    for (diagnostic in diagnostics.sortedByDescending { it.severity }) {
+        val range = diagnostic.range
+        var start = if (range.startInclusive.line == row.line) range.startInclusive.column.coerceIn(0, length) else 0
+        var end = if (range.endInclusive.line == row.line) range.endInclusive.column.coerceIn(start, length) else length
+        // An empty range marks the char after it, or the one before at the end of a line
+        if (start == end) {
+            if (end < length) end++ else if (start > 0) start--
         }
 
-        else -> newValue
+        for (visualLine in layout.getLineForOffset(start)..layout.getLineForOffset(end)) {
+            val from = maxOf(start, layout.getLineStart(visualLine))
+            val to = minOf(end, layout.getLineEnd(visualLine))
+            if (from > to) continue
+
+            val left = layout.getHorizontalPosition(from, true)
+            val right = maxOf(layout.getHorizontalPosition(to, false), left + 6.dp.toPx())
+            val y = layout.getLineBottom(visualLine) - 1.dp.toPx()
+            val color = theme.diagnosticColor(diagnostic.severity)
+            if (diagnostic.severity == DiagnosticSeverity.HINT) {
+                drawLine(
+                    color = color,
+                    start = Offset(left, y),
+                    end = Offset(right, y),
+                    strokeWidth = 1.dp.toPx(),
+                    pathEffect = PathEffect.dashPathEffect(floatArrayOf(2.dp.toPx(), 2.dp.toPx()))
+                )
+            } else {
+                drawSquiggle(left, right, y, color)
+            }
+        }
     }
-    return updatedTextFieldValue
 }
 
-private fun handleAutoIndent(
-    newValue: TextFieldValue,
-    oldValue: TextFieldValue
-): TextFieldValue {
-    val oldText = oldValue.text
-    val newText = newValue.text
-    val cursor = newValue.selection.start
-    val oldCursor = oldValue.selection.start
-
-    val isNewline = cursor > 0 &&
-            cursor > oldCursor &&
-            newText.length > oldText.length &&
-            newText.getOrNull(cursor - 1) == '\n'
-
-    if (!isNewline) return newValue
-
-    val before = newText.substring(0, cursor)
-    val after = newText.substring(cursor)
-
-    val lines = before.lines()
-    val prevLine = lines.getOrNull(lines.size - 2) ?: ""
-    val indentMatch = Regex("^[ \t]*").find(prevLine)
-    val baseIndent = indentMatch?.value ?: ""
-
-    val trimmedPrev = prevLine.trimEnd()
-    val extraIndent = if (trimmedPrev.endsWith("{") || trimmedPrev.endsWith("[") || trimmedPrev.endsWith("(")) {
-        "    "
-    } else ""
-
-    val currentIndent = baseIndent + extraIndent
-    val nextChar = after.firstOrNull()
-
-    val isClosingBracket = nextChar in listOf(')', '}', ']')
-
-    if (isClosingBracket && currentIndent.length >= 4) {
-        val closingLine = baseIndent + nextChar + after.drop(1)
-        val finalText = before + currentIndent + "\n" + closingLine
-        val finalCursor = (before + currentIndent).length
-
-        return TextFieldValue(
-            text = finalText,
-            selection = TextRange(finalCursor),
-            composition = newValue.composition
+private fun DrawScope.drawSquiggle(left: Float, right: Float, y: Float, color: Color) {
+    val halfWave = 2.dp.toPx()
+    val height = 2.dp.toPx()
+    val path = Path().apply {
+        moveTo(left, y)
+        var x = left
+        var up = true
+        while (x < right) {
+            x = minOf(x + halfWave, right)
+            lineTo(x, if (up) y - height else y)
+            up = !up
+        }
+    }
+    drawPath(path, color, style = Stroke(width = 1.dp.toPx()))
+}
+
+private fun DrawScope.drawCarets(row: VisibleRow, selections: List<CursorRange>, theme: EditorTheme, alpha: Float) {
+    if (row.displayLine is DisplayLine.FoldedMarker) return
+    val length = row.layout.layoutInput.text.length
+
+    for (selection in selections) {
+        if (selection.end.line != row.line) continue
+        val caret = row.layout.getCursorRect(selection.end.column.coerceIn(0, length))
+        drawLine(
+            color = theme.cursorColor.copy(alpha = alpha),
+            start = Offset(caret.left, caret.top),
+            end = Offset(caret.left, caret.bottom),
+            strokeWidth = 2.dp.toPx()
         )
     }
+}
 
-    val finalText = before + currentIndent + after
-    val finalCursor = cursor + currentIndent.length
+private fun DrawScope.drawGutter(
+    width: Float,
+    rows: List<VisibleRow>,
+    activeLine: Int,
+    foldStartLines: Set<Int>,
+    foldedLines: Set<Int>,
+    theme: EditorTheme,
+    numberStyle: TextStyle,
+    foldMarkerStyle: TextStyle,
+    textLayout: EditorTextLayout,
+    diagnosticsOnLine: (Int) -> List<Diagnostic>
+) {
+    drawRect(color = theme.gutterBgColor, size = Size(width, size.height))
+    // Icons line up left of the widest line number on screen
+    val numberWidth = rows.maxOfOrNull { textLayout.measureLabel((it.line + 1).toString(), numberStyle).size.width } ?: 0
+    val iconRadius = 3.5.dp.toPx()
+    val iconX = width - 3.dp.toPx() - numberWidth - 3.dp.toPx() - iconRadius
+
+    for (row in rows) {
+        if (row.line == activeLine && row.displayLine is DisplayLine.Code) {
+            drawRect(color = theme.activeLineColor, topLeft = Offset(0f, row.top), size = Size(width, row.height))
+        }
+
+        val number = textLayout.measureLabel((row.line + 1).toString(), numberStyle)
+        drawText(number, topLeft = Offset(width - 3.dp.toPx() - number.size.width, row.top))
+
+        // The most severe diagnostic starting on the line; hints get no icon
+        val severity = diagnosticsOnLine(row.line)
+            .filter { it.range.startInclusive.line == row.line && it.severity != DiagnosticSeverity.HINT }
+            .minOfOrNull { it.severity }
+        if (severity != null) {
+            drawDiagnosticIcon(Offset(iconX, row.top + textLayout.lineHeight / 2), iconRadius, severity, theme)
+        }
 
-    return TextFieldValue(
-        text = finalText,
-        selection = TextRange(finalCursor),
-        composition = newValue.composition
+        if (row.line in foldStartLines) {
+            val marker = textLayout.measureLabel(if (row.line in foldedLines) "[+]" else "[-]", foldMarkerStyle)
+            drawText(marker, topLeft = Offset(2.dp.toPx(), row.top + (textLayout.lineHeight - marker.size.height) / 2))
+        }
+    }
+
+    drawLine(
+        color = theme.gutterBorderColor,
+        start = Offset(width, 0f),
+        end = Offset(width, size.height),
+        strokeWidth = 1f
     )
 }
 
-private fun findBracketIndices(textFieldValue: TextFieldValue): Set<Int> {
-    val cursorPos = textFieldValue.selection.start
-    val text = textFieldValue.text
-    val brackets = setOf('(', ')', '{', '}', '[', ']')
+// A dot for errors, a triangle for warnings and a ring for infos
+private fun DrawScope.drawDiagnosticIcon(center: Offset, radius: Float, severity: DiagnosticSeverity, theme: EditorTheme) {
+    val color = theme.diagnosticColor(severity)
+    when (severity) {
+        DiagnosticSeverity.ERROR -> drawCircle(color, radius, center)
+        DiagnosticSeverity.WARNING -> drawPath(
+            Path().apply {
+                moveTo(center.x, center.y - radius)
+                lineTo(center.x + radius, center.y + radius * 0.8f)
+                lineTo(center.x - radius, center.y + radius * 0.8f)
+                close()
+            },
+            color
+        )
+        else -> drawCircle(color, radius - 0.5.dp.toPx(), center, style = Stroke(width = 1.dp.toPx()))
+    }
+}
+
+// Selected columns [start, end) on a line, and whether the selection continues past its line break
+private fun CursorRange.columnsOn(line: Int, lineLength: Int): Triple<Int, Int, Boolean>? {
+    val from = startInclusive
+    val to = endInclusive
+    if (isCollapsed || line < from.line || line > to.line) return null
+
+    val start = if (line == from.line) from.column.coerceIn(0, lineLength) else 0
+    val end = if (line == to.line) to.column.coerceIn(start, lineLength) else lineLength
+    return Triple(start, end, line < to.line)
+}
+
+// Runs the command bound to the key, or types the char it produces. Keys for picking a completion come first.
+private fun handleKeyEvent(
+    state: CodeEditorState,
+    event: KeyEvent,
+    keymap: Keymap,
+    visibleLineCount: Int,
+    clipboard: EditorClipboard
+): Boolean {
+    if (state.completion.isShowing && handleCompletionKey(state.completion, event)) return true
+    if (keymap.dispatch(event, state, visibleLineCount, clipboard)) return true
+    if (event.type != KeyEventType.KeyDown || event.isCtrlPressed || event.isAltPressed || event.isMetaPressed) return false
+
+    val codePoint = event.utf16CodePoint
+    if (codePoint == 0) return false
+    val text = String(Character.toChars(codePoint))
+    if (!text[0].isPrintable()) return false
+
+    state.typeText(text)
+    return true
+}
 
-    val bracketIndices = when {
-        cursorPos < text.length && text[cursorPos] in brackets -> findBracketPairIndices(text, cursorPos)
-        cursorPos > 0 && text[cursorPos - 1] in brackets -> findBracketPairIndices(text, cursorPos - 1)
-        else -> emptySet()
+private fun handleCompletionKey(completion: EditorCompletion, event: KeyEvent): Boolean {
+    if (event.type != KeyEventType.KeyDown || event.isCtrlPressed || event.isAltPressed || event.isMetaPressed || event.isShiftPressed) return false
+    when (event.key) {
+        Key.DirectionUp -> completion.selectPrevious()
+        Key.DirectionDown -> completion.selectNext()
+        Key.Enter, Key.NumPadEnter, Key.Tab -> completion.accept()
+        Key.Escape -> completion.dismiss()
+        else -> return false
     }
-    return bracketIndices
+    return true
 }
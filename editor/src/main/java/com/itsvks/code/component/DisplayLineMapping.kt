@@ -0,0 +1,105 @@
+package com.itsvks.code.component
+
+import com.itsvks.code.core.FoldableRange
+
+/**
+ * Maps display rows to document lines without materializing a row per line.
+ *
+ * Only the folded ranges are stored, so building a mapping is O(folds) and each lookup is a binary
+ * search, which keeps the editor independent of the document's line count.
+ */
+internal class DisplayLineMapping(
+    private val lineCount: Int,
+    foldedLines: Set<Int>,
+    foldableRanges: List<FoldableRange>
+) {
+    // Outermost folded ranges, sorted by start line
+    private val folds: List<FoldableRange>
+
+    // hiddenBefore[i] = number of lines hidden by folds[0 until i]
+    private val hiddenBefore: IntArray
+
+    val size: Int
+
+    init {
+        val active = mutableListOf<FoldableRange>()
+        if (foldedLines.isNotEmpty()) {
+            for (range in foldableRanges.sortedWith(compareBy({ it.startLine }, { -it.endLine }))) {
+                if (range.startLine !in foldedLines || range.endLine >= lineCount) continue
+                val last = active.lastOrNull()
+                if (last != null && range.startLine <= last.endLine) continue // Hidden by the previous fold
+                active.add(range)
+            }
+        }
+        folds = active
+
+        hiddenBefore = IntArray(active.size + 1)
+        for (i in active.indices) {
+            hiddenBefore[i + 1] = hiddenBefore[i] + active[i].hiddenLineCount
+        }
+        size = lineCount - hiddenBefore[active.size]
+    }
+
+    operator fun get(displayIndex: Int): DisplayLine {
+        val foldIndex = foldAtOrBefore(displayIndex)
+        if (foldIndex < 0) return DisplayLine.Code(displayIndex)
+
+        val fold = folds[foldIndex]
+        return if (displayIndex == displayIndexOfFold(foldIndex)) {
+            DisplayLine.FoldedMarker(
+                originalLineIndex = fold.startLine,
+                endLine = fold.endLine,
+                numberOfHiddenLines = fold.hiddenLineCount
+            )
+        } else {
+            DisplayLine.Code(displayIndex + hiddenBefore[foldIndex + 1])
+        }
+    }
+
+    /**
+     * Display row showing [line]; lines hidden inside a fold map to the fold's marker row.
+     */
+    fun displayIndexOf(line: Int): Int {
+        var low = 0
+        var high = folds.size - 1
+        var foldIndex = -1
+        while (low <= high) {
+            val mid = (low + high) ushr 1
+            if (folds[mid].startLine <= line) {
+                foldIndex = mid
+                low = mid + 1
+            } else {
+                high = mid - 1
+            }
+        }
+
+        if (foldIndex < 0) return line
+        val fold = folds[foldIndex]
+        return if (line <= fold.endLine) {
+            displayIndexOfFold(foldIndex)
+        } else {
+            line - hiddenBefore[foldIndex + 1]
+        }
+    }
+
+    private fun displayIndexOfFold(foldIndex: Int) = folds[foldIndex].startLine - hiddenBefore[foldIndex]
+
+    // Last fold whose marker row is at or before displayIndex
+    private fun foldAtOrBefore(displayIndex: Int): Int {
+        var low = 0
+        var high = folds.size - 1
+        var result = -1
+        while (low <= high) {
+            val mid = (low + high) ushr 1
+            if (displayIndexOfFold(mid) <= displayIndex) {
+                result = mid
+                low = mid + 1
+            } else {
+                high = mid - 1
+            }
+        }
+        return result
+    }
+
+    private val FoldableRange.hiddenLineCount get() = endLine - startLine
+}
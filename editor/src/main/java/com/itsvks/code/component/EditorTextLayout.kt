@@ -0,0 +1,204 @@
+package com.itsvks.code.component
+
+import androidx.compose.ui.geometry.Offset
+import androidx.compose.ui.geometry.Rect
+import androidx.compose.ui.text.AnnotatedString
+import androidx.compose.ui.text.TextLayoutResult
+import androidx.compose.ui.text.TextMeasurer
+import androidx.compose.ui.text.TextStyle
+import androidx.compose.ui.unit.Constraints
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.LruCache
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.language.Language
+import com.itsvks.code.syntax.SemanticToken
+import com.itsvks.code.syntax.Token
+import com.itsvks.code.theme.EditorTheme
+import com.itsvks.code.theme.highlight
+
+private const val LINE_CACHE_SIZE = 512
+private const val LABEL_CACHE_SIZE = 256
+
+/**
+ * A display row laid out on the editor surface. [top] is relative to the top of the viewport.
+ */
+internal class VisibleRow(
+    val displayIndex: Int,
+    val displayLine: DisplayLine,
+    val top: Float,
+    val layout: TextLayoutResult,
+    val height: Float
+) {
+    val line get() = displayLine.originalLineIndex
+    val bottom get() = top + height
+}
+
+/**
+ * Lays out document lines for the editor surface and maps between surface coordinates and
+ * document positions.
+ *
+ * Layouts are cached by line text, so scrolling and edits elsewhere in the document reuse them;
+ * changing the style, theme, language or wrap width starts over.
+ */
+internal class EditorTextLayout(private val textMeasurer: TextMeasurer) {
+    private val cache = LruCache<LineKey, TextLayoutResult>(LINE_CACHE_SIZE)
+    private val labelCache = LruCache<Pair<String, TextStyle>, TextLayoutResult>(LABEL_CACHE_SIZE)
+
+    private var style = TextStyle.Default
+    private var theme: EditorTheme? = null
+    private var language: Language? = null
+    private var wrapWidth: Int? = null
+
+    var lineHeight = 0f
+        private set
+
+    var charWidth = 0f
+        private set
+
+    // Rows from the last frame, used for hit testing so taps map to what is on screen
+    var rows: List<VisibleRow> = emptyList()
+        private set
+
+    // X of column 0 on the surface, after horizontal scrolling
+    var textLeft = 0f
+        private set
+
+    private data class LineKey(
+        val text: String,
+        val tokens: List<Token>,
+        val bracketIndices: Set<Int>,
+        val semanticTokens: List<SemanticToken>
+    )
+
+    fun update(style: TextStyle, theme: EditorTheme, language: Language, wrapWidth: Int?) {
+        if (style == this.style && theme == this.theme && language == this.language && wrapWidth == this.wrapWidth) return
+
+        this.style = style
+        this.theme = theme
+        this.language = language
+        this.wrapWidth = wrapWidth
+        cache.clear()
+        labelCache.clear()
+
+        val sample = textMeasurer.measure("M", style)
+        lineHeight = sample.size.height.toFloat()
+        charWidth = sample.size.width.toFloat()
+    }
+
+    /**
+     * Layout of [text], a single document line, styled by [tokens] and then [semanticTokens].
+     * [bracketIndices] are emphasized as a matching pair.
+     */
+    fun measureLine(
+        text: String,
+        tokens: List<Token> = emptyList(),
+        bracketIndices: Set<Int> = emptySet(),
+        semanticTokens: List<SemanticToken> = emptyList()
+    ): TextLayoutResult {
+        val key = LineKey(text, tokens, bracketIndices, semanticTokens)
+        return cache.getOrPut(key) {
+            val annotated = theme?.let { text.highlight(it, tokens, bracketIndices, semanticTokens) } ?: AnnotatedString(text)
+            measure(annotated, style)
+        }
+    }
+
+    private fun measure(text: AnnotatedString, style: TextStyle): TextLayoutResult {
+        val width = wrapWidth
+        return textMeasurer.measure(
+            text = text,
+            style = style,
+            softWrap = width != null,
+            constraints = if (width != null) Constraints(maxWidth = width.coerceAtLeast(0)) else Constraints()
+        )
+    }
+
+    /**
+     * Layout of a short single-line label such as a line number, never wrapped.
+     */
+    fun measureLabel(text: String, style: TextStyle): TextLayoutResult {
+        return labelCache.getOrPut(text to style) { textMeasurer.measure(text, style, softWrap = false) }
+    }
+
+    fun rowHeight(displayLine: DisplayLine, snapshot: TextSnapshot): Float {
+        if (wrapWidth == null || displayLine is DisplayLine.FoldedMarker) return lineHeight
+        val text = snapshot.getLineOrNull(displayLine.originalLineIndex) ?: return lineHeight
+        return measureLine(text).size.height.toFloat().coerceAtLeast(lineHeight)
+    }
+
+    /**
+     * Lays out the rows that intersect a viewport [viewportHeight] pixels high.
+     */
+    fun layoutRows(
+        viewport: EditorViewport,
+        displayLines: DisplayLineMapping,
+        snapshot: TextSnapshot,
+        viewportHeight: Float,
+        textLeft: Float,
+        tokensOf: (line: Int) -> List<Token>,
+        semanticTokensOf: (line: Int) -> List<SemanticToken> = { emptyList() },
+        bracketIndicesOf: (line: Int, text: String) -> Set<Int>
+    ): List<VisibleRow> {
+        val result = mutableListOf<VisibleRow>()
+        var top = -viewport.firstRowOffset
+        var displayIndex = viewport.firstVisibleRow
+
+        while (displayIndex < displayLines.size && top < viewportHeight) {
+            val displayLine = displayLines[displayIndex]
+            val text = snapshot.getLineOrNull(displayLine.originalLineIndex) ?: ""
+            val line = displayLine.originalLineIndex
+            val layout = measureLine(text, tokensOf(line), bracketIndicesOf(line, text), semanticTokensOf(line))
+            val height = if (displayLine is DisplayLine.FoldedMarker) lineHeight else layout.size.height.toFloat().coerceAtLeast(lineHeight)
+
+            result.add(VisibleRow(displayIndex, displayLine, top, layout, height))
+            top += height
+            displayIndex++
+        }
+
+        rows = result
+        this.textLeft = textLeft
+        return result
+    }
+
+    fun rowAt(y: Float): VisibleRow? {
+        val visible = rows
+        if (visible.isEmpty()) return null
+        if (y < visible.first().top) return visible.first()
+        return visible.firstOrNull { y < it.bottom } ?: visible.last()
+    }
+
+    /**
+     * Where a caret at [position] is on the surface, or null if its line is folded away or scrolled
+     * out of view. Unlike [rows] this follows the viewport right away, not after the next frame.
+     */
+    fun caretRect(
+        position: CursorPosition,
+        snapshot: TextSnapshot,
+        displayLines: DisplayLineMapping,
+        viewport: EditorViewport,
+        viewportHeight: Float,
+        textLeft: Float
+    ): Rect? {
+        val displayIndex = displayLines.displayIndexOf(position.line)
+        if (displayIndex < viewport.firstVisibleRow || displayIndex >= displayLines.size) return null
+        if (displayLines[displayIndex] !is DisplayLine.Code) return null
+
+        var top = -viewport.firstRowOffset
+        for (row in viewport.firstVisibleRow until displayIndex) {
+            top += rowHeight(displayLines[row], snapshot)
+            if (top >= viewportHeight) return null
+        }
+        val text = snapshot.getLineOrNull(position.line) ?: return null
+        val rect = measureLine(text).getCursorRect(position.column.coerceIn(0, text.length))
+        return rect.translate(textLeft, top)
+    }
+
+    /**
+     * Document position under [offset] on the surface, or null before the first frame.
+     */
+    fun positionAt(offset: Offset): CursorPosition? {
+        val row = rowAt(offset.y) ?: return null
+        val local = Offset(offset.x - textLeft, (offset.y - row.top).coerceIn(0f, row.height - 1f))
+        val column = row.layout.getOffsetForPosition(local)
+        return CursorPosition(row.line, column.coerceAtMost(row.layout.layoutInput.text.length))
+    }
+}
@@ -0,0 +1,111 @@
+package com.itsvks.code.component
+
+import androidx.compose.runtime.getValue
+import androidx.compose.runtime.mutableFloatStateOf
+import androidx.compose.runtime.mutableIntStateOf
+import androidx.compose.runtime.setValue
+
+/**
+ * Scroll position of the editor surface.
+ *
+ * The vertical position is anchored to a display row plus a pixel offset into it, like a lazy list,
+ * so scrolling only ever measures the rows it passes over and never the whole document.
+ */
+internal class EditorViewport {
+    var firstVisibleRow by mutableIntStateOf(0)
+        private set
+
+    // Pixels of the first visible row scrolled out of view, always in [0, its height)
+    var firstRowOffset by mutableFloatStateOf(0f)
+        private set
+
+    var horizontalOffset by mutableFloatStateOf(0f)
+        private set
+
+    /**
+     * Scrolls the content up by [delta] pixels (down for negative values) and returns how much was
+     * actually scrolled. The last row can be scrolled up to the top of the viewport but not past it.
+     */
+    fun scrollBy(delta: Float, rowCount: Int, heightOf: (Int) -> Float): Float {
+        if (rowCount == 0) return 0f
+
+        var row = firstVisibleRow.coerceIn(0, rowCount - 1)
+        var offset = firstRowOffset + delta
+        var consumed = delta
+
+        while (offset < 0f && row > 0) {
+            row--
+            offset += heightOf(row)
+        }
+        if (offset < 0f) {
+            consumed -= offset
+            offset = 0f
+        }
+        while (row < rowCount - 1 && offset >= heightOf(row)) {
+            offset -= heightOf(row)
+            row++
+        }
+        if (row == rowCount - 1 && offset > 0f) {
+            consumed -= offset
+            offset = 0f
+        }
+
+        firstVisibleRow = row
+        firstRowOffset = offset
+        return consumed
+    }
+
+    fun scrollHorizontallyBy(delta: Float, maxOffset: Float): Float {
+        val old = horizontalOffset
+        horizontalOffset = (old + delta).coerceIn(0f, maxOffset.coerceAtLeast(0f))
+        return horizontalOffset - old
+    }
+
+    fun scrollToRow(row: Int, rowCount: Int) {
+        firstVisibleRow = row.coerceIn(0, (rowCount - 1).coerceAtLeast(0))
+        firstRowOffset = 0f
+    }
+
+    /**
+     * Scrolls the least amount needed to show all of [row] in a viewport [viewportHeight] pixels high.
+     */
+    fun ensureRowVisible(row: Int, rowCount: Int, viewportHeight: Float, heightOf: (Int) -> Float) {
+        if (rowCount == 0) return
+        if (row < firstVisibleRow || row == firstVisibleRow && firstRowOffset > 0f) {
+            scrollToRow(row, rowCount)
+            return
+        }
+
+        var bottom = -firstRowOffset
+        var current = firstVisibleRow
+        while (current <= row && bottom <= viewportHeight) {
+            bottom += heightOf(current)
+            current++
+        }
+
+        if (current <= row) {
+            // Far below the viewport: put the row at the bottom without measuring everything in between
+            scrollToRow(row, rowCount)
+            scrollBy(-(viewportHeight - heightOf(row)), rowCount, heightOf)
+        } else if (bottom > viewportHeight) {
+            scrollBy(bottom - viewportHeight, rowCount, heightOf)
+        }
+    }
+
+    /**
+     * Scrolls horizontally so that the span `[left, right]` of the text is inside a viewport [width] pixels wide.
+     */
+    fun ensureHorizontallyVisible(left: Float, right: Float, width: Float) {
+        if (left < horizontalOffset) {
+            horizontalOffset = left.coerceAtLeast(0f)
+        } else if (right > horizontalOffset + width) {
+            horizontalOffset = right - width
+        }
+    }
+
+    fun reset() {
+        firstVisibleRow = 0
+        firstRowOffset = 0f
+        horizontalOffset = 0f
+    }
+}
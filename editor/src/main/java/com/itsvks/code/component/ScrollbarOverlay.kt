@@ -1,6 +1,7 @@
 package com.itsvks.code.component
 
 import androidx.compose.foundation.Canvas
+import androidx.compose.foundation.gestures.detectVerticalDragGestures
 import androidx.compose.foundation.layout.BoxScope
 import androidx.compose.foundation.layout.fillMaxHeight
 import androidx.compose.foundation.layout.padding
@@ -9,14 +10,18 @@ import androidx.compose.foundation.lazy.LazyListState
 import androidx.compose.runtime.Composable
 import androidx.compose.runtime.derivedStateOf
 import androidx.compose.runtime.getValue
+import androidx.compose.runtime.mutableStateOf
 import androidx.compose.runtime.remember
+import androidx.compose.runtime.setValue
 import androidx.compose.ui.Alignment
 import androidx.compose.ui.Modifier
 import androidx.compose.ui.geometry.CornerRadius
 import androidx.compose.ui.geometry.Offset
 import androidx.compose.ui.geometry.Size
 import androidx.compose.ui.graphics.Color
+import androidx.compose.ui.input.pointer.pointerInput
 import androidx.compose.ui.unit.dp
+import com.itsvks.code.theme.EditorTheme
 
 @Composable
 fun BoxScope.ScrollbarOverlay(listState: LazyListState) {
@@ -58,3 +63,43 @@ fun BoxScope.ScrollbarOverlay(listState: LazyListState) {
         }
     }
 }
+
+/**
+ * Draggable vertical scrollbar for the editor surface.
+ */
+@Composable
+internal fun BoxScope.EditorScrollbar(
+    viewport: EditorViewport,
+    rowCount: Int,
+    visibleRowCount: Int,
+    theme: EditorTheme
+) {
+    if (rowCount <= visibleRowCount || rowCount <= 1) return
+    var isDragging by remember { mutableStateOf(false) }
+
+    Canvas(
+        modifier = Modifier
+            .fillMaxHeight()
+            .width(16.dp)
+            .align(Alignment.CenterEnd)
+            .pointerInput(rowCount) {
+                detectVerticalDragGestures(
+                    onDragStart = { isDragging = true },
+                    onDragEnd = { isDragging = false },
+                    onDragCancel = { isDragging = false }
+                ) { change, _ ->
+                    val fraction = (change.position.y / size.height).coerceIn(0f, 1f)
+                    viewport.scrollToRow((fraction * (rowCount - 1)).toInt(), rowCount)
+                }
+            }
+    ) {
+        val thumbWidth = 10.dp.toPx()
+        val thumbHeight = (size.height * visibleRowCount / rowCount).coerceAtLeast(24.dp.toPx())
+        val thumbTop = (size.height - thumbHeight) * viewport.firstVisibleRow / (rowCount - 1)
+        drawRect(
+            color = if (isDragging) theme.scrollBarSelectedColor else theme.scrollBarColor,
+            topLeft = Offset(size.width - thumbWidth, thumbTop),
+            size = Size(thumbWidth, thumbHeight)
+        )
+    }
+}
@@ -17,7 +17,20 @@ data class CursorPosition(val line: Int, val column: Int) : Comparable<CursorPos
     //operator fun plus(other: CursorPosition): CursorPosition = CursorPosition(line + other.line, column + other.column)
 }
 
+/**
+ * A selection in document coordinates. [start] is the anchor and [end] is where the caret is, so
+ * [start] can come after [end] when selecting backwards.
+ */
 data class CursorRange(val start: CursorPosition, val end: CursorPosition) {
+    companion object {
+        val Zero = CursorRange(CursorPosition.Zero, CursorPosition.Zero)
+
+        fun collapsed(position: CursorPosition) = CursorRange(position, position)
+    }
+
+    val isCollapsed: Boolean
+        get() = start == end
+
     val endInclusive: CursorPosition
         get() = if (start <= end) end else start
 
@@ -25,8 +38,7 @@ data class CursorRange(val start: CursorPosition, val end: CursorPosition) {
         get() = if (start <= end) start else end
 
     operator fun contains(position: CursorPosition): Boolean {
-        return start.line <= position.line && position.line <= end.line &&
-                start.column <= position.column && position.column <= end.column
+        return position >= startInclusive && position <= endInclusive
     }
 
     private fun compareCursorPositions(): Int {
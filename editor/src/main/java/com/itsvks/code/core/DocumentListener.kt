@@ -0,0 +1,17 @@
+package com.itsvks.code.core
+
+/**
+ * Notified of every change to a document, synchronously and in order, e.g. to keep a language
+ * server in sync. Called on the thread that edits the document.
+ */
+interface DocumentListener {
+    /**
+     * [change] was applied, giving [snapshot].
+     */
+    fun onChange(change: TextChange, snapshot: TextSnapshot)
+
+    /**
+     * The whole document was replaced by [snapshot], e.g. by loading a file.
+     */
+    fun onReset(snapshot: TextSnapshot) {}
+}
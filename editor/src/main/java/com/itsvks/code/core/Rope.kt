@@ -0,0 +1,547 @@
+package com.itsvks.code.core
+
+import java.io.File
+import java.io.InputStream
+import java.io.Reader
+import java.nio.charset.Charset
+
+/**
+ * Immutable, height-balanced rope.
+ *
+ * Every node caches its length, its number of line breaks and the line length metrics used by
+ * [maxLineLength], so inserts, removals and line lookups are O(log n) regardless of file size.
+ *
+ * Note that [lineCount] is the number of `'\n'` characters; a rope always has `lineCount + 1` lines.
+ */
+sealed class Rope {
+    abstract val length: Int
+    abstract val lineCount: Int
+
+    internal abstract val depth: Int
+
+    // Characters before the first line break (the whole length if there is none)
+    internal abstract val firstLineLength: Int
+
+    // Characters after the last line break
+    internal abstract val lastLineLength: Int
+
+    // Longest line, line breaks excluded
+    internal abstract val longestLine: Int
+
+    class Leaf(val text: String) : Rope() {
+        override val length: Int get() = text.length
+        override val depth: Int get() = 0
+        override val lineCount: Int
+        override val firstLineLength: Int
+        override val lastLineLength: Int
+        override val longestLine: Int
+
+        init {
+            var newlines = 0
+            var firstBreak = -1
+            var lineStart = 0
+            var longest = 0
+
+            for (i in text.indices) {
+                if (text[i] == '\n') {
+                    if (firstBreak < 0) firstBreak = i
+                    longest = maxOf(longest, i - lineStart)
+                    lineStart = i + 1
+                    newlines++
+                }
+            }
+
+            lineCount = newlines
+            firstLineLength = if (firstBreak < 0) text.length else firstBreak
+            lastLineLength = text.length - lineStart
+            longestLine = maxOf(longest, lastLineLength)
+        }
+    }
+
+    class Node(val left: Rope, val right: Rope) : Rope() {
+        override val length = left.length + right.length
+        override val lineCount = left.lineCount + right.lineCount
+        override val depth = maxOf(left.depth, right.depth) + 1
+
+        override val firstLineLength = if (left.lineCount == 0) {
+            left.length + right.firstLineLength
+        } else left.firstLineLength
+
+        override val lastLineLength = if (right.lineCount == 0) {
+            left.lastLineLength + right.length
+        } else right.lastLineLength
+
+        override val longestLine = maxOf(
+            left.longestLine,
+            right.longestLine,
+            left.lastLineLength + right.firstLineLength
+        )
+    }
+
+    operator fun get(index: Int): Char {
+        require(index in 0 until length) { "Index $index out of bounds for length $length" }
+
+        var node: Rope = this
+        var i = index
+        while (node is Node) {
+            if (i < node.left.length) {
+                node = node.left
+            } else {
+                i -= node.left.length
+                node = node.right
+            }
+        }
+        return (node as Leaf).text[i]
+    }
+
+    fun charAt(index: Int) = get(index)
+
+    fun isEmpty() = length == 0
+
+    fun isNotEmpty() = length != 0
+
+    fun slice(start: Int, end: Int): Rope {
+        require(start in 0..end && end <= length) { "Invalid slice [$start, $end) for length $length" }
+        if (start == 0 && end == length) return this
+
+        return when (this) {
+            is Leaf -> Leaf(text.substring(start, end))
+            is Node -> {
+                val leftLength = left.length
+                when {
+                    end <= leftLength -> left.slice(start, end)
+                    start >= leftLength -> right.slice(start - leftLength, end - leftLength)
+                    else -> concat(left.slice(start, leftLength), right.slice(0, end - leftLength))
+                }
+            }
+        }
+    }
+
+    fun slice(range: IntRange) = slice(range.first, range.last + 1)
+
+    fun split(index: Int): Pair<Rope, Rope> {
+        require(index in 0..length) { "Split index $index out of bounds for length $length" }
+        return slice(0, index) to slice(index, length)
+    }
+
+    fun insert(index: Int, text: String): Rope {
+        require(index in 0..length) { "Insert index $index out of bounds for length $length" }
+        if (text.isEmpty()) return this
+
+        return when (this) {
+            is Leaf -> {
+                val combined = this.text.substring(0, index) + text + this.text.substring(index)
+                if (combined.length <= MAX_LEAF_SIZE) Leaf(combined) else fromString(combined)
+            }
+
+            is Node -> if (index <= left.length) {
+                concat(left.insert(index, text), right)
+            } else {
+                concat(left, right.insert(index - left.length, text))
+            }
+        }
+    }
+
+    fun insert(index: Int, rope: Rope): Rope {
+        require(index in 0..length) { "Insert index $index out of bounds for length $length" }
+        return concat(concat(slice(0, index), rope), slice(index, length))
+    }
+
+    fun remove(start: Int, end: Int): Rope {
+        require(start in 0..end && end <= length) { "Invalid removal [$start, $end) for length $length" }
+        if (start == end) return this
+        return concat(slice(0, start), slice(end, length))
+    }
+
+    fun remove(range: IntRange) = remove(range.first, range.last + 1)
+
+    fun replace(start: Int, end: Int, text: String) = remove(start, end).insert(start, text)
+
+    operator fun plus(other: Rope) = concat(this, other)
+
+    /**
+     * Ropes are immutable, so a clone simply shares the existing structure.
+     */
+    fun clone(): Rope = this
+
+    /**
+     * Char index of the first character of [line].
+     */
+    fun lineStartIndex(line: Int): Int {
+        require(line in 0..lineCount) { "Line $line out of bounds for $lineCount line breaks" }
+        return if (line == 0) 0 else lineBreakIndex(line - 1) + 1
+    }
+
+    /**
+     * Char index just past the end of [line], including its line break if it has one.
+     */
+    fun lineEndIndex(line: Int): Int {
+        require(line in 0..lineCount) { "Line $line out of bounds for $lineCount line breaks" }
+        return if (line < lineCount) lineBreakIndex(line) + 1 else length
+    }
+
+    fun line(index: Int): Rope = slice(lineStartIndex(index), lineEndIndex(index))
+
+    fun lineLength(line: Int) = lineEndIndex(line) - lineStartIndex(line)
+
+    fun maxLineLength() = longestLine
+
+    fun lineColumnToCharIndex(line: Int, column: Int): Int {
+        require(column >= 0) { "Column must be non-negative" }
+        return lineStartIndex(line) + column
+    }
+
+    fun lineColumnToCharIndex(position: CursorPosition) = lineColumnToCharIndex(position.line, position.column)
+
+    /**
+     * Line containing the char at [index]. An index equal to [length] belongs to the last line.
+     */
+    fun lineOfCharIndex(index: Int): Int {
+        require(index in 0..length) { "Index $index out of bounds for length $length" }
+
+        var node: Rope = this
+        var i = index
+        var line = 0
+        while (node is Node) {
+            if (i < node.left.length) {
+                node = node.left
+            } else {
+                i -= node.left.length
+                line += node.left.lineCount
+                node = node.right
+            }
+        }
+
+        val text = (node as Leaf).text
+        for (c in 0 until i) {
+            if (text[c] == '\n') line++
+        }
+        return line
+    }
+
+    fun charIndexToLineColumn(index: Int): CursorPosition {
+        val line = lineOfCharIndex(index)
+        return CursorPosition(line, index - lineStartIndex(line))
+    }
+
+    fun indexOf(char: Char, startIndex: Int = 0): Int {
+        val from = startIndex.coerceAtLeast(0)
+        var result = -1
+
+        visitLeaves(from, length, 0, reverse = false) { leaf, offset ->
+            val i = leaf.text.indexOf(char, maxOf(0, from - offset))
+            if (i >= 0) result = offset + i
+            i < 0
+        }
+        return result
+    }
+
+    fun indexOf(string: String, startIndex: Int = 0): Int {
+        val from = startIndex.coerceAtLeast(0)
+        if (from > length) return -1
+        if (string.isEmpty()) return from
+        if (string.length > length - from) return -1
+
+        val table = string.partialMatchTable()
+        var matched = 0
+        var result = -1
+
+        visitLeaves(from, length, 0, reverse = false) { leaf, offset ->
+            val text = leaf.text
+            var i = maxOf(0, from - offset)
+            while (i < text.length) {
+                val c = text[i]
+                while (matched > 0 && string[matched] != c) matched = table[matched - 1]
+                if (string[matched] == c) matched++
+                if (matched == string.length) {
+                    result = offset + i - string.length + 1
+                    return@visitLeaves false
+                }
+                i++
+            }
+            true
+        }
+        return result
+    }
+
+    fun lastIndexOf(char: Char, startIndex: Int = length - 1): Int {
+        if (startIndex < 0) return -1
+        val end = minOf(startIndex + 1, length)
+        var result = -1
+
+        visitLeaves(0, end, 0, reverse = true) { leaf, offset ->
+            val i = leaf.text.lastIndexOf(char, end - 1 - offset)
+            if (i >= 0) result = offset + i
+            i < 0
+        }
+        return result
+    }
+
+    fun lastIndexOf(string: String, startIndex: Int = length - 1): Int {
+        if (startIndex < 0) return -1
+        if (string.isEmpty()) return minOf(startIndex, length)
+
+        // The match must start at or before startIndex, so scan backwards from where it would end
+        val end = minOf(startIndex.coerceAtMost(length) + string.length, length)
+        val reversed = string.reversed()
+        val table = reversed.partialMatchTable()
+        var matched = 0
+        var result = -1
+
+        visitLeaves(0, end, 0, reverse = true) { leaf, offset ->
+            val text = leaf.text
+            var i = minOf(text.length, end - offset) - 1
+            while (i >= 0) {
+                val c = text[i]
+                while (matched > 0 && reversed[matched] != c) matched = table[matched - 1]
+                if (reversed[matched] == c) matched++
+                if (matched == reversed.length) {
+                    result = offset + i
+                    return@visitLeaves false
+                }
+                i--
+            }
+            true
+        }
+        return result
+    }
+
+    operator fun contains(char: Char) = indexOf(char) >= 0
+
+    operator fun contains(string: String) = indexOf(string) >= 0
+
+    inline fun forEachLine(action: (Rope) -> Unit) {
+        forLinesIndexed(0, lineCount + 1) { _, line -> action(line) }
+    }
+
+    inline fun forEachLineIndexed(action: (Int, Rope) -> Unit) {
+        forLinesIndexed(0, lineCount + 1, action)
+    }
+
+    inline fun forLines(range: IntRange, action: (Rope) -> Unit) {
+        forLinesIndexed(range.first, range.last + 1) { _, line -> action(line) }
+    }
+
+    /**
+     * Visits lines in `[startLine, endLine)`; [endLine] is clamped to the number of lines.
+     */
+    inline fun forLinesIndexed(startLine: Int, endLine: Int, action: (Int, Rope) -> Unit) {
+        val end = minOf(endLine, lineCount + 1)
+        for (i in startLine.coerceAtLeast(0) until end) {
+            action(i, line(i))
+        }
+    }
+
+    fun lines(): List<Rope> = List(lineCount + 1, ::line)
+
+    fun toPlainString() = toString()
+
+    override fun toString(): String {
+        val builder = StringBuilder(length)
+        visitLeaves(0, length, 0, reverse = false) { leaf, _ ->
+            builder.append(leaf.text)
+            true
+        }
+        return builder.toString()
+    }
+
+    override fun equals(other: Any?): Boolean {
+        if (this === other) return true
+        if (other !is Rope) return false
+        return length == other.length && lineCount == other.lineCount && toString() == other.toString()
+    }
+
+    override fun hashCode() = toString().hashCode()
+
+    // Char index of the n-th (0-based) line break
+    private fun lineBreakIndex(n: Int): Int {
+        var node: Rope = this
+        var k = n
+        var offset = 0
+        while (node is Node) {
+            if (k < node.left.lineCount) {
+                node = node.left
+            } else {
+                k -= node.left.lineCount
+                offset += node.left.length
+                node = node.right
+            }
+        }
+
+        val text = (node as Leaf).text
+        var seen = 0
+        for (i in text.indices) {
+            if (text[i] == '\n') {
+                if (seen == k) return offset + i
+                seen++
+            }
+        }
+        throw IllegalStateException("Line break $n not found")
+    }
+
+    // Visits the leaves overlapping [start, end) in order; stops as soon as the visitor returns false
+    private fun visitLeaves(
+        start: Int,
+        end: Int,
+        offset: Int,
+        reverse: Boolean,
+        visitor: (Leaf, Int) -> Boolean
+    ): Boolean {
+        if (length == 0 || end <= offset || start >= offset + length) return true
+
+        return when (this) {
+            is Leaf -> visitor(this, offset)
+            is Node -> {
+                val rightOffset = offset + left.length
+                if (reverse) {
+                    right.visitLeaves(start, end, rightOffset, true, visitor) &&
+                            left.visitLeaves(start, end, offset, true, visitor)
+                } else {
+                    left.visitLeaves(start, end, offset, false, visitor) &&
+                            right.visitLeaves(start, end, rightOffset, false, visitor)
+                }
+            }
+        }
+    }
+
+    companion object {
+        const val MAX_LEAF_SIZE = 512
+
+        @JvmField
+        val Empty: Rope = Leaf("")
+
+        @JvmStatic
+        fun fromString(text: String): Rope {
+            if (text.length <= MAX_LEAF_SIZE) return Leaf(text)
+
+            val leaves = ArrayList<Rope>(text.length / MAX_LEAF_SIZE + 1)
+            var start = 0
+            while (start < text.length) {
+                val end = minOf(start + MAX_LEAF_SIZE, text.length)
+                leaves.add(Leaf(text.substring(start, end)))
+                start = end
+            }
+            return buildBalanced(leaves, 0, leaves.size)
+        }
+
+        @JvmStatic
+        fun fromCharArray(chars: CharArray, offset: Int, length: Int): Rope {
+            require(offset >= 0 && length >= 0 && offset + length <= chars.size) {
+                "Invalid range [$offset, ${offset + length}) for array of size ${chars.size}"
+            }
+            if (length <= MAX_LEAF_SIZE) return Leaf(String(chars, offset, length))
+
+            val leaves = ArrayList<Rope>(length / MAX_LEAF_SIZE + 1)
+            var start = offset
+            val end = offset + length
+            while (start < end) {
+                val count = minOf(MAX_LEAF_SIZE, end - start)
+                leaves.add(Leaf(String(chars, start, count)))
+                start += count
+            }
+            return buildBalanced(leaves, 0, leaves.size)
+        }
+
+        @JvmStatic
+        @JvmOverloads
+        fun fromFile(file: File, charset: Charset = Charsets.UTF_8): Rope = file.reader(charset).use(::fromReader)
+
+        @JvmStatic
+        @JvmOverloads
+        fun fromInputStream(inputStream: InputStream, charset: Charset = Charsets.UTF_8): Rope {
+            return inputStream.reader(charset).use(::fromReader)
+        }
+
+        @JvmStatic
+        fun fromReader(reader: Reader): Rope {
+            val leaves = mutableListOf<Rope>()
+            val buffer = CharArray(MAX_LEAF_SIZE)
+
+            while (true) {
+                var filled = 0
+                while (filled < buffer.size) {
+                    val read = reader.read(buffer, filled, buffer.size - filled)
+                    if (read < 0) break
+                    filled += read
+                }
+                if (filled > 0) leaves.add(Leaf(String(buffer, 0, filled)))
+                if (filled < buffer.size) break
+            }
+
+            return if (leaves.isEmpty()) Empty else buildBalanced(leaves, 0, leaves.size)
+        }
+
+        @JvmStatic
+        fun concat(left: Rope, right: Rope): Rope = when {
+            left.length == 0 -> right
+            right.length == 0 -> left
+            else -> join(left, right)
+        }
+
+        private fun buildBalanced(leaves: List<Rope>, from: Int, to: Int): Rope {
+            if (to - from == 1) return leaves[from]
+            val mid = (from + to) ushr 1
+            return Node(buildBalanced(leaves, from, mid), buildBalanced(leaves, mid, to))
+        }
+
+        // AVL-style join: descend the taller side until the heights are close, then rebalance upwards
+        private fun join(left: Rope, right: Rope): Rope {
+            val diff = left.depth - right.depth
+            return when {
+                diff > 1 -> {
+                    val node = left as Node
+                    rebalance(node.left, join(node.right, right))
+                }
+
+                diff < -1 -> {
+                    val node = right as Node
+                    rebalance(join(left, node.left), node.right)
+                }
+
+                left is Leaf && right is Leaf && left.length + right.length <= MAX_LEAF_SIZE -> {
+                    Leaf(left.text + right.text)
+                }
+
+                else -> Node(left, right)
+            }
+        }
+
+        private fun rebalance(left: Rope, right: Rope): Rope {
+            val diff = left.depth - right.depth
+            return when {
+                diff > 1 -> {
+                    val node = left as Node
+                    if (node.left.depth >= node.right.depth) {
+                        Node(node.left, Node(node.right, right))
+                    } else {
+                        val inner = node.right as Node
+                        Node(Node(node.left, inner.left), Node(inner.right, right))
+                    }
+                }
+
+                diff < -1 -> {
+                    val node = right as Node
+                    if (node.right.depth >= node.left.depth) {
+                        Node(Node(left, node.left), node.right)
+                    } else {
+                        val inner = node.left as Node
+                        Node(Node(left, inner.left), Node(inner.right, node.right))
+                    }
+                }
+
+                else -> Node(left, right)
+            }
+        }
+
+        private fun String.partialMatchTable(): IntArray {
+            val table = IntArray(length)
+            var k = 0
+            for (i in 1 until length) {
+                while (k > 0 && this[i] != this[k]) k = table[k - 1]
+                if (this[i] == this[k]) k++
+                table[i] = k
+            }
+            return table
+        }
+    }
+}
@@ -0,0 +1,28 @@
+package com.itsvks.code.core
+
+/**
+ * A single replacement applied to the document: [oldText] at [start] was replaced by [newText].
+ */
+data class TextChange(
+    val start: Int,
+    val oldText: String,
+    val newText: String
+) {
+    val oldEnd get() = start + oldText.length
+    val newEnd get() = start + newText.length
+
+    val isInsertion get() = oldText.isEmpty() && newText.isNotEmpty()
+    val isDeletion get() = newText.isEmpty() && oldText.isNotEmpty()
+
+    fun inverted() = TextChange(start, newText, oldText)
+
+    /**
+     * Where [offset] ends up after this change. Offsets at or after the replaced text move with it,
+     * offsets inside it end up after the new text.
+     */
+    fun mapOffset(offset: Int): Int = when {
+        offset < start -> offset
+        offset >= oldEnd -> offset + newText.length - oldText.length
+        else -> newEnd
+    }
+}
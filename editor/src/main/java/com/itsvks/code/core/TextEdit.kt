@@ -0,0 +1,7 @@
+package com.itsvks.code.core
+
+/**
+ * Replaces the text in [range] with [newText]. Unlike a [TextChange] it refers to positions, and
+ * several edits meant to be applied together all refer to the text before any of them.
+ */
+data class TextEdit(val range: CursorRange, val newText: String)
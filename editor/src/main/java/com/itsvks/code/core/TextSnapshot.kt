@@ -0,0 +1,111 @@
+package com.itsvks.code.core
+
+/**
+ * Immutable view of the document at a given [version].
+ *
+ * Snapshots share structure with the [Rope] they wrap, so taking one is O(1) and they can be
+ * read from any thread while the editor keeps changing.
+ */
+class TextSnapshot internal constructor(
+    val rope: Rope,
+    val version: Long
+) {
+    val length get() = rope.length
+
+    /**
+     * Number of lines in the document. An empty document has a single empty line.
+     */
+    val lineCount get() = rope.lineCount + 1
+
+    val lastPosition: CursorPosition
+        get() = CursorPosition(lineCount - 1, getLineLength(lineCount - 1))
+
+    /**
+     * Text of the line at [lineIndex], without its line break.
+     */
+    fun getLine(lineIndex: Int): String {
+        return rope.slice(rope.lineStartIndex(lineIndex), lineContentEnd(lineIndex)).toString()
+    }
+
+    fun getLineOrNull(lineIndex: Int): String? = if (lineIndex in 0 until lineCount) getLine(lineIndex) else null
+
+    /**
+     * Length of the line at [lineIndex], without its line break.
+     */
+    fun getLineLength(lineIndex: Int) = lineContentEnd(lineIndex) - rope.lineStartIndex(lineIndex)
+
+    fun getText(): String = rope.toString()
+
+    fun getText(start: Int, end: Int): String = rope.slice(start, end).toString()
+
+    fun getText(range: CursorRange): String {
+        val normalized = range.normalize()
+        return getText(offsetOf(normalized.start), offsetOf(normalized.end))
+    }
+
+    fun charAt(offset: Int) = rope[offset]
+
+    /**
+     * Char offset of [position], clamped to the document.
+     */
+    fun offsetOf(position: CursorPosition): Int {
+        val clamped = clamp(position)
+        return rope.lineColumnToCharIndex(clamped.line, clamped.column)
+    }
+
+    fun positionOf(offset: Int): CursorPosition = rope.charIndexToLineColumn(offset.coerceIn(0, length))
+
+    fun clamp(position: CursorPosition): CursorPosition {
+        val line = position.line.coerceIn(0, lineCount - 1)
+        val column = position.column.coerceIn(0, getLineLength(line))
+        return if (line == position.line && column == position.column) position else CursorPosition(line, column)
+    }
+
+    /**
+     * Range of the word touching [position], or null if there is no word char on either side of it.
+     */
+    fun wordRangeAt(position: CursorPosition): CursorRange? {
+        val clamped = clamp(position)
+        val line = getLine(clamped.line)
+        var start = clamped.column
+        var end = clamped.column
+        while (start > 0 && line[start - 1].isWordChar()) start--
+        while (end < line.length && line[end].isWordChar()) end++
+        if (start == end) return null
+
+        return CursorRange(CursorPosition(clamped.line, start), CursorPosition(clamped.line, end))
+    }
+
+    /**
+     * Offset a word-wise caret move from [offset] ends at: the end of the next word when going
+     * [forward], the start of the previous one otherwise. A line break is a stop of its own.
+     */
+    fun wordBoundary(offset: Int, forward: Boolean): Int {
+        fun isBlank(c: Char) = c.isWhitespace() && c != '\n'
+
+        var i = offset.coerceIn(0, length)
+        if (forward) {
+            if (i < length && charAt(i) == '\n') return i + 1
+            while (i < length && isBlank(charAt(i))) i++
+            if (i < length && charAt(i) != '\n') {
+                val isWord = charAt(i).isWordChar()
+                while (i < length && !charAt(i).isWhitespace() && charAt(i).isWordChar() == isWord) i++
+            }
+        } else {
+            if (i > 0 && charAt(i - 1) == '\n') return i - 1
+            while (i > 0 && isBlank(charAt(i - 1))) i--
+            if (i > 0 && charAt(i - 1) != '\n') {
+                val isWord = charAt(i - 1).isWordChar()
+                while (i > 0 && !charAt(i - 1).isWhitespace() && charAt(i - 1).isWordChar() == isWord) i--
+            }
+        }
+        return i
+    }
+
+    override fun toString() = getText()
+
+    private fun lineContentEnd(lineIndex: Int): Int {
+        val end = rope.lineEndIndex(lineIndex)
+        return if (lineIndex < rope.lineCount) end - 1 else end
+    }
+}
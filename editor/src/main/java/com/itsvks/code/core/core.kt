@@ -7,3 +7,5 @@ val bracketPairs = mapOf(
     '"' to '"',
     '\'' to '\''
 )
+
+internal fun Char.isWordChar() = isLetterOrDigit() || this == '_'
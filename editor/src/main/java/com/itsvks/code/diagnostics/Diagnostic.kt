@@ -0,0 +1,27 @@
+package com.itsvks.code.diagnostics
+
+import androidx.compose.ui.graphics.Color
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.theme.EditorTheme
+
+// Most severe first
+enum class DiagnosticSeverity { ERROR, WARNING, INFORMATION, HINT }
+
+/**
+ * A problem reported about the text in [range], e.g. by a compiler or a language server. [source]
+ * names what reported it and [code] identifies the kind of problem there.
+ */
+data class Diagnostic(
+    val range: CursorRange,
+    val severity: DiagnosticSeverity,
+    val message: String,
+    val source: String? = null,
+    val code: String? = null
+)
+
+fun EditorTheme.diagnosticColor(severity: DiagnosticSeverity): Color = when (severity) {
+    DiagnosticSeverity.ERROR -> errorColor
+    DiagnosticSeverity.WARNING -> warningColor
+    DiagnosticSeverity.INFORMATION -> infoColor
+    DiagnosticSeverity.HINT -> hintColor
+}
@@ -0,0 +1,72 @@
+package com.itsvks.code.diagnostics
+
+import androidx.compose.foundation.background
+import androidx.compose.foundation.border
+import androidx.compose.foundation.layout.Box
+import androidx.compose.foundation.layout.Column
+import androidx.compose.foundation.layout.Row
+import androidx.compose.foundation.layout.Spacer
+import androidx.compose.foundation.layout.padding
+import androidx.compose.foundation.layout.size
+import androidx.compose.foundation.layout.width
+import androidx.compose.foundation.layout.widthIn
+import androidx.compose.foundation.shape.CircleShape
+import androidx.compose.foundation.shape.RoundedCornerShape
+import androidx.compose.material3.Text
+import androidx.compose.runtime.Composable
+import androidx.compose.ui.Modifier
+import androidx.compose.ui.text.TextStyle
+import androidx.compose.ui.text.font.FontFamily
+import androidx.compose.ui.unit.IntOffset
+import androidx.compose.ui.unit.dp
+import androidx.compose.ui.unit.sp
+import androidx.compose.ui.window.Popup
+import com.itsvks.code.theme.EditorTheme
+
+/**
+ * Lists [diagnostics] with their messages and where they come from, drawn with the colors of [theme].
+ * [position] is the popup's top left corner relative to its parent, usually just below the problem.
+ */
+@Composable
+fun DiagnosticPopup(
+    diagnostics: List<Diagnostic>,
+    theme: EditorTheme,
+    position: IntOffset,
+    onDismissRequest: () -> Unit,
+    textStyle: TextStyle = TextStyle(fontSize = 14.sp, fontFamily = FontFamily.Monospace)
+) {
+    Popup(offset = position, onDismissRequest = onDismissRequest) {
+        val shape = RoundedCornerShape(4.dp)
+        Column(
+            modifier = Modifier
+                .widthIn(max = 400.dp)
+                .background(theme.diagnosticPopupBackgroundColor, shape)
+                .border(1.dp, theme.diagnosticPopupBorderColor, shape)
+                .padding(horizontal = 8.dp, vertical = 6.dp)
+        ) {
+            for (diagnostic in diagnostics) {
+                Row(modifier = Modifier.padding(vertical = 2.dp)) {
+                    Box(
+                        modifier = Modifier
+                            .padding(top = 5.dp)
+                            .size(8.dp)
+                            .background(theme.diagnosticColor(diagnostic.severity), CircleShape)
+                    )
+                    Spacer(Modifier.width(8.dp))
+                    Column {
+                        Text(text = diagnostic.message, style = textStyle, color = theme.diagnosticPopupTextColor)
+                        // Like VS Code: `source(code)`
+                        val origin = diagnostic.source.orEmpty() + diagnostic.code?.let { "($it)" }.orEmpty()
+                        if (origin.isNotEmpty()) {
+                            Text(
+                                text = origin,
+                                style = textStyle.copy(fontSize = textStyle.fontSize * 0.85f),
+                                color = theme.completionDetailColor
+                            )
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
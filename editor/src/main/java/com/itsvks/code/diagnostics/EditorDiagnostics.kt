@@ -0,0 +1,149 @@
+package com.itsvks.code.diagnostics
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.DocumentListener
+import com.itsvks.code.core.TextChange
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.marker.Marker
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+
+/**
+ * Errors, warnings and other diagnostics of a [CodeEditorState], drawn as squiggles under their text
+ * and icons in the gutter.
+ *
+ * The host [set]s them whenever its checker has run; until the next run they move with the text they
+ * were reported for as the document is edited. Can be set from any thread.
+ */
+class EditorDiagnostics internal constructor(private val state: CodeEditorState) {
+    private val lock = Any()
+
+    // A marker for each of the items, moving its range with the text
+    private var markers: List<Marker> = emptyList()
+
+    @Volatile
+    private var byLine: Map<Int, List<Diagnostic>> = emptyMap()
+
+    private val _items = MutableStateFlow<List<Diagnostic>>(emptyList())
+
+    /**
+     * Every diagnostic, sorted by where it starts.
+     */
+    val items: StateFlow<List<Diagnostic>> = _items.asStateFlow()
+
+    private val _shown = MutableStateFlow<List<Diagnostic>>(emptyList())
+
+    /**
+     * Diagnostics whose popup is open, e.g. after tapping their squiggle, or empty while it's closed.
+     */
+    val shown: StateFlow<List<Diagnostic>> = _shown.asStateFlow()
+
+    private val tracker = object : DocumentListener {
+        override fun onChange(change: TextChange, snapshot: TextSnapshot) = shift(change, snapshot)
+
+        override fun onReset(snapshot: TextSnapshot) = clear()
+    }
+
+    init {
+        state.addDocumentListener(tracker)
+    }
+
+    /**
+     * Replaces all diagnostics with [diagnostics], which refer to the current text.
+     */
+    fun set(diagnostics: List<Diagnostic>): Unit = synchronized(lock) {
+        val snapshot = state.snapshot
+        val sorted = diagnostics
+            .map { it.copy(range = CursorRange(snapshot.clamp(it.range.startInclusive), snapshot.clamp(it.range.endInclusive))) }
+            .sortedWith(compareBy<Diagnostic>({ it.range.startInclusive }, { it.severity }))
+        markers.forEach(Marker::remove)
+        // Text typed at either end of a range stays out of it
+        markers = sorted.map { state.internalMarkers.track(it.range) }
+        publish(sorted)
+        _shown.value = emptyList()
+    }
+
+    fun clear() = set(emptyList())
+
+    /**
+     * Diagnostics whose range covers some of [line].
+     */
+    fun diagnosticsOnLine(line: Int): List<Diagnostic> = byLine[line].orEmpty()
+
+    /**
+     * Diagnostics whose range contains [position], its ends included.
+     */
+    fun diagnosticsAt(position: CursorPosition): List<Diagnostic> {
+        return diagnosticsOnLine(position.line).filter { position in it.range }
+    }
+
+    /**
+     * Opens the popup for [diagnostics], or closes it if there are none.
+     */
+    fun show(diagnostics: List<Diagnostic>) {
+        _shown.value = diagnostics
+    }
+
+    /**
+     * Opens the popup for the diagnostics at [position]. Returns false, closing the popup, if there are none.
+     */
+    fun showAt(position: CursorPosition): Boolean {
+        val found = diagnosticsAt(position)
+        show(found)
+        return found.isNotEmpty()
+    }
+
+    fun hide() = show(emptyList())
+
+    /**
+     * Moves the caret to the next diagnostic after it, wrapping around at the end, and shows it.
+     * Returns the diagnostic, or null if there are none.
+     */
+    fun goToNext(): Diagnostic? {
+        val diagnostics = _items.value
+        val caret = state.cursor
+        return goTo(diagnostics.firstOrNull { it.range.startInclusive > caret } ?: diagnostics.firstOrNull())
+    }
+
+    fun goToPrevious(): Diagnostic? {
+        val diagnostics = _items.value
+        val caret = state.cursor
+        return goTo(diagnostics.lastOrNull { it.range.startInclusive < caret } ?: diagnostics.lastOrNull())
+    }
+
+    private fun goTo(diagnostic: Diagnostic?): Diagnostic? {
+        if (diagnostic == null) return null
+        val start = diagnostic.range.startInclusive
+        state.setCursor(start)
+        show(diagnosticsAt(start).ifEmpty { listOf(diagnostic) })
+        return diagnostic
+    }
+
+    // The markers have moved with the change already; ranges before it keep their positions
+    private fun shift(change: TextChange, snapshot: TextSnapshot): Unit = synchronized(lock) {
+        val diagnostics = _items.value
+        if (diagnostics.isEmpty()) return
+
+        val shifted = diagnostics.mapIndexed { i, diagnostic ->
+            val marker = markers[i]
+            if (marker.endOffset < change.start) return@mapIndexed diagnostic
+            diagnostic.copy(range = CursorRange(snapshot.positionOf(marker.startOffset), snapshot.positionOf(marker.endOffset)))
+        }
+        publish(shifted)
+        _shown.value = emptyList()
+    }
+
+    private fun publish(diagnostics: List<Diagnostic>) {
+        val lines = HashMap<Int, MutableList<Diagnostic>>()
+        for (diagnostic in diagnostics) {
+            for (line in diagnostic.range.startInclusive.line..diagnostic.range.endInclusive.line) {
+                lines.getOrPut(line) { ArrayList() }.add(diagnostic)
+            }
+        }
+        byLine = lines
+        _items.value = diagnostics
+    }
+}
@@ -0,0 +1,43 @@
+package com.itsvks.code.folding
+
+import com.itsvks.code.core.FoldableRange
+
+/**
+ * Blocks between brackets, from the line of the opening one to that of the closing one. Brackets in
+ * strings and comments don't count. Uses the syntax tree if there is one.
+ */
+class BraceFoldingProvider : FoldingProvider {
+    override fun provideFoldingRanges(context: FoldingContext): List<FoldableRange> {
+        context.tree?.let { tree -> return tree.foldableRanges().filter { it.type != FoldableRange.COMMENT } }
+
+        val snapshot = context.snapshot
+        val ranges = ArrayList<FoldableRange>()
+        // Opening brackets and their lines
+        val stack = ArrayDeque<Pair<Char, Int>>()
+        for (line in 0 until snapshot.lineCount) {
+            val text = snapshot.getLine(line)
+            for (column in text.indices) {
+                val c = text[column]
+                if (c !in OPENING && c !in CLOSING || !context.isCode(line, column)) continue
+                if (c in OPENING) {
+                    stack.addLast(c to line)
+                    continue
+                }
+                // A stray closing bracket closes nothing; one that's never closed is skipped
+                val index = stack.indexOfLast { it.first == OPENING[CLOSING.indexOf(c)] }
+                if (index < 0) continue
+                while (stack.size > index + 1) stack.removeLast()
+                val (open, startLine) = stack.removeLast()
+                if (line > startLine + 1) {
+                    ranges += FoldableRange(startLine, line, if (open == '{') FoldableRange.BRACES else FoldableRange.BRACKETS)
+                }
+            }
+        }
+        return ranges
+    }
+
+    private companion object {
+        const val OPENING = "{[("
+        const val CLOSING = "}])"
+    }
+}
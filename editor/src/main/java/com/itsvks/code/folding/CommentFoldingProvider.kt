@@ -0,0 +1,27 @@
+package com.itsvks.code.folding
+
+import com.itsvks.code.core.FoldableRange
+
+/**
+ * Runs of two or more lines holding only comments, such as a block comment or consecutive line
+ * comments. Region markers aren't part of them, so they fold as regions instead.
+ */
+class CommentFoldingProvider : FoldingProvider {
+    override fun provideFoldingRanges(context: FoldingContext): List<FoldableRange> {
+        val snapshot = context.snapshot
+        val ranges = ArrayList<FoldableRange>()
+        var first = -1
+        for (line in 0..snapshot.lineCount) {
+            val isComment = line < snapshot.lineCount &&
+                context.isCommentLine(line) &&
+                !REGION_MARKER.containsMatchIn(snapshot.getLine(line))
+            if (isComment) {
+                if (first < 0) first = line
+                continue
+            }
+            if (first >= 0 && line - 1 > first) ranges += FoldableRange(first, line - 1, FoldableRange.COMMENT)
+            first = -1
+        }
+        return ranges
+    }
+}
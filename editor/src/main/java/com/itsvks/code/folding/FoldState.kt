@@ -0,0 +1,34 @@
+package com.itsvks.code.folding
+
+import com.itsvks.code.util.Json
+
+/**
+ * A folded region as saved: its first [line] and the [text] of that line, trimmed, by which it's found
+ * again if lines were added or removed above it meanwhile.
+ */
+data class SavedFold(val line: Int, val text: String)
+
+/**
+ * The folded regions of a document from [com.itsvks.code.CodeEditorState.saveFolds], to restore them
+ * when the file is opened again. [toJson] turns them into a string to store with the file's other
+ * editor state.
+ */
+data class FoldState(val folds: List<SavedFold>) {
+    fun toJson(): String = Json.stringify(folds.map { mapOf("line" to it.line, "text" to it.text) })
+
+    companion object {
+        /**
+         * Reads a state written by [toJson]. Entries it doesn't understand are skipped.
+         */
+        @JvmStatic
+        fun fromJson(json: String): FoldState {
+            val entries = Json.parse(json) as? List<*> ?: throw IllegalArgumentException("Fold state must be a JSON array")
+            return FoldState(entries.mapNotNull { entry ->
+                val fold = entry as? Map<*, *> ?: return@mapNotNull null
+                val line = (fold["line"] as? Number)?.toInt() ?: return@mapNotNull null
+                val text = fold["text"] as? String ?: return@mapNotNull null
+                SavedFold(line, text)
+            })
+        }
+    }
+}
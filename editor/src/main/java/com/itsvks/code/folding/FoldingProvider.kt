@@ -0,0 +1,112 @@
+package com.itsvks.code.folding
+
+import com.itsvks.code.core.FoldableRange
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.language.Language
+import com.itsvks.code.parser.SyntaxTree
+import com.itsvks.code.syntax.LexerState
+import com.itsvks.code.syntax.SyntaxHighlighter
+import com.itsvks.code.syntax.SyntaxHighlighterFactory
+import com.itsvks.code.syntax.Token
+import com.itsvks.code.syntax.TokenType
+
+/**
+ * The document folding ranges are asked for. [tree] is its syntax tree if the language has a parser.
+ * [lineStates] are the states the lines of [snapshot] end in with [highlighter], as far as the editor
+ * already knows them, so lines can be lexed without starting from the top.
+ */
+class FoldingContext(
+    val snapshot: TextSnapshot,
+    val language: Language,
+    val tree: SyntaxTree? = null,
+    highlighter: SyntaxHighlighter? = null,
+    private val lineStates: List<LexerState?> = emptyList()
+) {
+    private val highlighter by lazy { highlighter ?: SyntaxHighlighterFactory.createHighlighter(language) }
+    private val lexed = HashMap<Int, List<Token>>()
+    private val endStates = HashMap<Int, LexerState>()
+
+    /**
+     * Tokens of [line], from the syntax tree or else lexed from the closest line before it whose state is
+     * known, so providers can tell code from strings and comments.
+     */
+    fun tokensOn(line: Int): List<Token> {
+        tree?.let { return it.tokensOn(line) }
+        lexed[line]?.let { return it }
+
+        var from = line
+        while (from > 0 && endStateOf(from - 1) == null) from--
+        var state = if (from == 0) highlighter.initialState else endStateOf(from - 1)!!
+        for (i in from..line) {
+            val highlight = highlighter.highlightLine(snapshot.getLine(i), state)
+            lexed[i] = highlight.tokens
+            endStates[i] = highlight.endState
+            state = highlight.endState
+        }
+        return lexed.getValue(line)
+    }
+
+    private fun endStateOf(line: Int): LexerState? = endStates[line] ?: lineStates.getOrNull(line)
+
+    /**
+     * Whether [column] of [line] is code rather than part of a string or a comment.
+     */
+    fun isCode(line: Int, column: Int): Boolean {
+        val token = tokensOn(line).firstOrNull { column >= it.start && column < it.end } ?: return true
+        return token.type != TokenType.STRING && token.type != TokenType.COMMENT
+    }
+
+    /**
+     * Whether [line] holds nothing but comments.
+     */
+    fun isCommentLine(line: Int): Boolean {
+        val text = snapshot.getLine(line)
+        val tokens = tokensOn(line).filter { token -> (token.start until token.end).any { !text[it].isWhitespace() } }
+        return tokens.isNotEmpty() && tokens.all { it.type == TokenType.COMMENT }
+    }
+}
+
+/**
+ * Finds the ranges of a document that can be folded. Each [Language] has one, usually a
+ * [CompositeFoldingProvider] of the built-in strategies. Runs off the main thread, except for the
+ * text a state is created or reset with, which is lexed without a syntax tree.
+ */
+fun interface FoldingProvider {
+    fun provideFoldingRanges(context: FoldingContext): List<FoldableRange>
+
+    companion object {
+        /**
+         * Bracketed blocks, comment blocks, imports and regions, for languages with braces.
+         */
+        val Default: FoldingProvider = CompositeFoldingProvider(
+            BraceFoldingProvider(),
+            CommentFoldingProvider(),
+            ImportFoldingProvider(),
+            RegionFoldingProvider()
+        )
+
+        /**
+         * Indented blocks, comment blocks, imports and regions, for languages like Python and YAML.
+         */
+        val IndentBased: FoldingProvider = CompositeFoldingProvider(
+            IndentFoldingProvider(),
+            CommentFoldingProvider(),
+            ImportFoldingProvider(),
+            RegionFoldingProvider()
+        )
+    }
+}
+
+/**
+ * The ranges of all [providers]. Of ranges starting on the same line only the outermost is kept, as
+ * a line has a single fold marker.
+ */
+class CompositeFoldingProvider(private val providers: List<FoldingProvider>) : FoldingProvider {
+    constructor(vararg providers: FoldingProvider) : this(providers.toList())
+
+    override fun provideFoldingRanges(context: FoldingContext): List<FoldableRange> {
+        return providers.flatMap { it.provideFoldingRanges(context) }
+            .sortedWith(compareBy({ it.startLine }, { -it.endLine }))
+            .distinctBy { it.startLine }
+    }
+}
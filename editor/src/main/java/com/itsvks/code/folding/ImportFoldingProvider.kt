@@ -0,0 +1,38 @@
+package com.itsvks.code.folding
+
+import com.itsvks.code.core.FoldableRange
+
+/**
+ * Groups of import lines, from the first to the last, with blank lines in between allowed. A line is
+ * an import if it starts with one of [prefixes] outside a string or comment.
+ */
+class ImportFoldingProvider(
+    private val prefixes: Set<String> = setOf("import ", "from ", "#include", "use ", "using ")
+) : FoldingProvider {
+    override fun provideFoldingRanges(context: FoldingContext): List<FoldableRange> {
+        val snapshot = context.snapshot
+        val ranges = ArrayList<FoldableRange>()
+        var first = -1
+        var last = -1
+
+        fun endGroup() {
+            if (first >= 0 && last > first) ranges += FoldableRange(first, last, FoldableRange.IMPORTS)
+            first = -1
+        }
+
+        for (line in 0 until snapshot.lineCount) {
+            val text = snapshot.getLine(line)
+            val column = text.indexOfFirst { !it.isWhitespace() }
+            if (column < 0) continue
+            val isImport = prefixes.any { text.startsWith(it, column) } && context.isCode(line, column)
+            if (!isImport) {
+                endGroup()
+                continue
+            }
+            if (first < 0) first = line
+            last = line
+        }
+        endGroup()
+        return ranges
+    }
+}
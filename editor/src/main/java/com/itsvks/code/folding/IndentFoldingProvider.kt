@@ -0,0 +1,46 @@
+package com.itsvks.code.folding
+
+import com.itsvks.code.core.FoldableRange
+
+/**
+ * Blocks of lines indented deeper than the line before them, as in Python or YAML. A block ends at
+ * its last non-blank line, so blank lines before the next block stay visible.
+ */
+class IndentFoldingProvider(private val tabSize: Int = 4) : FoldingProvider {
+    override fun provideFoldingRanges(context: FoldingContext): List<FoldableRange> {
+        val snapshot = context.snapshot
+        val ranges = ArrayList<FoldableRange>()
+        // Lines that may start a block, with their indentation
+        val stack = ArrayDeque<Pair<Int, Int>>()
+        var lastNonBlank = -1
+
+        fun close(indent: Int) {
+            while (stack.isNotEmpty() && stack.last().second >= indent) {
+                val (startLine, _) = stack.removeLast()
+                if (lastNonBlank > startLine) ranges += FoldableRange(startLine, lastNonBlank, FoldableRange.INDENT)
+            }
+        }
+
+        for (line in 0 until snapshot.lineCount) {
+            val indent = indentOf(snapshot.getLine(line)) ?: continue
+            close(indent)
+            stack.addLast(line to indent)
+            lastNonBlank = line
+        }
+        close(0)
+        return ranges
+    }
+
+    // Columns of leading whitespace, or null for a blank line
+    private fun indentOf(text: String): Int? {
+        var columns = 0
+        for (c in text) {
+            when (c) {
+                ' ' -> columns++
+                '\t' -> columns += tabSize - columns % tabSize
+                else -> return columns
+            }
+        }
+        return null
+    }
+}
@@ -0,0 +1,35 @@
+package com.itsvks.code.folding
+
+import com.itsvks.code.core.FoldableRange
+import com.itsvks.code.syntax.TokenType
+
+// `// region`, `//#region`, `# region`, `#region`, `/* region */`, `-- region`, `<!-- #region -->`,
+// and the same with endregion
+internal val REGION_MARKER = Regex("""^\s*(?://|#|/\*|--|<!--)\s*#?(end)?region\b""")
+
+/**
+ * Regions between `// region` and `// endregion` markers, or the language's comment style of them.
+ * Markers nest; an end marker without a start is ignored.
+ */
+class RegionFoldingProvider : FoldingProvider {
+    override fun provideFoldingRanges(context: FoldingContext): List<FoldableRange> {
+        val snapshot = context.snapshot
+        val ranges = ArrayList<FoldableRange>()
+        val starts = ArrayDeque<Int>()
+        for (line in 0 until snapshot.lineCount) {
+            val text = snapshot.getLine(line)
+            val marker = REGION_MARKER.find(text) ?: continue
+            // A marker inside a multi-line string is just text
+            val column = text.indexOfFirst { !it.isWhitespace() }
+            if (context.tokensOn(line).any { it.type == TokenType.STRING && column >= it.start && column < it.end }) continue
+
+            if (marker.groups[1] == null) {
+                starts.addLast(line)
+            } else {
+                val startLine = starts.removeLastOrNull() ?: continue
+                if (line > startLine) ranges += FoldableRange(startLine, line, FoldableRange.REGION)
+            }
+        }
+        return ranges
+    }
+}
@@ -0,0 +1,227 @@
+package com.itsvks.code.history
+
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextChange
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+
+/**
+ * Records the edits made to a document as undoable steps.
+ *
+ * Consecutive typing (or consecutive deletes) within [mergeIntervalMillis] collapse into a single
+ * step, and everything between [beginTransaction] and [endTransaction] is undone as one unit.
+ * The manager never touches the text itself; [undo] and [redo] hand the changes back to the caller.
+ */
+class UndoManager(
+    private val maxHistorySize: Int = 1000,
+    private val mergeIntervalMillis: Long = 1000,
+    private val clock: () -> Long = System::currentTimeMillis
+) {
+    class Entry internal constructor(
+        val selectionsBefore: List<CursorRange>,
+        selectionsAfter: List<CursorRange>,
+        internal var timestamp: Long
+    ) {
+        var selectionsAfter = selectionsAfter
+            internal set
+
+        internal val mutableChanges = mutableListOf<TextChange>()
+        val changes: List<TextChange> get() = mutableChanges
+
+        // Kind and number of changes of the last merged edit, used to decide whether the next one can join
+        internal var kind: EditKind? = null
+        internal var groupSize = 0
+    }
+
+    internal enum class EditKind { TYPING, DELETING, COMPOSING }
+
+    private val undoStack = ArrayDeque<Entry>()
+    private val redoStack = ArrayDeque<Entry>()
+
+    private val _canUndo = MutableStateFlow(false)
+    val canUndo: StateFlow<Boolean> = _canUndo.asStateFlow()
+
+    private val _canRedo = MutableStateFlow(false)
+    val canRedo: StateFlow<Boolean> = _canRedo.asStateFlow()
+
+    private var transaction: Entry? = null
+    private var transactionSelections: List<CursorRange>? = null
+    private var transactionDepth = 0
+
+    // Set while undoing or redoing so the replayed changes are not recorded again
+    private var isReplaying = false
+
+    // Prevents the next change from merging into the previous step
+    private var mergeBroken = true
+
+    val isInTransaction get() = transactionDepth > 0
+
+    /**
+     * Starts grouping changes into one step. Without explicit [selectionsBefore], the selections
+     * recorded with the first change of the transaction are used.
+     */
+    fun beginTransaction(selectionsBefore: List<CursorRange>? = null) {
+        if (transactionDepth++ == 0) {
+            transaction = null
+            transactionSelections = selectionsBefore
+        }
+    }
+
+    /**
+     * Closes the outermost transaction. A [mergeable] transaction, such as typing at several carets at
+     * once, can join the previous step the same way single keystrokes do. A [composing] one, an update of
+     * an input method's composing text, joins the previous composing step until [breakMerge], so a whole
+     * composition is undone at once.
+     */
+    fun endTransaction(selectionsAfter: List<CursorRange>? = null, mergeable: Boolean = false, composing: Boolean = false) {
+        check(transactionDepth > 0) { "endTransaction() called without a matching beginTransaction()" }
+        if (--transactionDepth > 0) return
+
+        val entry = transaction ?: return
+        transaction = null
+        transactionSelections = null
+        if (selectionsAfter != null) entry.selectionsAfter = selectionsAfter
+
+        if (mergeable || composing) {
+            commit(entry.changes, entry, composing)
+        } else {
+            push(entry)
+            mergeBroken = true
+        }
+    }
+
+    fun record(change: TextChange, selectionsBefore: List<CursorRange>, selectionsAfter: List<CursorRange>) {
+        if (isReplaying) return
+
+        if (transactionDepth > 0) {
+            val entry = transaction ?: Entry(transactionSelections ?: selectionsBefore, selectionsAfter, clock())
+                .also { transaction = it }
+            entry.mutableChanges.add(change)
+            entry.selectionsAfter = selectionsAfter
+            return
+        }
+
+        val entry = Entry(selectionsBefore, selectionsAfter, clock())
+        entry.mutableChanges.add(change)
+        commit(entry.changes, entry)
+    }
+
+    /**
+     * Stops the next recorded change from merging into the current step, e.g. after the caret moved.
+     */
+    fun breakMerge() {
+        mergeBroken = true
+    }
+
+    /**
+     * Reverts the last step by passing the inverse of its changes to [apply], latest first.
+     * Returns the step so the caller can restore [Entry.selectionsBefore], or null if there is nothing to undo.
+     */
+    fun undo(apply: (TextChange) -> Unit): Entry? {
+        check(transactionDepth == 0) { "Cannot undo inside a transaction" }
+        val entry = undoStack.removeLastOrNull() ?: return null
+
+        replay { entry.changes.asReversed().forEach { apply(it.inverted()) } }
+        redoStack.addLast(entry)
+        mergeBroken = true
+        updateFlags()
+        return entry
+    }
+
+    /**
+     * Re-applies the last undone step. Returns the step so the caller can restore [Entry.selectionsAfter].
+     */
+    fun redo(apply: (TextChange) -> Unit): Entry? {
+        check(transactionDepth == 0) { "Cannot redo inside a transaction" }
+        val entry = redoStack.removeLastOrNull() ?: return null
+
+        replay { entry.changes.forEach(apply) }
+        undoStack.addLast(entry)
+        mergeBroken = true
+        updateFlags()
+        return entry
+    }
+
+    fun clear() {
+        undoStack.clear()
+        redoStack.clear()
+        transaction = null
+        transactionSelections = null
+        transactionDepth = 0
+        mergeBroken = true
+        updateFlags()
+    }
+
+    private inline fun replay(block: () -> Unit) {
+        isReplaying = true
+        try {
+            block()
+        } finally {
+            isReplaying = false
+        }
+    }
+
+    // Pushes a new step, or folds it into the previous one if both are part of the same typing burst
+    private fun commit(changes: List<TextChange>, entry: Entry, composing: Boolean = false) {
+        val kind = if (composing) EditKind.COMPOSING else kindOf(changes)
+        val last = undoStack.lastOrNull()
+
+        if (last != null && kind != null && canMerge(last, changes, kind, entry.timestamp)) {
+            last.mutableChanges.addAll(changes)
+            last.selectionsAfter = entry.selectionsAfter
+            last.timestamp = entry.timestamp
+            last.groupSize = changes.size
+            clearRedo()
+            return
+        }
+
+        entry.kind = kind
+        entry.groupSize = changes.size
+        push(entry)
+        mergeBroken = kind == null
+    }
+
+    private fun push(entry: Entry) {
+        undoStack.addLast(entry)
+        while (undoStack.size > maxHistorySize) undoStack.removeFirst()
+        clearRedo()
+    }
+
+    private fun clearRedo() {
+        redoStack.clear()
+        updateFlags()
+    }
+
+    private fun updateFlags() {
+        _canUndo.value = undoStack.isNotEmpty()
+        _canRedo.value = redoStack.isNotEmpty()
+    }
+
+    private fun canMerge(entry: Entry, changes: List<TextChange>, kind: EditKind, now: Long): Boolean {
+        if (mergeBroken || entry.kind != kind) return false
+        // However long it takes, a composition is one step
+        if (kind == EditKind.COMPOSING) return true
+        if (now - entry.timestamp > mergeIntervalMillis) return false
+        if (entry.groupSize != changes.size) return false
+        if (changes.size > 1) return true
+
+        val previous = entry.changes.last()
+        val change = changes.single()
+        return when (kind) {
+            // Typing: contiguous insertions
+            EditKind.TYPING -> change.start == previous.newEnd
+            // Backspace or forward delete over adjacent text
+            EditKind.DELETING -> change.oldEnd == previous.start || change.start == previous.start
+            EditKind.COMPOSING -> true
+        }
+    }
+
+    // A line break always starts a new step
+    private fun kindOf(changes: List<TextChange>): EditKind? = when {
+        changes.isEmpty() -> null
+        changes.all { it.isInsertion && '\n' !in it.newText } -> EditKind.TYPING
+        changes.all { it.isDeletion && '\n' !in it.oldText } -> EditKind.DELETING
+        else -> null
+    }
+}
@@ -0,0 +1,109 @@
+package com.itsvks.code.hover
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import kotlinx.coroutines.CancellationException
+import kotlinx.coroutines.async
+import kotlinx.coroutines.awaitAll
+import kotlinx.coroutines.coroutineScope
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+
+/**
+ * Where a hover was asked for, and the document version and selection it's valid for.
+ */
+class HoverRequest internal constructor(
+    val position: CursorPosition,
+    internal val version: Long,
+    internal val selection: CursorRange
+)
+
+/**
+ * Hover cards for a [CodeEditorState]: long-pressing a symbol, or resting the mouse on it, shows what
+ * the [providers] know about it. The card closes once the document is edited or the caret moves.
+ */
+class EditorHover internal constructor(private val state: CodeEditorState) {
+    /**
+     * Asked in parallel; the contents of all that answer are shown, in this order.
+     */
+    var providers: List<HoverProvider> = emptyList()
+
+    private val _request = MutableStateFlow<HoverRequest?>(null)
+    val request: StateFlow<HoverRequest?> = _request.asStateFlow()
+
+    private val _hover = MutableStateFlow<Hover?>(null)
+
+    /**
+     * The card being shown, or null.
+     */
+    val hover: StateFlow<Hover?> = _hover.asStateFlow()
+
+    // The request the current card answers
+    private var answered: HoverRequest? = null
+
+    /**
+     * Asks the providers about the symbol at [position]; [refresh] then shows the answer.
+     */
+    fun show(position: CursorPosition) {
+        if (providers.isEmpty()) return
+        val current = _request.value
+        if (current != null && _hover.value?.range?.let { position in it } == true && isValid(current)) return
+        _hover.value = null
+        answered = null
+        _request.value = HoverRequest(position, state.snapshot.version, state.selection.value)
+    }
+
+    fun dismiss() {
+        _request.value = null
+        _hover.value = null
+        answered = null
+    }
+
+    /**
+     * Whether the card being shown is about [position], so moving the mouse within it keeps it open.
+     */
+    fun isShowingAt(position: CursorPosition): Boolean {
+        val request = _request.value ?: return false
+        val range = _hover.value?.range ?: state.snapshot.wordRangeAt(request.position) ?: return position == request.position
+        return position in range
+    }
+
+    /**
+     * Asks the providers about the requested position, or closes the card if the document or the
+     * caret changed since it was asked for.
+     */
+    suspend fun refresh() {
+        val request = _request.value ?: return
+        if (!isValid(request)) return dismiss()
+        if (answered === request) return
+
+        val context = HoverContext(state.snapshot, request.position, state.language)
+        val hovers = coroutineScope {
+            providers.map { provider -> async { provideSafely(provider, context) } }.awaitAll().filterNotNull()
+        }
+        if (_request.value !== request) return
+        answered = request
+        _hover.value = if (hovers.isEmpty()) {
+            null
+        } else {
+            Hover(hovers.joinToString("\n\n---\n\n") { it.contents }, hovers.firstNotNullOfOrNull { it.range })
+        }
+    }
+
+    private fun isValid(request: HoverRequest): Boolean {
+        return request.version == state.snapshot.version && request.selection == state.selection.value
+    }
+
+    // A failing provider shouldn't take the others' hovers with it
+    private suspend fun provideSafely(provider: HoverProvider, context: HoverContext): Hover? {
+        return try {
+            provider.provideHover(context)
+        } catch (e: CancellationException) {
+            throw e
+        } catch (_: Exception) {
+            null
+        }
+    }
+}
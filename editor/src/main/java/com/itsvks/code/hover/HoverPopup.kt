@@ -0,0 +1,48 @@
+package com.itsvks.code.hover
+
+import androidx.compose.foundation.background
+import androidx.compose.foundation.border
+import androidx.compose.foundation.layout.heightIn
+import androidx.compose.foundation.layout.padding
+import androidx.compose.foundation.layout.widthIn
+import androidx.compose.foundation.rememberScrollState
+import androidx.compose.foundation.shape.RoundedCornerShape
+import androidx.compose.foundation.verticalScroll
+import androidx.compose.runtime.Composable
+import androidx.compose.ui.Modifier
+import androidx.compose.ui.text.TextStyle
+import androidx.compose.ui.text.font.FontFamily
+import androidx.compose.ui.unit.IntOffset
+import androidx.compose.ui.unit.dp
+import androidx.compose.ui.unit.sp
+import androidx.compose.ui.window.Popup
+import com.itsvks.code.theme.EditorTheme
+
+/**
+ * The card for a [hover], its markdown drawn with the colors of [theme]. [position] is the popup's
+ * top left corner relative to its parent, usually just below the symbol. Long contents scroll.
+ */
+@Composable
+fun HoverPopup(
+    hover: Hover,
+    theme: EditorTheme,
+    position: IntOffset,
+    onDismissRequest: () -> Unit,
+    textStyle: TextStyle = TextStyle(fontSize = 14.sp, fontFamily = FontFamily.Monospace)
+) {
+    Popup(offset = position, onDismissRequest = onDismissRequest) {
+        val shape = RoundedCornerShape(4.dp)
+        MarkdownContent(
+            markdown = hover.contents,
+            theme = theme,
+            textStyle = textStyle,
+            modifier = Modifier
+                .widthIn(max = 400.dp)
+                .heightIn(max = 300.dp)
+                .background(theme.hoverBackgroundColor, shape)
+                .border(1.dp, theme.hoverBorderColor, shape)
+                .verticalScroll(rememberScrollState())
+                .padding(horizontal = 8.dp, vertical = 6.dp)
+        )
+    }
+}
@@ -0,0 +1,32 @@
+package com.itsvks.code.hover
+
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.language.Language
+
+/**
+ * What to show about the symbol at a position: [contents] as markdown, and the [range] of text they
+ * are about, if known.
+ */
+data class Hover(val contents: String, val range: CursorRange? = null)
+
+/**
+ * Where a hover was asked for.
+ */
+class HoverContext(
+    val snapshot: TextSnapshot,
+    val position: CursorPosition,
+    val language: Language
+)
+
+/**
+ * Describes the symbol under the pointer, e.g. its type and documentation from a language server.
+ * A request is cancelled if the pointer or the caret moves on before it's answered.
+ */
+interface HoverProvider {
+    /**
+     * What to show at the position, or null if there's nothing to say about it.
+     */
+    suspend fun provideHover(context: HoverContext): Hover?
+}
@@ -0,0 +1,253 @@
+package com.itsvks.code.hover
+
+import androidx.compose.foundation.background
+import androidx.compose.foundation.horizontalScroll
+import androidx.compose.foundation.layout.Box
+import androidx.compose.foundation.layout.Column
+import androidx.compose.foundation.layout.fillMaxWidth
+import androidx.compose.foundation.layout.height
+import androidx.compose.foundation.layout.padding
+import androidx.compose.foundation.rememberScrollState
+import androidx.compose.foundation.shape.RoundedCornerShape
+import androidx.compose.material3.Text
+import androidx.compose.runtime.Composable
+import androidx.compose.runtime.remember
+import androidx.compose.ui.Modifier
+import androidx.compose.ui.text.AnnotatedString
+import androidx.compose.ui.text.SpanStyle
+import androidx.compose.ui.text.TextStyle
+import androidx.compose.ui.text.buildAnnotatedString
+import androidx.compose.ui.text.font.FontFamily
+import androidx.compose.ui.text.font.FontStyle
+import androidx.compose.ui.text.font.FontWeight
+import androidx.compose.ui.text.style.TextDecoration
+import androidx.compose.ui.text.withStyle
+import androidx.compose.ui.unit.dp
+import com.itsvks.code.language.LanguageRegistry
+import com.itsvks.code.syntax.SyntaxHighlighterFactory
+import com.itsvks.code.syntax.Token
+import com.itsvks.code.theme.EditorTheme
+import com.itsvks.code.theme.highlight
+
+/**
+ * A block of markdown as the hover card lays it out.
+ */
+internal sealed class MarkdownBlock {
+    data class Text(val text: AnnotatedString) : MarkdownBlock()
+
+    // A fenced code block, highlighted if its language is known
+    data class Code(val text: AnnotatedString) : MarkdownBlock()
+
+    data object Rule : MarkdownBlock()
+}
+
+private val FENCE = Regex("""^\s*(```+|~~~+)\s*([\w+#.-]*).*$""")
+private val HEADING = Regex("""^\s*#{1,6}\s+(.*?)\s*#*\s*$""")
+private val RULE = Regex("""^\s*([-*_])(\s*\1){2,}\s*$""")
+private val LIST_ITEM = Regex("""^(\s*)([-*+]|\d+[.)])\s+(.*)$""")
+
+/**
+ * Splits the markdown that hovers and documentation come in into blocks, styled with the colors of
+ * [theme]. Covers what language servers send: fenced code, headings, rules, lists and paragraphs with
+ * code spans, emphasis and links. Anything else is shown as text.
+ */
+internal fun parseMarkdown(markdown: String, theme: EditorTheme): List<MarkdownBlock> {
+    val blocks = ArrayList<MarkdownBlock>()
+    val paragraph = StringBuilder()
+
+    fun endParagraph() {
+        if (paragraph.isNotBlank()) blocks += MarkdownBlock.Text(inlineMarkdown(paragraph.toString().trimEnd(), theme))
+        paragraph.clear()
+    }
+
+    val lines = markdown.lines()
+    var i = 0
+    while (i < lines.size) {
+        val line = lines[i++]
+        val fence = FENCE.matchEntire(line)
+        val heading = HEADING.matchEntire(line)
+        val listItem = LIST_ITEM.matchEntire(line)
+        when {
+            fence != null -> {
+                endParagraph()
+                val marker = fence.groupValues[1]
+                val code = ArrayList<String>()
+                while (i < lines.size && !lines[i].trimStart().startsWith(marker)) code += lines[i++]
+                i++ // The closing fence
+                blocks += MarkdownBlock.Code(highlightCode(code, fence.groupValues[2], theme))
+            }
+
+            line.isBlank() -> endParagraph()
+
+            RULE.matches(line) -> {
+                endParagraph()
+                blocks += MarkdownBlock.Rule
+            }
+
+            heading != null -> {
+                endParagraph()
+                blocks += MarkdownBlock.Text(buildAnnotatedString {
+                    withStyle(SpanStyle(fontWeight = FontWeight.Bold)) { append(inlineMarkdown(heading.groupValues[1], theme)) }
+                })
+            }
+
+            listItem != null -> {
+                endParagraph()
+                val (indent, bullet, text) = listItem.destructured
+                val marker = if (bullet[0].isDigit()) bullet else "•"
+                paragraph.append(" ".repeat(indent.length)).append(marker).append(' ').append(text)
+                endParagraph()
+            }
+
+            else -> {
+                if (paragraph.isNotEmpty()) paragraph.append(' ')
+                // Two trailing spaces or a backslash break the line
+                when {
+                    line.endsWith("  ") -> paragraph.append(line.trim()).append('\n')
+                    line.endsWith("\\") && !line.endsWith("\\\\") -> paragraph.append(line.trim().dropLast(1)).append('\n')
+                    else -> paragraph.append(line.trim())
+                }
+            }
+        }
+    }
+    endParagraph()
+    return blocks
+}
+
+/**
+ * [text] with code spans, bold, italics, links and escapes styled and their markup removed.
+ */
+internal fun inlineMarkdown(text: String, theme: EditorTheme): AnnotatedString = buildAnnotatedString {
+    appendInline(text, theme)
+}
+
+private fun AnnotatedString.Builder.appendInline(text: String, theme: EditorTheme) {
+    var i = 0
+    while (i < text.length) {
+        val c = text[i]
+        when {
+            c == '\\' && i + 1 < text.length && text[i + 1].isMarkdownPunctuation() -> {
+                append(text[i + 1])
+                i += 2
+            }
+
+            c == '`' -> {
+                var ticks = 1
+                while (i + ticks < text.length && text[i + ticks] == '`') ticks++
+                val fence = "`".repeat(ticks)
+                val end = text.indexOf(fence, i + ticks)
+                if (end < 0) {
+                    append(fence)
+                    i += ticks
+                } else {
+                    withStyle(SpanStyle(fontFamily = FontFamily.Monospace, background = theme.hoverCodeBackgroundColor)) {
+                        append(text.substring(i + ticks, end).trim())
+                    }
+                    i = end + ticks
+                }
+            }
+
+            (c == '*' || c == '_') && text.startsWith("$c$c", i) -> {
+                val end = text.indexOf("$c$c", i + 2)
+                if (end <= i + 2) {
+                    append("$c$c")
+                    i += 2
+                } else {
+                    withStyle(SpanStyle(fontWeight = FontWeight.Bold)) { appendInline(text.substring(i + 2, end), theme) }
+                    i = end + 2
+                }
+            }
+
+            // Underscores within a word, as in snake_case, aren't emphasis
+            (c == '*' || (c == '_' && (i == 0 || !text[i - 1].isLetterOrDigit()))) -> {
+                val end = text.indexOf(c, i + 1)
+                if (end <= i + 1 || text[i + 1].isWhitespace()) {
+                    append(c)
+                    i++
+                } else {
+                    withStyle(SpanStyle(fontStyle = FontStyle.Italic)) { appendInline(text.substring(i + 1, end), theme) }
+                    i = end + 1
+                }
+            }
+
+            c == '[' -> {
+                val close = text.indexOf("](", i + 1)
+                val end = if (close < 0) -1 else text.indexOf(')', close + 2)
+                if (end < 0) {
+                    append(c)
+                    i++
+                } else {
+                    withStyle(SpanStyle(color = theme.infoColor, textDecoration = TextDecoration.Underline)) {
+                        appendInline(text.substring(i + 1, close), theme)
+                    }
+                    i = end + 1
+                }
+            }
+
+            else -> {
+                append(c)
+                i++
+            }
+        }
+    }
+}
+
+private fun Char.isMarkdownPunctuation() = this in "\\`*_{}[]()#+-.!<>|~"
+
+// Line by line, so constructs spanning lines like block comments carry over
+private fun highlightCode(lines: List<String>, languageName: String, theme: EditorTheme): AnnotatedString {
+    val code = lines.joinToString("\n")
+    val language = languageName.takeIf { it.isNotEmpty() }
+        ?.let { LanguageRegistry.getLanguageByName(it) ?: LanguageRegistry.getLanguageByExtension(it) }
+        ?: return AnnotatedString(code)
+    val highlighter = SyntaxHighlighterFactory.createHighlighter(language)
+    var state = highlighter.initialState
+    var offset = 0
+    val tokens = ArrayList<Token>()
+    for (line in lines) {
+        val highlight = highlighter.highlightLine(line, state)
+        highlight.tokens.mapTo(tokens) { it.copy(start = it.start + offset, end = it.end + offset) }
+        state = highlight.endState
+        offset += line.length + 1
+    }
+    return code.highlight(theme, tokens)
+}
+
+/**
+ * [markdown] laid out in blocks, with code on its own background.
+ */
+@Composable
+internal fun MarkdownContent(markdown: String, theme: EditorTheme, textStyle: TextStyle, modifier: Modifier = Modifier) {
+    val blocks = remember(markdown, theme) { parseMarkdown(markdown, theme) }
+    Column(modifier = modifier) {
+        for (block in blocks) {
+            when (block) {
+                is MarkdownBlock.Text -> Text(
+                    text = block.text,
+                    style = textStyle,
+                    color = theme.hoverTextColor,
+                    modifier = Modifier.padding(vertical = 2.dp)
+                )
+
+                is MarkdownBlock.Code -> Box(
+                    modifier = Modifier
+                        .padding(vertical = 2.dp)
+                        .fillMaxWidth()
+                        .background(theme.hoverCodeBackgroundColor, RoundedCornerShape(2.dp))
+                        .horizontalScroll(rememberScrollState())
+                        .padding(horizontal = 6.dp, vertical = 4.dp)
+                ) {
+                    Text(text = block.text, style = textStyle.copy(fontFamily = FontFamily.Monospace), color = theme.hoverTextColor)
+                }
+
+                MarkdownBlock.Rule -> Box(
+                    modifier = Modifier
+                        .padding(vertical = 4.dp)
+                        .fillMaxWidth()
+                        .height(1.dp)
+                        .background(theme.hoverBorderColor)
+                )
+            }
+        }
+    }
+}
@@ -16,6 +16,7 @@ import androidx.compose.ui.platform.SoftwareKeyboardController
 import androidx.compose.ui.platform.establishTextInputSession
 import com.itsvks.code.CodeEditorState
 import kotlinx.coroutines.Job
+import kotlinx.coroutines.flow.combine
 import kotlinx.coroutines.launch
 
 internal class CodeEditorTextInputNode(
@@ -25,6 +26,9 @@ internal class CodeEditorTextInputNode(
 ) : Modifier.Node(), FocusEventModifierNode, PlatformTextInputModifierNode {
     private var focusedJob: Job? = null
 
+    // Connection of the current input session, told about every change of the editor state
+    private var inputConnection: EditorInputConnection? = null
+
     override fun onFocusEvent(focusState: FocusState) {
         println("Focus: $focusState")
 
@@ -34,8 +38,8 @@ internal class CodeEditorTextInputNode(
             coroutineScope.launch {
                 establishTextInputSession {
                     launch {
-                        // TODO: Observe text field state, call into system to update it as
-                        //  required by the platform.
+                        combine(state.content, state.selection, state.composition) { _, _, _ -> }
+                            .collect { inputConnection?.updateInputMethod() }
                     }
 
                     val request: PlatformTextInputMethodRequest = createInputRequest()
@@ -44,6 +48,7 @@ internal class CodeEditorTextInputNode(
             }
         } else {
             softwareKeyboardController?.hide()
+            inputConnection = null
             null
         }
     }
@@ -56,6 +61,12 @@ internal class CodeEditorTextInputNode(
                     state = state,
                     sendKeyEventHandler = sendKeyEventHandler
                 )
+                inputConnection = connection
+
+                val snapshot = state.snapshot
+                val selection = state.selection.value
+                outAttributes.initialSelStart = snapshot.offsetOf(selection.start)
+                outAttributes.initialSelEnd = snapshot.offsetOf(selection.end)
                 outAttributes.inputType = InputType.TYPE_CLASS_TEXT or InputType.TYPE_TEXT_FLAG_MULTI_LINE or InputType.TYPE_TEXT_FLAG_NO_SUGGESTIONS
                 outAttributes.imeOptions = EditorInfo.IME_FLAG_NO_ENTER_ACTION or EditorInfo.IME_FLAG_NO_EXTRACT_UI
                 return connection
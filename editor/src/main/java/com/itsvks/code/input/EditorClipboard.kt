@@ -0,0 +1,28 @@
+package com.itsvks.code.input
+
+import android.content.ClipData
+import android.content.ClipboardManager
+import android.content.Context
+
+/**
+ * Where copied and cut text goes, and pasted text comes from. [of] wraps the system clipboard; a
+ * host with a clipboard of its own can implement this instead.
+ */
+interface EditorClipboard {
+    fun getText(): String?
+
+    fun setText(text: String)
+
+    companion object {
+        @JvmStatic
+        fun of(context: Context): EditorClipboard = SystemClipboard(context.getSystemService(ClipboardManager::class.java))
+    }
+}
+
+private class SystemClipboard(private val manager: ClipboardManager) : EditorClipboard {
+    override fun getText(): String? = manager.primaryClip?.takeIf { it.itemCount > 0 }?.getItemAt(0)?.text?.toString()
+
+    override fun setText(text: String) {
+        manager.setPrimaryClip(ClipData.newPlainText("code", text))
+    }
+}
@@ -1,12 +1,26 @@
 package com.itsvks.code.input
 
+import android.view.KeyEvent
 import android.view.View
 import android.view.inputmethod.BaseInputConnection
+import android.view.inputmethod.ExtractedText
+import android.view.inputmethod.ExtractedTextRequest
+import android.view.inputmethod.InputConnection
+import android.view.inputmethod.InputMethodManager
 import com.itsvks.code.CodeEditorState
 import kotlinx.coroutines.CoroutineScope
 import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.SupervisorJob
+import kotlinx.coroutines.cancel
+import kotlinx.coroutines.launch
 
+// Chars around the caret handed to the input method; it never needs the whole document
+private const val EXTRACTED_TEXT_WINDOW = 10_000
+
+/**
+ * Bridges the input method to [CodeEditorState]. Offsets exchanged with the input method are char
+ * offsets in the document, and only the primary selection is reported.
+ */
 class EditorInputConnection(
     private val view: View,
     private val state: CodeEditorState,
@@ -15,4 +29,157 @@ class EditorInputConnection(
 
     private val TAG = "EditorInputConnection"
     private val scope = CoroutineScope(Dispatchers.Unconfined + SupervisorJob())
+    private val inputMethodManager = view.context.getSystemService(InputMethodManager::class.java)
+    private val clipboard = EditorClipboard.of(view.context)
+
+    private var batchDepth = 0
+    private var hasPendingUpdate = false
+
+    // Set while the input method monitors extracted text
+    private var extractedTextToken: Int? = null
+
+    override fun beginBatchEdit(): Boolean {
+        batchDepth++
+        return true
+    }
+
+    override fun endBatchEdit(): Boolean {
+        if (batchDepth > 0) batchDepth--
+        if (batchDepth == 0 && hasPendingUpdate) updateInputMethod()
+        return batchDepth > 0
+    }
+
+    override fun commitText(text: CharSequence?, newCursorPosition: Int): Boolean {
+        state.commitText(text?.toString() ?: "", newCursorPosition)
+        return true
+    }
+
+    override fun setComposingText(text: CharSequence?, newCursorPosition: Int): Boolean {
+        state.setComposingText(text?.toString() ?: "", newCursorPosition)
+        return true
+    }
+
+    override fun setComposingRegion(start: Int, end: Int): Boolean {
+        state.setComposingRegion(start, end)
+        return true
+    }
+
+    override fun finishComposingText(): Boolean {
+        state.finishComposingText()
+        return true
+    }
+
+    override fun deleteSurroundingText(beforeLength: Int, afterLength: Int): Boolean {
+        state.deleteSurroundingText(beforeLength, afterLength)
+        return true
+    }
+
+    override fun getTextBeforeCursor(n: Int, flags: Int): CharSequence {
+        val snapshot = state.snapshot
+        val start = snapshot.offsetOf(state.selection.value.startInclusive)
+        return snapshot.getText((start - n).coerceAtLeast(0), start)
+    }
+
+    override fun getTextAfterCursor(n: Int, flags: Int): CharSequence {
+        val snapshot = state.snapshot
+        val end = snapshot.offsetOf(state.selection.value.endInclusive)
+        // Some input methods ask for Int.MAX_VALUE chars, so end + n would overflow
+        return snapshot.getText(end, end + minOf(n, snapshot.length - end))
+    }
+
+    override fun getSelectedText(flags: Int): CharSequence? = state.selectedText.ifEmpty { null }
+
+    // Code is not prose, never ask for automatic capitalization
+    override fun getCursorCapsMode(reqModes: Int) = 0
+
+    override fun getExtractedText(request: ExtractedTextRequest?, flags: Int): ExtractedText {
+        if ((flags and InputConnection.GET_EXTRACTED_TEXT_MONITOR) != 0) {
+            extractedTextToken = request?.token
+        }
+        return extractedText(request?.hintMaxChars ?: 0)
+    }
+
+    override fun setSelection(start: Int, end: Int): Boolean {
+        val snapshot = state.snapshot
+        state.setSelection(snapshot.positionOf(start), snapshot.positionOf(end))
+        return true
+    }
+
+    override fun performEditorAction(actionCode: Int): Boolean {
+        state.newLine()
+        return true
+    }
+
+    override fun performContextMenuAction(id: Int): Boolean {
+        when (id) {
+            android.R.id.selectAll -> state.selectAll()
+            android.R.id.copy -> state.copySelection()?.let(clipboard::setText)
+            android.R.id.cut -> state.cutSelection()?.let(clipboard::setText)
+            android.R.id.paste -> clipboard.getText()?.let(state::paste)
+            else -> return false
+        }
+        return true
+    }
+
+    override fun sendKeyEvent(event: KeyEvent): Boolean {
+        scope.launch {
+            if (!sendKeyEventHandler(androidx.compose.ui.input.key.KeyEvent(event))) {
+                super@EditorInputConnection.sendKeyEvent(event)
+            }
+        }
+        return true
+    }
+
+    override fun closeConnection() {
+        state.finishComposingText()
+        scope.cancel()
+        super.closeConnection()
+    }
+
+    /**
+     * Tells the input method where the selection and composing text are now. Called after every
+     * change of the editor state; deferred while the input method is in a batch edit.
+     */
+    fun updateInputMethod() {
+        if (batchDepth > 0) {
+            hasPendingUpdate = true
+            return
+        }
+        hasPendingUpdate = false
+
+        val snapshot = state.snapshot
+        val selection = state.selection.value
+        val composition = state.composition.value
+        inputMethodManager.updateSelection(
+            view,
+            snapshot.offsetOf(selection.start),
+            snapshot.offsetOf(selection.end),
+            composition?.let { snapshot.offsetOf(it.startInclusive) } ?: -1,
+            composition?.let { snapshot.offsetOf(it.endInclusive) } ?: -1
+        )
+
+        extractedTextToken?.let { token ->
+            inputMethodManager.updateExtractedText(view, token, extractedText(0))
+        }
+    }
+
+    private fun extractedText(maxChars: Int): ExtractedText {
+        val snapshot = state.snapshot
+        val selection = state.selection.value
+        val selectionStart = snapshot.offsetOf(selection.start)
+        val selectionEnd = snapshot.offsetOf(selection.end)
+
+        val window = if (maxChars > 0) minOf(maxChars, EXTRACTED_TEXT_WINDOW) else EXTRACTED_TEXT_WINDOW
+        val start = (minOf(selectionStart, selectionEnd) - window / 2).coerceAtLeast(0)
+        val end = (start + window).coerceAtMost(snapshot.length)
+
+        return ExtractedText().apply {
+            text = snapshot.getText(start, end)
+            startOffset = start
+            partialStartOffset = -1
+            partialEndOffset = -1
+            this.selectionStart = selectionStart - start
+            this.selectionEnd = selectionEnd - start
+        }
+    }
 }
@@ -0,0 +1,161 @@
+package com.itsvks.code.input
+
+import androidx.compose.ui.input.key.Key
+import androidx.compose.ui.input.key.KeyEvent
+import androidx.compose.ui.input.key.KeyEventType
+import androidx.compose.ui.input.key.isAltPressed
+import androidx.compose.ui.input.key.isCtrlPressed
+import androidx.compose.ui.input.key.isMetaPressed
+import androidx.compose.ui.input.key.isShiftPressed
+import androidx.compose.ui.input.key.key
+import androidx.compose.ui.input.key.type
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.Direction
+
+/**
+ * A key together with the modifiers that have to be held for it.
+ */
+data class KeyShortcut(
+    val key: Key,
+    val ctrl: Boolean = false,
+    val shift: Boolean = false,
+    val alt: Boolean = false,
+    val meta: Boolean = false
+) {
+    companion object {
+        fun of(event: KeyEvent) = KeyShortcut(
+            key = event.key,
+            ctrl = event.isCtrlPressed,
+            shift = event.isShiftPressed,
+            alt = event.isAltPressed,
+            meta = event.isMetaPressed
+        )
+    }
+}
+
+/**
+ * What a command gets to work with: the editor, how many lines currently fit on screen and the
+ * clipboard, if there is one.
+ */
+class EditorCommandContext(
+    val state: CodeEditorState,
+    val visibleLineCount: Int,
+    val clipboard: EditorClipboard? = null
+)
+
+fun interface EditorCommand {
+    fun execute(context: EditorCommandContext)
+}
+
+/**
+ * Binds [KeyShortcut]s to [EditorCommand]s. Keymaps are immutable; [with] and [without] return
+ * changed copies, so a custom keymap is usually [Default] with a few bindings swapped.
+ */
+class Keymap(bindings: Map<KeyShortcut, EditorCommand>) {
+    val bindings: Map<KeyShortcut, EditorCommand> = bindings.toMap()
+
+    operator fun get(shortcut: KeyShortcut): EditorCommand? = bindings[shortcut]
+
+    fun with(vararg bindings: Pair<KeyShortcut, EditorCommand>) = Keymap(this.bindings + bindings)
+
+    fun without(vararg shortcuts: KeyShortcut) = Keymap(bindings - shortcuts.toSet())
+
+    /**
+     * Runs the command bound to [event], if any. Returns whether the event was handled.
+     */
+    fun dispatch(event: KeyEvent, state: CodeEditorState, visibleLineCount: Int, clipboard: EditorClipboard? = null): Boolean {
+        if (event.type != KeyEventType.KeyDown) return false
+        val command = bindings[KeyShortcut.of(event)] ?: return false
+        command.execute(EditorCommandContext(state, visibleLineCount, clipboard))
+        return true
+    }
+
+    companion object {
+        @JvmStatic
+        val Default: Keymap = Keymap(buildMap {
+            // Caret movement, plain and with Shift to extend the selection
+            for (extend in listOf(false, true)) {
+                put(KeyShortcut(Key.DirectionLeft, shift = extend), EditorCommand { it.state.moveCursor(Direction.LEFT, extend) })
+                put(KeyShortcut(Key.DirectionRight, shift = extend), EditorCommand { it.state.moveCursor(Direction.RIGHT, extend) })
+                put(KeyShortcut(Key.DirectionUp, shift = extend), EditorCommand { it.state.moveCursor(Direction.UP, extend) })
+                put(KeyShortcut(Key.DirectionDown, shift = extend), EditorCommand { it.state.moveCursor(Direction.DOWN, extend) })
+                put(KeyShortcut(Key.DirectionLeft, ctrl = true, shift = extend), EditorCommand { it.state.moveWord(Direction.LEFT, extend) })
+                put(KeyShortcut(Key.DirectionRight, ctrl = true, shift = extend), EditorCommand { it.state.moveWord(Direction.RIGHT, extend) })
+                put(KeyShortcut(Key.MoveHome, shift = extend), EditorCommand { it.state.moveToLineStart(extend) })
+                put(KeyShortcut(Key.MoveEnd, shift = extend), EditorCommand { it.state.moveToLineEnd(extend) })
+                put(KeyShortcut(Key.MoveHome, ctrl = true, shift = extend), EditorCommand { it.state.moveToDocumentStart(extend) })
+                put(KeyShortcut(Key.MoveEnd, ctrl = true, shift = extend), EditorCommand { it.state.moveToDocumentEnd(extend) })
+                put(KeyShortcut(Key.PageUp, shift = extend), EditorCommand {
+                    it.state.movePage(Direction.UP, it.visibleLineCount, extend)
+                })
+                put(KeyShortcut(Key.PageDown, shift = extend), EditorCommand {
+                    it.state.movePage(Direction.DOWN, it.visibleLineCount, extend)
+                })
+            }
+
+            put(KeyShortcut(Key.Enter), EditorCommand { it.state.newLine() })
+            put(KeyShortcut(Key.NumPadEnter), EditorCommand { it.state.newLine() })
+            put(KeyShortcut(Key.Tab), EditorCommand {
+                when {
+                    it.state.snippets.nextTabStop() -> Unit
+                    it.state.selections.value.any { selection -> !selection.isCollapsed } -> it.state.indentLines()
+                    else -> it.state.typeText("    ")
+                }
+            })
+            put(KeyShortcut(Key.Tab, shift = true), EditorCommand { it.state.snippets.previousTabStop() })
+            put(KeyShortcut(Key.Backspace), EditorCommand { it.state.deleteBackward() })
+            put(KeyShortcut(Key.Delete), EditorCommand { it.state.deleteForward() })
+            put(KeyShortcut(Key.Backspace, ctrl = true), EditorCommand { it.state.deleteWordBackward() })
+            put(KeyShortcut(Key.Delete, ctrl = true), EditorCommand { it.state.deleteWordForward() })
+
+            put(KeyShortcut(Key.D, ctrl = true), EditorCommand { it.state.duplicateLines() })
+            put(KeyShortcut(Key.K, ctrl = true, shift = true), EditorCommand { it.state.deleteLines() })
+            put(KeyShortcut(Key.DirectionUp, alt = true), EditorCommand { it.state.moveSelectedLines(Direction.UP) })
+            put(KeyShortcut(Key.DirectionDown, alt = true), EditorCommand { it.state.moveSelectedLines(Direction.DOWN) })
+
+            put(KeyShortcut(Key.DirectionUp, ctrl = true, alt = true), EditorCommand { it.state.addCursorAbove() })
+            put(KeyShortcut(Key.DirectionDown, ctrl = true, alt = true), EditorCommand { it.state.addCursorBelow() })
+            put(KeyShortcut(Key.J, alt = true), EditorCommand { it.state.addNextOccurrence() })
+            put(KeyShortcut(Key.I, shift = true, alt = true), EditorCommand { it.state.splitSelectionIntoLines() })
+            put(KeyShortcut(Key.Escape), EditorCommand {
+                it.state.snippets.exit()
+                it.state.diagnostics.hide()
+                it.state.hover.dismiss()
+                it.state.signatureHelp.dismiss()
+                it.state.clearSecondarySelections()
+            })
+            put(KeyShortcut(Key.DirectionRight, shift = true, alt = true), EditorCommand { it.state.expandSelection() })
+            put(KeyShortcut(Key.DirectionLeft, shift = true, alt = true), EditorCommand { it.state.shrinkSelection() })
+
+            put(KeyShortcut(Key.Minus, ctrl = true), EditorCommand { it.state.toggleFold(it.state.cursor.line) })
+            put(KeyShortcut(Key.Minus, ctrl = true, shift = true), EditorCommand { it.state.foldAll() })
+            put(KeyShortcut(Key.Equals, ctrl = true, shift = true), EditorCommand { it.state.unfoldAll() })
+            put(KeyShortcut(Key.Minus, ctrl = true, alt = true), EditorCommand { it.state.foldRecursively() })
+            put(KeyShortcut(Key.Equals, ctrl = true, alt = true), EditorCommand { it.state.unfoldRecursively() })
+
+            put(KeyShortcut(Key.Spacebar, ctrl = true), EditorCommand { it.state.completion.show() })
+            put(KeyShortcut(Key.Spacebar, ctrl = true, shift = true), EditorCommand { it.state.signatureHelp.show() })
+            put(KeyShortcut(Key.F12), EditorCommand { it.state.navigation.requestDefinition() })
+            put(KeyShortcut(Key.F12, shift = true), EditorCommand { it.state.navigation.requestReferences() })
+            put(KeyShortcut(Key.DirectionLeft, alt = true), EditorCommand { it.state.navigation.goBack() })
+            put(KeyShortcut(Key.DirectionRight, alt = true), EditorCommand { it.state.navigation.goForward() })
+            put(KeyShortcut(Key.F8), EditorCommand { it.state.diagnostics.goToNext() })
+            put(KeyShortcut(Key.F8, shift = true), EditorCommand { it.state.diagnostics.goToPrevious() })
+
+            put(KeyShortcut(Key.A, ctrl = true), EditorCommand { it.state.selectAll() })
+            put(KeyShortcut(Key.C, ctrl = true), EditorCommand { context ->
+                context.state.copySelection()?.let { context.clipboard?.setText(it) }
+            })
+            put(KeyShortcut(Key.X, ctrl = true), EditorCommand { context ->
+                val clipboard = context.clipboard ?: return@EditorCommand
+                context.state.cutSelection()?.let(clipboard::setText)
+            })
+            put(KeyShortcut(Key.V, ctrl = true), EditorCommand { context ->
+                context.clipboard?.getText()?.let(context.state::paste)
+            })
+            put(KeyShortcut(Key.Z, ctrl = true), EditorCommand { it.state.undo() })
+            put(KeyShortcut(Key.Z, ctrl = true, shift = true), EditorCommand { it.state.redo() })
+            put(KeyShortcut(Key.Y, ctrl = true), EditorCommand { it.state.redo() })
+        })
+    }
+}
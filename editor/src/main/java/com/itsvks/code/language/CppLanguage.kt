@@ -1,5 +1,9 @@
 package com.itsvks.code.language
 
+import com.itsvks.code.parser.CLikeParser
+import com.itsvks.code.parser.LanguageParser
+import com.itsvks.code.syntax.TokenType
+
 object CppLanguage : Language {
     override val name = "C++"
     override val fileExtensions = listOf("cpp", "cc", "cxx", "c++", "h", "hpp", "hxx", "h++")
@@ -42,5 +46,12 @@ object CppLanguage : Language {
         "/\\*[\\s\\S]*?\\*/".toRegex()      // Multi-line comments
     )
 
+    override val blockPatterns = listOf(
+        BlockPattern("/\\*".toRegex(), "*/", TokenType.COMMENT),
+        BlockPattern("R\"([^(\\s]*)\\(".toRegex(), ")$1\"", TokenType.STRING)  // Raw strings like R"x(...)x"
+    )
+
     override val macroPattern = "#[a-zA-Z_][a-zA-Z0-9_]*".toRegex()
+
+    override val parser: LanguageParser by lazy { CLikeParser(this) }
 }
@@ -1,5 +1,9 @@
 package com.itsvks.code.language
 
+import com.itsvks.code.parser.CLikeParser
+import com.itsvks.code.parser.LanguageParser
+import com.itsvks.code.syntax.TokenType
+
 object JavaLanguage : Language {
     override val name = "Java"
     override val fileExtensions = listOf("java")
@@ -33,4 +37,11 @@ object JavaLanguage : Language {
     override val annotationPattern = "@[a-zA-Z_][a-zA-Z0-9_.]*".toRegex()
 
     override val staticPattern = "\\bstatic\\b".toRegex()
+
+    override val blockPatterns = listOf(
+        BlockPattern("/\\*".toRegex(), "*/", TokenType.COMMENT),
+        BlockPattern("\"\"\"".toRegex(), "\"\"\"", TokenType.STRING, escapable = true) // Text blocks
+    )
+
+    override val parser: LanguageParser by lazy { CLikeParser(this) }
 }
@@ -1,5 +1,9 @@
 package com.itsvks.code.language
 
+import com.itsvks.code.parser.CLikeParser
+import com.itsvks.code.parser.LanguageParser
+import com.itsvks.code.syntax.TokenType
+
 object JavaScriptLanguage : Language {
     override val name = "JavaScript"
     override val fileExtensions = listOf("js", "jsx", "mjs", "cjs")
@@ -42,6 +46,13 @@ object JavaScriptLanguage : Language {
         "/\\*[\\s\\S]*?\\*/".toRegex()                       // Multi-line comments
     )
 
+    override val blockPatterns = listOf(
+        BlockPattern("/\\*".toRegex(), "*/", TokenType.COMMENT),
+        BlockPattern("`".toRegex(), "`", TokenType.STRING, escapable = true)
+    )
+
     override val functionPattern =
         "\\b(?:function\\s+([a-zA-Z_$][a-zA-Z0-9_$]*)|([a-zA-Z_$][a-zA-Z0-9_$]*)\\s*[=:]\\s*(?:async\\s+)?(?:function\\b|\\([^)]*\\)\\s*=>)|\\b([a-zA-Z_$][a-zA-Z0-9_$]*)\\s*\\()".toRegex()
+
+    override val parser: LanguageParser by lazy { CLikeParser(this) }
 }
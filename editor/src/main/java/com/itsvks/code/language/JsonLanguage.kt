@@ -0,0 +1,25 @@
+package com.itsvks.code.language
+
+import com.itsvks.code.parser.JsonParser
+import com.itsvks.code.parser.LanguageParser
+
+object JsonLanguage : Language {
+    override val name = "JSON"
+    override val fileExtensions = listOf("json", "jsonc", "json5")
+
+    override val keywords = setOf("true", "false", "null")
+    override val types = emptySet<String>()
+    override val operators = emptySet<String>()
+    override val punctuation = setOf("{", "}", "[", "]", ":", ",")
+
+    // Keys are strings followed by a colon; they're highlighted as attributes
+    override val stringPatterns = listOf("\"([^\"\\\\]|\\\\.)*\"(?!\\s*:)".toRegex())
+    override val attributePattern = "\"([^\"\\\\]|\\\\.)*\"(?=\\s*:)".toRegex()
+
+    override val numberPattern = "-?\\b\\d+(\\.\\d+)?([eE][+-]?\\d+)?\\b".toRegex()
+    override val functionPattern: Regex? = null
+    override val annotationPattern: Regex? = null
+    override val constPattern: Regex? = null
+
+    override val parser: LanguageParser by lazy { JsonParser() }
+}
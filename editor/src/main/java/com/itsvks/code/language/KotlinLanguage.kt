@@ -1,5 +1,9 @@
 package com.itsvks.code.language
 
+import com.itsvks.code.parser.CLikeParser
+import com.itsvks.code.parser.LanguageParser
+import com.itsvks.code.syntax.TokenType
+
 object KotlinLanguage : Language {
     override val name = "Kotlin"
     override val fileExtensions = listOf("kt", "kts")
@@ -43,5 +47,12 @@ object KotlinLanguage : Language {
         "/\\*[\\s\\S]*?\\*/".toRegex()      // Multi-line comments
     )
 
+    override val blockPatterns = listOf(
+        BlockPattern("/\\*".toRegex(), "*/", TokenType.COMMENT),
+        BlockPattern("\"\"\"".toRegex(), "\"\"\"", TokenType.STRING)
+    )
+
     override val annotationPattern = "@[a-zA-Z_][a-zA-Z0-9_.]*".toRegex()
+
+    override val parser: LanguageParser by lazy { CLikeParser(this) }
 }
@@ -1,5 +1,47 @@
 package com.itsvks.code.language
 
+import com.itsvks.code.folding.FoldingProvider
+import com.itsvks.code.parser.LanguageParser
+import com.itsvks.code.syntax.SyntaxHighlighter
+import com.itsvks.code.syntax.TokenType
+
+/**
+ * A construct that can span lines, such as a block comment, from [begin] up to the next [end].
+ * [end] may refer to groups of the [begin] match as `$1`, e.g. the hashes of a Rust raw string.
+ * With [escapable], an [end] preceded by a backslash does not end the block.
+ */
+data class BlockPattern(
+    val begin: Regex,
+    val end: String,
+    val type: TokenType,
+    val escapable: Boolean = false
+) {
+    fun endFor(match: MatchResult): String {
+        return end.replace(GROUP_REFERENCE) { match.groupValues.getOrElse(it.groupValues[1].toInt()) { "" } }
+    }
+
+    /**
+     * Offset just past the end of the block in [text], searching from [from], or -1 if it doesn't end on this line.
+     */
+    fun findEnd(text: String, from: Int, end: String): Int {
+        var index = text.indexOf(end, from)
+        while (index >= 0 && escapable && isEscaped(text, index)) {
+            index = text.indexOf(end, index + 1)
+        }
+        return if (index < 0) -1 else index + end.length
+    }
+
+    private fun isEscaped(text: String, index: Int): Boolean {
+        var backslashes = 0
+        while (index - backslashes - 1 >= 0 && text[index - backslashes - 1] == '\\') backslashes++
+        return backslashes % 2 == 1
+    }
+
+    private companion object {
+        val GROUP_REFERENCE = "\\$(\\d)".toRegex()
+    }
+}
+
 interface Language {
     val name: String
     val fileExtensions: List<String>
@@ -22,6 +64,12 @@ interface Language {
             "/\\*[\\s\\S]*?\\*/".toRegex()
         )
 
+    /**
+     * Constructs that may continue on the next line; the highlighter carries them across lines.
+     */
+    val blockPatterns: List<BlockPattern>
+        get() = listOf(BlockPattern("/\\*".toRegex(), "*/", TokenType.COMMENT))
+
     val numberPattern: Regex get() = "\\b(\\d+(\\.\\d+)?([eE][+-]?\\d+)?|0x[0-9a-fA-F]+)\\b".toRegex()
     val functionPattern: Regex? get() = "\\b([a-zA-Z_][a-zA-Z0-9_]*)\\s*\\(".toRegex()
     val annotationPattern: Regex? get() = "@[a-zA-Z_][a-zA-Z0-9_]*".toRegex()
@@ -29,4 +77,20 @@ interface Language {
     val attributePattern: Regex? get() = null
     val constPattern: Regex? get() = "\\b[A-Z][A-Z0-9_]*\\b".toRegex()
     val staticPattern: Regex? get() = null
+
+    /**
+     * A highlighter of its own instead of one built from the patterns above, e.g. for a TextMate grammar.
+     */
+    fun createHighlighter(): SyntaxHighlighter? = null
+
+    /**
+     * Builds syntax trees for folding, bracket matching, selection expansion and the outline. Without one
+     * the editor falls back to scanning for brackets.
+     */
+    val parser: LanguageParser? get() = null
+
+    /**
+     * Finds what can be folded; languages without braces fold by indentation instead.
+     */
+    val foldingProvider: FoldingProvider get() = FoldingProvider.Default
 }
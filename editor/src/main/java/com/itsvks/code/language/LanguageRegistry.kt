@@ -12,6 +12,7 @@ object LanguageRegistry {
         register(CppLanguage)
         register(JavaScriptLanguage)
         register(RustLanguage)
+        register(JsonLanguage)
     }
 
     @JvmStatic
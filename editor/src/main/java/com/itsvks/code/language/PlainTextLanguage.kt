@@ -12,6 +12,7 @@ object PlainTextLanguage : Language {
 
     override val stringPatterns: List<Regex> = emptyList()
     override val commentPatterns: List<Regex> = emptyList()
+    override val blockPatterns: List<BlockPattern> = emptyList()
 
     override val numberPattern: Regex = Regex("(?!x)x")
     override val functionPattern: Regex? = null
@@ -1,5 +1,8 @@
 package com.itsvks.code.language
 
+import com.itsvks.code.folding.FoldingProvider
+import com.itsvks.code.syntax.TokenType
+
 object PythonLanguage : Language {
     override val name = "Python"
     override val fileExtensions = listOf("py", "pyw", "pyc", "pyo", "pyd")
@@ -40,7 +43,14 @@ object PythonLanguage : Language {
         "#.*".toRegex()                    // Single line comments
     )
 
+    override val blockPatterns = listOf(
+        BlockPattern("\"\"\"".toRegex(), "\"\"\"", TokenType.STRING, escapable = true),
+        BlockPattern("'''".toRegex(), "'''", TokenType.STRING, escapable = true)
+    )
+
     override val functionPattern = "\\bdef\\s+([a-zA-Z_][a-zA-Z0-9_]*)\\s*\\(".toRegex()
 
     override val annotationPattern = "@[a-zA-Z_][a-zA-Z0-9_.]*".toRegex()
+
+    override val foldingProvider = FoldingProvider.IndentBased
 }
@@ -1,5 +1,9 @@
 package com.itsvks.code.language
 
+import com.itsvks.code.parser.CLikeParser
+import com.itsvks.code.parser.LanguageParser
+import com.itsvks.code.syntax.TokenType
+
 object RustLanguage : Language {
     override val name: String = "Rust"
     override val fileExtensions: List<String> = listOf("rs")
@@ -39,7 +43,15 @@ object RustLanguage : Language {
             "/\\*([\\s\\S]*?)\\*/".toRegex()     // multi-line
         )
 
+    override val blockPatterns: List<BlockPattern>
+        get() = listOf(
+            BlockPattern("/\\*".toRegex(), "*/", TokenType.COMMENT),
+            BlockPattern("(?<!\\w)b?r(#*)\"".toRegex(), "\"$1", TokenType.STRING)  // raw strings like r#"..."#
+        )
+
     override val macroPattern = "\\b[a-zA-Z_][a-zA-Z0-9_]*!\\b".toRegex()
     override val attributePattern = "#\\[.*?]".toRegex()
     override val staticPattern = "\\bstatic\\b".toRegex()
+
+    override val parser: LanguageParser by lazy { CLikeParser(this) }
 }
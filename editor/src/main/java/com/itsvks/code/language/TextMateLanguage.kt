@@ -0,0 +1,65 @@
+package com.itsvks.code.language
+
+import android.content.res.AssetManager
+import com.itsvks.code.folding.FoldingProvider
+import com.itsvks.code.syntax.SyntaxHighlighter
+import com.itsvks.code.syntax.textmate.TextMateGrammar
+import com.itsvks.code.syntax.textmate.TextMateHighlighter
+import java.io.InputStream
+
+// Grammars of languages whose blocks are marked by indentation rather than brackets
+private val INDENTED_SCOPES = setOf("source.python", "source.yaml", "source.coffee", "source.nim", "source.sass", "text.pug")
+
+/**
+ * A language highlighted by a TextMate [grammar], e.g. one taken from a VS Code extension.
+ * Its [fileExtensions] default to the grammar's `fileTypes`, which many grammars leave out.
+ */
+class TextMateLanguage @JvmOverloads constructor(
+    val grammar: TextMateGrammar,
+    override val name: String = grammar.name ?: grammar.scopeName.substringAfterLast('.'),
+    override val fileExtensions: List<String> = grammar.fileTypes
+) : Language {
+    override val keywords: Set<String> = emptySet()
+    override val types: Set<String> = emptySet()
+    override val operators: Set<String> = emptySet()
+    override val punctuation: Set<String> = emptySet()
+
+    override val stringPatterns: List<Regex> = emptyList()
+    override val commentPatterns: List<Regex> = emptyList()
+    override val blockPatterns: List<BlockPattern> = emptyList()
+
+    override val numberPattern: Regex = Regex("(?!x)x")
+    override val functionPattern: Regex? = null
+    override val annotationPattern: Regex? = null
+    override val constPattern: Regex? = null
+
+    override val foldingProvider =
+        if (grammar.scopeName in INDENTED_SCOPES) FoldingProvider.IndentBased else FoldingProvider.Default
+
+    override fun createHighlighter(): SyntaxHighlighter = TextMateHighlighter(grammar)
+
+    companion object {
+        /**
+         * Loads a `.tmLanguage.json` grammar and registers it, with [LanguageRegistry] and for other
+         * grammars to include.
+         */
+        @JvmStatic
+        @JvmOverloads
+        fun register(stream: InputStream, fileExtensions: List<String>? = null): TextMateLanguage {
+            val grammar = TextMateGrammar.fromStream(stream)
+            TextMateGrammar.register(grammar)
+            val language = if (fileExtensions == null) TextMateLanguage(grammar) else TextMateLanguage(grammar, fileExtensions = fileExtensions)
+            LanguageRegistry.register(language)
+            return language
+        }
+
+        /**
+         * Registers the grammar at [path] in the app's assets, e.g. `grammars/go.tmLanguage.json`.
+         */
+        @JvmStatic
+        @JvmOverloads
+        fun register(assets: AssetManager, path: String, fileExtensions: List<String>? = null): TextMateLanguage {
+            return assets.open(path).use { register(it, fileExtensions) }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+package com.itsvks.code.lsp
+
+import com.itsvks.code.util.Json
+import kotlinx.coroutines.CancellationException
+import kotlinx.coroutines.CompletableDeferred
+import kotlinx.coroutines.CoroutineScope
+import kotlinx.coroutines.Dispatchers
+import kotlinx.coroutines.channels.Channel
+import kotlinx.coroutines.launch
+import java.io.BufferedInputStream
+import java.io.ByteArrayOutputStream
+import java.io.EOFException
+import java.io.IOException
+import java.io.InputStream
+import java.io.OutputStream
+import java.util.concurrent.ConcurrentHashMap
+import java.util.concurrent.atomic.AtomicLong
+
+/**
+ * An error response to a request, or a failure to get one.
+ */
+class ResponseException(val code: Int, message: String, val data: Any? = null) : IOException("$message ($code)")
+
+/**
+ * JSON-RPC 2.0 over a pair of streams, framed with `Content-Length` headers like the Language Server
+ * Protocol does. Messages are read and written on [Dispatchers.IO]; handlers are launched in [scope]
+ * as their messages arrive, so with a single-threaded dispatcher like the main one they run in order.
+ *
+ * Messages are plain JSON values as [Json] reads them: maps, lists, strings, numbers, booleans and null.
+ */
+class JsonRpcConnection(
+    input: InputStream,
+    private val output: OutputStream,
+    private val scope: CoroutineScope
+) {
+    private val input = BufferedInputStream(input)
+    private val nextId = AtomicLong(1)
+    private val pending = ConcurrentHashMap<Long, CompletableDeferred<Any?>>()
+    private val outgoing = Channel<String>(Channel.UNLIMITED)
+
+    private val notificationHandlers = ConcurrentHashMap<String, suspend (Any?) -> Unit>()
+    private val requestHandlers = ConcurrentHashMap<String, suspend (Any?) -> Any?>()
+
+    @Volatile
+    var isClosed = false
+        private set
+
+    init {
+        scope.launch(Dispatchers.IO) {
+            try {
+                for (message in outgoing) writeMessage(message)
+            } catch (e: IOException) {
+                close(e)
+            }
+        }
+        scope.launch(Dispatchers.IO) {
+            try {
+                while (!isClosed) {
+                    val message = readMessage() ?: break
+                    dispatch(Json.parse(message))
+                }
+                close(EOFException("Connection closed by the other side"))
+            } catch (e: IOException) {
+                close(e)
+            } catch (e: Json.ParseException) {
+                close(IOException("Malformed message", e))
+            }
+        }
+    }
+
+    /**
+     * Called with the params of every notification of [method], e.g. `textDocument/publishDiagnostics`.
+     */
+    fun onNotification(method: String, handler: suspend (params: Any?) -> Unit) {
+        notificationHandlers[method] = handler
+    }
+
+    /**
+     * Answers requests of [method] from the other side with what [handler] returns. Requests without
+     * a handler are answered with a method-not-found error.
+     */
+    fun onRequest(method: String, handler: suspend (params: Any?) -> Any?) {
+        requestHandlers[method] = handler
+    }
+
+    /**
+     * Sends a request and suspends until its result arrives. Cancelling the caller cancels the request
+     * on the other side too.
+     */
+    suspend fun request(method: String, params: Any? = null): Any? {
+        if (isClosed) throw ResponseException(CONNECTION_CLOSED, "Connection is closed")
+        val id = nextId.getAndIncrement()
+        val result = CompletableDeferred<Any?>()
+        pending[id] = result
+        send(mapOf("jsonrpc" to "2.0", "id" to id, "method" to method, "params" to params))
+        try {
+            return result.await()
+        } catch (e: CancellationException) {
+            if (pending.remove(id) != null) notify("\$/cancelRequest", mapOf("id" to id))
+            throw e
+        } finally {
+            pending.remove(id)
+        }
+    }
+
+    fun notify(method: String, params: Any? = null) {
+        send(mapOf("jsonrpc" to "2.0", "method" to method, "params" to params))
+    }
+
+    /**
+     * Stops handling messages; requests still waiting fail. Doesn't close the streams, which a read
+     * may still be blocked on: that's up to whoever opened them, e.g. by ending the server process.
+     */
+    fun close() = close(null)
+
+    private fun close(cause: IOException?) {
+        if (isClosed) return
+        isClosed = true
+        outgoing.close()
+        val error = ResponseException(CONNECTION_CLOSED, cause?.message ?: "Connection is closed")
+        for (id in pending.keys.toList()) pending.remove(id)?.completeExceptionally(error)
+    }
+
+    private fun send(message: Map<String, Any?>) {
+        outgoing.trySend(Json.stringify(message))
+    }
+
+    private fun dispatch(message: Any?) {
+        if (isClosed) return
+        val map = message as? Map<*, *> ?: return
+        val method = map["method"] as? String
+        val id = map["id"]
+        when {
+            method != null && id != null -> scope.launch { answer(id, method, map["params"]) }
+            method != null -> notificationHandlers[method]?.let { handler -> scope.launch { handler(map["params"]) } }
+            id != null -> {
+                val result = pending.remove((id as? Number)?.toLong() ?: return) ?: return
+                val error = map["error"] as? Map<*, *>
+                if (error != null) {
+                    val code = (error["code"] as? Number)?.toInt() ?: INTERNAL_ERROR
+                    result.completeExceptionally(ResponseException(code, error["message"] as? String ?: "Error", error["data"]))
+                } else {
+                    result.complete(map["result"])
+                }
+            }
+        }
+    }
+
+    private suspend fun answer(id: Any, method: String, params: Any?) {
+        val handler = requestHandlers[method]
+        val response = try {
+            if (handler == null) {
+                mapOf("jsonrpc" to "2.0", "id" to id, "error" to mapOf("code" to METHOD_NOT_FOUND, "message" to "Unhandled method $method"))
+            } else {
+                mapOf("jsonrpc" to "2.0", "id" to id, "result" to handler(params))
+            }
+        } catch (e: CancellationException) {
+            throw e
+        } catch (e: Exception) {
+            mapOf("jsonrpc" to "2.0", "id" to id, "error" to mapOf("code" to INTERNAL_ERROR, "message" to (e.message ?: e.toString())))
+        }
+        send(response)
+    }
+
+    private fun writeMessage(message: String) {
+        val body = message.toByteArray(Charsets.UTF_8)
+        output.write("Content-Length: ${body.size}\r\n\r\n".toByteArray(Charsets.US_ASCII))
+        output.write(body)
+        output.flush()
+    }
+
+    // The body of the next message, or null at the end of the stream
+    private fun readMessage(): String? {
+        var contentLength = -1
+        while (true) {
+            val line = readHeaderLine() ?: return null
+            if (line.isEmpty()) break
+            val colon = line.indexOf(':')
+            if (colon > 0 && line.substring(0, colon).trim().equals("Content-Length", ignoreCase = true)) {
+                contentLength = line.substring(colon + 1).trim().toIntOrNull() ?: -1
+            }
+        }
+        if (contentLength < 0) throw IOException("Message without Content-Length")
+
+        val body = ByteArray(contentLength)
+        var read = 0
+        while (read < contentLength) {
+            val count = input.read(body, read, contentLength - read)
+            if (count < 0) throw EOFException("Stream ended inside a message")
+            read += count
+        }
+        return String(body, Charsets.UTF_8)
+    }
+
+    private fun readHeaderLine(): String? {
+        val line = ByteArrayOutputStream()
+        while (true) {
+            val byte = input.read()
+            if (byte < 0) return if (line.size() == 0) null else throw EOFException("Stream ended inside a header")
+            if (byte == '\n'.code) break
+            if (byte != '\r'.code) line.write(byte)
+        }
+        return line.toString(Charsets.US_ASCII.name())
+    }
+
+    companion object {
+        const val METHOD_NOT_FOUND = -32601
+        const val INTERNAL_ERROR = -32603
+        const val REQUEST_CANCELLED = -32800
+        const val CONNECTION_CLOSED = -32099
+    }
+}
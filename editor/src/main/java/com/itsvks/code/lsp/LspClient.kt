@@ -0,0 +1,182 @@
+package com.itsvks.code.lsp
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.syntax.SemanticTokenModifiers
+import com.itsvks.code.syntax.SemanticTokenTypes
+import kotlinx.coroutines.CoroutineScope
+import java.io.InputStream
+import java.io.OutputStream
+import java.util.concurrent.ConcurrentHashMap
+
+/**
+ * A Language Server Protocol client talking to a server over [input] and [output], e.g. the streams
+ * of a server process, or of a stub server in tests.
+ *
+ * Call [initialize] first, then [open] each document to keep it in sync and query the server about it:
+ *
+ * ```
+ * val client = LspClient(process.inputStream, process.outputStream, scope)
+ * client.initialize(rootUri = "file:///project")
+ * val document = client.open(state, "file:///project/Main.kt", "kotlin")
+ * ```
+ */
+class LspClient(input: InputStream, output: OutputStream, private val scope: CoroutineScope) {
+    val connection = JsonRpcConnection(input, output, scope)
+
+    private val documents = ConcurrentHashMap<String, LspDocument>()
+
+    /**
+     * The `capabilities` the server answered [initialize] with, empty before that.
+     */
+    @Volatile
+    var serverCapabilities: Map<*, *> = emptyMap<String, Any?>()
+        private set
+
+    init {
+        connection.onNotification("textDocument/publishDiagnostics") { params ->
+            val uri = params.string("uri") ?: return@onNotification
+            val diagnostics = (params.field("diagnostics") as? List<*>).orEmpty().mapNotNull { parseDiagnostic(it) }
+            val version = (params.field("version") as? Number)?.toLong()
+            documents[uri]?.publishDiagnostics(diagnostics, version)
+        }
+        // Servers ask for settings; null leaves each at the server's default
+        connection.onRequest("workspace/configuration") { params ->
+            (params.field("items") as? List<*>).orEmpty().map { null }
+        }
+        connection.onRequest("client/registerCapability") { null }
+        connection.onRequest("client/unregisterCapability") { null }
+        connection.onRequest("window/workDoneProgress/create") { null }
+    }
+
+    /**
+     * Starts the session, telling the server what the editor supports. Returns the server's capabilities.
+     */
+    suspend fun initialize(rootUri: String?, initializationOptions: Any? = null): Map<*, *> {
+        val result = connection.request(
+            "initialize",
+            mapOf(
+                "processId" to null,
+                "clientInfo" to mapOf("name" to "code-editor"),
+                "rootUri" to rootUri,
+                "workspaceFolders" to rootUri?.let { listOf(mapOf("uri" to it, "name" to it.substringAfterLast('/'))) },
+                "initializationOptions" to initializationOptions,
+                "capabilities" to CLIENT_CAPABILITIES
+            )
+        )
+        serverCapabilities = result.field("capabilities") as? Map<*, *> ?: emptyMap<String, Any?>()
+        connection.notify("initialized", emptyMap<String, Any?>())
+        return serverCapabilities
+    }
+
+    /**
+     * Opens the document of [state] on the server as [uri], and keeps it in sync with every edit until
+     * [LspDocument.close].
+     */
+    fun open(state: CodeEditorState, uri: String, languageId: String): LspDocument {
+        documents.remove(uri)?.close()
+        return LspDocument(this, state, uri, languageId, scope).also {
+            documents[uri] = it
+            it.open()
+        }
+    }
+
+    /**
+     * Asks the server to shut down and exit, then closes the connection.
+     */
+    suspend fun shutdown() {
+        documents.values.toList().forEach { it.close() }
+        try {
+            connection.request("shutdown")
+            connection.notify("exit")
+        } finally {
+            connection.close()
+        }
+    }
+
+    internal fun onClosed(document: LspDocument) {
+        documents.remove(document.uri, document)
+    }
+
+    internal fun hasCapability(name: String): Boolean = serverCapabilities[name].let { it != null && it != false }
+
+    // TextDocumentSyncKind: 0 none, 1 full text, 2 incremental
+    internal val syncKind: Int
+        get() = when (val sync = serverCapabilities["textDocumentSync"]) {
+            is Number -> sync.toInt()
+            else -> sync.int("change") ?: SYNC_NONE
+        }
+
+    internal val completionTriggerCharacters: Set<Char>
+        get() = (serverCapabilities["completionProvider"].field("triggerCharacters") as? List<*>).orEmpty()
+            .mapNotNullTo(HashSet()) { (it as? String)?.singleOrNull() }
+
+    internal val signatureHelpTriggerCharacters: Set<Char>
+        get() = (serverCapabilities["signatureHelpProvider"].field("triggerCharacters") as? List<*>).orEmpty()
+            .mapNotNullTo(HashSet()) { (it as? String)?.singleOrNull() }
+
+    internal val semanticTokenLegend: Pair<List<String>, List<String>>?
+        get() {
+            val provider = serverCapabilities["semanticTokensProvider"] ?: return null
+            val legend = provider.field("legend")
+            val types = (legend.field("tokenTypes") as? List<*>)?.map { it as? String ?: "" } ?: return null
+            val modifiers = (legend.field("tokenModifiers") as? List<*>).orEmpty().map { it as? String ?: "" }
+            return types to modifiers
+        }
+
+    internal companion object {
+        const val SYNC_NONE = 0
+        const val SYNC_FULL = 1
+        const val SYNC_INCREMENTAL = 2
+
+        private val CLIENT_CAPABILITIES = mapOf(
+            "textDocument" to mapOf(
+                "synchronization" to mapOf("didSave" to false, "willSave" to false),
+                "completion" to mapOf(
+                    "completionItem" to mapOf(
+                        "snippetSupport" to true,
+                        "documentationFormat" to listOf("markdown", "plaintext"),
+                        "insertReplaceSupport" to true
+                    )
+                ),
+                "hover" to mapOf("contentFormat" to listOf("markdown", "plaintext")),
+                "signatureHelp" to mapOf(
+                    "signatureInformation" to mapOf(
+                        "documentationFormat" to listOf("markdown", "plaintext"),
+                        "parameterInformation" to mapOf("labelOffsetSupport" to true),
+                        "activeParameterSupport" to true
+                    ),
+                    "contextSupport" to true
+                ),
+                "definition" to mapOf("linkSupport" to true),
+                "references" to emptyMap<String, Any?>(),
+                "formatting" to emptyMap<String, Any?>(),
+                "publishDiagnostics" to mapOf("relatedInformation" to false),
+                "semanticTokens" to mapOf(
+                    "requests" to mapOf("full" to true),
+                    "tokenTypes" to listOf(
+                        SemanticTokenTypes.NAMESPACE, SemanticTokenTypes.TYPE, SemanticTokenTypes.CLASS,
+                        SemanticTokenTypes.ENUM, SemanticTokenTypes.INTERFACE, SemanticTokenTypes.STRUCT,
+                        SemanticTokenTypes.TYPE_PARAMETER, SemanticTokenTypes.PARAMETER, SemanticTokenTypes.VARIABLE,
+                        SemanticTokenTypes.PROPERTY, SemanticTokenTypes.ENUM_MEMBER, SemanticTokenTypes.EVENT,
+                        SemanticTokenTypes.FUNCTION, SemanticTokenTypes.METHOD, SemanticTokenTypes.MACRO,
+                        SemanticTokenTypes.KEYWORD, SemanticTokenTypes.MODIFIER, SemanticTokenTypes.COMMENT,
+                        SemanticTokenTypes.STRING, SemanticTokenTypes.NUMBER, SemanticTokenTypes.REGEXP,
+                        SemanticTokenTypes.OPERATOR, SemanticTokenTypes.DECORATOR
+                    ),
+                    "tokenModifiers" to listOf(
+                        SemanticTokenModifiers.DECLARATION, SemanticTokenModifiers.DEFINITION,
+                        SemanticTokenModifiers.READONLY, SemanticTokenModifiers.STATIC,
+                        SemanticTokenModifiers.DEPRECATED, SemanticTokenModifiers.ABSTRACT,
+                        SemanticTokenModifiers.ASYNC, SemanticTokenModifiers.MODIFICATION,
+                        SemanticTokenModifiers.DOCUMENTATION, SemanticTokenModifiers.DEFAULT_LIBRARY
+                    ),
+                    "formats" to listOf("relative"),
+                    "multilineTokenSupport" to false,
+                    "overlappingTokenSupport" to false
+                )
+            ),
+            "workspace" to mapOf("configuration" to true, "workspaceFolders" to true),
+            "general" to mapOf("positionEncodings" to listOf("utf-16"))
+        )
+    }
+}
@@ -0,0 +1,51 @@
+package com.itsvks.code.lsp
+
+import com.itsvks.code.completion.CompletionContext
+import com.itsvks.code.completion.CompletionItem
+import com.itsvks.code.completion.CompletionItemKind
+import com.itsvks.code.completion.CompletionProvider
+
+/**
+ * Completes from the language server [document] is open on. Snippet items are inserted as snippets.
+ */
+class LspCompletionProvider(private val document: LspDocument) : CompletionProvider {
+    override val triggerCharacters: Set<Char> get() = document.completionTriggerCharacters
+
+    override suspend fun provideCompletions(context: CompletionContext): List<CompletionItem> {
+        if (document.isClosed) return emptyList()
+        return document.completion(context.position, context.triggerCharacter).map { item ->
+            CompletionItem(
+                label = item.label,
+                kind = kindOf(item.kind),
+                detail = item.detail,
+                documentation = item.documentation,
+                insertText = item.insertText,
+                textEdit = item.textEdit,
+                additionalEdits = item.additionalEdits,
+                filterText = item.filterText ?: item.label,
+                sortText = item.sortText,
+                isSnippet = item.isSnippet
+            )
+        }
+    }
+
+    // CompletionItemKind of the protocol, numbered from 1
+    private fun kindOf(kind: Int?): CompletionItemKind = when (kind) {
+        2 -> CompletionItemKind.METHOD
+        3 -> CompletionItemKind.FUNCTION
+        4 -> CompletionItemKind.CONSTRUCTOR
+        5 -> CompletionItemKind.FIELD
+        6 -> CompletionItemKind.VARIABLE
+        7, 22 -> CompletionItemKind.CLASS
+        8 -> CompletionItemKind.INTERFACE
+        9 -> CompletionItemKind.MODULE
+        10 -> CompletionItemKind.PROPERTY
+        13 -> CompletionItemKind.ENUM
+        14 -> CompletionItemKind.KEYWORD
+        15 -> CompletionItemKind.SNIPPET
+        17, 19 -> CompletionItemKind.FILE
+        20, 21 -> CompletionItemKind.CONSTANT
+        25 -> CompletionItemKind.TYPE
+        else -> CompletionItemKind.TEXT
+    }
+}
@@ -0,0 +1,235 @@
+package com.itsvks.code.lsp
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.DocumentListener
+import com.itsvks.code.core.TextChange
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.diagnostics.Diagnostic
+import com.itsvks.code.hover.Hover
+import com.itsvks.code.signature.SignatureHelp
+import kotlinx.coroutines.CoroutineScope
+import kotlinx.coroutines.Job
+import kotlinx.coroutines.delay
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+import kotlinx.coroutines.launch
+
+// Pause after the last edit before asking for semantic tokens again
+private const val SEMANTIC_TOKENS_DELAY_MS = 300L
+
+/**
+ * A document open on a language server through [LspClient.open]. Every edit of [state] is sent to the
+ * server as it happens, so requests always see the text the editor has.
+ *
+ * Requests answer for the position and text at the time they were made; callers compare
+ * [TextSnapshot.version]s if the text may have changed while waiting.
+ */
+class LspDocument internal constructor(
+    private val client: LspClient,
+    val state: CodeEditorState,
+    val uri: String,
+    val languageId: String,
+    private val scope: CoroutineScope
+) : DocumentListener {
+    private val _diagnostics = MutableStateFlow<List<Diagnostic>>(emptyList())
+
+    /**
+     * What the server last reported about this document. They're also shown in the editor through
+     * [CodeEditorState.diagnostics].
+     */
+    val diagnostics: StateFlow<List<Diagnostic>> = _diagnostics.asStateFlow()
+
+    /**
+     * Chars the server wants completion opened for, besides those of a word.
+     */
+    val completionTriggerCharacters: Set<Char> get() = client.completionTriggerCharacters
+
+    val signatureHelpTriggerCharacters: Set<Char> get() = client.signatureHelpTriggerCharacters
+
+    @Volatile
+    var isClosed = false
+        private set
+
+    private var semanticTokensJob: Job? = null
+
+    private val textDocument get() = mapOf("uri" to uri)
+
+    internal fun open() {
+        val snapshot = state.snapshot
+        client.connection.notify(
+            "textDocument/didOpen",
+            mapOf(
+                "textDocument" to mapOf(
+                    "uri" to uri,
+                    "languageId" to languageId,
+                    "version" to snapshot.version,
+                    "text" to snapshot.getText()
+                )
+            )
+        )
+        state.addDocumentListener(this)
+        scheduleSemanticTokens(0)
+    }
+
+    override fun onChange(change: TextChange, snapshot: TextSnapshot) {
+        if (isClosed) return
+        val contentChange = when (client.syncKind) {
+            LspClient.SYNC_NONE -> return
+            LspClient.SYNC_INCREMENTAL -> {
+                // Text before the change is the same in both versions, so its position is too
+                val start = snapshot.positionOf(change.start)
+                val lastNewline = change.oldText.lastIndexOf('\n')
+                val end = if (lastNewline < 0) {
+                    CursorPosition(start.line, start.column + change.oldText.length)
+                } else {
+                    CursorPosition(start.line + change.oldText.count { it == '\n' }, change.oldText.length - lastNewline - 1)
+                }
+                mapOf("range" to (start..end).toJson(), "text" to change.newText)
+            }
+
+            else -> mapOf("text" to snapshot.getText())
+        }
+        didChange(snapshot, contentChange)
+    }
+
+    override fun onReset(snapshot: TextSnapshot) {
+        if (isClosed || client.syncKind == LspClient.SYNC_NONE) return
+        didChange(snapshot, mapOf("text" to snapshot.getText()))
+    }
+
+    private fun didChange(snapshot: TextSnapshot, contentChange: Map<String, Any?>) {
+        client.connection.notify(
+            "textDocument/didChange",
+            mapOf(
+                "textDocument" to mapOf("uri" to uri, "version" to snapshot.version),
+                "contentChanges" to listOf(contentChange)
+            )
+        )
+        scheduleSemanticTokens(SEMANTIC_TOKENS_DELAY_MS)
+    }
+
+    /**
+     * Completions at [position]; [triggerCharacter] is the char that opened completion, if any.
+     */
+    suspend fun completion(position: CursorPosition, triggerCharacter: Char? = null): List<LspCompletionItem> {
+        if (!client.hasCapability("completionProvider")) return emptyList()
+        // CompletionTriggerKind: 1 invoked or typing, 2 trigger character
+        val context = when (triggerCharacter) {
+            null -> mapOf("triggerKind" to 1)
+            else -> mapOf("triggerKind" to 2, "triggerCharacter" to triggerCharacter.toString())
+        }
+        val result = client.connection.request("textDocument/completion", positionParams(position) + ("context" to context))
+        // Either a CompletionList or a bare array of items
+        val items = result as? List<*> ?: result.field("items") as? List<*> ?: return emptyList()
+        return items.mapNotNull { parseCompletionItem(it) }
+    }
+
+    suspend fun hover(position: CursorPosition): Hover? {
+        if (!client.hasCapability("hoverProvider")) return null
+        val result = client.connection.request("textDocument/hover", positionParams(position)) ?: return null
+        val contents = markdownOf(result.field("contents"))?.takeIf { it.isNotBlank() } ?: return null
+        return Hover(contents, parseRange(result.field("range")))
+    }
+
+    /**
+     * Signatures of the call at [position]; [triggerCharacter] is the char that opened signature help,
+     * if any, and [isRetrigger] whether it's already open.
+     */
+    suspend fun signatureHelp(position: CursorPosition, triggerCharacter: Char? = null, isRetrigger: Boolean = false): SignatureHelp? {
+        if (!client.hasCapability("signatureHelpProvider")) return null
+        // SignatureHelpTriggerKind: 1 invoked, 2 trigger character, 3 content change
+        val context = buildMap<String, Any> {
+            put("triggerKind", if (triggerCharacter != null) 2 else if (isRetrigger) 3 else 1)
+            if (triggerCharacter != null) put("triggerCharacter", triggerCharacter.toString())
+            put("isRetrigger", isRetrigger)
+        }
+        return parseSignatureHelp(client.connection.request("textDocument/signatureHelp", positionParams(position) + ("context" to context)))
+    }
+
+    suspend fun definition(position: CursorPosition): List<LspLocation> {
+        if (!client.hasCapability("definitionProvider")) return emptyList()
+        return when (val result = client.connection.request("textDocument/definition", positionParams(position))) {
+            is List<*> -> result.mapNotNull { parseLocation(it) }
+            else -> listOfNotNull(parseLocation(result))
+        }
+    }
+
+    suspend fun references(position: CursorPosition, includeDeclaration: Boolean = true): List<LspLocation> {
+        if (!client.hasCapability("referencesProvider")) return emptyList()
+        val params = positionParams(position) + ("context" to mapOf("includeDeclaration" to includeDeclaration))
+        return (client.connection.request("textDocument/references", params) as? List<*>).orEmpty().mapNotNull { parseLocation(it) }
+    }
+
+    /**
+     * Formats the whole document and applies the edits as one undoable step. Returns false if the
+     * server can't format, or the text changed before its answer arrived.
+     */
+    suspend fun format(tabSize: Int = 4, insertSpaces: Boolean = true): Boolean {
+        if (!client.hasCapability("documentFormattingProvider")) return false
+        val version = state.snapshot.version
+        val result = client.connection.request(
+            "textDocument/formatting",
+            mapOf(
+                "textDocument" to textDocument,
+                "options" to mapOf("tabSize" to tabSize, "insertSpaces" to insertSpaces)
+            )
+        )
+        if (state.snapshot.version != version) return false
+        val edits = (result as? List<*>).orEmpty().mapNotNull { parseTextEdit(it) }
+        if (edits.isNotEmpty()) state.applyEdits(edits)
+        return true
+    }
+
+    /**
+     * Asks for the semantic tokens of the current text and hands them to [CodeEditorState.semanticTokens].
+     * Also done by itself a moment after each edit.
+     */
+    suspend fun refreshSemanticTokens() {
+        val (types, modifiers) = client.semanticTokenLegend ?: return
+        val version = state.snapshot.version
+        val result = client.connection.request("textDocument/semanticTokens/full", mapOf("textDocument" to textDocument))
+        val data = result.field("data") as? List<*> ?: return
+        state.semanticTokens.update(version, decodeSemanticTokens(data, types, modifiers))
+    }
+
+    /**
+     * Stops syncing the document and tells the server it's closed.
+     */
+    fun close() {
+        if (isClosed) return
+        isClosed = true
+        state.removeDocumentListener(this)
+        semanticTokensJob?.cancel()
+        _diagnostics.value = emptyList()
+        state.diagnostics.clear()
+        if (!client.connection.isClosed) client.connection.notify("textDocument/didClose", mapOf("textDocument" to textDocument))
+        client.onClosed(this)
+    }
+
+    // Diagnostics of an older version would land on the wrong text; newer ones follow soon
+    internal fun publishDiagnostics(diagnostics: List<Diagnostic>, version: Long?) {
+        if (isClosed || version != null && version != state.snapshot.version) return
+        state.diagnostics.set(diagnostics)
+        _diagnostics.value = diagnostics
+    }
+
+    private fun positionParams(position: CursorPosition) = mapOf(
+        "textDocument" to textDocument,
+        "position" to position.toJson()
+    )
+
+    private fun scheduleSemanticTokens(delayMillis: Long) {
+        if (client.semanticTokenLegend == null) return
+        semanticTokensJob?.cancel()
+        semanticTokensJob = scope.launch {
+            delay(delayMillis)
+            try {
+                refreshSemanticTokens()
+            } catch (_: ResponseException) {
+                // The server failed or went away; the lexical highlighting stays
+            }
+        }
+    }
+}
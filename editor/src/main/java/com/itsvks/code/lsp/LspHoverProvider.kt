@@ -0,0 +1,15 @@
+package com.itsvks.code.lsp
+
+import com.itsvks.code.hover.Hover
+import com.itsvks.code.hover.HoverContext
+import com.itsvks.code.hover.HoverProvider
+
+/**
+ * Hovers from the language server [document] is open on.
+ */
+class LspHoverProvider(private val document: LspDocument) : HoverProvider {
+    override suspend fun provideHover(context: HoverContext): Hover? {
+        if (document.isClosed) return null
+        return document.hover(context.position)
+    }
+}
@@ -0,0 +1,170 @@
+package com.itsvks.code.lsp
+
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextEdit
+import com.itsvks.code.diagnostics.Diagnostic
+import com.itsvks.code.diagnostics.DiagnosticSeverity
+import com.itsvks.code.signature.ParameterInformation
+import com.itsvks.code.signature.SignatureHelp
+import com.itsvks.code.signature.SignatureInformation
+import com.itsvks.code.syntax.SemanticToken
+
+data class LspLocation(val uri: String, val range: CursorRange)
+
+data class LspCompletionItem(
+    val label: String,
+    val kind: Int? = null,
+    val detail: String? = null,
+    val documentation: String? = null,
+    val insertText: String = label,
+    val textEdit: TextEdit? = null,
+    val additionalEdits: List<TextEdit> = emptyList(),
+    val sortText: String? = null,
+    val filterText: String? = null,
+    val isSnippet: Boolean = false
+)
+
+// Conversions between protocol JSON, as JsonRpcConnection passes it, and editor types. Positions
+// count UTF-16 code units like the editor's columns do.
+
+internal fun CursorPosition.toJson(): Map<String, Any?> = mapOf("line" to line, "character" to column)
+
+internal fun CursorRange.toJson(): Map<String, Any?> =
+    mapOf("start" to startInclusive.toJson(), "end" to endInclusive.toJson())
+
+internal fun Any?.int(key: String): Int? = ((this as? Map<*, *>)?.get(key) as? Number)?.toInt()
+
+internal fun Any?.string(key: String): String? = (this as? Map<*, *>)?.get(key) as? String
+
+internal fun Any?.field(key: String): Any? = (this as? Map<*, *>)?.get(key)
+
+internal fun parsePosition(json: Any?): CursorPosition? {
+    return CursorPosition(json.int("line") ?: return null, json.int("character") ?: return null)
+}
+
+internal fun parseRange(json: Any?): CursorRange? {
+    return CursorRange(parsePosition(json.field("start")) ?: return null, parsePosition(json.field("end")) ?: return null)
+}
+
+internal fun parseTextEdit(json: Any?): TextEdit? {
+    // InsertReplaceEdit has `insert` and `replace` instead of `range`
+    val range = parseRange(json.field("range")) ?: parseRange(json.field("replace")) ?: return null
+    return TextEdit(range, json.string("newText") ?: return null)
+}
+
+internal fun parseLocation(json: Any?): LspLocation? {
+    // LocationLink names its target differently
+    val uri = json.string("uri") ?: json.string("targetUri") ?: return null
+    val range = parseRange(json.field("range")) ?: parseRange(json.field("targetSelectionRange")) ?: return null
+    return LspLocation(uri, range)
+}
+
+internal fun parseDiagnostic(json: Any?): Diagnostic? {
+    val severity = when (json.int("severity")) {
+        2 -> DiagnosticSeverity.WARNING
+        3 -> DiagnosticSeverity.INFORMATION
+        4 -> DiagnosticSeverity.HINT
+        else -> DiagnosticSeverity.ERROR
+    }
+    return Diagnostic(
+        range = parseRange(json.field("range")) ?: return null,
+        severity = severity,
+        message = json.string("message") ?: return null,
+        source = json.string("source"),
+        code = when (val code = json.field("code")) {
+            is Number -> code.toLong().toString()
+            else -> code as? String
+        }
+    )
+}
+
+internal fun parseCompletionItem(json: Any?): LspCompletionItem? {
+    val label = json.string("label") ?: return null
+    val textEdit = parseTextEdit(json.field("textEdit"))
+    return LspCompletionItem(
+        label = label,
+        kind = json.int("kind"),
+        detail = json.string("detail"),
+        documentation = markdownOf(json.field("documentation")),
+        insertText = textEdit?.newText ?: json.string("insertText") ?: label,
+        textEdit = textEdit,
+        additionalEdits = (json.field("additionalTextEdits") as? List<*>).orEmpty().mapNotNull { parseTextEdit(it) },
+        sortText = json.string("sortText"),
+        filterText = json.string("filterText"),
+        isSnippet = json.int("insertTextFormat") == 2
+    )
+}
+
+internal fun parseSignatureHelp(json: Any?): SignatureHelp? {
+    val signatures = (json.field("signatures") as? List<*>)?.mapNotNull { signature ->
+        val label = signature.string("label") ?: return@mapNotNull null
+        val parameters = (signature.field("parameters") as? List<*>).orEmpty().map { parameter ->
+            val name = when (val parameterLabel = parameter.field("label")) {
+                // [start, end) offsets into the signature label
+                is List<*> -> {
+                    val start = (parameterLabel.getOrNull(0) as? Number)?.toInt() ?: 0
+                    val end = (parameterLabel.getOrNull(1) as? Number)?.toInt() ?: start
+                    label.substring(start.coerceIn(0, label.length), end.coerceIn(start.coerceIn(0, label.length), label.length))
+                }
+
+                else -> parameterLabel as? String ?: ""
+            }
+            ParameterInformation(name, markdownOf(parameter.field("documentation")))
+        }
+        SignatureInformation(label, markdownOf(signature.field("documentation")), parameters, signature.int("activeParameter"))
+    }
+    if (signatures.isNullOrEmpty()) return null
+    return SignatureHelp(signatures, json.int("activeSignature") ?: 0, json.int("activeParameter") ?: 0)
+}
+
+/**
+ * Markdown for a `MarkupContent`, a `MarkedString` or a list of them, as hovers and documentation
+ * come in any of those shapes.
+ */
+internal fun markdownOf(json: Any?): String? = when (json) {
+    null -> null
+    is String -> json
+    is List<*> -> json.mapNotNull { markdownOf(it) }.joinToString("\n\n").ifEmpty { null }
+    is Map<*, *> -> {
+        val value = json["value"] as? String
+        val language = json["language"] as? String
+        when {
+            value == null -> null
+            // A MarkedString with a language is a code block, MarkupContent has a `kind` instead
+            language != null -> "```$language\n$value\n```"
+            json["kind"] == "plaintext" -> value.replace(Regex("""([\\`*_{}\[\]()#+\-.!|<>])"""), """\\$1""")
+            else -> value
+        }
+    }
+
+    else -> null
+}
+
+/**
+ * Decodes the relative integer encoding of `textDocument/semanticTokens/full` using the server's
+ * [tokenTypes] and [tokenModifiers] legend.
+ */
+internal fun decodeSemanticTokens(data: List<*>, tokenTypes: List<String>, tokenModifiers: List<String>): List<SemanticToken> {
+    val tokens = ArrayList<SemanticToken>(data.size / 5)
+    var line = 0
+    var column = 0
+    var i = 0
+    while (i + 4 < data.size) {
+        val deltaLine = (data[i] as? Number)?.toInt() ?: 0
+        val deltaColumn = (data[i + 1] as? Number)?.toInt() ?: 0
+        val length = (data[i + 2] as? Number)?.toInt() ?: 0
+        val typeIndex = (data[i + 3] as? Number)?.toInt() ?: -1
+        val modifierBits = (data[i + 4] as? Number)?.toInt() ?: 0
+        i += 5
+
+        line += deltaLine
+        column = if (deltaLine == 0) column + deltaColumn else deltaColumn
+        val type = tokenTypes.getOrNull(typeIndex) ?: continue
+        val modifiers = if (modifierBits == 0) emptySet() else {
+            tokenModifiers.filterIndexedTo(LinkedHashSet()) { bit, _ -> modifierBits and (1 shl bit) != 0 }
+        }
+        tokens.add(SemanticToken(line, column, length, type, modifiers))
+    }
+    return tokens
+}
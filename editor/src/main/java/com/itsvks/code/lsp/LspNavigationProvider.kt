@@ -0,0 +1,33 @@
+package com.itsvks.code.lsp
+
+import com.itsvks.code.navigation.DefinitionProvider
+import com.itsvks.code.navigation.Location
+import com.itsvks.code.navigation.NavigationContext
+import com.itsvks.code.navigation.ReferenceProvider
+import java.io.File
+import java.net.URI
+
+/**
+ * Definitions and references from the language server [document] is open on. Locations in other
+ * files name them by path if they're `file:` URIs, like [com.itsvks.code.CodeEditorState.filePath].
+ */
+class LspNavigationProvider(private val document: LspDocument) : DefinitionProvider, ReferenceProvider {
+    override suspend fun provideDefinitions(context: NavigationContext): List<Location> {
+        if (document.isClosed) return emptyList()
+        return document.definition(context.position).map { it.toLocation() }
+    }
+
+    override suspend fun provideReferences(context: NavigationContext, includeDeclaration: Boolean): List<Location> {
+        if (document.isClosed) return emptyList()
+        return document.references(context.position, includeDeclaration).map { it.toLocation() }
+    }
+
+    private fun LspLocation.toLocation(): Location {
+        val file = when {
+            uri == document.uri -> null
+            uri.startsWith("file:") -> runCatching { File(URI(uri)).path }.getOrDefault(uri)
+            else -> uri
+        }
+        return Location(file, range)
+    }
+}
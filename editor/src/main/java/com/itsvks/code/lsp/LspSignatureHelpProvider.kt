@@ -0,0 +1,18 @@
+package com.itsvks.code.lsp
+
+import com.itsvks.code.signature.SignatureHelp
+import com.itsvks.code.signature.SignatureHelpContext
+import com.itsvks.code.signature.SignatureHelpProvider
+
+/**
+ * Signature help from the language server [document] is open on, opened by the chars the server asks for.
+ */
+class LspSignatureHelpProvider(private val document: LspDocument) : SignatureHelpProvider {
+    override val triggerCharacters: Set<Char>
+        get() = document.signatureHelpTriggerCharacters.ifEmpty { super.triggerCharacters }
+
+    override suspend fun provideSignatureHelp(context: SignatureHelpContext): SignatureHelp? {
+        if (document.isClosed) return null
+        return document.signatureHelp(context.position, context.triggerCharacter, isRetrigger = context.current != null)
+    }
+}
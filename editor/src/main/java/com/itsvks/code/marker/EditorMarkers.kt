@@ -0,0 +1,136 @@
+package com.itsvks.code.marker
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextChange
+
+/**
+ * Positions and ranges that stay attached to the text of a [CodeEditorState] while it changes, for
+ * folds, bookmarks, breakpoints, search hits, other people's cursors and the like.
+ *
+ * Markers are kept in an interval tree, so an edit only looks at the markers it touches; those after
+ * it are moved all at once. When an edit deletes all of a marker's text, the marker is removed and
+ * its `onDeleted` callback is called. Markers can be created and read from any thread.
+ */
+class EditorMarkers internal constructor(private val state: CodeEditorState) {
+    internal val lock = Any()
+    internal val tree = IntervalTree<Marker>()
+
+    val size: Int get() = synchronized(lock) { tree.size }
+
+    fun create(
+        position: CursorPosition,
+        gravity: MarkerGravity = MarkerGravity.RIGHT,
+        onDeleted: ((Marker) -> Unit)? = null
+    ): Marker = create(CursorRange.collapsed(position), gravity, gravity, onDeleted)
+
+    /**
+     * A marker over [range]. With the default gravities text typed at its edges stays outside of it;
+     * text typed inside makes it grow.
+     */
+    fun create(
+        range: CursorRange,
+        startGravity: MarkerGravity = MarkerGravity.RIGHT,
+        endGravity: MarkerGravity = MarkerGravity.LEFT,
+        onDeleted: ((Marker) -> Unit)? = null
+    ): Marker = insert(Marker(this, startGravity, endGravity, onDeleted), range)
+
+    /**
+     * A marker for the editor's own use that is never deleted: when its text is, it ends up where the
+     * text was, like a caret.
+     */
+    internal fun track(
+        range: CursorRange,
+        startGravity: MarkerGravity = MarkerGravity.RIGHT,
+        endGravity: MarkerGravity = MarkerGravity.LEFT
+    ): Marker = insert(Marker(this, startGravity, endGravity, null, outlivesText = true), range)
+
+    private fun insert(marker: Marker, range: CursorRange): Marker {
+        val snapshot = state.snapshot
+        synchronized(lock) {
+            marker.node = tree.insert(marker, snapshot.offsetOf(range.startInclusive), snapshot.offsetOf(range.endInclusive))
+        }
+        return marker
+    }
+
+    /**
+     * Markers overlapping or touching [range], in the order they start.
+     */
+    fun markersIn(range: CursorRange): List<Marker> {
+        val snapshot = state.snapshot
+        return synchronized(lock) {
+            tree.overlapping(snapshot.offsetOf(range.startInclusive), snapshot.offsetOf(range.endInclusive)).map { it.value }
+        }
+    }
+
+    fun remove(marker: Marker): Unit = synchronized(lock) {
+        val node = marker.node ?: return
+        marker.removedStart = tree.startOf(node)
+        marker.removedEnd = tree.endOf(node)
+        tree.remove(node)
+        marker.node = null
+    }
+
+    /**
+     * Removes all markers, without calling their `onDeleted`.
+     */
+    fun clear(): Unit = synchronized(lock) {
+        for (node in tree.overlapping(0, Int.MAX_VALUE)) remove(node.value)
+    }
+
+    internal fun positionOf(offset: Int): CursorPosition = state.snapshot.positionOf(offset)
+
+    internal fun onChange(change: TextChange) {
+        val deleted = ArrayList<Marker>()
+        synchronized(lock) {
+            if (tree.size == 0) return
+            val touched = tree.overlapping(change.start, change.oldEnd).map { it.value }
+            val offsets = touched.map { it.startOffset to it.endOffset }
+            touched.forEach(::remove)
+            tree.shiftAfter(change.oldEnd, change.newText.length - change.oldText.length)
+
+            touched.forEachIndexed { i, marker ->
+                val (start, end) = offsets[i]
+                if (!marker.outlivesText && isDeleted(start, end, change)) {
+                    marker.removedStart = change.start
+                    marker.removedEnd = change.start
+                    deleted += marker
+                } else {
+                    val newStart = map(start, marker.startGravity, change)
+                    marker.node = tree.insert(marker, newStart, maxOf(newStart, map(end, marker.endGravity, change)))
+                }
+            }
+        }
+        // Outside the lock, so callbacks can create markers of their own
+        deleted.forEach { it.onDeleted?.invoke(it) }
+    }
+
+    // The whole text was replaced, and every marker's text with it
+    internal fun onReset() {
+        val markers = synchronized(lock) {
+            tree.overlapping(0, Int.MAX_VALUE).map { it.value }.onEach(::remove)
+        }
+        for (marker in markers) {
+            marker.removedStart = 0
+            marker.removedEnd = 0
+            marker.onDeleted?.invoke(marker)
+        }
+    }
+
+    private fun map(offset: Int, gravity: MarkerGravity, change: TextChange): Int = when {
+        offset < change.start -> offset
+        offset > change.oldEnd -> offset + change.newText.length - change.oldText.length
+        offset == change.oldEnd && offset > change.start -> change.newEnd
+        offset == change.start && offset < change.oldEnd -> change.start
+        // Right at an insertion, or inside replaced text
+        gravity == MarkerGravity.LEFT -> change.start
+        else -> change.newEnd
+    }
+
+    // Whether the change took all of a marker's text, or the place of an empty one
+    private fun isDeleted(start: Int, end: Int, change: TextChange): Boolean {
+        if (change.oldText.isEmpty()) return false
+        return if (start == end) start > change.start && start < change.oldEnd else start >= change.start && end <= change.oldEnd
+    }
+}
@@ -0,0 +1,177 @@
+package com.itsvks.code.marker
+
+import kotlin.random.Random
+
+/**
+ * Intervals of text offsets that move together as the text is edited.
+ *
+ * A treap ordered by start offset. Moving all intervals after an edit is a split, a delta kept on the
+ * subtree root and a merge, so it costs O(log n) however many intervals move; the delta is pushed down
+ * to the children only when a path is walked. Each node also knows the largest end below it, which
+ * finds the intervals overlapping a range without looking at the others.
+ */
+internal class IntervalTree<T> {
+    class Node<T>(val value: T, var start: Int, var end: Int, val id: Long) {
+        var maxEnd = end
+
+        // Added to the offsets of the whole subtree below, not yet to the children's
+        var pending = 0
+        val priority = Random.nextInt()
+        var left: Node<T>? = null
+        var right: Node<T>? = null
+        var parent: Node<T>? = null
+    }
+
+    private var root: Node<T>? = null
+
+    // Breaks ties between intervals starting at the same offset
+    private var nextId = 0L
+
+    var size = 0
+        private set
+
+    fun insert(value: T, start: Int, end: Int): Node<T> {
+        val node = Node(value, start, end, nextId++)
+        val (left, right) = split(root, start, node.id)
+        setRoot(merge(merge(left, node), right))
+        size++
+        return node
+    }
+
+    fun remove(node: Node<T>) {
+        // Brings the node and its children up to date, so they can be merged
+        val path = generateSequence(node) { it.parent }.toList()
+        for (i in path.indices.reversed()) pushDown(path[i])
+
+        val parent = node.parent
+        val replacement = merge(node.left, node.right)
+        replacement?.parent = parent
+        when {
+            parent == null -> root = replacement
+            parent.left === node -> parent.left = replacement
+            else -> parent.right = replacement
+        }
+        for (ancestor in path.drop(1)) update(ancestor)
+        node.left = null
+        node.right = null
+        node.parent = null
+        node.maxEnd = node.end
+        size--
+    }
+
+    fun clear() {
+        root = null
+        size = 0
+    }
+
+    fun startOf(node: Node<T>): Int = node.start + pendingAbove(node)
+
+    fun endOf(node: Node<T>): Int = node.end + pendingAbove(node)
+
+    /**
+     * Moves the intervals starting after [offset] by [delta]. They must stay after those that don't move.
+     */
+    fun shiftAfter(offset: Int, delta: Int) {
+        if (delta == 0) return
+        val (left, right) = split(root, offset + 1, Long.MIN_VALUE)
+        right?.let { shift(it, delta) }
+        setRoot(merge(left, right))
+    }
+
+    /**
+     * Intervals overlapping or touching `[start, end]`, in the order they start.
+     */
+    fun overlapping(start: Int, end: Int): List<Node<T>> {
+        val result = ArrayList<Node<T>>()
+        collect(root, 0, start, end, result)
+        return result
+    }
+
+    // [delta] is what the ancestors of [node] still have to add to its offsets
+    private fun collect(node: Node<T>?, delta: Int, start: Int, end: Int, result: MutableList<Node<T>>) {
+        if (node == null || node.maxEnd + delta < start) return
+        collect(node.left, delta + node.pending, start, end, result)
+        // This node and those right of it start after the range
+        if (node.start + delta > end) return
+        if (node.end + delta >= start) result += node
+        collect(node.right, delta + node.pending, start, end, result)
+    }
+
+    private fun pendingAbove(node: Node<T>): Int {
+        var delta = 0
+        var ancestor = node.parent
+        while (ancestor != null) {
+            delta += ancestor.pending
+            ancestor = ancestor.parent
+        }
+        return delta
+    }
+
+    // Nodes ordered before (start, id) go left
+    private fun split(node: Node<T>?, start: Int, id: Long): Pair<Node<T>?, Node<T>?> {
+        if (node == null) return null to null
+        pushDown(node)
+        node.parent = null
+        return if (node.start < start || node.start == start && node.id < id) {
+            val (left, right) = split(node.right, start, id)
+            setRight(node, left)
+            update(node)
+            node to right
+        } else {
+            val (left, right) = split(node.left, start, id)
+            setLeft(node, right)
+            update(node)
+            left to node
+        }
+    }
+
+    // Every node of [a] is ordered before every node of [b]
+    private fun merge(a: Node<T>?, b: Node<T>?): Node<T>? {
+        if (a == null) return b
+        if (b == null) return a
+        return if (a.priority > b.priority) {
+            pushDown(a)
+            setRight(a, merge(a.right, b))
+            update(a)
+            a
+        } else {
+            pushDown(b)
+            setLeft(b, merge(a, b.left))
+            update(b)
+            b
+        }
+    }
+
+    private fun pushDown(node: Node<T>) {
+        if (node.pending == 0) return
+        node.left?.let { shift(it, node.pending) }
+        node.right?.let { shift(it, node.pending) }
+        node.pending = 0
+    }
+
+    private fun shift(node: Node<T>, delta: Int) {
+        node.start += delta
+        node.end += delta
+        node.maxEnd += delta
+        node.pending += delta
+    }
+
+    private fun update(node: Node<T>) {
+        node.maxEnd = maxOf(node.end, node.left?.maxEnd ?: node.end, node.right?.maxEnd ?: node.end)
+    }
+
+    private fun setLeft(node: Node<T>, child: Node<T>?) {
+        node.left = child
+        child?.parent = node
+    }
+
+    private fun setRight(node: Node<T>, child: Node<T>?) {
+        node.right = child
+        child?.parent = node
+    }
+
+    private fun setRoot(node: Node<T>?) {
+        root = node
+        node?.parent = null
+    }
+}
@@ -0,0 +1,42 @@
+package com.itsvks.code.marker
+
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+
+/**
+ * Which way a marker's end goes when text is inserted right at it: [LEFT] keeps it before the new text,
+ * [RIGHT] moves it after, like a caret.
+ */
+enum class MarkerGravity { LEFT, RIGHT }
+
+/**
+ * A position or range in the document that moves with the text around it as the document is edited.
+ * Created with [EditorMarkers.create].
+ */
+class Marker internal constructor(
+    private val markers: EditorMarkers,
+    val startGravity: MarkerGravity,
+    val endGravity: MarkerGravity,
+    internal val onDeleted: ((Marker) -> Unit)?,
+    internal val outlivesText: Boolean = false
+) {
+    internal var node: IntervalTree.Node<Marker>? = null
+
+    // Where the marker was when it was removed
+    internal var removedStart = 0
+    internal var removedEnd = 0
+
+    /**
+     * False once the marker was removed, or its text deleted.
+     */
+    val isValid: Boolean get() = node != null
+
+    val startOffset: Int get() = synchronized(markers.lock) { node?.let(markers.tree::startOf) ?: removedStart }
+    val endOffset: Int get() = synchronized(markers.lock) { node?.let(markers.tree::endOf) ?: removedEnd }
+
+    val start: CursorPosition get() = markers.positionOf(startOffset)
+    val end: CursorPosition get() = markers.positionOf(endOffset)
+    val range: CursorRange get() = CursorRange(start, end)
+
+    fun remove() = markers.remove(this)
+}
@@ -0,0 +1,41 @@
+package com.itsvks.code.navigation
+
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.language.Language
+
+/**
+ * A range of text in the document being edited if [uri] is null, or else in the file it names.
+ * Locations in other files are opened by the host through [EditorNavigation.onOpenLocation].
+ */
+data class Location(val uri: String?, val range: CursorRange)
+
+/**
+ * The symbol a definition or its references were asked for.
+ */
+class NavigationContext(
+    val snapshot: TextSnapshot,
+    val position: CursorPosition,
+    val language: Language
+)
+
+/**
+ * Finds where the symbol at a position is defined, e.g. through a language server.
+ */
+interface DefinitionProvider {
+    /**
+     * Where the symbol is defined, usually just once; empty if it's unknown.
+     */
+    suspend fun provideDefinitions(context: NavigationContext): List<Location>
+}
+
+/**
+ * Finds where the symbol at a position is used.
+ */
+interface ReferenceProvider {
+    /**
+     * Every use of the symbol, and its definition too if [includeDeclaration].
+     */
+    suspend fun provideReferences(context: NavigationContext, includeDeclaration: Boolean): List<Location>
+}
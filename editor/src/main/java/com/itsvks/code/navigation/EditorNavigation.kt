@@ -0,0 +1,277 @@
+package com.itsvks.code.navigation
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.marker.Marker
+import com.itsvks.code.marker.MarkerGravity
+import com.itsvks.code.syntax.TokenType
+import kotlinx.coroutines.CancellationException
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+
+private const val MAX_HISTORY = 50
+
+// Tokens that name something that can be looked up
+private val NAVIGABLE_TYPES = setOf(TokenType.IDENTIFIER, TokenType.FUNCTION, TokenType.TYPE)
+
+/**
+ * A place navigated away from: the selection in the file at [uri], null for an unsaved document, and
+ * the first line that was on screen.
+ */
+data class NavigationEntry(val uri: String?, val selection: CursorRange, val firstVisibleLine: Int)
+
+/**
+ * A definition or references lookup the editor runs through [EditorNavigation.refresh].
+ */
+class NavigationRequest internal constructor(
+    val position: CursorPosition,
+    val findReferences: Boolean,
+    internal val version: Long
+)
+
+/**
+ * Go to definition, find references and back/forward history for a [CodeEditorState].
+ *
+ * Ctrl-clicking a name, or F12, looks up its definition with the [definitionProviders] and jumps to
+ * it. Each jump remembers where it came from, so [goBack] and [goForward] return to the caret and the
+ * scroll position there; places in this document move with its text as it's edited.
+ */
+class EditorNavigation internal constructor(private val state: CodeEditorState) {
+    /**
+     * Asked in this order; the first that knows the definition wins.
+     */
+    var definitionProviders: List<DefinitionProvider> = emptyList()
+
+    /**
+     * Asked in this order; the references of all are listed.
+     */
+    var referenceProviders: List<ReferenceProvider> = emptyList()
+
+    /**
+     * Opens a location in another file, e.g. in a new tab, and moves its caret there. Returns false if
+     * the host can't open it; without a callback only this document can be navigated.
+     */
+    var onOpenLocation: ((Location) -> Boolean)? = null
+
+    private val _request = MutableStateFlow<NavigationRequest?>(null)
+
+    /**
+     * The lookup waiting to be run, or null.
+     */
+    val request: StateFlow<NavigationRequest?> = _request.asStateFlow()
+
+    private val _locations = MutableStateFlow<List<Location>>(emptyList())
+
+    /**
+     * The references found last, or the definitions if there were several, for the host to list.
+     */
+    val locations: StateFlow<List<Location>> = _locations.asStateFlow()
+
+    // A place in the history. In this document, markers at the anchor and the caret of its selection
+    // move it with the text
+    private inner class Place(private val recorded: NavigationEntry) {
+        private val anchor = track(recorded.selection.start)
+        private val caret = track(recorded.selection.end)
+
+        val entry: NavigationEntry
+            get() {
+                if (anchor == null || caret == null || !anchor.isValid) return recorded
+                return recorded.copy(selection = CursorRange(anchor.start, caret.start))
+            }
+
+        private fun track(position: CursorPosition): Marker? {
+            if (recorded.uri != state.filePath) return null
+            return state.internalMarkers.track(CursorRange.collapsed(position), MarkerGravity.RIGHT, MarkerGravity.RIGHT)
+        }
+
+        fun forget() {
+            anchor?.remove()
+            caret?.remove()
+        }
+    }
+
+    private val back = ArrayDeque<Place>()
+    private val forward = ArrayDeque<Place>()
+
+    private val _canGoBack = MutableStateFlow(false)
+    val canGoBack: StateFlow<Boolean> = _canGoBack.asStateFlow()
+
+    private val _canGoForward = MutableStateFlow(false)
+    val canGoForward: StateFlow<Boolean> = _canGoForward.asStateFlow()
+
+    // The first line on screen, kept up to date by the editor
+    internal var firstVisibleLine = 0
+
+    private val _scrollRequest = MutableStateFlow<Int?>(null)
+
+    // A line the editor should scroll to the top, after going back or forward
+    internal val scrollRequest: StateFlow<Int?> = _scrollRequest.asStateFlow()
+
+    /**
+     * Whether there's a name at [position] to look up: an identifier, a function or a type, with the
+     * caret on it or just after it.
+     */
+    fun isNavigable(position: CursorPosition): Boolean {
+        val snapshot = state.snapshot
+        val line = snapshot.getLineOrNull(position.line) ?: return false
+        val highlighting = state.highlighting
+        val tokens = state.syntax.tree.value?.takeIf { it.version == snapshot.version }?.tokensOn(position.line)
+            ?: highlighting.tokensFor(position.line)
+            ?: highlighting.highlighter.highlightLine(line, highlighting.stateAfter(position.line - 1) ?: highlighting.highlighter.initialState).tokens
+        val token = tokens.firstOrNull { position.column >= it.start && position.column < it.end }
+            ?: tokens.firstOrNull { it.end == position.column }
+        return token != null && token.type in NAVIGABLE_TYPES
+    }
+
+    /**
+     * Looks up the definition of the name at [position] and jumps to it once [refresh] runs.
+     */
+    fun requestDefinition(position: CursorPosition = state.cursor) {
+        if (definitionProviders.isEmpty() || !isNavigable(position)) return
+        _request.value = NavigationRequest(position, findReferences = false, state.snapshot.version)
+    }
+
+    /**
+     * Looks up the references of the name at [position] into [locations] once [refresh] runs.
+     */
+    fun requestReferences(position: CursorPosition = state.cursor) {
+        if (referenceProviders.isEmpty() || !isNavigable(position)) return
+        _request.value = NavigationRequest(position, findReferences = true, state.snapshot.version)
+    }
+
+    /**
+     * Runs the requested lookup, unless the document changed since it was asked for.
+     */
+    suspend fun refresh() {
+        val request = _request.value ?: return
+        if (request.version == state.snapshot.version) {
+            if (request.findReferences) findReferences(request.position) else goToDefinition(request.position)
+        }
+        _request.compareAndSet(request, null)
+    }
+
+    /**
+     * Jumps to the definition of the name at [position]. If there are several, they're also put in
+     * [locations]. Returns false if none was found or it couldn't be opened.
+     */
+    suspend fun goToDefinition(position: CursorPosition = state.cursor): Boolean {
+        val context = NavigationContext(state.snapshot, position, state.language)
+        val version = state.snapshot.version
+        var definitions = emptyList<Location>()
+        for (provider in definitionProviders) {
+            definitions = provideSafely { provider.provideDefinitions(context) }
+            if (definitions.isNotEmpty()) break
+        }
+        // Typing while waiting wins over the jump
+        if (definitions.isEmpty() || state.snapshot.version != version) return false
+        if (definitions.size > 1) _locations.value = definitions
+        return navigateTo(definitions.first())
+    }
+
+    /**
+     * Finds the references of the name at [position], sorted by file and position, and puts them in
+     * [locations].
+     */
+    suspend fun findReferences(position: CursorPosition = state.cursor, includeDeclaration: Boolean = true): List<Location> {
+        val context = NavigationContext(state.snapshot, position, state.language)
+        val references = referenceProviders
+            .flatMap { provider -> provideSafely { provider.provideReferences(context, includeDeclaration) } }
+            .distinct()
+            .sortedWith(compareBy<Location>({ it.uri.orEmpty() }, { it.range.startInclusive }))
+        _locations.value = references
+        return references
+    }
+
+    fun clearLocations() {
+        _locations.value = emptyList()
+    }
+
+    /**
+     * Moves the caret to [location], opening its file through [onOpenLocation] if it's in another one,
+     * and remembers where it was for [goBack]. Returns false if the location couldn't be opened.
+     */
+    fun navigateTo(location: Location): Boolean {
+        val from = currentEntry()
+        if (!open(location.uri, location.range)) return false
+        push(back, from)
+        clearForward()
+        updateFlags()
+        return true
+    }
+
+    /**
+     * Remembers the current place for [goBack], e.g. before the host moves the caret far away.
+     */
+    fun recordPosition() {
+        push(back, currentEntry())
+        clearForward()
+        updateFlags()
+    }
+
+    /**
+     * Returns to where the last jump came from. Returns false if there's nowhere to go back to.
+     */
+    fun goBack(): Boolean = travel(from = back, to = forward)
+
+    fun goForward(): Boolean = travel(from = forward, to = back)
+
+    internal fun onScrollRestored() {
+        _scrollRequest.value = null
+    }
+
+    private fun travel(from: ArrayDeque<Place>, to: ArrayDeque<Place>): Boolean {
+        val place = from.removeLastOrNull() ?: return false
+        val entry = place.entry
+        place.forget()
+        val current = currentEntry()
+        if (!open(entry.uri, entry.selection)) {
+            updateFlags()
+            return false
+        }
+        push(to, current)
+        if (entry.uri == state.filePath) _scrollRequest.value = entry.firstVisibleLine
+        updateFlags()
+        return true
+    }
+
+    private fun open(uri: String?, range: CursorRange): Boolean {
+        if (uri == null || uri == state.filePath) {
+            val snapshot = state.snapshot
+            state.setSelection(snapshot.clamp(range.start), snapshot.clamp(range.end))
+            return true
+        }
+        return onOpenLocation?.invoke(Location(uri, range)) == true
+    }
+
+    private fun currentEntry() = NavigationEntry(state.filePath, state.selection.value, firstVisibleLine)
+
+    private fun push(places: ArrayDeque<Place>, entry: NavigationEntry) {
+        // Jumping back and forth between two places shouldn't fill the history with them
+        if (places.lastOrNull()?.entry == entry) return
+        places.addLast(Place(entry))
+        if (places.size > MAX_HISTORY) places.removeFirst().forget()
+    }
+
+    private fun clearForward() {
+        forward.forEach(Place::forget)
+        forward.clear()
+    }
+
+    private fun updateFlags() {
+        _canGoBack.value = back.isNotEmpty()
+        _canGoForward.value = forward.isNotEmpty()
+    }
+
+    // A failing provider shouldn't take the others' locations with it
+    private suspend fun provideSafely(block: suspend () -> List<Location>): List<Location> {
+        return try {
+            block()
+        } catch (e: CancellationException) {
+            throw e
+        } catch (_: Exception) {
+            emptyList()
+        }
+    }
+}
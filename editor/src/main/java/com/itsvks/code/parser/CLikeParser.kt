@@ -0,0 +1,135 @@
+package com.itsvks.code.parser
+
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.language.Language
+import com.itsvks.code.syntax.LanguageBasedSyntaxHighlighter
+import com.itsvks.code.syntax.TokenType
+
+/**
+ * A loose parser for languages with C-like syntax. It only knows about brackets and statements: a
+ * statement runs up to a `;`, a block, or a line break where the line can't continue. That's enough
+ * for folding, bracket matching, selection expansion and an outline of classes and functions.
+ */
+class CLikeParser(private val language: Language) : TokenParser(LanguageBasedSyntaxHighlighter(language)) {
+    private val requiresFunctionKeyword = "fun" in language.keywords || "fn" in language.keywords
+
+    override fun parseDocument(context: ParseContext): List<Child> = parseItems(context, closing = null)
+
+    // Statements up to [closing], which is left for the caller
+    private fun parseItems(context: ParseContext, closing: String?): List<Child> = buildList {
+        while (!context.atEnd) {
+            val kind = context.peek()
+            when {
+                kind == closing -> break
+                kind in CLOSERS -> if (closing == null) add(context.advance()) else break
+                kind == "comment" -> add(context.advance())
+                else -> add(parseStatement(context))
+            }
+        }
+    }
+
+    private fun parseStatement(context: ParseContext): Child {
+        val rope = context.snapshot.rope
+        val children = ArrayList<Child>()
+        while (!context.atEnd) {
+            val kind = context.peek()
+            if (kind in CLOSERS) break
+            if (children.isNotEmpty()) {
+                val previousLine = rope.lineOfCharIndex(children.last().end)
+                val nextLine = rope.lineOfCharIndex(context.peekStart())
+                if (nextLine > previousLine) {
+                    if (children.last().kind == BLOCK || !continues(context, kind)) break
+                }
+            }
+            val child = parseElement(context)
+            children.add(child)
+            if (child.kind == ";") break
+        }
+        return if (children.size == 1) children[0] else context.node(STATEMENT, children)
+    }
+
+    // Whether the statement goes on past a line break, before a token of [next] kind
+    private fun continues(context: ParseContext, next: String?): Boolean {
+        val previous = context.previousLeaf() ?: return false
+        if (previous.tokenType == TokenType.OPERATOR || previous.kind in OPENERS || previous.kind == "," || previous.kind == ".") return true
+        if (next == "{" || next == "." || next == "?.") return true
+        return next != null && next in language.operators && next != "!" && next != "++" && next != "--"
+    }
+
+    private fun parseElement(context: ParseContext): Child = when (context.peek()) {
+        "{" -> context.reuse(BLOCK) ?: bracketed(context, BLOCK, "}", statements = true)
+        "(" -> context.reuse(PARENTHESIZED) ?: bracketed(context, PARENTHESIZED, ")", statements = false)
+        "[" -> context.reuse(BRACKETED) ?: bracketed(context, BRACKETED, "]", statements = false)
+        else -> context.advance()
+    }
+
+    private fun bracketed(context: ParseContext, kind: String, closing: String, statements: Boolean): Child {
+        val children = mutableListOf(context.advance())
+        if (statements) {
+            children.addAll(parseItems(context, closing))
+        } else {
+            while (!context.atEnd && context.peek() !in CLOSERS) children.add(parseElement(context))
+        }
+        // A missing or mismatched closer is left for an enclosing node
+        if (context.peek() == closing) children.add(context.advance())
+        return context.node(kind, children)
+    }
+
+    /**
+     * Classes and functions, i.e. statements ending in a block whose header looks like a declaration.
+     */
+    override fun outline(tree: SyntaxTree): List<OutlineItem> = outlineOf(tree, tree.root)
+
+    private fun outlineOf(tree: SyntaxTree, parent: SyntaxNode): List<OutlineItem> {
+        val items = ArrayList<OutlineItem>()
+        for (node in parent.children) {
+            if (node.kind != STATEMENT) continue
+            val body = node.lastChild?.takeIf { it.kind == BLOCK } ?: continue
+            val header = node.children.dropLast(1)
+            val (name, kind) = declarationIn(tree, header) ?: continue
+
+            fun range(from: Int, to: Int) = CursorRange(tree.snapshot.positionOf(from), tree.snapshot.positionOf(to))
+            items.add(OutlineItem(tree.textOf(name), kind, range(node.start, node.end), range(name.start, name.end), outlineOf(tree, body)))
+        }
+        return items
+    }
+
+    private fun declarationIn(tree: SyntaxTree, header: List<SyntaxNode>): Pair<SyntaxNode, OutlineKind>? {
+        val leaves = header.filter { it.isLeaf }
+        val classKeyword = leaves.indexOfFirst { it.tokenType == TokenType.KEYWORD && it.kind in CLASS_KEYWORDS }
+        if (classKeyword >= 0) {
+            val name = leaves.drop(classKeyword + 1).firstOrNull { it.tokenType in NAME_TYPES } ?: return null
+            return name to OutlineKind.CLASS
+        }
+
+        val hasFunctionKeyword = leaves.any { it.tokenType == TokenType.KEYWORD && it.kind in FUNCTION_KEYWORDS }
+        // In Kotlin and Rust anything else is a call with a trailing lambda or a macro
+        if (!hasFunctionKeyword && requiresFunctionKeyword) return null
+        for (i in 0 until header.size - 1) {
+            val node = header[i]
+            if (!node.isLeaf || node.tokenType != TokenType.IDENTIFIER && node.tokenType != TokenType.FUNCTION) continue
+            if (header[i + 1].kind != PARENTHESIZED) continue
+            if (tree.textOf(node) in language.keywords) continue
+            // A call on something, like `list.forEach(...) { }`, isn't a declaration
+            if (!hasFunctionKeyword && i > 0 && header[i - 1].kind == ".") continue
+            if (!hasFunctionKeyword && header.take(i).any { it.tokenType == TokenType.OPERATOR }) continue
+            return node to OutlineKind.FUNCTION
+        }
+        return null
+    }
+
+    companion object {
+        const val BLOCK = "block"
+        const val PARENTHESIZED = "parenthesized"
+        const val BRACKETED = "brackets"
+        const val STATEMENT = "statement"
+
+        private val OPENERS = setOf("(", "[", "{")
+        private val CLOSERS = setOf(")", "]", "}")
+        private val NAME_TYPES = setOf(TokenType.IDENTIFIER, TokenType.TYPE, TokenType.FUNCTION, TokenType.CONST)
+        private val CLASS_KEYWORDS = setOf(
+            "class", "interface", "object", "enum", "struct", "trait", "impl", "namespace", "record", "union"
+        )
+        private val FUNCTION_KEYWORDS = setOf("fun", "function", "fn", "func", "def")
+    }
+}
@@ -0,0 +1,133 @@
+package com.itsvks.code.parser
+
+import com.itsvks.code.core.TextChange
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.syntax.Token
+import kotlinx.coroutines.Dispatchers
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+import kotlinx.coroutines.withContext
+
+// Documents longer than this aren't parsed; a tree costs a node per token
+internal const val MAX_PARSED_LENGTH = 2_000_000
+
+// Edits kept for the next parse; past this many it starts from scratch anyway
+private const val MAX_PENDING_EDITS = 1024
+
+/**
+ * The syntax tree of a document, kept up to date with [parser] as it is edited. Edits are collected as
+ * they happen and handed to the parser with the previous tree, so each parse only redoes what changed.
+ */
+class DocumentSyntax(parser: LanguageParser?) {
+    private val lock = Any()
+
+    @Volatile
+    var parser: LanguageParser? = parser
+        private set
+
+    // Changes since the current tree, with the version each one produced
+    private val edits = ArrayList<Pair<Long, TextChange>>()
+
+    // The lines each of those changes replaced
+    private val lineEdits = ArrayList<Pair<Long, LineEdit>>()
+
+    private val _tree = MutableStateFlow<SyntaxTree?>(null)
+
+    /**
+     * The latest tree, which may be of an older version of the document than the current one.
+     */
+    val tree: StateFlow<SyntaxTree?> = _tree.asStateFlow()
+
+    fun reset(parser: LanguageParser?) {
+        synchronized(lock) {
+            this.parser = parser
+            edits.clear()
+            lineEdits.clear()
+            _tree.value = null
+        }
+    }
+
+    /**
+     * [change] turned the document into version [version], replacing [removedLines] lines from [startLine]
+     * on with [insertedLines] lines.
+     */
+    fun onChange(version: Long, change: TextChange, startLine: Int, removedLines: Int, insertedLines: Int) {
+        synchronized(lock) {
+            if (_tree.value == null) return
+            if (edits.size >= MAX_PENDING_EDITS) {
+                edits.clear()
+                lineEdits.clear()
+                _tree.value = null
+                return
+            }
+            edits.add(version to change)
+            lineEdits.add(version to LineEdit(startLine, removedLines, insertedLines))
+        }
+    }
+
+    /**
+     * Tokens of the latest tree moved onto the current document, or null if there is no tree. Lines
+     * edited since it was parsed have none until the next parse.
+     */
+    fun currentTokens(): TreeTokens? = synchronized(lock) {
+        val tree = _tree.value ?: return null
+        TreeTokens(tree, lineEdits.filter { it.first > tree.version }.map { it.second })
+    }
+
+    /**
+     * The tree of [snapshot], parsed on the calling thread unless it's the current one. Null without
+     * a parser or for documents too long to parse.
+     */
+    fun treeFor(snapshot: TextSnapshot): SyntaxTree? {
+        val (parser, previous, pending) = synchronized(lock) {
+            val previous = _tree.value?.takeIf { it.version <= snapshot.version }
+            val pending = previous?.let { tree -> edits.filter { it.first > tree.version && it.first <= snapshot.version } }
+            Triple(parser, previous, pending.orEmpty())
+        }
+        if (parser == null || snapshot.length > MAX_PARSED_LENGTH) return null
+        if (previous != null && previous.version == snapshot.version) return previous
+
+        // Without every edit since the previous tree it can't be reused
+        val reusable = previous?.takeIf { pending.size.toLong() == snapshot.version - it.version }
+        val tree = parser.parse(snapshot, reusable, if (reusable != null) pending.map { it.second } else emptyList())
+
+        synchronized(lock) {
+            val current = _tree.value
+            if (this.parser === parser && (current == null || current.version < tree.version)) {
+                _tree.value = tree
+                edits.removeAll { it.first <= tree.version }
+                lineEdits.removeAll { it.first <= tree.version }
+            }
+        }
+        return tree
+    }
+
+    /**
+     * Parses [snapshot] on a background thread.
+     */
+    suspend fun parse(snapshot: TextSnapshot): SyntaxTree? = withContext(Dispatchers.Default) { treeFor(snapshot) }
+}
+
+internal class LineEdit(val startLine: Int, val removedLines: Int, val insertedLines: Int)
+
+/**
+ * The tokens of [tree] on the lines of a later version of its document, given the [edits] made since.
+ */
+class TreeTokens internal constructor(private val tree: SyntaxTree, private val edits: List<LineEdit>) {
+    /**
+     * Tokens on [line] of the later version, or null if an edit touched it.
+     */
+    fun tokensOn(line: Int): List<Token>? {
+        var treeLine = line
+        // Back through the edits to the line it was in the tree
+        for (edit in edits.asReversed()) {
+            treeLine = when {
+                treeLine < edit.startLine -> treeLine
+                treeLine >= edit.startLine + edit.insertedLines -> treeLine - edit.insertedLines + edit.removedLines
+                else -> return null
+            }
+        }
+        return if (treeLine < tree.snapshot.lineCount) tree.tokensOn(treeLine) else null
+    }
+}
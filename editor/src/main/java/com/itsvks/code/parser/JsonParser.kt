@@ -0,0 +1,105 @@
+package com.itsvks.code.parser
+
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.language.JsonLanguage
+import com.itsvks.code.syntax.LanguageBasedSyntaxHighlighter
+import com.itsvks.code.syntax.TokenType
+
+/**
+ * Parses JSON, with comments, into `object`, `pair` and `array` nodes. Errors don't stop it: a missing
+ * bracket ends the node where its parent ends, and stray tokens stay where they are.
+ */
+class JsonParser : TokenParser(LanguageBasedSyntaxHighlighter(JsonLanguage)) {
+    override fun parseDocument(context: ParseContext): List<Child> = buildList {
+        while (!context.atEnd) add(parseValue(context))
+    }
+
+    private fun parseValue(context: ParseContext): Child = when (context.peek()) {
+        "{" -> context.reuse(OBJECT) ?: parseObject(context)
+        "[" -> context.reuse(ARRAY) ?: parseArray(context)
+        else -> context.advance()
+    }
+
+    private fun parseObject(context: ParseContext): Child {
+        val children = mutableListOf(context.advance())
+        while (!context.atEnd) {
+            when (context.peek()) {
+                "}" -> {
+                    children.add(context.advance())
+                    break
+                }
+
+                "]" -> break
+                "attribute", "string" -> children.add(parsePair(context))
+                "comment", "," -> children.add(context.advance())
+                else -> children.add(parseValue(context))
+            }
+        }
+        return context.node(OBJECT, children)
+    }
+
+    private fun parsePair(context: ParseContext): Child {
+        val key = context.advance()
+        val children = mutableListOf(Child(key.start, key.green.withTokenType(TokenType.ATTRIBUTE)))
+        while (context.peek() == "comment") children.add(context.advance())
+        if (context.peek() == ":") {
+            children.add(context.advance())
+            while (context.peek() == "comment") children.add(context.advance())
+            if (context.peek() !in VALUE_END) children.add(parseValue(context))
+        }
+        return context.node(PAIR, children)
+    }
+
+    private fun parseArray(context: ParseContext): Child {
+        val children = mutableListOf(context.advance())
+        while (!context.atEnd) {
+            when (context.peek()) {
+                "]" -> {
+                    children.add(context.advance())
+                    break
+                }
+
+                "}" -> break
+                "comment", "," -> children.add(context.advance())
+                else -> children.add(parseValue(context))
+            }
+        }
+        return context.node(ARRAY, children)
+    }
+
+    /**
+     * Every property, with those of nested objects as its children. Objects in arrays are named by their index.
+     */
+    override fun outline(tree: SyntaxTree): List<OutlineItem> {
+        return tree.root.children.flatMap { outlineOf(tree, it) }
+    }
+
+    private fun outlineOf(tree: SyntaxTree, value: SyntaxNode?): List<OutlineItem> = when (value?.kind) {
+        null -> emptyList()
+        OBJECT -> value!!.children.filter { it.kind == PAIR }.map { pair ->
+            val key = pair.firstChild!!
+            val name = tree.textOf(key).removeSurrounding("\"")
+            item(tree, name, pair, key, outlineOf(tree, pair.children.lastOrNull { it.kind == OBJECT || it.kind == ARRAY }))
+        }
+
+        ARRAY -> value!!.children.filter { it.kind !in SEPARATORS }.withIndex()
+            .filter { (_, element) -> element.kind == OBJECT || element.kind == ARRAY }
+            .map { (i, element) -> item(tree, i.toString(), element, element, outlineOf(tree, element)) }
+
+        else -> emptyList()
+    }
+
+    private fun item(tree: SyntaxTree, name: String, node: SyntaxNode, nameNode: SyntaxNode, children: List<OutlineItem>): OutlineItem {
+        fun range(node: SyntaxNode) = CursorRange(tree.snapshot.positionOf(node.start), tree.snapshot.positionOf(node.end))
+        return OutlineItem(name, OutlineKind.PROPERTY, range(node), range(nameNode), children)
+    }
+
+    companion object {
+        const val OBJECT = "object"
+        const val PAIR = "pair"
+        const val ARRAY = "array"
+
+        private val VALUE_END = setOf(",", "}", "]", null)
+        private val SEPARATORS = setOf("[", "]", ",", "comment")
+    }
+}
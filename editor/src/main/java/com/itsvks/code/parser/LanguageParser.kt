@@ -0,0 +1,128 @@
+package com.itsvks.code.parser
+
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextChange
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.syntax.SyntaxHighlighter
+import com.itsvks.code.syntax.TokenType
+
+enum class OutlineKind { CLASS, FUNCTION, PROPERTY }
+
+/**
+ * A declaration in the document outline. [range] covers the whole declaration, [selectionRange] its name.
+ */
+data class OutlineItem(
+    val name: String,
+    val kind: OutlineKind,
+    val range: CursorRange,
+    val selectionRange: CursorRange,
+    val children: List<OutlineItem> = emptyList()
+)
+
+/**
+ * Builds [SyntaxTree]s for a language, reusing what an earlier tree of the same document still has right.
+ */
+interface LanguageParser {
+    /**
+     * Parses [snapshot]. [previous] is an earlier tree of the same document and [edits] the changes made
+     * to it since, in order; parts of [previous] the edits didn't touch are reused.
+     */
+    fun parse(snapshot: TextSnapshot, previous: SyntaxTree? = null, edits: List<TextChange> = emptyList()): SyntaxTree
+
+    fun outline(tree: SyntaxTree): List<OutlineItem> = emptyList()
+}
+
+/**
+ * A node being built: where it starts and what it is.
+ */
+internal class Child(val start: Int, val green: GreenNode) {
+    val end: Int get() = start + green.length
+    val kind: String get() = green.kind
+}
+
+/**
+ * Cursor over the tokens a [TokenParser] parses, which also hands out subtrees of the previous tree
+ * that can be taken over unchanged.
+ */
+internal class ParseContext(
+    val snapshot: TextSnapshot,
+    private val lexed: LexedTokens,
+    private val previous: SyntaxTree?
+) {
+    val tokens = lexed.tokens
+    var position = 0
+        private set
+
+    val atEnd: Boolean get() = position >= tokens.size
+
+    fun peek(): String? = if (atEnd) null else tokens.leaves[position].kind
+
+    fun peekStart(): Int = tokens.starts[position]
+
+    // The token before the current one
+    fun previousLeaf(): GreenNode? = if (position > 0) tokens.leaves[position - 1] else null
+
+    fun previousEnd(): Int = if (position > 0) tokens.end(position - 1) else 0
+
+    fun advance(): Child = Child(tokens.starts[position], tokens.leaves[position]).also { position++ }
+
+    /**
+     * The node of the previous tree of [kind] starting where the current token starts, if every token
+     * it covers was taken over from the previous tokens. Skips past it when found.
+     */
+    fun reuse(kind: String): Child? {
+        if (previous == null || atEnd) return null
+        val start = peekStart()
+        val oldStart = when {
+            start < lexed.prefixEnd -> start
+            start >= lexed.suffixStart -> start - lexed.delta
+            else -> return null
+        }
+        val old = previous.findNode(oldStart, kind) ?: return null
+        val end = start + old.length
+        if (start < lexed.prefixEnd && end > lexed.prefixEnd) return null
+
+        val next = tokens.indexAtOrAfter(end)
+        if (next == 0 || tokens.end(next - 1) != end) return null
+        position = next
+        return Child(start, old.green)
+    }
+
+    fun node(kind: String, children: List<Child>): Child {
+        val start = children.first().start
+        val offsets = IntArray(children.size) { children[it].start - start }
+        val green = GreenNode(kind, children.last().end - start, Array(children.size) { children[it].green }, offsets)
+        return Child(start, green)
+    }
+}
+
+/**
+ * A parser over the tokens [lexer] produces, lexing again only from the edited lines on until the
+ * lexer state is the same as before. Subclasses turn the tokens into nodes with [parseDocument].
+ */
+abstract class TokenParser internal constructor(private val lexer: SyntaxHighlighter) : LanguageParser {
+    final override fun parse(snapshot: TextSnapshot, previous: SyntaxTree?, edits: List<TextChange>): SyntaxTree {
+        if (previous != null && previous.parser === this && previous.version == snapshot.version) return previous
+        val edit = if (previous?.parser === this) EditRegion.of(edits) else null
+        val reused = previous.takeIf { edit != null }
+
+        val lexed = lexTokens(snapshot, lexer, ::kindOf, reused?.tokens, edit)
+        val context = ParseContext(snapshot, lexed, reused)
+        val children = parseDocument(context)
+        val root = GreenNode(
+            kind = DOCUMENT,
+            length = snapshot.length,
+            children = Array(children.size) { children[it].green },
+            offsets = IntArray(children.size) { children[it].start }
+        )
+        return SyntaxTree(root, lexed.tokens, snapshot, this)
+    }
+
+    internal open fun kindOf(type: TokenType, text: String): String = tokenKind(type, text)
+
+    internal abstract fun parseDocument(context: ParseContext): List<Child>
+
+    companion object {
+        const val DOCUMENT = "document"
+    }
+}
@@ -0,0 +1,117 @@
+package com.itsvks.code.parser
+
+import com.itsvks.code.syntax.TokenType
+
+/**
+ * The immutable part of a node. Children are positioned relative to their parent, so a subtree that an
+ * edit didn't touch is shared as is by the next tree, wherever it moved to. Leaves are tokens.
+ */
+internal class GreenNode(
+    val kind: String,
+    val length: Int,
+    val children: Array<GreenNode> = NO_CHILDREN,
+    val offsets: IntArray = NO_OFFSETS,
+    val tokenType: TokenType? = null
+) {
+    fun withTokenType(type: TokenType) = GreenNode(kind, length, children, offsets, type)
+
+    companion object {
+        val NO_CHILDREN = emptyArray<GreenNode>()
+        val NO_OFFSETS = IntArray(0)
+    }
+}
+
+/**
+ * A node of a [SyntaxTree], covering the document offsets `[start, end)`. Leaves are tokens whose [kind]
+ * is their text for keywords, operators and punctuation, or a name like `string` or `comment` otherwise.
+ */
+class SyntaxNode internal constructor(
+    internal val green: GreenNode,
+    val start: Int,
+    val parent: SyntaxNode?,
+    private val index: Int
+) {
+    val kind: String get() = green.kind
+    val end: Int get() = start + green.length
+    val length: Int get() = green.length
+    val isLeaf: Boolean get() = green.children.isEmpty() && green.tokenType != null
+
+    /**
+     * How the token is highlighted; null for inner nodes.
+     */
+    val tokenType: TokenType? get() = green.tokenType
+
+    val childCount: Int get() = green.children.size
+
+    fun child(index: Int): SyntaxNode = SyntaxNode(green.children[index], start + green.offsets[index], this, index)
+
+    val children: List<SyntaxNode> get() = List(childCount, ::child)
+
+    val firstChild: SyntaxNode? get() = if (childCount > 0) child(0) else null
+
+    val lastChild: SyntaxNode? get() = if (childCount > 0) child(childCount - 1) else null
+
+    val nextSibling: SyntaxNode? get() = parent?.takeIf { index + 1 < it.childCount }?.child(index + 1)
+
+    val previousSibling: SyntaxNode? get() = parent?.takeIf { index > 0 }?.child(index - 1)
+
+    /**
+     * Whether this node is enclosed in a pair of brackets, e.g. a block or an argument list.
+     */
+    val isBracketed: Boolean
+        get() = childCount >= 2 && BRACKETS[green.children.first().kind] == green.children.last().kind
+
+    /**
+     * Index of the child covering [offset], or of the last one starting before it; -1 if none does.
+     */
+    fun childIndexAt(offset: Int): Int {
+        var low = 0
+        var high = childCount - 1
+        while (low <= high) {
+            val mid = (low + high) ushr 1
+            if (start + green.offsets[mid] <= offset) low = mid + 1 else high = mid - 1
+        }
+        return high
+    }
+
+    /**
+     * The deepest node containing `[from, to]`. Ends count as inside, but a node starting at [from]
+     * wins over one ending there.
+     */
+    fun descendantFor(from: Int, to: Int = from): SyntaxNode {
+        var node = this
+        while (true) {
+            val i = node.childIndexAt(from)
+            if (i < 0) return node
+            val child = node.child(i)
+            if (to > child.end || child.length == 0) return node
+            node = child
+        }
+    }
+
+    /**
+     * Leaves within `[from, to)`, in order.
+     */
+    fun leavesIn(from: Int, to: Int): Sequence<SyntaxNode> = sequence {
+        if (end <= from || start >= to) return@sequence
+        if (isLeaf) {
+            yield(this@SyntaxNode)
+            return@sequence
+        }
+        for (i in childIndexAt(from).coerceAtLeast(0) until childCount) {
+            val child = child(i)
+            if (child.start >= to) break
+            yieldAll(child.leavesIn(from, to))
+        }
+    }
+
+    override fun equals(other: Any?) = other is SyntaxNode && other.green === green && other.start == start
+
+    override fun hashCode() = System.identityHashCode(green) * 31 + start
+
+    override fun toString() = "$kind[$start, $end)"
+
+    internal companion object {
+        val BRACKETS = mapOf("(" to ")", "[" to "]", "{" to "}")
+    }
+}
@@ -0,0 +1,115 @@
+package com.itsvks.code.parser
+
+import com.itsvks.code.core.FoldableRange
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.syntax.Token
+
+/**
+ * The syntax tree of [snapshot], as built by [parser]. Trees are immutable and safe to read from any thread.
+ */
+class SyntaxTree internal constructor(
+    internal val green: GreenNode,
+    internal val tokens: TokenList,
+    val snapshot: TextSnapshot,
+    val parser: LanguageParser
+) {
+    val version: Long get() = snapshot.version
+
+    val root: SyntaxNode = SyntaxNode(green, 0, null, 0)
+
+    fun textOf(node: SyntaxNode): String = snapshot.getText(node.start, node.end)
+
+    /**
+     * The token covering [offset], i.e. starting at or before it and ending after it.
+     */
+    fun leafAt(offset: Int): SyntaxNode? = root.descendantFor(offset).takeIf { it.isLeaf && offset < it.end }
+
+    /**
+     * Tokens on [line], with columns relative to the line. Constructs spanning lines are cut at the line's ends.
+     */
+    fun tokensOn(line: Int): List<Token> {
+        val lineStart = snapshot.rope.lineStartIndex(line)
+        val lineEnd = lineStart + snapshot.getLineLength(line)
+        return root.leavesIn(lineStart, lineEnd).map { leaf ->
+            Token(leaf.tokenType!!, maxOf(leaf.start, lineStart) - lineStart, minOf(leaf.end, lineEnd) - lineStart)
+        }.toList()
+    }
+
+    /**
+     * Offsets of the bracket at [offset], or else of the one just before it, and of its partner;
+     * null if neither is a bracket with a partner. Brackets in strings and comments are not brackets.
+     */
+    fun bracketPairAt(offset: Int): Pair<Int, Int>? {
+        for (at in intArrayOf(offset, offset - 1)) {
+            if (at < 0) continue
+            val leaf = leafAt(at) ?: continue
+            val parent = leaf.parent?.takeIf { it.isBracketed } ?: continue
+            val open = parent.firstChild!!
+            val close = parent.lastChild!!
+            if (leaf == open || leaf == close) return open.start to close.start
+        }
+        return null
+    }
+
+    /**
+     * Bracketed nodes that span lines, from their opening to their closing line, and multi-line comments.
+     * Of ranges starting on the same line only the outermost is kept.
+     */
+    fun foldableRanges(): List<FoldableRange> {
+        val rope = snapshot.rope
+        val ranges = ArrayList<FoldableRange>()
+        val stack = ArrayDeque<SyntaxNode>().apply { add(root) }
+        while (stack.isNotEmpty()) {
+            val node = stack.removeLast()
+            val startLine = rope.lineOfCharIndex(node.start)
+            val endLine = rope.lineOfCharIndex(node.end)
+            // Nothing on a single line folds
+            if (endLine == startLine) continue
+
+            when {
+                node.isBracketed -> {
+                    val closeLine = rope.lineOfCharIndex(node.lastChild!!.start)
+                    val type = if (node.firstChild!!.kind == "{") FoldableRange.BRACES else FoldableRange.BRACKETS
+                    if (closeLine > startLine + 1) ranges.add(FoldableRange(startLine, closeLine, type))
+                }
+
+                node.isLeaf && node.kind == "comment" -> ranges.add(FoldableRange(startLine, endLine, FoldableRange.COMMENT))
+            }
+            for (i in node.childCount - 1 downTo 0) stack.add(node.child(i))
+        }
+        return ranges.sortedWith(compareBy({ it.startLine }, { -it.endLine })).distinctBy { it.startLine }
+    }
+
+    /**
+     * The next larger syntactic range around `[start, end)`: the contents of the brackets around it,
+     * or else the smallest node that contains more than it. Null if there is nothing larger.
+     */
+    fun expandSelection(start: Int, end: Int): Pair<Int, Int>? {
+        var node: SyntaxNode? = root.descendantFor(start, end)
+        while (node != null) {
+            if (node.isBracketed && node.childCount > 2) {
+                val innerStart = node.child(1).start
+                val innerEnd = node.child(node.childCount - 2).end
+                if (innerStart <= start && end <= innerEnd && (innerStart < start || end < innerEnd)) return innerStart to innerEnd
+            }
+            if (node.start <= start && end <= node.end && (node.start < start || end < node.end)) return node.start to node.end
+            node = node.parent
+        }
+        return null
+    }
+
+    fun outline(): List<OutlineItem> = parser.outline(this)
+
+    // The node of [kind] starting at [offset], the outermost if several do
+    internal fun findNode(offset: Int, kind: String): SyntaxNode? {
+        var node = root
+        while (true) {
+            val i = node.childIndexAt(offset)
+            if (i < 0) return null
+            val child = node.child(i)
+            if (child.start == offset && child.kind == kind) return child
+            if (child.isLeaf || offset >= child.end) return null
+            node = child
+        }
+    }
+}
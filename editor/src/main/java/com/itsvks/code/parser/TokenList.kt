@@ -0,0 +1,172 @@
+package com.itsvks.code.parser
+
+import com.itsvks.code.core.TextChange
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.syntax.LexerState
+import com.itsvks.code.syntax.SyntaxHighlighter
+import com.itsvks.code.syntax.TokenType
+
+/**
+ * Tokens of a whole document in order, as leaves and the offsets they start at.
+ */
+internal class TokenList(val starts: IntArray, val leaves: Array<GreenNode>) {
+    val size: Int get() = starts.size
+
+    fun end(index: Int) = starts[index] + leaves[index].length
+
+    /**
+     * Index of the first token starting at or after [offset]; [size] if there is none.
+     */
+    fun indexAtOrAfter(offset: Int): Int {
+        var low = 0
+        var high = size
+        while (low < high) {
+            val mid = (low + high) ushr 1
+            if (starts[mid] < offset) low = mid + 1 else high = mid
+        }
+        return low
+    }
+
+    // Whether a token starts before [offset] and ends after it
+    fun covers(offset: Int): Boolean {
+        val before = indexAtOrAfter(offset) - 1
+        return before >= 0 && end(before) > offset
+    }
+
+    class Builder {
+        private var starts = IntArray(256)
+        private val leaves = ArrayList<GreenNode>()
+
+        val size: Int get() = leaves.size
+
+        fun add(start: Int, leaf: GreenNode) {
+            if (leaves.size == starts.size) starts = starts.copyOf(starts.size * 2)
+            starts[leaves.size] = start
+            leaves.add(leaf)
+        }
+
+        fun addAll(tokens: TokenList, from: Int, until: Int, delta: Int) {
+            for (i in from until until) add(tokens.starts[i] + delta, tokens.leaves[i])
+        }
+
+        // Extends the last token up to [end], when a construct carries on into the next line
+        fun extendLast(end: Int) {
+            val last = leaves.size - 1
+            val leaf = leaves[last]
+            leaves[last] = GreenNode(leaf.kind, end - starts[last], tokenType = leaf.tokenType)
+        }
+
+        fun lastType(): TokenType? = leaves.lastOrNull()?.tokenType
+
+        fun build() = TokenList(starts.copyOf(leaves.size), leaves.toTypedArray())
+    }
+}
+
+/**
+ * Where a batch of edits changed the document: `[start, oldEnd)` of the old text became `[start, newEnd)`.
+ * Everything before [start] is unchanged, everything after moved by [delta].
+ */
+internal class EditRegion(val start: Int, val oldEnd: Int, val newEnd: Int) {
+    val delta: Int get() = newEnd - oldEnd
+
+    companion object {
+        fun of(changes: List<TextChange>): EditRegion? {
+            if (changes.isEmpty()) return null
+            var start = changes[0].start
+            var oldEnd = changes[0].oldEnd
+            var newEnd = changes[0].newEnd
+            for (change in changes.drop(1)) {
+                // Offsets are those of the text the change applied to, i.e. after the changes before it
+                if (change.oldEnd >= newEnd) {
+                    oldEnd += change.oldEnd - newEnd
+                    newEnd = change.newEnd
+                } else {
+                    newEnd += change.newEnd - change.oldEnd
+                }
+                if (change.start < start) start = change.start
+            }
+            return EditRegion(start, oldEnd, newEnd)
+        }
+    }
+}
+
+/**
+ * Tokens of a document, and which of them were taken over from the previous tokens: those ending
+ * before [prefixEnd] and those starting at or after [suffixStart], moved by [delta].
+ */
+internal class LexedTokens(val tokens: TokenList, val prefixEnd: Int, val suffixStart: Int, val delta: Int)
+
+/**
+ * Lexes [snapshot] line by line with [lexer]. Given the [previous] tokens and the [edit] made since, only
+ * lexes from the first edited line until a line after the edit starts outside any token, as it did before.
+ *
+ * A construct that continues on the next line becomes a single token spanning the line break, so a line
+ * start no token covers is always lexed from the lexer's initial state.
+ */
+internal fun lexTokens(
+    snapshot: TextSnapshot,
+    lexer: SyntaxHighlighter,
+    kindOf: (TokenType, String) -> String,
+    previous: TokenList?,
+    edit: EditRegion?
+): LexedTokens {
+    val rope = snapshot.rope
+    val builder = TokenList.Builder()
+    var line = 0
+    var prefixEnd = 0
+
+    if (previous != null && edit != null) {
+        var restart = rope.lineStartIndex(rope.lineOfCharIndex(edit.start.coerceAtMost(snapshot.length)))
+        while (previous.covers(restart)) {
+            restart = rope.lineStartIndex(rope.lineOfCharIndex(previous.starts[previous.indexAtOrAfter(restart) - 1]))
+        }
+        builder.addAll(previous, 0, previous.indexAtOrAfter(restart), 0)
+        line = rope.lineOfCharIndex(restart)
+        prefixEnd = restart
+    }
+
+    val initial = lexer.initialState
+    var state: LexerState = initial
+    val lineCount = snapshot.lineCount
+    while (line < lineCount) {
+        val lineStart = rope.lineStartIndex(line)
+        if (previous != null && edit != null && state == initial && lineStart > edit.newEnd) {
+            // The line break before this line wasn't edited, so it starts where an old line did
+            val oldStart = lineStart - edit.delta
+            if (!previous.covers(oldStart)) {
+                builder.addAll(previous, previous.indexAtOrAfter(oldStart), previous.size, edit.delta)
+                return LexedTokens(builder.build(), prefixEnd, lineStart, edit.delta)
+            }
+        }
+
+        val text = snapshot.getLine(line)
+        val result = lexer.highlightLine(text, state)
+        for ((i, token) in result.tokens.withIndex()) {
+            var start = token.start
+            var end = token.end
+            if (token.type == TokenType.PLAIN) {
+                while (start < end && text[start].isWhitespace()) start++
+                while (end > start && text[end - 1].isWhitespace()) end--
+            }
+            if (start >= end) continue
+
+            if (i == 0 && start == 0 && state != initial && builder.lastType() == token.type) {
+                builder.extendLast(lineStart + end)
+            } else {
+                builder.add(lineStart + start, GreenNode(kindOf(token.type, text.substring(start, end)), end - start, tokenType = token.type))
+            }
+        }
+        state = result.endState
+        line++
+    }
+    return LexedTokens(builder.build(), prefixEnd, Int.MAX_VALUE, edit?.delta ?: 0)
+}
+
+/**
+ * Kind of a token: its text for keywords, operators and punctuation, a name for everything else.
+ */
+internal fun tokenKind(type: TokenType, text: String): String = when (type) {
+    TokenType.KEYWORD, TokenType.OPERATOR, TokenType.PUNCTUATION -> text.intern()
+    TokenType.PLAIN -> "text"
+    else -> type.name.lowercase()
+}
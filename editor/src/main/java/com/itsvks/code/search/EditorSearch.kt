@@ -0,0 +1,303 @@
+package com.itsvks.code.search
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextEdit
+import com.itsvks.code.core.TextSnapshot
+import kotlinx.coroutines.Dispatchers
+import kotlinx.coroutines.ensureActive
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+import kotlinx.coroutines.withContext
+import java.util.regex.PatternSyntaxException
+
+// Partial results are published every this many matches while a search is running
+private const val PUBLISH_CHUNK_SIZE = 1000
+private const val MAX_MATCHES = 100_000
+
+// Chars read between two cancellation checks
+private const val CANCEL_CHECK_INTERVAL = 16_384
+
+data class SearchQuery(
+    val text: String,
+    val caseSensitive: Boolean = false,
+    val wholeWord: Boolean = false,
+    val isRegex: Boolean = false
+) {
+    /**
+     * Pattern matching this query. Throws [PatternSyntaxException] for an invalid regex.
+     */
+    fun toRegex(): Regex {
+        val pattern = if (isRegex) text else Regex.escape(text)
+        val bounded = if (wholeWord) "(?<!\\w)(?:$pattern)(?!\\w)" else pattern
+        val options = if (caseSensitive) setOf(RegexOption.MULTILINE) else setOf(RegexOption.MULTILINE, RegexOption.IGNORE_CASE)
+        return Regex(bounded, options)
+    }
+}
+
+/**
+ * A match in document coordinates. [groups] holds the capture groups of a regex match, starting with the whole match.
+ */
+data class SearchMatch(val range: CursorRange, val groups: List<String> = emptyList())
+
+/**
+ * Finds and replaces text in a [CodeEditorState].
+ *
+ * [setQuery] only records what to look for; [refresh] does the actual search off the main thread.
+ * The editor runs it whenever the query or the document changes, publishing matches as they are found.
+ * Moving between and replacing matches suspend to search again first if the document changed since.
+ */
+class EditorSearch internal constructor(private val state: CodeEditorState) {
+    private val _query = MutableStateFlow<SearchQuery?>(null)
+    val query: StateFlow<SearchQuery?> = _query.asStateFlow()
+
+    private val _matches = MutableStateFlow<List<SearchMatch>>(emptyList())
+    val matches: StateFlow<List<SearchMatch>> = _matches.asStateFlow()
+
+    private val _currentIndex = MutableStateFlow(-1)
+
+    /**
+     * Index of the current match in [matches], or -1 if there is none.
+     */
+    val currentIndex: StateFlow<Int> = _currentIndex.asStateFlow()
+
+    private val _isSearching = MutableStateFlow(false)
+    val isSearching: StateFlow<Boolean> = _isSearching.asStateFlow()
+
+    private val _error = MutableStateFlow<String?>(null)
+
+    /**
+     * Why the current query could not be searched for, e.g. a regex syntax error.
+     */
+    val error: StateFlow<String?> = _error.asStateFlow()
+
+    // Version of the snapshot the matches were found in
+    private var matchesVersion = -1L
+
+    // Select the current match once the next search finishes, set when the query changes
+    private var selectPending = false
+
+    /**
+     * Starts searching for [query], or stops searching if it is null or empty.
+     */
+    fun setQuery(query: SearchQuery?) {
+        val effective = query?.takeIf { it.text.isNotEmpty() }
+        if (effective == _query.value) return
+        _query.value = effective
+        selectPending = effective != null
+        if (effective == null) clearResults()
+    }
+
+    /**
+     * Searches the current snapshot for the query. Cancelling the calling coroutine stops the search;
+     * a newer call simply starts over.
+     */
+    suspend fun refresh() {
+        val query = _query.value ?: return clearResults()
+        val regex = compile(query) ?: return
+        val snapshot = state.snapshot
+
+        _isSearching.value = true
+        try {
+            val found = withContext(Dispatchers.Default) {
+                findMatches(snapshot, regex, query.isRegex, checkCancelled = { ensureActive() }) { partial ->
+                    _matches.value = partial
+                }
+            }
+            publish(found, snapshot)
+        } finally {
+            _isSearching.value = false
+        }
+    }
+
+    suspend fun findNext() = moveCurrent(1)
+
+    suspend fun findPrevious() = moveCurrent(-1)
+
+    /**
+     * Replaces the current match. With a regex query, `$1` or `${1}` in [replacement] insert capture groups.
+     * Returns false if there was no match to replace.
+     */
+    suspend fun replaceCurrent(replacement: String): Boolean {
+        val query = _query.value ?: return false
+        ensureUpToDate()
+        val match = _matches.value.getOrNull(_currentIndex.value) ?: return false
+
+        state.replace(match.range, match.replacement(replacement, query.isRegex))
+        selectPending = true
+        return true
+    }
+
+    /**
+     * Replaces every match as a single undoable edit and returns how many were replaced.
+     */
+    suspend fun replaceAll(replacement: String): Int {
+        val query = _query.value ?: return 0
+        ensureUpToDate()
+        val matches = _matches.value
+        if (matches.isEmpty()) return 0
+
+        state.applyEdits(matches.map { TextEdit(it.range, it.replacement(replacement, query.isRegex)) })
+        return matches.size
+    }
+
+    fun clear() = setQuery(null)
+
+    private suspend fun moveCurrent(step: Int) {
+        ensureUpToDate()
+        val matches = _matches.value
+        if (matches.isEmpty()) return
+
+        val current = _currentIndex.value
+        val index = if (current < 0) 0 else Math.floorMod(current + step, matches.size)
+        _currentIndex.value = index
+        state.setSelection(matches[index].range)
+    }
+
+    // Searches again when the document changed since the last search, so replacing never uses stale ranges
+    private suspend fun ensureUpToDate() {
+        while (_query.value != null && matchesVersion != state.snapshot.version) {
+            refresh()
+            if (_error.value != null) return
+        }
+    }
+
+    private fun compile(query: SearchQuery): Regex? {
+        return try {
+            query.toRegex().also { _error.value = null }
+        } catch (e: PatternSyntaxException) {
+            _error.value = e.description
+            clearResults()
+            null
+        }
+    }
+
+    private fun publish(found: List<SearchMatch>, snapshot: TextSnapshot) {
+        _matches.value = found
+        matchesVersion = snapshot.version
+
+        // The current match is the first one at or after the primary selection
+        val selectionStart = state.selection.value.startInclusive
+        val index = found.binarySearchBy(selectionStart) { it.range.startInclusive }.let { if (it < 0) -it - 1 else it }
+        _currentIndex.value = when {
+            found.isEmpty() -> -1
+            index < found.size -> index
+            else -> 0
+        }
+
+        if (selectPending && found.isNotEmpty()) {
+            selectPending = false
+            state.setSelection(found[_currentIndex.value].range)
+        }
+    }
+
+    private fun clearResults() {
+        _matches.value = emptyList()
+        _currentIndex.value = -1
+        matchesVersion = -1L
+    }
+
+    private fun findMatches(
+        snapshot: TextSnapshot,
+        regex: Regex,
+        keepGroups: Boolean,
+        checkCancelled: () -> Unit,
+        onProgress: (List<SearchMatch>) -> Unit
+    ): List<SearchMatch> {
+        val text = CancellableText(snapshot.getText(), checkCancelled)
+        val result = ArrayList<SearchMatch>()
+        var match = regex.find(text)
+
+        while (match != null && result.size < MAX_MATCHES) {
+            checkCancelled()
+            val range = match.range
+            // Empty matches, e.g. for `a*`, can't be highlighted or replaced sensibly
+            if (!range.isEmpty()) {
+                result.add(
+                    SearchMatch(
+                        range = CursorRange(snapshot.positionOf(range.first), snapshot.positionOf(range.last + 1)),
+                        groups = if (keepGroups) match.groupValues else emptyList()
+                    )
+                )
+                if (result.size % PUBLISH_CHUNK_SIZE == 0) onProgress(result.toList())
+            }
+            match = match.next()
+        }
+        return result
+    }
+}
+
+/**
+ * Text that calls [checkCancelled] every so many chars read, so a long regex scan between two matches can
+ * still be cancelled.
+ */
+private class CancellableText(private val text: String, private val checkCancelled: () -> Unit) : CharSequence {
+    private var reads = 0
+
+    override val length get() = text.length
+
+    override fun get(index: Int): Char {
+        if (++reads % CANCEL_CHECK_INTERVAL == 0) checkCancelled()
+        return text[index]
+    }
+
+    override fun subSequence(startIndex: Int, endIndex: Int) = text.subSequence(startIndex, endIndex)
+
+    override fun toString() = text
+}
+
+/**
+ * Text replacing this match. For regex matches `$n` and `${n}` insert capture groups, `$$` is a literal
+ * dollar sign and `\n`, `\t` and `\\` are escapes.
+ */
+internal fun SearchMatch.replacement(template: String, isRegex: Boolean): String {
+    if (!isRegex) return template
+
+    val result = StringBuilder(template.length)
+    var i = 0
+    while (i < template.length) {
+        val c = template[i]
+        val next = template.getOrNull(i + 1)
+        when {
+            c == '\\' && next != null -> {
+                result.append(
+                    when (next) {
+                        'n' -> '\n'
+                        't' -> '\t'
+                        else -> next
+                    }
+                )
+                i += 2
+            }
+
+            c == '$' && next == '$' -> {
+                result.append('$')
+                i += 2
+            }
+
+            c == '$' && next == '{' -> {
+                val close = template.indexOf('}', i + 2)
+                val group = if (close > 0) template.substring(i + 2, close).toIntOrNull() else null
+                if (group == null) {
+                    result.append(c)
+                    i++
+                } else {
+                    result.append(groups.getOrElse(group) { "" })
+                    i = close + 1
+                }
+            }
+
+            c == '$' && next != null && next.isDigit() -> {
+                result.append(groups.getOrElse(next.digitToInt()) { "" })
+                i += 2
+            }
+
+            else -> {
+                result.append(c)
+                i++
+            }
+        }
+    }
+    return result.toString()
+}
@@ -0,0 +1,117 @@
+package com.itsvks.code.signature
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorRange
+import kotlinx.coroutines.CancellationException
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+
+/**
+ * Why signature help is open: [triggerCharacter] is the char that opened it, or null if the user
+ * asked for it.
+ */
+class SignatureHelpRequest internal constructor(val triggerCharacter: Char?) {
+    // The document version and selection last answered for
+    internal var version = -1L
+    internal var selection: CursorRange? = null
+}
+
+/**
+ * Signature help for a [CodeEditorState]: typing a trigger char such as `(` or `,` in a call shows
+ * the function's signatures with the parameter being typed highlighted. [refresh] asks the
+ * [providers] again as the arguments are typed; moving the caret without editing closes it.
+ */
+class EditorSignatureHelp internal constructor(private val state: CodeEditorState) {
+    /**
+     * Asked in this order; the first that knows the call wins.
+     */
+    var providers: List<SignatureHelpProvider> = emptyList()
+
+    private val _request = MutableStateFlow<SignatureHelpRequest?>(null)
+
+    /**
+     * Why signature help is open, or null while it's closed.
+     */
+    val request: StateFlow<SignatureHelpRequest?> = _request.asStateFlow()
+
+    private val _help = MutableStateFlow<SignatureHelp?>(null)
+
+    /**
+     * The signatures being shown, or null.
+     */
+    val help: StateFlow<SignatureHelp?> = _help.asStateFlow()
+
+    val isShowing: Boolean get() = _help.value != null
+
+    /**
+     * Opens signature help for the call around the caret.
+     */
+    fun show() = open(null)
+
+    fun dismiss() {
+        _request.value = null
+        _help.value = null
+    }
+
+    /**
+     * Opens signature help after [typed] was typed at the caret, if it's a trigger char.
+     */
+    internal fun onTyped(typed: Char) {
+        when {
+            state.hasMultipleSelections -> dismiss()
+            providers.any { typed in it.triggerCharacters } -> open(typed)
+        }
+    }
+
+    private fun open(triggerCharacter: Char?) {
+        if (providers.isEmpty()) return
+        _request.value = SignatureHelpRequest(triggerCharacter)
+    }
+
+    /**
+     * Asks the providers about the call at the caret. Closes signature help if the caret moved without
+     * an edit since it was last asked, or if no provider knows the call.
+     */
+    suspend fun refresh() {
+        val request = _request.value ?: return
+        val snapshot = state.snapshot
+        val selection = state.selection.value
+        if (request.version == snapshot.version) {
+            if (request.selection != selection) dismiss()
+            return
+        }
+        if (!selection.isCollapsed || state.hasMultipleSelections) return dismiss()
+
+        // Only the first ask carries the trigger char; later ones follow the typing
+        val isFirst = request.version < 0
+        val context = SignatureHelpContext(
+            snapshot = snapshot,
+            position = selection.end,
+            language = state.language,
+            triggerCharacter = if (isFirst) request.triggerCharacter else null,
+            current = _help.value
+        )
+        var help: SignatureHelp? = null
+        for (provider in providers) {
+            help = provideSafely(provider, context)?.takeIf { it.signatures.isNotEmpty() }
+            if (help != null) break
+        }
+        if (_request.value !== request) return
+        if (help == null) return dismiss()
+        request.version = snapshot.version
+        request.selection = selection
+        _help.value = help
+    }
+
+    // A failing provider shouldn't take the others' help with it
+    private suspend fun provideSafely(provider: SignatureHelpProvider, context: SignatureHelpContext): SignatureHelp? {
+        return try {
+            provider.provideSignatureHelp(context)
+        } catch (e: CancellationException) {
+            throw e
+        } catch (_: Exception) {
+            null
+        }
+    }
+}
@@ -0,0 +1,101 @@
+package com.itsvks.code.signature
+
+import androidx.compose.foundation.background
+import androidx.compose.foundation.border
+import androidx.compose.foundation.layout.Column
+import androidx.compose.foundation.layout.heightIn
+import androidx.compose.foundation.layout.padding
+import androidx.compose.foundation.layout.widthIn
+import androidx.compose.foundation.rememberScrollState
+import androidx.compose.foundation.shape.RoundedCornerShape
+import androidx.compose.foundation.verticalScroll
+import androidx.compose.material3.Text
+import androidx.compose.runtime.Composable
+import androidx.compose.runtime.remember
+import androidx.compose.ui.Modifier
+import androidx.compose.ui.text.SpanStyle
+import androidx.compose.ui.text.TextStyle
+import androidx.compose.ui.text.buildAnnotatedString
+import androidx.compose.ui.text.font.FontFamily
+import androidx.compose.ui.text.font.FontWeight
+import androidx.compose.ui.text.withStyle
+import androidx.compose.ui.unit.IntOffset
+import androidx.compose.ui.unit.IntRect
+import androidx.compose.ui.unit.IntSize
+import androidx.compose.ui.unit.LayoutDirection
+import androidx.compose.ui.unit.dp
+import androidx.compose.ui.unit.sp
+import androidx.compose.ui.window.Popup
+import androidx.compose.ui.window.PopupPositionProvider
+import com.itsvks.code.hover.MarkdownContent
+import com.itsvks.code.theme.EditorTheme
+
+/**
+ * The active signature of [help] with the parameter being typed highlighted, followed by its
+ * documentation, drawn with the colors of [theme]. [anchor] is the caret's rectangle relative to the
+ * popup's parent: the popup goes above it, so it doesn't cover completion, or below if there's no room.
+ */
+@Composable
+fun SignatureHelpPopup(
+    help: SignatureHelp,
+    theme: EditorTheme,
+    anchor: IntRect,
+    textStyle: TextStyle = TextStyle(fontSize = 14.sp, fontFamily = FontFamily.Monospace)
+) {
+    val signature = help.active ?: return
+    val activeParameter = help.activeParameterIndex
+    val label = remember(signature, activeParameter, theme) {
+        val range = signature.parameterRanges().getOrNull(activeParameter)
+        buildAnnotatedString {
+            append(signature.label)
+            if (range != null) addStyle(SpanStyle(color = theme.activeParameterColor, fontWeight = FontWeight.Bold), range.first, range.last + 1)
+        }
+    }
+    val positionProvider = remember(anchor) { AboveOrBelowPositionProvider(anchor) }
+
+    Popup(popupPositionProvider = positionProvider) {
+        val shape = RoundedCornerShape(4.dp)
+        Column(
+            modifier = Modifier
+                .widthIn(max = 480.dp)
+                .heightIn(max = 240.dp)
+                .background(theme.hoverBackgroundColor, shape)
+                .border(1.dp, theme.hoverBorderColor, shape)
+                .verticalScroll(rememberScrollState())
+                .padding(horizontal = 8.dp, vertical = 6.dp)
+        ) {
+            Text(
+                text = buildAnnotatedString {
+                    // Which overload this is, like VS Code's `1/3`
+                    if (help.signatures.size > 1) {
+                        withStyle(SpanStyle(color = theme.completionDetailColor)) {
+                            append("${help.signatures.indexOf(signature) + 1}/${help.signatures.size}  ")
+                        }
+                    }
+                    append(label)
+                },
+                style = textStyle,
+                color = theme.hoverTextColor
+            )
+            signature.parameters.getOrNull(activeParameter)?.documentation?.let {
+                MarkdownContent(it, theme, textStyle, Modifier.padding(top = 4.dp))
+            }
+            signature.documentation?.let {
+                MarkdownContent(it, theme, textStyle, Modifier.padding(top = 4.dp))
+            }
+        }
+    }
+}
+
+private class AboveOrBelowPositionProvider(private val anchor: IntRect) : PopupPositionProvider {
+    override fun calculatePosition(
+        anchorBounds: IntRect,
+        windowSize: IntSize,
+        layoutDirection: LayoutDirection,
+        popupContentSize: IntSize
+    ): IntOffset {
+        val x = (anchorBounds.left + anchor.left).coerceAtMost(windowSize.width - popupContentSize.width).coerceAtLeast(0)
+        val above = anchorBounds.top + anchor.top - popupContentSize.height
+        return IntOffset(x, if (above >= 0) above else anchorBounds.top + anchor.bottom)
+    }
+}
@@ -0,0 +1,80 @@
+package com.itsvks.code.signature
+
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.language.Language
+
+/**
+ * A parameter of a [SignatureInformation]. [label] is its text within the signature's label.
+ */
+data class ParameterInformation(val label: String, val documentation: String? = null)
+
+/**
+ * One way of calling a function, e.g. an overload: [label] is the whole signature as shown, like
+ * `fun max(a: Int, b: Int): Int`. [documentation] is markdown. [activeParameter] overrides the one of
+ * the [SignatureHelp] for this signature.
+ */
+data class SignatureInformation(
+    val label: String,
+    val documentation: String? = null,
+    val parameters: List<ParameterInformation> = emptyList(),
+    val activeParameter: Int? = null
+) {
+    /**
+     * Where each parameter's label is in [label], or null for one that isn't in it. Labels are looked
+     * for left to right, so parameters with the same text are told apart.
+     */
+    fun parameterRanges(): List<IntRange?> {
+        var from = 0
+        return parameters.map { parameter ->
+            val start = if (parameter.label.isEmpty()) -1 else label.indexOf(parameter.label, from)
+            if (start < 0) {
+                null
+            } else {
+                from = start + parameter.label.length
+                start until from
+            }
+        }
+    }
+}
+
+/**
+ * The signatures of the call around the caret, with the one and the parameter being filled in.
+ */
+data class SignatureHelp(
+    val signatures: List<SignatureInformation>,
+    val activeSignature: Int = 0,
+    val activeParameter: Int = 0
+) {
+    val active: SignatureInformation? get() = signatures.getOrNull(activeSignature) ?: signatures.firstOrNull()
+
+    /**
+     * Index of the parameter being filled in within [active].
+     */
+    val activeParameterIndex: Int get() = active?.activeParameter ?: activeParameter
+}
+
+/**
+ * Where signature help was asked for. [triggerCharacter] is the char that opened it, or null if it
+ * was asked for again because the text changed or the user asked. [current] is what's shown, if it's open.
+ */
+class SignatureHelpContext(
+    val snapshot: TextSnapshot,
+    val position: CursorPosition,
+    val language: Language,
+    val triggerCharacter: Char? = null,
+    val current: SignatureHelp? = null
+)
+
+/**
+ * Describes the call the caret is in, e.g. from a language server. The editor asks again as the
+ * arguments are typed, and closes the popup once a provider answers null.
+ */
+interface SignatureHelpProvider {
+    /**
+     * Chars that open signature help when typed.
+     */
+    val triggerCharacters: Set<Char> get() = setOf('(', ',')
+
+    suspend fun provideSignatureHelp(context: SignatureHelpContext): SignatureHelp?
+}
@@ -0,0 +1,210 @@
+package com.itsvks.code.snippet
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.completion.CompletionItem
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.DocumentListener
+import com.itsvks.code.core.TextChange
+import com.itsvks.code.core.TextEdit
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.marker.Marker
+import com.itsvks.code.marker.MarkerGravity
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+import java.io.File
+import java.time.LocalDateTime
+import java.time.format.TextStyle
+import java.util.Locale
+import java.util.UUID
+import kotlin.random.Random
+
+/**
+ * Snippet insertion and tab stop navigation for a [CodeEditorState].
+ *
+ * Inserting a snippet selects its first tab stop; [nextTabStop] and [previousTabStop], bound to Tab
+ * and Shift+Tab, move between the rest. A tab stop that occurs more than once is selected with one
+ * caret per occurrence, so typing edits all of them at once. The snippet ends at its final stop, on
+ * [exit], or once an edit is made outside the current tab stop.
+ */
+class EditorSnippets internal constructor(private val state: CodeEditorState) {
+    // Markers over every occurrence of a tab stop. Those of the current one take in text typed at their edges.
+    private class Stop(val index: Int, var markers: List<Marker>, val choices: List<String>)
+
+    private var stops: List<Stop> = emptyList()
+    private var current = -1
+
+    private val _isActive = MutableStateFlow(false)
+
+    /**
+     * Whether a snippet is being filled in, so Tab moves to its next tab stop.
+     */
+    val isActive: StateFlow<Boolean> = _isActive.asStateFlow()
+
+    /**
+     * Values for variables the editor can't resolve itself, e.g. `CLIPBOARD`. They take precedence
+     * over the built-in ones.
+     */
+    var variables: Map<String, String> = emptyMap()
+
+    private val tracker = object : DocumentListener {
+        override fun onChange(change: TextChange, snapshot: TextSnapshot) = track(change)
+
+        override fun onReset(snapshot: TextSnapshot) = exit()
+    }
+
+    init {
+        state.addDocumentListener(tracker)
+    }
+
+    /**
+     * Parses [body] and inserts it in place of [range].
+     */
+    fun insert(body: String, range: CursorRange = state.selection.value, additionalEdits: List<TextEdit> = emptyList()) {
+        insert(Snippet.parse(body), range, additionalEdits)
+    }
+
+    /**
+     * Replaces [range] with [snippet] as one undo step, along with [additionalEdits], and selects its
+     * first tab stop. A snippet inserted while another is active replaces it.
+     */
+    fun insert(snippet: Snippet, range: CursorRange = state.selection.value, additionalEdits: List<TextEdit> = emptyList()) {
+        exit()
+        val before = state.snapshot
+        val start = range.startInclusive
+        val line = before.getLine(start.line)
+        val indent = line.take(start.column).takeWhile { it == ' ' || it == '\t' }
+        val expanded = snippet.expand(resolver(before, range), indent)
+
+        val inserted = state.applyEdits(listOf(TextEdit(range, expanded.text)) + additionalEdits)
+        val after = state.snapshot
+        val base = after.offsetOf(inserted[0].startInclusive)
+        stops = expanded.tabStops.map { stop ->
+            val markers = stop.ranges.map {
+                state.internalMarkers.track(CursorRange(after.positionOf(base + it.start), after.positionOf(base + it.end)))
+            }
+            Stop(stop.index, markers, stop.choices)
+        }
+        _isActive.value = true
+        select(0)
+    }
+
+    /**
+     * Selects the next tab stop. Returns false, ending the snippet, if there is no snippet or the caret
+     * has left it, so the key can do what it usually does.
+     */
+    fun nextTabStop(): Boolean {
+        if (!_isActive.value) return false
+        if (!isCaretInside()) {
+            exit()
+            return false
+        }
+        select(current + 1)
+        return true
+    }
+
+    /**
+     * Selects the previous tab stop. Returns false if there is none.
+     */
+    fun previousTabStop(): Boolean {
+        if (!_isActive.value || current <= 0) return false
+        if (!isCaretInside()) {
+            exit()
+            return false
+        }
+        select(current - 1)
+        return true
+    }
+
+    /**
+     * Stops tracking the snippet, leaving its text and the carets as they are.
+     */
+    fun exit() {
+        stops.forEach { stop -> stop.markers.forEach(Marker::remove) }
+        stops = emptyList()
+        current = -1
+        _isActive.value = false
+    }
+
+    private fun select(index: Int) {
+        stops.getOrNull(current)?.let { retrack(it, isCurrent = false) }
+        current = index
+        val stop = stops[index]
+        retrack(stop, isCurrent = true)
+        val ranges = stop.markers.map { it.range }
+        // The final stop ends the snippet
+        if (stop.index == 0) exit()
+        state.setSelections(ranges, primaryIndex = 0)
+
+        if (stop.choices.isNotEmpty() && _isActive.value) {
+            state.completion.showItems(stop.choices.map { choice ->
+                CompletionItem(
+                    label = choice,
+                    textEdit = TextEdit(ranges[0], choice),
+                    additionalEdits = ranges.drop(1).map { TextEdit(it, choice) }
+                )
+            })
+        }
+    }
+
+    // Text typed right before or after the current tab stop belongs to it, and not to the others
+    private fun retrack(stop: Stop, isCurrent: Boolean) {
+        stop.markers = stop.markers.map { marker ->
+            val range = marker.range
+            marker.remove()
+            if (isCurrent) {
+                state.internalMarkers.track(range, MarkerGravity.LEFT, MarkerGravity.RIGHT)
+            } else {
+                state.internalMarkers.track(range)
+            }
+        }
+    }
+
+    private fun isCaretInside(): Boolean {
+        val caret = state.snapshot.offsetOf(state.cursor)
+        return stops.any { stop -> stop.markers.any { caret in it.startOffset..it.endOffset } }
+    }
+
+    // The markers have moved with the change already; an edit outside the current tab stop ends the snippet
+    private fun track(change: TextChange) {
+        if (!_isActive.value) return
+        val markers = stops.getOrNull(current)?.markers.orEmpty()
+        if (markers.none { change.start >= it.startOffset && change.newEnd <= it.endOffset }) exit()
+    }
+
+    // Built-in variables, as in VS Code and TextMate
+    private fun resolver(snapshot: TextSnapshot, range: CursorRange): (String) -> String? {
+        val position = range.startInclusive
+        val file = state.filePath?.let(::File)
+        val now = LocalDateTime.now()
+        return { name ->
+            variables[name] ?: when (name) {
+                "TM_SELECTED_TEXT" -> state.selectedText
+                "TM_CURRENT_LINE" -> snapshot.getLine(position.line)
+                "TM_CURRENT_WORD" -> snapshot.wordRangeAt(position)?.let(snapshot::getText).orEmpty()
+                "TM_LINE_INDEX" -> position.line.toString()
+                "TM_LINE_NUMBER" -> (position.line + 1).toString()
+                "TM_FILENAME" -> file?.name
+                "TM_FILENAME_BASE" -> file?.nameWithoutExtension
+                "TM_DIRECTORY" -> file?.parent
+                "TM_FILEPATH" -> file?.path
+                "CURRENT_YEAR" -> now.year.toString()
+                "CURRENT_YEAR_SHORT" -> (now.year % 100).toString().padStart(2, '0')
+                "CURRENT_MONTH" -> now.monthValue.toString().padStart(2, '0')
+                "CURRENT_MONTH_NAME" -> now.month.getDisplayName(TextStyle.FULL, Locale.getDefault())
+                "CURRENT_MONTH_NAME_SHORT" -> now.month.getDisplayName(TextStyle.SHORT, Locale.getDefault())
+                "CURRENT_DATE" -> now.dayOfMonth.toString().padStart(2, '0')
+                "CURRENT_DAY_NAME" -> now.dayOfWeek.getDisplayName(TextStyle.FULL, Locale.getDefault())
+                "CURRENT_DAY_NAME_SHORT" -> now.dayOfWeek.getDisplayName(TextStyle.SHORT, Locale.getDefault())
+                "CURRENT_HOUR" -> now.hour.toString().padStart(2, '0')
+                "CURRENT_MINUTE" -> now.minute.toString().padStart(2, '0')
+                "CURRENT_SECOND" -> now.second.toString().padStart(2, '0')
+                "CURRENT_SECONDS_UNIX" -> (System.currentTimeMillis() / 1000).toString()
+                "RANDOM" -> Random.nextInt(1_000_000).toString().padStart(6, '0')
+                "RANDOM_HEX" -> Random.nextInt(0x1000000).toString(16).padStart(6, '0')
+                "UUID" -> UUID.randomUUID().toString()
+                else -> null
+            }
+        }
+    }
+}
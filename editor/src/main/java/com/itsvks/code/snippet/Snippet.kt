@@ -0,0 +1,396 @@
+package com.itsvks.code.snippet
+
+/**
+ * Where a placeholder ended up in [ExpandedSnippet.text]: the chars in `[start, end)`.
+ */
+data class SnippetRange(val start: Int, val end: Int)
+
+/**
+ * A tab stop of an expanded snippet: all places its placeholder occurs, the first one being the
+ * one that defined it, and the values offered for it if it is a choice. Index 0 is where the caret
+ * ends up.
+ */
+data class SnippetTabStop(val index: Int, val ranges: List<SnippetRange>, val choices: List<String> = emptyList())
+
+/**
+ * A snippet with its variables resolved and its tab stops located. [tabStops] come in the order Tab
+ * visits them, so the final stop, `$0`, is always last.
+ */
+data class ExpandedSnippet(val text: String, val tabStops: List<SnippetTabStop>)
+
+/**
+ * A snippet in the syntax VS Code and TextMate use: `$1` and `${1:default}` are tab stops and
+ * placeholders, `${1|one,two|}` a choice, `$0` the final caret position, and `$NAME` or
+ * `${NAME:default}` a variable such as `TM_FILENAME`. A tab stop used more than once is mirrored: all
+ * its occurrences get the same text. Variables can be transformed with `${NAME/regex/format/flags}`.
+ *
+ * Parse a snippet once with [parse], then [expand] it wherever it's inserted.
+ */
+class Snippet private constructor(private val nodes: List<Node>) {
+    private sealed class Node {
+        class Text(val text: String) : Node()
+        class TabStop(val index: Int, val children: List<Node>?, val choices: List<String> = emptyList()) : Node()
+        class Variable(val name: String, val children: List<Node>?, val transform: Transform?) : Node()
+    }
+
+    private class Transform(val regex: Regex, val format: String, val global: Boolean)
+
+    /**
+     * The text to insert and where its tab stops are. Unknown variables expand to their default, or
+     * else to their name. Lines after the first are indented with [indent], and tabs replaced by [tab],
+     * so the snippet lines up with the line it's inserted on.
+     */
+    fun expand(variables: (name: String) -> String? = { null }, indent: String = "", tab: String = "    "): ExpandedSnippet {
+        // A tab stop's text is set by its first occurrence with a default or choices
+        val definitions = HashMap<Int, Node.TabStop>()
+        fun collect(nodes: List<Node>) {
+            for (node in nodes) {
+                when (node) {
+                    is Node.TabStop -> {
+                        if (node.children != null || node.choices.isNotEmpty()) definitions.putIfAbsent(node.index, node)
+                        node.children?.let(::collect)
+                    }
+
+                    is Node.Variable -> node.children?.let(::collect)
+                    is Node.Text -> {}
+                }
+            }
+        }
+        collect(nodes)
+
+        val out = StringBuilder()
+        val ranges = LinkedHashMap<Int, MutableList<SnippetRange>>()
+        // Tab stops being rendered, so a placeholder that contains itself doesn't recurse forever
+        val rendering = HashSet<Int>()
+
+        fun emit(text: String) {
+            for (char in text) {
+                when (char) {
+                    '\n' -> out.append('\n').append(indent)
+                    '\t' -> out.append(tab)
+                    else -> out.append(char)
+                }
+            }
+        }
+
+        // Copies of a placeholder are plain text; only the definition's own tab stops are tab stops
+        fun render(nodes: List<Node>, placeholders: Boolean) {
+            for (node in nodes) {
+                when (node) {
+                    is Node.Text -> emit(node.text)
+                    is Node.TabStop -> {
+                        val start = out.length
+                        val definition = definitions[node.index]
+                        val isDefinition = placeholders && definition === node
+                        if (definition != null && rendering.add(node.index)) {
+                            if (definition.choices.isNotEmpty()) {
+                                emit(definition.choices.first())
+                            } else {
+                                render(definition.children.orEmpty(), isDefinition)
+                            }
+                            rendering.remove(node.index)
+                        }
+                        if (placeholders) {
+                            val list = ranges.getOrPut(node.index) { ArrayList() }
+                            // Edits go to the defining placeholder first; mirrors follow in document order
+                            list.add(if (isDefinition) 0 else list.size, SnippetRange(start, out.length))
+                        }
+                    }
+
+                    is Node.Variable -> {
+                        val value = variables(node.name)
+                        when {
+                            value != null -> emit(node.transform?.let { applyTransform(value, it) } ?: value)
+                            node.children != null -> render(node.children, placeholders)
+                            else -> emit(node.name)
+                        }
+                    }
+                }
+            }
+        }
+        render(nodes, placeholders = true)
+
+        val stops = ranges.entries
+            .filter { it.key != 0 }
+            .sortedBy { it.key }
+            .map { (index, list) -> SnippetTabStop(index, list, definitions[index]?.choices.orEmpty()) }
+        val final = ranges[0]?.let { SnippetTabStop(0, it) } ?: SnippetTabStop(0, listOf(SnippetRange(out.length, out.length)))
+        return ExpandedSnippet(out.toString(), stops + final)
+    }
+
+    private fun applyTransform(value: String, transform: Transform): String {
+        val replace = { match: MatchResult -> formatMatch(transform.format, match) }
+        return if (transform.global) {
+            transform.regex.replace(value, replace)
+        } else {
+            transform.regex.find(value)?.let { value.replaceRange(it.range, replace(it)) } ?: value
+        }
+    }
+
+    // Formats support `$1`, `${1}`, `${1:/upcase}`, `/downcase`, `/capitalize`, `${1:+if}`, `${1:-else}` and `${1:else}`
+    private fun formatMatch(format: String, match: MatchResult): String {
+        val out = StringBuilder()
+        var i = 0
+        while (i < format.length) {
+            val char = format[i]
+            if (char == '\\' && i + 1 < format.length) {
+                out.append(format[i + 1])
+                i += 2
+                continue
+            }
+            if (char != '$') {
+                out.append(char)
+                i++
+                continue
+            }
+
+            val simple = SIMPLE_GROUP.matchAt(format, i)
+            if (simple != null) {
+                out.append(match.groupValues.getOrNull(simple.groupValues[1].toInt()).orEmpty())
+                i = simple.range.last + 1
+                continue
+            }
+            val complex = COMPLEX_GROUP.matchAt(format, i)
+            if (complex == null) {
+                out.append(char)
+                i++
+                continue
+            }
+            val group = match.groupValues.getOrNull(complex.groupValues[1].toInt()).orEmpty()
+            val option = complex.groupValues[2]
+            out.append(
+                when {
+                    option.isEmpty() -> group
+                    option == ":/upcase" -> group.uppercase()
+                    option == ":/downcase" -> group.lowercase()
+                    option == ":/capitalize" -> group.replaceFirstChar { it.uppercase() }
+                    option.startsWith(":+") -> if (group.isNotEmpty()) option.substring(2) else ""
+                    option.startsWith(":-") -> group.ifEmpty { option.substring(2) }
+                    else -> group.ifEmpty { option.substring(1) }
+                }
+            )
+            i = complex.range.last + 1
+        }
+        return out.toString()
+    }
+
+    companion object {
+        private val SIMPLE_GROUP = Regex("""\$(\d+)""")
+        private val COMPLEX_GROUP = Regex("""\$\{(\d+)(:[^}]*)?}""")
+
+        /**
+         * Parses [body]. Anything that isn't valid snippet syntax, such as a lone `$`, is kept as text.
+         */
+        @JvmStatic
+        fun parse(body: String): Snippet {
+            val normalized = if ('\r' in body) body.replace("\r\n", "\n").replace('\r', '\n') else body
+            return Snippet(Parser(normalized).parseAll())
+        }
+    }
+
+    private class Parser(private val text: String) {
+        private var position = 0
+
+        fun parseAll(): List<Node> = parseNodes(insidePlaceholder = false)
+
+        // Up to the '}' closing the enclosing placeholder, which is left for the caller
+        private fun parseNodes(insidePlaceholder: Boolean): List<Node> {
+            val nodes = ArrayList<Node>()
+            val pending = StringBuilder()
+            fun flush() {
+                if (pending.isNotEmpty()) nodes.add(Node.Text(pending.toString()))
+                pending.clear()
+            }
+
+            while (position < text.length) {
+                val char = text[position]
+                when {
+                    char == '\\' && position + 1 < text.length && text[position + 1] in ESCAPABLE -> {
+                        pending.append(text[position + 1])
+                        position += 2
+                    }
+
+                    char == '}' && insidePlaceholder -> break
+
+                    char == '$' -> {
+                        val start = position
+                        val node = parseDollar()
+                        if (node == null) {
+                            position = start + 1
+                            pending.append('$')
+                        } else {
+                            flush()
+                            nodes.add(node)
+                        }
+                    }
+
+                    else -> {
+                        pending.append(char)
+                        position++
+                    }
+                }
+            }
+            flush()
+            return nodes
+        }
+
+        // At a '$': a tab stop, placeholder, choice or variable, or null if it's just a '$'
+        private fun parseDollar(): Node? {
+            position++
+            if (position >= text.length) return null
+            val next = text[position]
+            return when {
+                next.isDigit() -> Node.TabStop(readInt(), children = null)
+                next.isVariableStart() -> Node.Variable(readName(), children = null, transform = null)
+                next == '{' -> {
+                    position++
+                    parseBraced()
+                }
+
+                else -> null
+            }
+        }
+
+        private fun parseBraced(): Node? {
+            if (position >= text.length) return null
+            val next = text[position]
+            if (next.isDigit()) {
+                val index = readInt()
+                return when (text.getOrNull(position)) {
+                    '}' -> Node.TabStop(index, children = null).also { position++ }
+                    ':' -> {
+                        position++
+                        val children = parseNodes(insidePlaceholder = true)
+                        if (!consume('}')) null else Node.TabStop(index, children)
+                    }
+
+                    '|' -> {
+                        position++
+                        val choices = parseChoices() ?: return null
+                        Node.TabStop(index, children = null, choices = choices)
+                    }
+
+                    // Transforms of tab stops aren't applied: the stop just mirrors its placeholder
+                    '/' -> if (parseTransform() == null) null else Node.TabStop(index, children = null)
+                    else -> null
+                }
+            }
+            if (!next.isVariableStart()) return null
+
+            val name = readName()
+            return when (text.getOrNull(position)) {
+                '}' -> Node.Variable(name, children = null, transform = null).also { position++ }
+                ':' -> {
+                    position++
+                    val children = parseNodes(insidePlaceholder = true)
+                    if (!consume('}')) null else Node.Variable(name, children, transform = null)
+                }
+
+                '/' -> parseTransform()?.let { Node.Variable(name, children = null, transform = it) }
+                else -> null
+            }
+        }
+
+        // After the '|': choices separated by ',' up to "|}"
+        private fun parseChoices(): List<String>? {
+            val choices = ArrayList<String>()
+            val current = StringBuilder()
+            while (position < text.length) {
+                val char = text[position]
+                when {
+                    char == '\\' && position + 1 < text.length && text[position + 1] in CHOICE_ESCAPABLE -> {
+                        current.append(text[position + 1])
+                        position += 2
+                    }
+
+                    char == ',' -> {
+                        choices.add(current.toString())
+                        current.clear()
+                        position++
+                    }
+
+                    char == '|' && text.getOrNull(position + 1) == '}' -> {
+                        choices.add(current.toString())
+                        position += 2
+                        return choices
+                    }
+
+                    else -> {
+                        current.append(char)
+                        position++
+                    }
+                }
+            }
+            return null
+        }
+
+        // At the '/' of "/regex/format/flags}", consuming the closing '}'
+        private fun parseTransform(): Transform? {
+            position++
+            val regex = readUntilSlash() ?: return null
+            val format = readUntilSlash() ?: return null
+            val flagsEnd = text.indexOf('}', position)
+            if (flagsEnd < 0) return null
+            val flags = text.substring(position, flagsEnd)
+            position = flagsEnd + 1
+
+            val options = buildSet {
+                if ('i' in flags) add(RegexOption.IGNORE_CASE)
+                if ('m' in flags) add(RegexOption.MULTILINE)
+            }
+            return try {
+                Transform(Regex(regex, options), format, global = 'g' in flags)
+            } catch (_: IllegalArgumentException) {
+                null
+            }
+        }
+
+        // Up to the next unescaped '/' outside a `${...}` of the format, consuming it. Only "\/" is
+        // unescaped here; other escapes are left to the regex or the format.
+        private fun readUntilSlash(): String? {
+            val out = StringBuilder()
+            var depth = 0
+            while (position < text.length) {
+                val char = text[position]
+                if (char == '\\' && position + 1 < text.length) {
+                    if (text[position + 1] == '/') out.append('/') else out.append(char).append(text[position + 1])
+                    position += 2
+                    continue
+                }
+                position++
+                when {
+                    char == '$' && text.getOrNull(position) == '{' -> depth++
+                    char == '}' && depth > 0 -> depth--
+                    char == '/' && depth == 0 -> return out.toString()
+                }
+                out.append(char)
+            }
+            return null
+        }
+
+        private fun readInt(): Int {
+            val start = position
+            while (position < text.length && text[position].isDigit()) position++
+            return text.substring(start, position).toIntOrNull() ?: Int.MAX_VALUE
+        }
+
+        private fun readName(): String {
+            val start = position
+            while (position < text.length && (text[position].isVariableStart() || text[position].isDigit())) position++
+            return text.substring(start, position)
+        }
+
+        private fun consume(char: Char): Boolean {
+            if (text.getOrNull(position) != char) return false
+            position++
+            return true
+        }
+
+        private fun Char.isVariableStart() = this == '_' || this in 'a'..'z' || this in 'A'..'Z'
+
+        private companion object {
+            const val ESCAPABLE = "\$}\\"
+            const val CHOICE_ESCAPABLE = ",|\\"
+        }
+    }
+}
@@ -0,0 +1,57 @@
+package com.itsvks.code.snippet
+
+import android.content.res.AssetManager
+import com.itsvks.code.util.Json
+import java.io.InputStream
+
+/**
+ * A named snippet, offered in completion when one of its [prefixes] is typed.
+ */
+data class SnippetDefinition(
+    val name: String,
+    val prefixes: List<String>,
+    val body: String,
+    val description: String? = null
+)
+
+/**
+ * Snippets for one language, usually read from a VS Code snippet file: a JSON object mapping each
+ * snippet's name to its `prefix`, `body` and `description`, where prefix and body can also be arrays.
+ */
+class SnippetCollection(val definitions: List<SnippetDefinition>) {
+    companion object {
+        @JvmStatic
+        fun fromJson(json: String): SnippetCollection {
+            val root = Json.parse(json) as? Map<*, *> ?: throw IllegalArgumentException("Snippets must be a JSON object")
+            val definitions = root.mapNotNull { (name, value) ->
+                val entry = value as? Map<*, *> ?: return@mapNotNull null
+                val prefixes = when (val prefix = entry["prefix"]) {
+                    is String -> listOf(prefix)
+                    is List<*> -> prefix.filterIsInstance<String>()
+                    else -> emptyList()
+                }
+                val body = when (val body = entry["body"]) {
+                    is String -> body
+                    is List<*> -> body.joinToString("\n")
+                    else -> return@mapNotNull null
+                }
+                if (prefixes.isEmpty()) return@mapNotNull null
+                SnippetDefinition(name.toString(), prefixes, body, entry["description"] as? String)
+            }
+            return SnippetCollection(definitions)
+        }
+
+        @JvmStatic
+        fun fromStream(stream: InputStream): SnippetCollection {
+            return fromJson(stream.bufferedReader().use { it.readText() })
+        }
+
+        /**
+         * Reads the snippets at [path] in the app's assets, e.g. `snippets/kotlin.json`.
+         */
+        @JvmStatic
+        fun fromAssets(assets: AssetManager, path: String): SnippetCollection {
+            return assets.open(path).use(::fromStream)
+        }
+    }
+}
@@ -0,0 +1,30 @@
+package com.itsvks.code.snippet
+
+import com.itsvks.code.language.Language
+import java.util.concurrent.ConcurrentHashMap
+
+// Snippets of each language, by language name
+object SnippetRegistry {
+    private val collections = ConcurrentHashMap<String, List<SnippetCollection>>()
+
+    /**
+     * Adds [collection] to the snippets offered for the language called [languageName].
+     */
+    @JvmStatic
+    fun register(languageName: String, collection: SnippetCollection) {
+        collections.merge(languageName.lowercase(), listOf(collection)) { old, new -> old + new }
+    }
+
+    @JvmStatic
+    fun register(language: Language, collection: SnippetCollection) = register(language.name, collection)
+
+    @JvmStatic
+    fun unregisterAll(language: Language) {
+        collections.remove(language.name.lowercase())
+    }
+
+    @JvmStatic
+    fun getSnippets(language: Language): List<SnippetDefinition> {
+        return collections[language.name.lowercase()].orEmpty().flatMap { it.definitions }
+    }
+}
@@ -0,0 +1,215 @@
+package com.itsvks.code.syntax
+
+import com.itsvks.code.core.LruCache
+import com.itsvks.code.core.TextSnapshot
+import kotlinx.coroutines.CoroutineScope
+import kotlinx.coroutines.Dispatchers
+import kotlinx.coroutines.ensureActive
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+import kotlinx.coroutines.flow.update
+import kotlinx.coroutines.withContext
+
+private const val TOKEN_CACHE_SIZE = 2048
+
+// Lines lexed between checks for edits and cancellation
+private const val BATCH_SIZE = 256
+
+/**
+ * Highlights a whole document line by line in the background, carrying the lexer state from each
+ * line into the next.
+ *
+ * The end state of every line is kept, tokens only for recently shown lines. After an edit only the
+ * edited lines are lexed again, then the lines after them until one ends in the same state as it did
+ * before; everything past that point is still valid.
+ */
+class DocumentHighlighter(highlighter: SyntaxHighlighter, snapshot: TextSnapshot) {
+    private val lock = Any()
+
+    @Volatile
+    var highlighter: SyntaxHighlighter = highlighter
+        private set
+
+    // End state per line; null for lines that were edited, or lexed from a state that changed since
+    private val states = ArrayList<LexerState?>()
+    private val tokens = LruCache<Int, List<Token>>(TOKEN_CACHE_SIZE)
+
+    // Lines before this one were lexed in the state the previous line actually ends in
+    private var validLines = 0
+
+    // Version of the snapshot the lines belong to; a pass over an older snapshot is dropped
+    private var version = -1L
+
+    // Bumped whenever the lines change under a running pass, so it doesn't commit stale results
+    private var generation = 0
+    private var hasNewTokens = false
+
+    private val _updates = MutableStateFlow(0L)
+
+    /**
+     * Changes whenever tokens for lines that are being shown become available.
+     */
+    val updates: StateFlow<Long> = _updates.asStateFlow()
+
+    init {
+        reset(highlighter, snapshot)
+    }
+
+    fun reset(highlighter: SyntaxHighlighter, snapshot: TextSnapshot) {
+        synchronized(lock) {
+            this.highlighter = highlighter
+            states.clear()
+            repeat(snapshot.lineCount) { states.add(null) }
+            tokens.clear()
+            validLines = 0
+            version = snapshot.version
+            generation++
+        }
+    }
+
+    /**
+     * Lines `[startLine, startLine + removedLines)` were replaced by [insertedLines] new lines, giving [snapshot].
+     */
+    fun onLinesReplaced(snapshot: TextSnapshot, startLine: Int, removedLines: Int, insertedLines: Int) {
+        synchronized(lock) {
+            val end = (startLine + removedLines).coerceAtMost(states.size)
+            states.subList(startLine, end).clear()
+            states.addAll(startLine, List(insertedLines) { null })
+            validLines = minOf(validLines, startLine)
+
+            // Cached tokens move with their lines; until lexed again, lines after the edit keep showing their old tokens
+            val delta = insertedLines - removedLines
+            val kept = tokens.entries.mapNotNull { (line, lineTokens) ->
+                when {
+                    line < startLine -> line to lineTokens
+                    line >= end -> line + delta to lineTokens
+                    else -> null
+                }
+            }
+            tokens.clear()
+            for ((line, lineTokens) in kept) tokens[line] = lineTokens
+
+            version = snapshot.version
+            generation++
+        }
+    }
+
+    /**
+     * Tokens of [line], or null if it hasn't been lexed yet. Never lexes on the calling thread.
+     */
+    fun tokensFor(line: Int): List<Token>? = synchronized(lock) { tokens[line] }
+
+    /**
+     * The state [line] ends in, e.g. to tell whether it ends inside a block comment, or null if not known yet.
+     */
+    fun stateAfter(line: Int): LexerState? = synchronized(lock) { if (line < validLines) states[line] else null }
+
+    /**
+     * The states the lines of [snapshot] end in, as far as they are known, or an empty list if the
+     * highlighter is at another version of the document.
+     */
+    fun statesFor(snapshot: TextSnapshot): List<LexerState?> = synchronized(lock) {
+        if (snapshot.version == version) states.subList(0, validLines).toList() else emptyList()
+    }
+
+    /**
+     * Lexes [snapshot] on a background thread: [priorityLines] first, publishing their tokens through
+     * [updates], then the rest of the document. Stops early when the document changes; the next call
+     * continues from where this one got to.
+     */
+    suspend fun highlight(snapshot: TextSnapshot, priorityLines: IntRange) {
+        withContext(Dispatchers.Default) {
+            val lastLine = snapshot.lineCount - 1
+            val visible = priorityLines.first.coerceAtLeast(0)..priorityLines.last.coerceAtMost(lastLine)
+
+            if (!lexStates(snapshot, visible.last, visible) || !fillTokens(snapshot, visible)) return@withContext
+            val published = synchronized(lock) { hasNewTokens.also { hasNewTokens = false } }
+            if (published) _updates.update { it + 1 }
+
+            lexStates(snapshot, lastLine, visible)
+        }
+    }
+
+    private class Batch(
+        val generation: Int,
+        val from: Int,
+        val state: LexerState,
+        val highlighter: SyntaxHighlighter,
+        val oldStates: List<LexerState?>
+    )
+
+    // Returns false if the snapshot is out of date
+    private fun CoroutineScope.lexStates(snapshot: TextSnapshot, upTo: Int, keepTokens: IntRange): Boolean {
+        while (true) {
+            ensureActive()
+            val batch = synchronized(lock) {
+                if (snapshot.version != version) return false
+                if (validLines > upTo) return true
+                val end = minOf(validLines + BATCH_SIZE, upTo + 1)
+                Batch(generation, validLines, stateBefore(validLines), highlighter, states.subList(validLines, end).toList())
+            }
+
+            val results = ArrayList<LineHighlight>(batch.oldStates.size)
+            var state = batch.state
+            var converged = false
+            for ((i, old) in batch.oldStates.withIndex()) {
+                val result = batch.highlighter.highlightLine(snapshot.getLine(batch.from + i), state)
+                results.add(result)
+                state = result.endState
+                if (old != null && old == state) {
+                    converged = true
+                    break
+                }
+            }
+
+            synchronized(lock) {
+                if (generation == batch.generation) commit(batch.from, results, converged, keepTokens)
+            }
+        }
+    }
+
+    private fun commit(from: Int, results: List<LineHighlight>, converged: Boolean, keepTokens: IntRange) {
+        for ((i, result) in results.withIndex()) {
+            val line = from + i
+            states[line] = result.endState
+            if (line in keepTokens) {
+                tokens[line] = result.tokens
+                hasNewTokens = true
+            } else {
+                tokens.remove(line)
+            }
+        }
+        validLines = from + results.size
+
+        if (converged) {
+            // The next lines start in the same state as before, so they lex the same up to the next edited line
+            while (validLines < states.size && states[validLines] != null) validLines++
+        } else if (validLines < states.size) {
+            // The next line was lexed in a state that may have changed; don't let it count as converged later
+            states[validLines] = null
+        }
+    }
+
+    // Tokens for lines whose state is known but whose tokens were evicted or never kept
+    private fun CoroutineScope.fillTokens(snapshot: TextSnapshot, lines: IntRange): Boolean {
+        val (batchGeneration, missing) = synchronized(lock) {
+            if (snapshot.version != version) return false
+            generation to lines.filter { it < validLines && tokens[it] == null }.map { it to stateBefore(it) }
+        }
+        if (missing.isEmpty()) return true
+
+        val lexed = missing.map { (line, state) ->
+            ensureActive()
+            line to highlighter.highlightLine(snapshot.getLine(line), state).tokens
+        }
+        synchronized(lock) {
+            if (generation != batchGeneration) return false
+            for ((line, lineTokens) in lexed) tokens[line] = lineTokens
+            hasNewTokens = true
+        }
+        return true
+    }
+
+    private fun stateBefore(line: Int): LexerState = if (line == 0) highlighter.initialState else states[line - 1]!!
+}
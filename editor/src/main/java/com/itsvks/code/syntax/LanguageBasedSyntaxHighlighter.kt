@@ -1,136 +1,170 @@
 package com.itsvks.code.syntax
 
 import com.itsvks.code.language.Language
-
-// Implementation of the SyntaxHighlighter that uses Language definitions
-class LanguageBasedSyntaxHighlighter(private val language: Language) : SyntaxHighlighter {
-
-    private val keywordPattern: Regex? = language.keywords
-        .takeIf { it.isNotEmpty() }
-        ?.joinToString(separator = "|", prefix = "\b(?:", postfix = ")\b")
-        ?.toRegex()
-
-    private val typePattern: Regex? = language.types
-        .takeIf { it.isNotEmpty() }
-        ?.joinToString(separator = "|", prefix = "\b(?:", postfix = ")\b")
-        ?.toRegex()
-
-    private val operatorPattern: Regex? = language.operators
-        .takeIf { it.isNotEmpty() }
-        ?.joinToString(separator = "|") { Regex.escape(it) }
-        ?.toRegex()
-
-    private val punctuationPattern: Regex? = language.punctuation
-        .takeIf { it.isNotEmpty() }
-        ?.joinToString(separator = "|") { Regex.escape(it) }
-        ?.toRegex()
-
-    override fun highlight(text: String): List<Token> {
-        val tokens = mutableListOf<Token>()
-
-        findAndAddTokens(text, language.commentPatterns, TokenType.COMMENT, tokens)
-        findAndAddTokens(text, language.stringPatterns, TokenType.STRING, tokens)
-        findAndAddPattern(text, language.numberPattern, TokenType.NUMBER, tokens)
-
-        val functionPattern = language.functionPattern
-        if (functionPattern != null) {
-            val results = functionPattern.findAll(text)
-            for (result in results) {
-                val matchText = result.groupValues[1]
-                if (!isInExistingToken(result.range.first, tokens)) {
-                    tokens.add(Token(
-                        TokenType.FUNCTION,
-                        result.range.first,
-                        result.range.first + matchText.length,
-                        matchText
-                    ))
-                }
-            }
+import java.util.regex.Matcher
+import java.util.regex.Pattern
+
+// Inside block pattern [index] of the language, waiting for [end]
+private data class BlockState(val index: Int, val end: String) : LexerState
+
+private class Rule(val type: TokenType, val pattern: String, val blockIndex: Int = -1)
+
+/**
+ * All [rules] compiled into one alternation, so a line is tokenized by a single `find` loop: at every
+ * position the earliest match wins and ties go to the rule listed first. Rule patterns must not use
+ * back references, since their groups are renumbered.
+ */
+private class Scanner(val rules: List<Rule>) {
+    // Index of the group wrapping each rule; the rule's own groups follow it
+    private val groups = IntArray(rules.size)
+    private val groupCounts = IntArray(rules.size)
+    val pattern: Pattern
+
+    init {
+        var group = 1
+        val alternation = rules.mapIndexed { i, rule ->
+            groups[i] = group
+            groupCounts[i] = Pattern.compile(rule.pattern).matcher("").groupCount()
+            group += 1 + groupCounts[i]
+            "(${rule.pattern})"
         }
+        pattern = Pattern.compile(alternation.joinToString("|").ifEmpty { "(?!)" })
+    }
 
-        language.annotationPattern?.let { pattern ->
-            findAndAddPattern(text, pattern, TokenType.ANNOTATION, tokens)
-        }
+    fun ruleOf(matcher: Matcher): Int = groups.indices.first { matcher.start(groups[it]) >= 0 }
 
-        language.macroPattern?.let { pattern ->
-            findAndAddPattern(text, pattern, TokenType.MACRO, tokens)
-        }
+    // Group [n] of [rule]'s own pattern in the combined one
+    fun group(rule: Int, n: Int) = groups[rule] + n
 
-        language.attributePattern?.let { pattern ->
-            findAndAddPattern(text, pattern, TokenType.ATTRIBUTE, tokens)
-        }
+    fun groupCount(rule: Int) = groupCounts[rule]
+}
 
-        language.constPattern?.let { pattern ->
-            findAndAddPattern(text, pattern, TokenType.CONST, tokens)
-        }
+/**
+ * Highlights code described by a [Language] in one pass over each line, producing tokens that never overlap.
+ */
+class LanguageBasedSyntaxHighlighter(private val language: Language) : SyntaxHighlighter {
+    private val blockPatterns = language.blockPatterns
+    private val scanner = Scanner(rules(withBlocks = true, withFunctions = true))
+
+    // Tokenizes what precedes a function name inside a function match, e.g. `def ` in Python
+    private val prefixScanner = Scanner(rules(withBlocks = false, withFunctions = false))
+
+    private fun rules(withBlocks: Boolean, withFunctions: Boolean) = buildList {
+        if (withBlocks) blockPatterns.forEachIndexed { i, block -> add(Rule(block.type, block.begin.pattern, blockIndex = i)) }
+        language.commentPatterns.forEach { add(Rule(TokenType.COMMENT, it.pattern)) }
+        language.stringPatterns.forEach { add(Rule(TokenType.STRING, it.pattern)) }
+        add(Rule(TokenType.NUMBER, language.numberPattern.pattern))
+        if (withFunctions) language.functionPattern?.let { add(Rule(TokenType.FUNCTION, it.pattern)) }
+        language.annotationPattern?.let { add(Rule(TokenType.ANNOTATION, it.pattern)) }
+        language.macroPattern?.let { add(Rule(TokenType.MACRO, it.pattern)) }
+        language.attributePattern?.let { add(Rule(TokenType.ATTRIBUTE, it.pattern)) }
+        language.constPattern?.let { add(Rule(TokenType.CONST, it.pattern)) }
+        language.staticPattern?.let { add(Rule(TokenType.STATIC, it.pattern)) }
+        words(language.keywords)?.let { add(Rule(TokenType.KEYWORD, it)) }
+        words(language.types)?.let { add(Rule(TokenType.TYPE, it)) }
+        symbols(language.operators)?.let { add(Rule(TokenType.OPERATOR, it)) }
+        symbols(language.punctuation)?.let { add(Rule(TokenType.PUNCTUATION, it)) }
+        add(Rule(TokenType.IDENTIFIER, "\\b[a-zA-Z_][a-zA-Z0-9_]*\\b"))
+    }
 
-        language.staticPattern?.let { pattern ->
-            findAndAddPattern(text, pattern, TokenType.STATIC, tokens)
-        }
+    private fun words(words: Set<String>): String? {
+        if (words.isEmpty()) return null
+        return words.joinToString(separator = "|", prefix = "\\b(?:", postfix = ")\\b") { Regex.escape(it) }
+    }
 
-        keywordPattern?.let { pattern ->
-            findAndAddPattern(text, pattern, TokenType.KEYWORD, tokens)
-        }
+    // Longest first, so `+=` isn't split into `+` and `=`
+    private fun symbols(symbols: Set<String>): String? {
+        if (symbols.isEmpty()) return null
+        return symbols.sortedByDescending { it.length }.joinToString(separator = "|") { Regex.escape(it) }
+    }
 
-        typePattern?.let { pattern ->
-            findAndAddPattern(text, pattern, TokenType.TYPE, tokens)
-        }
+    override fun highlight(text: String): List<Token> = highlightLine(text, initialState).tokens
 
-        operatorPattern?.let { pattern ->
-            findAndAddPattern(text, pattern, TokenType.OPERATOR, tokens)
-        }
+    override fun highlightLine(text: String, state: LexerState): LineHighlight {
+        val tokens = ArrayList<Token>()
+        // End of the last token, and where to look for the next one; they differ after an empty match
+        var last = 0
+        var position = 0
 
-        punctuationPattern?.let { pattern ->
-            findAndAddPattern(text, pattern, TokenType.PUNCTUATION, tokens)
+        if (state is BlockState) {
+            val block = blockPatterns[state.index]
+            val end = block.findEnd(text, 0, state.end)
+            if (end < 0) {
+                tokens.addToken(block.type, text, 0, text.length)
+                return LineHighlight(tokens, state)
+            }
+            tokens.addToken(block.type, text, 0, end)
+            last = end
+            position = end
         }
 
-        val identifierPattern = "\\b[a-zA-Z_][a-zA-Z0-9_]*\\b".toRegex()
-        val results = identifierPattern.findAll(text)
-        for (result in results) {
-            if (!isInExistingToken(result.range.first, tokens)) {
-                tokens.add(Token(TokenType.IDENTIFIER, result.range, result.value))
+        val matcher = scanner.pattern.matcher(text)
+        while (position < text.length && matcher.find(position)) {
+            val start = matcher.start()
+            if (matcher.end() == start) {
+                position = start + 1
+                continue
             }
-        }
 
-        // Any text not covered by the above tokens gets TokenType.PLAIN
-        val coveredRanges = tokens.map { it.range }.sortedBy { it.first }
-        var lastEnd = 0
+            tokens.addToken(TokenType.PLAIN, text, last, start)
+            val index = scanner.ruleOf(matcher)
+            val rule = scanner.rules[index]
+            last = when {
+                rule.blockIndex >= 0 -> {
+                    val block = blockPatterns[rule.blockIndex]
+                    val blockEnd = block.endFor(block.begin.matchAt(text, start)!!)
+                    val end = block.findEnd(text, matcher.end(), blockEnd)
+                    if (end < 0) {
+                        tokens.addToken(block.type, text, start, text.length)
+                        return LineHighlight(tokens, BlockState(rule.blockIndex, blockEnd))
+                    }
+                    tokens.addToken(block.type, text, start, end)
+                    end
+                }
 
-        for (range in coveredRanges) {
-            if (range.first > lastEnd) {
-                tokens.add(Token(TokenType.PLAIN, lastEnd, range.first, text.substring(lastEnd, range.first)))
+                rule.type == TokenType.FUNCTION -> functionToken(matcher, index, text, tokens)
+                else -> {
+                    tokens.addToken(rule.type, text, start, matcher.end())
+                    matcher.end()
+                }
             }
-            lastEnd = range.last + 1
-        }
-
-        if (lastEnd < text.length) {
-            tokens.add(Token(TokenType.PLAIN, lastEnd, text.length, text.substring(lastEnd)))
+            position = last
         }
 
-        return tokens.sortedBy { it.start }
+        tokens.addToken(TokenType.PLAIN, text, last, text.length)
+        return LineHighlight(tokens, initialState)
     }
 
-    private fun findAndAddTokens(text: String, patterns: List<Regex>, type: TokenType, tokens: MutableList<Token>) {
-        for (pattern in patterns) {
-            val results = pattern.findAll(text)
-            for (result in results) {
-                if (!isInExistingToken(result.range.first, tokens)) {
-                    tokens.add(Token(type, result.range, result.value))
-                }
-            }
+    // Only the first group of a function pattern is the name; the rest of the match is tokenized as usual
+    private fun functionToken(matcher: Matcher, rule: Int, text: String, tokens: MutableList<Token>): Int {
+        val nameGroup = (1..scanner.groupCount(rule)).map { scanner.group(rule, it) }.firstOrNull { matcher.start(it) >= 0 }
+        val nameStart = nameGroup?.let { matcher.start(it) } ?: matcher.start()
+        val nameEnd = nameGroup?.let { matcher.end(it) } ?: matcher.end()
+        if (nameStart == nameEnd) {
+            // Nothing to highlight as a function; let the other rules have the match
+            return prefix(text, matcher.start(), matcher.end(), tokens)
         }
+
+        prefix(text, matcher.start(), nameStart, tokens)
+        tokens.addToken(TokenType.FUNCTION, text, nameStart, nameEnd)
+        return nameEnd
     }
 
-    private fun findAndAddPattern(text: String, pattern: Regex, type: TokenType, tokens: MutableList<Token>) {
-        val results = pattern.findAll(text)
-        for (result in results) {
-            if (!isInExistingToken(result.range.first, tokens)) {
-                tokens.add(Token(type, result.range, result.value))
-            }
+    private fun prefix(text: String, from: Int, to: Int, tokens: MutableList<Token>): Int {
+        if (from >= to) return to
+        val matcher = prefixScanner.pattern.matcher(text).region(from, to).useTransparentBounds(true).useAnchoringBounds(false)
+        var last = from
+        while (matcher.find()) {
+            if (matcher.end() == matcher.start()) continue
+            tokens.addToken(TokenType.PLAIN, text, last, matcher.start())
+            tokens.addToken(prefixScanner.rules[prefixScanner.ruleOf(matcher)].type, text, matcher.start(), matcher.end())
+            last = matcher.end()
         }
+        tokens.addToken(TokenType.PLAIN, text, last, to)
+        return to
     }
 
-    private fun isInExistingToken(position: Int, tokens: List<Token>): Boolean {
-        return tokens.any { position in it.range }
+    private fun MutableList<Token>.addToken(type: TokenType, text: String, start: Int, end: Int) {
+        if (start < end) add(Token(type, start, end, text.substring(start, end)))
     }
 }
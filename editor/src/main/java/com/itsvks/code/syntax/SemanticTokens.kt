@@ -0,0 +1,176 @@
+package com.itsvks.code.syntax
+
+import kotlinx.coroutines.flow.MutableStateFlow
+import kotlinx.coroutines.flow.StateFlow
+import kotlinx.coroutines.flow.asStateFlow
+import kotlinx.coroutines.flow.update
+
+// Edits remembered to move tokens computed for an older version onto the current one
+private const val MAX_EDIT_HISTORY = 256
+
+/**
+ * A range of a single line that a language backend classified by meaning, e.g. as a parameter or a
+ * deprecated function. [type] and [modifiers] are free-form, usually those of [SemanticTokenTypes]
+ * and [SemanticTokenModifiers]; the theme decides how each looks.
+ */
+data class SemanticToken(
+    val line: Int,
+    val column: Int,
+    val length: Int,
+    val type: String,
+    val modifiers: Set<String> = emptySet()
+) {
+    val endColumn: Int get() = column + length
+}
+
+/**
+ * Token types of the Language Server Protocol, plus `local` and `field` that some backends use instead
+ * of `variable` and `property`.
+ */
+object SemanticTokenTypes {
+    const val NAMESPACE = "namespace"
+    const val TYPE = "type"
+    const val CLASS = "class"
+    const val ENUM = "enum"
+    const val INTERFACE = "interface"
+    const val STRUCT = "struct"
+    const val TYPE_PARAMETER = "typeParameter"
+    const val PARAMETER = "parameter"
+    const val VARIABLE = "variable"
+    const val LOCAL = "local"
+    const val PROPERTY = "property"
+    const val FIELD = "field"
+    const val ENUM_MEMBER = "enumMember"
+    const val EVENT = "event"
+    const val FUNCTION = "function"
+    const val METHOD = "method"
+    const val MACRO = "macro"
+    const val KEYWORD = "keyword"
+    const val MODIFIER = "modifier"
+    const val COMMENT = "comment"
+    const val STRING = "string"
+    const val NUMBER = "number"
+    const val REGEXP = "regexp"
+    const val OPERATOR = "operator"
+    const val DECORATOR = "decorator"
+}
+
+/**
+ * Token modifiers of the Language Server Protocol, plus `mutable`.
+ */
+object SemanticTokenModifiers {
+    const val DECLARATION = "declaration"
+    const val DEFINITION = "definition"
+    const val READONLY = "readonly"
+    const val STATIC = "static"
+    const val DEPRECATED = "deprecated"
+    const val ABSTRACT = "abstract"
+    const val ASYNC = "async"
+    const val MODIFICATION = "modification"
+    const val DOCUMENTATION = "documentation"
+    const val DEFAULT_LIBRARY = "defaultLibrary"
+    const val MUTABLE = "mutable"
+}
+
+/**
+ * Semantic tokens the host pushed for a document, drawn over the lexical highlighting.
+ *
+ * Tokens belong to the document version they were computed for. Backends answer late, so tokens for
+ * an older version are moved along with the edits made since; tokens on edited lines are dropped
+ * until the next update.
+ */
+class DocumentSemanticTokens {
+    private val lock = Any()
+
+    // Tokens per line of the current version, sorted by column
+    private val lines = ArrayList<List<SemanticToken>?>()
+    private var version = 0L
+
+    private class LineEdit(val version: Long, val startLine: Int, val removedLines: Int, val insertedLines: Int)
+
+    private val edits = ArrayDeque<LineEdit>()
+
+    // Tokens for versions before this one can't be moved to the current version any more
+    private var oldestMappableVersion = 0L
+
+    private val _updates = MutableStateFlow(0L)
+
+    /**
+     * Changes whenever the tokens change, so the editor redraws.
+     */
+    val updates: StateFlow<Long> = _updates.asStateFlow()
+
+    /**
+     * Replaces all tokens with [tokens], computed for document [version]. Returns false if they can't
+     * be used: the version is newer than the document, or so old its edits are no longer known.
+     */
+    fun update(version: Long, tokens: List<SemanticToken>): Boolean {
+        synchronized(lock) {
+            if (version > this.version || version < oldestMappableVersion) return false
+
+            var byLine: Map<Int, List<SemanticToken>> = tokens.filter { it.length > 0 }.groupBy { it.line }
+            for (edit in edits) {
+                if (edit.version <= version) continue
+                val end = edit.startLine + edit.removedLines
+                val delta = edit.insertedLines - edit.removedLines
+                byLine = byLine.entries.mapNotNull { (line, lineTokens) ->
+                    when {
+                        line < edit.startLine -> line to lineTokens
+                        line >= end -> line + delta to lineTokens
+                        else -> null
+                    }
+                }.toMap()
+            }
+
+            for (i in lines.indices) lines[i] = null
+            for ((line, lineTokens) in byLine) {
+                if (line in lines.indices) lines[line] = lineTokens.map { it.copy(line = line) }.sortedBy { it.column }
+            }
+        }
+        _updates.update { it + 1 }
+        return true
+    }
+
+    fun clear() {
+        synchronized(lock) { for (i in lines.indices) lines[i] = null }
+        _updates.update { it + 1 }
+    }
+
+    /**
+     * Tokens on [line], sorted by column.
+     */
+    fun tokensOn(line: Int): List<SemanticToken> {
+        val lineTokens = synchronized(lock) { lines.getOrNull(line) } ?: return emptyList()
+        // Edits above the line move its tokens without rewriting them
+        return if (lineTokens[0].line == line) lineTokens else lineTokens.map { it.copy(line = line) }
+    }
+
+    /**
+     * The document was replaced by one of [lineCount] lines, at [version]. Tokens for earlier versions no longer apply.
+     */
+    internal fun reset(version: Long, lineCount: Int) {
+        synchronized(lock) {
+            lines.clear()
+            repeat(lineCount) { lines.add(null) }
+            edits.clear()
+            this.version = version
+            oldestMappableVersion = version
+        }
+        _updates.update { it + 1 }
+    }
+
+    /**
+     * Lines `[startLine, startLine + removedLines)` were replaced by [insertedLines] new lines, giving [version].
+     */
+    internal fun onLinesReplaced(version: Long, startLine: Int, removedLines: Int, insertedLines: Int) {
+        synchronized(lock) {
+            val end = (startLine + removedLines).coerceAtMost(lines.size)
+            lines.subList(startLine.coerceAtMost(end), end).clear()
+            lines.addAll(startLine.coerceAtMost(lines.size), List(insertedLines) { null })
+
+            edits.addLast(LineEdit(version, startLine, removedLines, insertedLines))
+            if (edits.size > MAX_EDIT_HISTORY) oldestMappableVersion = edits.removeFirst().version
+            this.version = version
+        }
+    }
+}
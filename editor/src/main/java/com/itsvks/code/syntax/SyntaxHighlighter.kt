@@ -1,5 +1,23 @@
 package com.itsvks.code.syntax
 
+/**
+ * Where a highlighter stands at the end of a line, e.g. inside a block comment. States must
+ * implement [equals]: re-lexing after an edit stops once a line ends in the same state as before.
+ */
+interface LexerState {
+    data object Initial : LexerState
+}
+
+class LineHighlight(val tokens: List<Token>, val endState: LexerState)
+
 interface SyntaxHighlighter {
     fun highlight(text: String): List<Token>
+
+    val initialState: LexerState get() = LexerState.Initial
+
+    /**
+     * Tokens of a single line that starts in [state], together with the state the line ends in.
+     * Highlighters without constructs spanning lines can rely on the default.
+     */
+    fun highlightLine(text: String, state: LexerState): LineHighlight = LineHighlight(highlight(text), state)
 }
@@ -0,0 +1,154 @@
+package com.itsvks.code.syntax.textmate
+
+import com.itsvks.code.core.LruCache
+import java.util.regex.Matcher
+import java.util.regex.Pattern
+
+/**
+ * Compiles the Oniguruma regexes TextMate grammars are written in to [Pattern]s, translating what Java
+ * spells differently. Patterns Java can't compile at all never match, so one bad rule doesn't break a grammar.
+ */
+internal object Oniguruma {
+    val NEVER: Pattern = Pattern.compile("(?!)")
+
+    private const val CACHE_SIZE = 2048
+
+    // Holds the grammars' own patterns as well as end patterns resolved against what their begin matched,
+    // which are as many as there are different captures, so it is bounded
+    private val cache = LruCache<String, Pattern>(CACHE_SIZE)
+    private val QUANTIFIER = "\\{(?:\\d+(?:,\\d*)?|,\\d+)\\}".toRegex()
+    private val POSIX_CLASSES = mapOf(
+        "alnum" to "Alnum", "alpha" to "Alpha", "ascii" to "ASCII", "blank" to "Blank", "cntrl" to "Cntrl",
+        "digit" to "Digit", "graph" to "Graph", "lower" to "Lower", "print" to "Print", "punct" to "Punct",
+        "space" to "Space", "upper" to "Upper", "xdigit" to "XDigit"
+    )
+
+    fun compile(source: String): Pattern {
+        synchronized(cache) { cache[source] }?.let { return it }
+        val pattern = try {
+            Pattern.compile(translate(source))
+        } catch (_: Exception) {
+            NEVER
+        }
+        synchronized(cache) { cache[source] = pattern }
+        return pattern
+    }
+
+    fun translate(source: String): String {
+        val out = StringBuilder(source.length + 16)
+        var inClass = 0
+        var i = 0
+        while (i < source.length) {
+            val char = source[i]
+            when {
+                source.startsWith("\\k<", i) -> {
+                    val end = source.indexOf('>', i + 3)
+                    if (end < 0) throw IllegalArgumentException("Unterminated back reference")
+                    out.append("\\k<").append(groupName(source.substring(i + 3, end))).append('>')
+                    i = end + 1
+                }
+
+                char == '\\' && i + 1 < source.length -> {
+                    when (val next = source[i + 1]) {
+                        // Hex digits in Oniguruma, horizontal whitespace in Java
+                        'h' -> out.append("\\p{XDigit}")
+                        'H' -> out.append("\\P{XDigit}")
+                        else -> out.append(char).append(next)
+                    }
+                    i += 2
+                }
+
+                char == '[' && inClass > 0 && source.startsWith("[:", i) && source.indexOf(":]", i + 2) > 0 -> {
+                    val end = source.indexOf(":]", i + 2)
+                    val name = source.substring(i + 2, end)
+                    val negated = name.startsWith('^')
+                    val className = POSIX_CLASSES[name.removePrefix("^")]
+                    when {
+                        name.removePrefix("^") == "word" -> out.append(if (negated) "\\W" else "\\w")
+                        className != null -> out.append(if (negated) "\\P{$className}" else "\\p{$className}")
+                        else -> throw IllegalArgumentException("Unknown class $name")
+                    }
+                    i = end + 2
+                }
+
+                char == '[' -> {
+                    inClass++
+                    out.append(char)
+                    i++
+                    if (source.getOrNull(i) == '^') out.append(source[i++])
+                    // A leading `]` is literal in Oniguruma, an empty class in Java
+                    if (source.getOrNull(i) == ']') {
+                        out.append("\\]")
+                        i++
+                    }
+                }
+
+                char == ']' && inClass > 0 -> {
+                    inClass--
+                    out.append(char)
+                    i++
+                }
+
+                inClass == 0 && char == '(' && source.startsWith("(?<", i) && source.getOrNull(i + 3) !in listOf('=', '!') -> {
+                    val end = source.indexOf('>', i + 3)
+                    if (end < 0) throw IllegalArgumentException("Unterminated group name")
+                    out.append("(?<").append(groupName(source.substring(i + 3, end))).append('>')
+                    i = end + 1
+                }
+
+                inClass == 0 && char == '{' -> {
+                    val quantifier = QUANTIFIER.matchAt(source, i)
+                    when {
+                        quantifier == null -> out.append("\\{")
+                        quantifier.value.startsWith("{,") -> out.append("{0").append(quantifier.value, 1, quantifier.value.length)
+                        else -> out.append(quantifier.value)
+                    }
+                    i += quantifier?.value?.length ?: 1
+                }
+
+                else -> {
+                    out.append(char)
+                    i++
+                }
+            }
+        }
+        return out.toString()
+    }
+
+    /**
+     * [end] with its back references, e.g. `\1`, replaced by the text [begin] captured.
+     */
+    fun resolveBackReferences(end: String, begin: Matcher): String {
+        if ('\\' !in end) return end
+        val out = StringBuilder(end.length)
+        var i = 0
+        while (i < end.length) {
+            val char = end[i]
+            if (char == '\\' && i + 1 < end.length) {
+                val digits = end.substring(i + 1).takeWhile { it.isDigit() }
+                if (digits.isEmpty()) {
+                    out.append(char).append(end[i + 1])
+                    i += 2
+                    continue
+                }
+                val group = digits.toInt()
+                val captured = if (group <= begin.groupCount()) begin.group(group).orEmpty() else ""
+                for (c in captured) {
+                    if (!c.isLetterOrDigit()) out.append('\\')
+                    out.append(c)
+                }
+                i += 1 + digits.length
+            } else {
+                out.append(char)
+                i++
+            }
+        }
+        return out.toString()
+    }
+
+    // Java group names are letters and digits only
+    private fun groupName(name: String): String {
+        val cleaned = name.filter { it.isLetterOrDigit() && it.code < 128 }
+        return if (cleaned.firstOrNull()?.isLetter() == true) cleaned else "g$cleaned"
+    }
+}
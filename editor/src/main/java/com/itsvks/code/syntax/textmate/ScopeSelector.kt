@@ -0,0 +1,46 @@
+package com.itsvks.code.syntax.textmate
+
+/**
+ * A scope selector such as `L:source.js -comment -string, text.html`. Each comma separated alternative
+ * lists scopes that must appear in order in the scope stack, and `-` prefixed ones that must not appear.
+ * Grouping with parentheses is flattened.
+ */
+internal class ScopeSelector(selector: String) {
+    private class Alternative(val required: List<String>, val excluded: List<String>)
+
+    val prefersLeft: Boolean
+    private val alternatives: List<Alternative>
+
+    init {
+        var left = false
+        alternatives = selector.split(',').map { part ->
+            var text = part.trim()
+            if (text.startsWith("L:")) left = true
+            if (text.length > 1 && text[1] == ':') text = text.substring(2)
+            val words = text.replace("(", " ").replace(")", " ").replace("- ", "-").split(' ').filter { it.isNotEmpty() }
+            Alternative(
+                required = words.filterNot { it.startsWith('-') },
+                excluded = words.filter { it.startsWith('-') }.map { it.removePrefix("-") }
+            )
+        }
+        prefersLeft = left
+    }
+
+    fun matches(scopes: List<String>): Boolean = alternatives.any { alternative ->
+        alternative.excluded.none { excluded -> scopes.any { it.matchesScope(excluded) } } &&
+            matchesInOrder(alternative.required, scopes)
+    }
+
+    private fun matchesInOrder(required: List<String>, scopes: List<String>): Boolean {
+        var next = 0
+        for (scope in scopes) {
+            if (next < required.size && scope.matchesScope(required[next])) next++
+        }
+        return next == required.size
+    }
+}
+
+// `string.quoted` matches `string.quoted.double.js`, but not `string.quotedx`
+internal fun String.matchesScope(prefix: String): Boolean {
+    return this == prefix || (startsWith(prefix) && this[prefix.length] == '.')
+}
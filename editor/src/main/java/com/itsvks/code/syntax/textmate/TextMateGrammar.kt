@@ -0,0 +1,115 @@
+package com.itsvks.code.syntax.textmate
+
+import com.itsvks.code.util.Json
+import java.io.InputStream
+import java.util.concurrent.ConcurrentHashMap
+
+/**
+ * A rule of a TextMate grammar: a single-line [match], a [begin]/[end] or [begin]/[whilePattern] region
+ * with nested [patterns], an [include] of another rule, or just a list of [patterns].
+ * Captures map group numbers to scope names.
+ */
+internal class TextMateRule(
+    val name: String? = null,
+    val contentName: String? = null,
+    val match: String? = null,
+    val begin: String? = null,
+    val end: String? = null,
+    val whilePattern: String? = null,
+    val captures: Map<Int, String> = emptyMap(),
+    val beginCaptures: Map<Int, String> = emptyMap(),
+    val endCaptures: Map<Int, String> = emptyMap(),
+    val whileCaptures: Map<Int, String> = emptyMap(),
+    val patterns: List<TextMateRule> = emptyList(),
+    val include: String? = null,
+    val applyEndPatternLast: Boolean = false
+) {
+    val isRegion: Boolean get() = begin != null && (end != null || whilePattern != null)
+}
+
+/**
+ * Rules injected into any scope matching [selector], before the normal patterns on ties if [selector]
+ * starts with `L:`.
+ */
+internal class TextMateInjection(val selector: ScopeSelector, val rule: TextMateRule)
+
+/**
+ * A TextMate grammar, as found in `.tmLanguage.json` files of VS Code extensions.
+ */
+class TextMateGrammar internal constructor(
+    val scopeName: String,
+    val name: String?,
+    val fileTypes: List<String>,
+    internal val root: TextMateRule,
+    internal val repository: Map<String, TextMateRule>,
+    internal val injections: List<TextMateInjection>
+) {
+    companion object {
+        private val grammars = ConcurrentHashMap<String, TextMateGrammar>()
+
+        @JvmStatic
+        fun fromJson(json: String): TextMateGrammar {
+            val map = Json.parse(json) as? Map<*, *> ?: throw IllegalArgumentException("Grammar must be a JSON object")
+            val scopeName = map["scopeName"] as? String ?: throw IllegalArgumentException("Grammar has no scopeName")
+            return TextMateGrammar(
+                scopeName = scopeName,
+                name = map["name"] as? String,
+                fileTypes = (map["fileTypes"] as? List<*>).orEmpty().filterIsInstance<String>().map { it.removePrefix(".") },
+                root = parseRule(map),
+                repository = parseRepository(map["repository"]),
+                injections = (map["injections"] as? Map<*, *>)?.entries.orEmpty().mapNotNull { (selector, rule) ->
+                    (rule as? Map<*, *>)?.let { TextMateInjection(ScopeSelector(selector.toString()), parseRule(it)) }
+                }
+            )
+        }
+
+        @JvmStatic
+        fun fromStream(stream: InputStream): TextMateGrammar = fromJson(stream.bufferedReader().use { it.readText() })
+
+        /**
+         * Makes [grammar] available to other grammars that include it by its scope name, e.g. `source.css`.
+         */
+        @JvmStatic
+        fun register(grammar: TextMateGrammar) {
+            grammars[grammar.scopeName] = grammar
+        }
+
+        @JvmStatic
+        fun forScope(scopeName: String): TextMateGrammar? = grammars[scopeName]
+
+        private fun parseRepository(value: Any?): Map<String, TextMateRule> {
+            return (value as? Map<*, *>)?.entries.orEmpty().mapNotNull { (key, rule) ->
+                (rule as? Map<*, *>)?.let { key.toString() to parseRule(it) }
+            }.toMap()
+        }
+
+        private fun parseRule(map: Map<*, *>): TextMateRule {
+            return TextMateRule(
+                name = map["name"] as? String,
+                contentName = map["contentName"] as? String,
+                match = map["match"] as? String,
+                begin = map["begin"] as? String,
+                end = map["end"] as? String,
+                whilePattern = map["while"] as? String,
+                captures = parseCaptures(map["captures"]),
+                beginCaptures = parseCaptures(map["beginCaptures"] ?: map["captures"]),
+                endCaptures = parseCaptures(map["endCaptures"] ?: map["captures"]),
+                whileCaptures = parseCaptures(map["whileCaptures"] ?: map["captures"]),
+                patterns = (map["patterns"] as? List<*>).orEmpty().filterIsInstance<Map<*, *>>().map(::parseRule),
+                include = map["include"] as? String,
+                applyEndPatternLast = map["applyEndPatternLast"].let { it == true || it == 1L }
+            )
+        }
+
+        // Either `{"1": {"name": "..."}}` or, in older grammars, a list indexed by group
+        private fun parseCaptures(value: Any?): Map<Int, String> {
+            val entries = when (value) {
+                is Map<*, *> -> value.entries.mapNotNull { (key, capture) -> key.toString().toIntOrNull()?.let { it to capture } }
+                is List<*> -> value.mapIndexed { i, capture -> i to capture }
+                else -> return emptyMap()
+            }
+            return entries.mapNotNull { (group, capture) -> ((capture as? Map<*, *>)?.get("name") as? String)?.let { group to it } }
+                .toMap(sortedMapOf())
+        }
+    }
+}
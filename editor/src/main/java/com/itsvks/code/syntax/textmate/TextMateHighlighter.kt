@@ -0,0 +1,299 @@
+package com.itsvks.code.syntax.textmate
+
+import com.itsvks.code.syntax.LexerState
+import com.itsvks.code.syntax.LineHighlight
+import com.itsvks.code.syntax.SyntaxHighlighter
+import com.itsvks.code.syntax.Token
+import com.itsvks.code.syntax.TokenType
+import java.util.IdentityHashMap
+import java.util.concurrent.ConcurrentHashMap
+import java.util.regex.Matcher
+import java.util.regex.Pattern
+
+// Steps allowed per char of a line before giving up on a grammar that loops without consuming text
+private const val MAX_STEPS_PER_CHAR = 8
+
+/**
+ * An open begin/end or begin/while region, with its end (or while) pattern as resolved against the begin match.
+ */
+private data class Frame(
+    val rule: TextMateRule,
+    val grammar: TextMateGrammar,
+    val endSource: String,
+    val scopes: List<String>,
+    val contentScopes: List<String>
+)
+
+// Open regions, outermost first
+private data class TextMateState(val frames: List<Frame>) : LexerState
+
+// A match or begin rule, with the grammar its includes resolve in
+private class Candidate(val rule: TextMateRule, val grammar: TextMateGrammar) {
+    val pattern: Pattern = Oniguruma.compile(rule.match ?: rule.begin!!)
+}
+
+/**
+ * Finds the earliest match of a pattern in a line, remembering results: a match found searching from
+ * an earlier position is still the earliest one from any position up to its start.
+ */
+private class LineMatchers(private val line: String) {
+    private class Result(val matcher: Matcher, val anchored: Boolean, var from: Int, var start: Int)
+
+    private val results = HashMap<Pattern, Result>()
+
+    fun find(pattern: Pattern, from: Int): Matcher? {
+        val result = results.getOrPut(pattern) { Result(pattern.matcher(line), "\\G" in pattern.pattern(), -1, -1) }
+        val reusable = !result.anchored && result.from in 0..from && (result.start < 0 || result.start >= from)
+        if (!reusable) {
+            result.from = from
+            result.start = if (result.matcher.find(from)) result.matcher.start() else -1
+        }
+        return if (result.start >= 0) result.matcher else null
+    }
+}
+
+private class TokenWriter(private val text: String, private val tokenTypeOf: (List<String>) -> TokenType) {
+    val tokens = ArrayList<Token>()
+    private var type = TokenType.PLAIN
+    private var start = 0
+    private var end = 0
+    private var lastScopes: List<String>? = null
+    private var lastType = TokenType.PLAIN
+
+    fun add(from: Int, to: Int, scopes: List<String>) {
+        val until = to.coerceAtMost(text.length)
+        if (from >= until) return
+        val scopesType = if (scopes === lastScopes) lastType else tokenTypeOf(scopes)
+        lastScopes = scopes
+        lastType = scopesType
+        if (scopesType != type || from != end) {
+            flush()
+            type = scopesType
+            start = from
+        }
+        end = until
+    }
+
+    fun finish(): List<Token> {
+        flush()
+        return tokens
+    }
+
+    private fun flush() {
+        if (start < end) tokens.add(Token(type, start, end, text.substring(start, end)))
+        start = end
+    }
+}
+
+/**
+ * Highlights with a TextMate [grammar]: match and begin/end or begin/while rules with captures,
+ * includes of the grammar's repository, of itself or of other grammars registered with
+ * [TextMateGrammar.register], and injections. Scopes are mapped to token types by [tokenTypeOf],
+ * given the scope stack from outermost to innermost.
+ */
+class TextMateHighlighter @JvmOverloads constructor(
+    val grammar: TextMateGrammar,
+    private val tokenTypeOf: (List<String>) -> TokenType = TextMateScopes::tokenTypeOf
+) : SyntaxHighlighter {
+    private class Found(val matcher: Matcher, val candidate: Candidate?)
+
+    private val rootScopes = listOf(grammar.scopeName)
+    private val rootState = TextMateState(emptyList())
+
+    // Rules to try inside each rule, with includes resolved
+    private val candidates = ConcurrentHashMap<TextMateRule, List<Candidate>>()
+    private val injections by lazy {
+        grammar.injections.map { it.selector to resolve(listOf(it.rule), grammar) }
+    }
+
+    override val initialState: LexerState get() = rootState
+
+    override fun highlight(text: String): List<Token> = highlightLine(text, initialState).tokens
+
+    override fun highlightLine(text: String, state: LexerState): LineHighlight {
+        // Grammars expect lines to end in a newline, e.g. single line comments up to `\n`
+        val line = "$text\n"
+        val matchers = LineMatchers(line)
+        val out = TokenWriter(text, tokenTypeOf)
+        var frames = (state as? TextMateState)?.frames.orEmpty()
+        var position = 0
+
+        // A begin/while region lasts as long as each line starts with its while pattern
+        for ((i, frame) in frames.withIndex()) {
+            if (frame.rule.whilePattern == null) continue
+            val matcher = matchers.find(Oniguruma.compile(frame.endSource), position)
+            if (matcher == null || matcher.start() != position) {
+                frames = frames.take(i)
+                break
+            }
+            out.addMatch(matcher, frame.scopes, frame.rule.whileCaptures)
+            position = matcher.end()
+        }
+
+        val pushedAt = IdentityHashMap<Frame, Int>()
+        var steps = 0
+        while (position < line.length) {
+            val frame = frames.lastOrNull()
+            val scopes = frame?.contentScopes ?: rootScopes
+            val found = if (++steps > MAX_STEPS_PER_CHAR * line.length) null else findNext(matchers, position, frame, scopes)
+            if (found == null) {
+                out.add(position, line.length, scopes)
+                break
+            }
+
+            val matcher = found.matcher
+            out.add(position, matcher.start(), scopes)
+            val candidate = found.candidate
+            when {
+                candidate == null -> {
+                    out.addMatch(matcher, frame!!.scopes, frame.rule.endCaptures)
+                    frames = frames.dropLast(1)
+                    if (pushedAt[frame] == matcher.end()) {
+                        // Pushed and popped without consuming anything; it would only match again
+                        out.add(matcher.end(), line.length, frames.lastOrNull()?.contentScopes ?: rootScopes)
+                        break
+                    }
+                }
+
+                candidate.rule.isRegion -> {
+                    val rule = candidate.rule
+                    val beginScopes = scopes.plusScopes(rule.name, matcher)
+                    out.addMatch(matcher, beginScopes, rule.beginCaptures)
+                    val endSource = Oniguruma.resolveBackReferences(rule.end ?: rule.whilePattern!!, matcher)
+                    val pushed = Frame(rule, candidate.grammar, endSource, beginScopes, beginScopes.plusScopes(rule.contentName, matcher))
+                    frames = frames + pushed
+                    pushedAt[pushed] = matcher.end()
+                }
+
+                else -> {
+                    out.addMatch(matcher, scopes.plusScopes(candidate.rule.name, matcher), candidate.rule.captures)
+                    if (matcher.end() == position) {
+                        // An empty match; step over a char so the same rule doesn't match here forever
+                        out.add(position, position + 1, scopes)
+                        position++
+                        continue
+                    }
+                }
+            }
+            position = matcher.end()
+        }
+
+        return LineHighlight(out.finish(), if (frames.isEmpty()) rootState else TextMateState(frames))
+    }
+
+    // The earliest match at or after [position]; ties go to the end pattern, unless the rule applies it last
+    private fun findNext(matchers: LineMatchers, position: Int, frame: Frame?, scopes: List<String>): Found? {
+        var best: Found? = null
+
+        fun consider(pattern: Pattern, candidate: Candidate?) {
+            val matcher = matchers.find(pattern, position) ?: return
+            if (best == null || matcher.start() < best!!.matcher.start()) best = Found(matcher, candidate)
+        }
+
+        val end = frame?.takeIf { it.rule.end != null }?.let { Oniguruma.compile(it.endSource) }
+        val endLast = frame?.rule?.applyEndPatternLast == true
+        if (end != null && !endLast) consider(end, null)
+
+        val injected = injections.filter { (selector, _) -> selector.matches(scopes) }
+        for ((_, rules) in injected.filter { it.first.prefersLeft }) rules.forEach { consider(it.pattern, it) }
+        for (candidate in candidatesOf(frame)) consider(candidate.pattern, candidate)
+        for ((_, rules) in injected.filterNot { it.first.prefersLeft }) rules.forEach { consider(it.pattern, it) }
+
+        if (end != null && endLast) consider(end, null)
+        return best
+    }
+
+    private fun candidatesOf(frame: Frame?): List<Candidate> {
+        val rule = frame?.rule ?: grammar.root
+        return candidates.getOrPut(rule) { resolve(rule.patterns, frame?.grammar ?: grammar) }
+    }
+
+    // Match and begin rules among [patterns], in order, following includes and nested pattern lists
+    private fun resolve(patterns: List<TextMateRule>, owner: TextMateGrammar): List<Candidate> {
+        val result = ArrayList<Candidate>()
+        val visited = HashSet<TextMateRule>()
+
+        fun add(rule: TextMateRule, owner: TextMateGrammar) {
+            if (!visited.add(rule)) return
+            val include = rule.include
+            when {
+                include != null -> included(include, owner)?.let { (target, targetGrammar) -> add(target, targetGrammar) }
+                rule.match != null || rule.isRegion -> result.add(Candidate(rule, owner))
+                else -> rule.patterns.forEach { add(it, owner) }
+            }
+        }
+
+        patterns.forEach { add(it, owner) }
+        return result
+    }
+
+    private fun included(include: String, owner: TextMateGrammar): Pair<TextMateRule, TextMateGrammar>? {
+        return when {
+            include == "\$self" -> owner.root to owner
+            include == "\$base" -> grammar.root to grammar
+            include.startsWith('#') -> owner.repository[include.substring(1)]?.let { it to owner }
+            else -> {
+                val other = TextMateGrammar.forScope(include.substringBefore('#')) ?: return null
+                val key = include.substringAfter('#', "")
+                val rule = if (key.isEmpty()) other.root else other.repository[key] ?: return null
+                rule to other
+            }
+        }
+    }
+
+    private fun TokenWriter.addMatch(matcher: Matcher, scopes: List<String>, captures: Map<Int, String>) {
+        val start = matcher.start()
+        val end = matcher.end()
+        if (captures.isEmpty() || start == end) {
+            add(start, end, scopes)
+            return
+        }
+
+        val charScopes = Array(end - start) { scopes }
+        for ((group, name) in captures) {
+            if (group > matcher.groupCount() || matcher.start(group) < 0) continue
+            // Consecutive chars mostly share their scopes, so add the capture's once per distinct list
+            var from: List<String>? = null
+            var to = scopes
+            for (i in maxOf(matcher.start(group), start) until minOf(matcher.end(group), end)) {
+                val current = charScopes[i - start]
+                if (current !== from) {
+                    from = current
+                    to = current.plusScopes(name, matcher)
+                }
+                charScopes[i - start] = to
+            }
+        }
+
+        var runStart = 0
+        for (i in 1..charScopes.size) {
+            if (i == charScopes.size || charScopes[i] !== charScopes[runStart]) {
+                add(start + runStart, start + i, charScopes[runStart])
+                runStart = i
+            }
+        }
+    }
+
+    private fun List<String>.plusScopes(name: String?, matcher: Matcher): List<String> {
+        if (name.isNullOrBlank()) return this
+        val resolved = if ('$' in name) {
+            CAPTURE_REFERENCE.replace(name) { reference ->
+                val group = (reference.groups[1] ?: reference.groups[2])!!.value.toInt()
+                val captured = if (group <= matcher.groupCount()) matcher.group(group).orEmpty() else ""
+                when (reference.groups[3]?.value) {
+                    "downcase" -> captured.lowercase()
+                    "upcase" -> captured.uppercase()
+                    else -> captured
+                }
+            }
+        } else {
+            name
+        }
+        return this + resolved.split(' ').filter { it.isNotEmpty() }
+    }
+
+    private companion object {
+        // `$1`, or `${1:/downcase}`, in scope names
+        val CAPTURE_REFERENCE = "\\$(\\d+)|\\$\\{(\\d+):/(downcase|upcase)\\}".toRegex()
+    }
+}
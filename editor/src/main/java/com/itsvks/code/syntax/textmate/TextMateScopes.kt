@@ -0,0 +1,64 @@
+package com.itsvks.code.syntax.textmate
+
+import com.itsvks.code.syntax.TokenType
+
+/**
+ * Maps TextMate scopes to [TokenType]s by the conventions of VS Code's themes, e.g. `keyword.operator`
+ * is an operator and any other `keyword` a keyword.
+ */
+object TextMateScopes {
+    // More specific scopes first, since the first matching prefix wins
+    private val mappings = listOf(
+        "comment" to TokenType.COMMENT,
+        "punctuation.definition.comment" to TokenType.COMMENT,
+        "string" to TokenType.STRING,
+        "punctuation.definition.string" to TokenType.STRING,
+        "constant.character.escape" to TokenType.STRING,
+        "constant.numeric" to TokenType.NUMBER,
+        "constant.language" to TokenType.KEYWORD,
+        "constant" to TokenType.CONST,
+        "variable.other.constant" to TokenType.CONST,
+        "variable.other.enummember" to TokenType.CONST,
+        "keyword.control.directive" to TokenType.MACRO,
+        "meta.preprocessor" to TokenType.MACRO,
+        "entity.name.function.preprocessor" to TokenType.MACRO,
+        "entity.name.function.macro" to TokenType.MACRO,
+        "keyword.operator" to TokenType.OPERATOR,
+        "keyword" to TokenType.KEYWORD,
+        "storage.type.annotation" to TokenType.ANNOTATION,
+        "punctuation.definition.annotation" to TokenType.ANNOTATION,
+        "entity.name.type.annotation" to TokenType.ANNOTATION,
+        "storage.type.primitive" to TokenType.TYPE,
+        "storage.type.built-in" to TokenType.TYPE,
+        "storage.modifier.static" to TokenType.STATIC,
+        "storage" to TokenType.KEYWORD,
+        "entity.name.function" to TokenType.FUNCTION,
+        "support.function" to TokenType.FUNCTION,
+        "variable.function" to TokenType.FUNCTION,
+        "entity.name.type" to TokenType.TYPE,
+        "entity.name.class" to TokenType.TYPE,
+        "entity.name.namespace" to TokenType.TYPE,
+        "entity.other.inherited-class" to TokenType.TYPE,
+        "support.class" to TokenType.TYPE,
+        "support.type" to TokenType.TYPE,
+        "entity.name.tag" to TokenType.KEYWORD,
+        "entity.other.attribute-name" to TokenType.ATTRIBUTE,
+        "variable.language" to TokenType.KEYWORD,
+        "variable" to TokenType.IDENTIFIER,
+        "punctuation" to TokenType.PUNCTUATION
+    )
+
+    /**
+     * Type of text with the given [scopes], from outermost to innermost: that of the innermost scope with one.
+     */
+    @JvmStatic
+    fun tokenTypeOf(scopes: List<String>): TokenType {
+        for (scope in scopes.asReversed()) {
+            tokenTypeOf(scope)?.let { return it }
+        }
+        return TokenType.PLAIN
+    }
+
+    @JvmStatic
+    fun tokenTypeOf(scope: String): TokenType? = mappings.firstOrNull { (prefix, _) -> scope.matchesScope(prefix) }?.second
+}
@@ -4,8 +4,13 @@ import androidx.compose.ui.graphics.Color
 import androidx.compose.ui.text.AnnotatedString
 import androidx.compose.ui.text.SpanStyle
 import androidx.compose.ui.text.buildAnnotatedString
+import androidx.compose.ui.text.font.FontStyle
 import androidx.compose.ui.text.font.FontWeight
+import androidx.compose.ui.text.style.TextDecoration
 import androidx.compose.ui.util.fastForEach
+import com.itsvks.code.syntax.SemanticToken
+import com.itsvks.code.syntax.SemanticTokenModifiers
+import com.itsvks.code.syntax.SemanticTokenTypes
 import com.itsvks.code.syntax.SyntaxHighlighter
 import com.itsvks.code.syntax.Token
 import com.itsvks.code.syntax.TokenType
@@ -23,8 +28,71 @@ interface EditorTheme {
     val selectionHandleColor: Color get() = cursorColor
     val scrollBarSelectedColor: Color get() = Color.White.copy(alpha = 0.5f)
     val scrollBarColor: Color get() = Color.LightGray.copy(alpha = 0.5f)
+    val searchMatchColor: Color get() = Color(0x55FFD33D)
+    val currentSearchMatchColor: Color get() = Color(0x99FF9632)
+
+    // The completion popup; matched chars of its items use the highlight color
+    val completionBackgroundColor: Color get() = gutterBgColor
+    val completionTextColor: Color get() = defaultTextColor
+    val completionDetailColor: Color get() = gutterTextColor
+    val completionSelectedColor: Color get() = selectionColor
+    val completionHighlightColor: Color get() = getStyleForToken(TokenType.KEYWORD).color
+    val completionBorderColor: Color get() = gutterBorderColor
+
+    // Diagnostics: squiggles under their text, icons in the gutter and the popup listing them
+    val errorColor: Color get() = Color(0xFFF14C4C)
+    val warningColor: Color get() = Color(0xFFCCA700)
+    val infoColor: Color get() = Color(0xFF3794FF)
+    val hintColor: Color get() = gutterTextColor
+    val diagnosticPopupBackgroundColor: Color get() = hoverBackgroundColor
+    val diagnosticPopupTextColor: Color get() = hoverTextColor
+    val diagnosticPopupBorderColor: Color get() = hoverBorderColor
+
+    // Hover cards and signature help; code in them has its own background
+    val hoverBackgroundColor: Color get() = completionBackgroundColor
+    val hoverTextColor: Color get() = completionTextColor
+    val hoverBorderColor: Color get() = completionBorderColor
+    val hoverCodeBackgroundColor: Color get() = backgroundColor
+    val activeParameterColor: Color get() = completionHighlightColor
 
     fun getStyleForToken(type: TokenType): SpanStyle
+
+    /**
+     * Style of a semantic token of [type] with [modifiers], drawn over the lexical style of its text.
+     * Only what the style sets is overridden, so e.g. a plain underline keeps the lexical color; null
+     * leaves the text as it is.
+     */
+    fun getStyleForSemanticToken(type: String, modifiers: Set<String>): SpanStyle? = defaultSemanticTokenStyle(type, modifiers)
+}
+
+/**
+ * Styles semantic tokens like the lexical tokens closest to them, with parameters in italics,
+ * deprecated symbols struck through and mutable ones underlined.
+ */
+fun EditorTheme.defaultSemanticTokenStyle(type: String, modifiers: Set<String>): SpanStyle? {
+    val lexicalType = when (type) {
+        SemanticTokenTypes.NAMESPACE, SemanticTokenTypes.TYPE, SemanticTokenTypes.CLASS, SemanticTokenTypes.ENUM,
+        SemanticTokenTypes.INTERFACE, SemanticTokenTypes.STRUCT, SemanticTokenTypes.TYPE_PARAMETER -> TokenType.TYPE
+        SemanticTokenTypes.FUNCTION, SemanticTokenTypes.METHOD -> TokenType.FUNCTION
+        SemanticTokenTypes.PROPERTY, SemanticTokenTypes.FIELD -> TokenType.STATIC
+        SemanticTokenTypes.ENUM_MEMBER -> TokenType.CONST
+        SemanticTokenTypes.MACRO -> TokenType.MACRO
+        SemanticTokenTypes.DECORATOR -> TokenType.ANNOTATION
+        SemanticTokenTypes.KEYWORD, SemanticTokenTypes.MODIFIER -> TokenType.KEYWORD
+        else -> null
+    }
+    val decorations = listOfNotNull(
+        TextDecoration.LineThrough.takeIf { SemanticTokenModifiers.DEPRECATED in modifiers },
+        TextDecoration.Underline.takeIf { SemanticTokenModifiers.MUTABLE in modifiers }
+    )
+    val italic = type == SemanticTokenTypes.PARAMETER
+    if (lexicalType == null && decorations.isEmpty() && !italic) return null
+
+    return SpanStyle(
+        color = lexicalType?.let { getStyleForToken(it).color } ?: Color.Unspecified,
+        fontStyle = if (italic) FontStyle.Italic else null,
+        textDecoration = if (decorations.isEmpty()) null else TextDecoration.combine(decorations)
+    )
 }
 
 internal fun AnnotatedString.highlight(
@@ -46,11 +114,21 @@ internal fun String.highlight(
     highlightToken(tokens, theme, this@highlight, bracketIndices)
 }
 
+internal fun String.highlight(
+    theme: EditorTheme,
+    tokens: List<Token>,
+    bracketIndices: Set<Int> = emptySet(),
+    semanticTokens: List<SemanticToken> = emptyList()
+) = buildAnnotatedString {
+    highlightToken(tokens, theme, this@highlight, bracketIndices, semanticTokens)
+}
+
 private fun AnnotatedString.Builder.highlightToken(
     tokens: List<Token>,
     theme: EditorTheme,
     lineString: CharSequence, // Changed from 'line: CharSequence' to 'lineString' for clarity
-    bracketIndices: Set<Int>
+    bracketIndices: Set<Int>,
+    semanticTokens: List<SemanticToken> = emptyList()
 ) {
     // 1. Append the entire line string without any initial style.
     append(lineString.toString())
@@ -67,7 +145,17 @@ private fun AnnotatedString.Builder.highlightToken(
         }
     }
 
-    // 3. Apply special styling for bracket characters.
+    // 3. Semantic tokens go over the lexical styles, overriding only what their style sets.
+    semanticTokens.fastForEach { token ->
+        val style = theme.getStyleForSemanticToken(token.type, token.modifiers) ?: return@fastForEach
+        val effectiveStart = token.column.coerceIn(0, lineString.length)
+        val effectiveEnd = token.endColumn.coerceIn(effectiveStart, lineString.length)
+        if (effectiveStart < effectiveEnd) {
+            addStyle(style, effectiveStart, effectiveEnd)
+        }
+    }
+
+    // 4. Apply special styling for bracket characters.
     // This will overlay or merge with the token styles.
     if (bracketIndices.isNotEmpty()) {
         val bracketStyle = SpanStyle(
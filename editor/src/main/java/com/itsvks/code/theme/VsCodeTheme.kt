@@ -0,0 +1,365 @@
+package com.itsvks.code.theme
+
+import android.content.res.AssetManager
+import androidx.compose.ui.graphics.Color
+import androidx.compose.ui.text.SpanStyle
+import androidx.compose.ui.text.font.FontStyle
+import androidx.compose.ui.text.font.FontWeight
+import androidx.compose.ui.text.style.TextDecoration
+import com.itsvks.code.syntax.SemanticTokenModifiers
+import com.itsvks.code.syntax.SemanticTokenTypes
+import com.itsvks.code.syntax.TokenType
+import com.itsvks.code.syntax.textmate.matchesScope
+import com.itsvks.code.util.Json
+import java.io.InputStream
+import java.util.EnumMap
+import java.util.concurrent.ConcurrentHashMap
+
+/**
+ * A theme read from a VS Code color theme file. Editor colors come from its `colors`, e.g.
+ * `editor.background`, and token colors from the `tokenColors` rules that best match the scopes each
+ * [TokenType] stands for. Semantic tokens are styled by its `semanticTokenColors`, or else by the
+ * `tokenColors` of the scopes VS Code maps them to. Whatever the file leaves out is taken from
+ * [VsCodeDarkTheme] or, for light themes, [AtomOneLightTheme].
+ */
+class VsCodeTheme private constructor(
+    val name: String,
+    val isDark: Boolean,
+    private val colors: Map<String, Color>,
+    private val rules: List<TokenColorRule>,
+    private val semanticRules: List<SemanticTokenRule>
+) : EditorTheme {
+    private val fallback: EditorTheme = if (isDark) VsCodeDarkTheme else AtomOneLightTheme
+
+    // The rule without a scope holds the defaults in themes converted from `.tmTheme` files
+    private val globalSettings = rules.firstOrNull { it.selectors.isEmpty() }
+
+    override val backgroundColor = color("editor.background") ?: globalSettings?.background ?: fallback.backgroundColor
+    override val defaultTextColor = color("editor.foreground", "foreground") ?: globalSettings?.foreground ?: fallback.defaultTextColor
+    override val cursorColor = color("editorCursor.foreground") ?: fallback.cursorColor
+    override val selectionColor = color("editor.selectionBackground", "selection.background") ?: fallback.selectionColor
+    override val gutterBgColor = color("editorGutter.background") ?: backgroundColor
+
+    override val activeLineColor = color("editor.lineHighlightBackground") ?: fallback.activeLineColor
+    override val gutterTextColor = color("editorLineNumber.foreground") ?: fallback.gutterTextColor
+    override val gutterBorderColor = color("editorGroup.border", "panel.border") ?: fallback.gutterBorderColor
+    override val invisibleCharColor = color("editorWhitespace.foreground") ?: fallback.invisibleCharColor
+    override val selectionHandleColor = color("editorCursor.foreground", "focusBorder") ?: fallback.selectionHandleColor
+    override val scrollBarSelectedColor = color("scrollbarSlider.activeBackground") ?: fallback.scrollBarSelectedColor
+    override val scrollBarColor = color("scrollbarSlider.background") ?: fallback.scrollBarColor
+    override val searchMatchColor = color("editor.findMatchHighlightBackground") ?: fallback.searchMatchColor
+    override val currentSearchMatchColor = color("editor.findMatchBackground") ?: fallback.currentSearchMatchColor
+    override val completionBackgroundColor = color("editorSuggestWidget.background", "editorWidget.background") ?: gutterBgColor
+    override val completionTextColor = color("editorSuggestWidget.foreground") ?: defaultTextColor
+    override val completionDetailColor = color("descriptionForeground") ?: gutterTextColor
+    override val completionSelectedColor = color("editorSuggestWidget.selectedBackground", "list.activeSelectionBackground") ?: selectionColor
+    override val completionBorderColor = color("editorSuggestWidget.border", "editorWidget.border") ?: gutterBorderColor
+    override val errorColor = color("editorError.foreground") ?: fallback.errorColor
+    override val warningColor = color("editorWarning.foreground") ?: fallback.warningColor
+    override val infoColor = color("editorInfo.foreground") ?: fallback.infoColor
+    override val hintColor = color("editorHint.foreground") ?: gutterTextColor
+    override val hoverBackgroundColor = color("editorHoverWidget.background", "editorWidget.background") ?: gutterBgColor
+    override val hoverTextColor = color("editorHoverWidget.foreground", "editorWidget.foreground") ?: defaultTextColor
+    override val hoverBorderColor = color("editorHoverWidget.border", "editorWidget.border") ?: gutterBorderColor
+    override val hoverCodeBackgroundColor = color("textCodeBlock.background") ?: backgroundColor
+
+    private val tokenStyles = EnumMap<TokenType, SpanStyle>(TokenType::class.java).apply {
+        for (type in TokenType.entries) put(type, styleOf(SCOPES[type].orEmpty()))
+    }
+
+    override fun getStyleForToken(type: TokenType): SpanStyle = tokenStyles.getValue(type)
+
+    override val completionHighlightColor: Color
+        get() = color("editorSuggestWidget.highlightForeground", "list.highlightForeground") ?: super.completionHighlightColor
+
+    override val activeParameterColor: Color
+        get() = color("editorHoverWidget.highlightForeground") ?: completionHighlightColor
+
+    private val semanticStyles = ConcurrentHashMap<Pair<String, Set<String>>, SpanStyle>()
+
+    override fun getStyleForSemanticToken(type: String, modifiers: Set<String>): SpanStyle? {
+        val style = semanticStyles.getOrPut(type to modifiers) { semanticStyleOf(type, modifiers) ?: NO_STYLE }
+        return style.takeIf { it !== NO_STYLE }
+    }
+
+    private fun semanticStyleOf(type: String, modifiers: Set<String>): SpanStyle? {
+        // The rule naming the type beats a wildcard one, then the one naming more of the modifiers
+        val rule = semanticRules
+            .filter { (it.type == "*" || it.type == type) && modifiers.containsAll(it.modifiers) }
+            .maxByOrNull { (if (it.type == type) 100 else 0) + it.modifiers.size }
+        if (rule != null) return partialStyle(rule.foreground, rule.fontStyle)
+
+        val scopes = if (SemanticTokenModifiers.READONLY in modifiers && type == SemanticTokenTypes.VARIABLE) {
+            listOf("variable.other.constant")
+        } else {
+            SEMANTIC_SCOPES[type].orEmpty()
+        }
+        val scopeRule = scopes.firstNotNullOfOrNull { scope -> bestRule(scope) { it.foreground != null } }
+        val fallback = defaultSemanticTokenStyle(type, modifiers)
+        if (scopeRule == null) return fallback
+
+        // The scope's color, with the default decorations for modifiers like deprecated
+        val style = partialStyle(scopeRule.foreground, scopeRule.fontStyle)
+        return fallback?.merge(style) ?: style
+    }
+
+    /**
+     * The value of [key] in the theme's `colors`, e.g. `editorBracketMatch.background`, or null if it has none.
+     */
+    fun getColor(key: String): Color? = colors[key]
+
+    /**
+     * Style of text in [scope], as the theme's `tokenColors` rules resolve it; the default text color if none applies.
+     */
+    fun getStyleForScope(scope: String): SpanStyle = styleOf(listOf(scope))
+
+    private fun color(vararg keys: String): Color? = keys.firstNotNullOfOrNull { colors[it] }
+
+    // Only sets what the rule sets, so the rest of the lexical style shows through
+    private fun partialStyle(foreground: Color?, fontStyle: String?): SpanStyle {
+        if (fontStyle == null) return SpanStyle(color = foreground ?: Color.Unspecified)
+        return SpanStyle(
+            color = foreground ?: Color.Unspecified,
+            fontWeight = if ("bold" in fontStyle) FontWeight.Bold else FontWeight.Normal,
+            fontStyle = if ("italic" in fontStyle) FontStyle.Italic else FontStyle.Normal,
+            textDecoration = decorationOf(fontStyle)
+        )
+    }
+
+    // Foreground and font style are resolved separately, like VS Code does: a rule may set only one of them
+    private fun styleOf(scopes: List<String>): SpanStyle {
+        val foreground = scopes.firstNotNullOfOrNull { scope -> bestRule(scope) { it.foreground != null }?.foreground }
+        val fontStyle = scopes.firstNotNullOfOrNull { scope -> bestRule(scope) { it.fontStyle != null }?.fontStyle }.orEmpty()
+        return SpanStyle(
+            color = foreground ?: defaultTextColor,
+            fontWeight = if ("bold" in fontStyle) FontWeight.Bold else null,
+            fontStyle = if ("italic" in fontStyle) FontStyle.Italic else FontStyle.Normal,
+            textDecoration = decorationOf(fontStyle)
+        )
+    }
+
+    private fun decorationOf(fontStyle: String) = TextDecoration.combine(
+        listOfNotNull(
+            TextDecoration.Underline.takeIf { "underline" in fontStyle },
+            TextDecoration.LineThrough.takeIf { "strikethrough" in fontStyle }
+        )
+    )
+
+    // The rule whose selector matches the most of [scope]; of equally specific ones the last wins
+    private fun bestRule(scope: String, predicate: (TokenColorRule) -> Boolean): TokenColorRule? {
+        var best: TokenColorRule? = null
+        var bestSpecificity = 0
+        for (rule in rules) {
+            if (!predicate(rule)) continue
+            for (selector in rule.selectors) {
+                if (!scope.matchesScope(selector)) continue
+                val specificity = selector.count { it == '.' } + 1
+                if (specificity >= bestSpecificity) {
+                    best = rule
+                    bestSpecificity = specificity
+                }
+            }
+        }
+        return best
+    }
+
+    override fun toString() = "VsCodeTheme($name)"
+
+    /**
+     * A `tokenColors` entry. Selectors are single scopes; ones naming parent scopes, like
+     * `meta.embedded string`, can't match a token type on its own and are left out.
+     */
+    private class TokenColorRule(
+        val selectors: List<String>,
+        val foreground: Color?,
+        val background: Color?,
+        val fontStyle: String?
+    )
+
+    /**
+     * A `semanticTokenColors` entry such as `variable.readonly`, or `*.deprecated` for any type.
+     */
+    private class SemanticTokenRule(
+        val type: String,
+        val modifiers: Set<String>,
+        val foreground: Color?,
+        val fontStyle: String?
+    )
+
+    // What a theme file consists of, gathered across the themes it includes
+    private class Contents {
+        val colors = LinkedHashMap<String, Color>()
+        val rules = ArrayList<TokenColorRule>()
+        val semanticRules = ArrayList<SemanticTokenRule>()
+    }
+
+    companion object {
+        // Scopes each token type stands for, most specific first
+        private val SCOPES = mapOf(
+            TokenType.KEYWORD to listOf("keyword.control", "keyword", "storage.type", "storage"),
+            TokenType.TYPE to listOf("entity.name.type", "support.type", "support.class", "storage.type"),
+            TokenType.STRING to listOf("string.quoted.double", "string"),
+            TokenType.COMMENT to listOf("comment.line", "comment"),
+            TokenType.NUMBER to listOf("constant.numeric"),
+            TokenType.OPERATOR to listOf("keyword.operator"),
+            TokenType.PUNCTUATION to listOf("punctuation"),
+            TokenType.FUNCTION to listOf("entity.name.function", "support.function"),
+            TokenType.IDENTIFIER to listOf("variable.other", "variable"),
+            TokenType.ANNOTATION to listOf("storage.type.annotation", "meta.annotation", "entity.name.type.annotation"),
+            TokenType.MACRO to listOf("entity.name.function.preprocessor", "meta.preprocessor", "keyword.control.directive"),
+            TokenType.ATTRIBUTE to listOf("entity.other.attribute-name", "support.type.property-name"),
+            TokenType.CONST to listOf("variable.other.constant", "constant.other", "constant"),
+            TokenType.STATIC to listOf("variable.other.static", "variable.other.property", "variable")
+        )
+
+        private val NO_STYLE = SpanStyle()
+
+        // The TextMate scopes VS Code falls back to for semantic token types
+        private val SEMANTIC_SCOPES = mapOf(
+            SemanticTokenTypes.NAMESPACE to listOf("entity.name.namespace"),
+            SemanticTokenTypes.TYPE to listOf("entity.name.type", "support.type"),
+            SemanticTokenTypes.CLASS to listOf("entity.name.type.class", "support.class"),
+            SemanticTokenTypes.ENUM to listOf("entity.name.type.enum"),
+            SemanticTokenTypes.INTERFACE to listOf("entity.name.type.interface"),
+            SemanticTokenTypes.STRUCT to listOf("entity.name.type.struct"),
+            SemanticTokenTypes.TYPE_PARAMETER to listOf("entity.name.type.parameter"),
+            SemanticTokenTypes.PARAMETER to listOf("variable.parameter"),
+            SemanticTokenTypes.VARIABLE to listOf("variable.other.readwrite", "entity.name.variable"),
+            SemanticTokenTypes.LOCAL to listOf("variable.other.readwrite", "entity.name.variable"),
+            SemanticTokenTypes.PROPERTY to listOf("variable.other.property"),
+            SemanticTokenTypes.FIELD to listOf("variable.other.property"),
+            SemanticTokenTypes.ENUM_MEMBER to listOf("variable.other.enummember"),
+            SemanticTokenTypes.FUNCTION to listOf("entity.name.function", "support.function"),
+            SemanticTokenTypes.METHOD to listOf("entity.name.function.member", "support.function"),
+            SemanticTokenTypes.MACRO to listOf("entity.name.function.macro"),
+            SemanticTokenTypes.KEYWORD to listOf("keyword"),
+            SemanticTokenTypes.MODIFIER to listOf("storage.modifier"),
+            SemanticTokenTypes.COMMENT to listOf("comment"),
+            SemanticTokenTypes.STRING to listOf("string"),
+            SemanticTokenTypes.NUMBER to listOf("constant.numeric"),
+            SemanticTokenTypes.REGEXP to listOf("constant.regexp"),
+            SemanticTokenTypes.OPERATOR to listOf("keyword.operator"),
+            SemanticTokenTypes.DECORATOR to listOf("entity.name.decorator", "entity.name.function")
+        )
+
+        /**
+         * Reads a theme from the JSON of a VS Code color theme. A theme that `include`s another is read
+         * on top of it, with [resolveInclude] turning the include path into that theme's JSON.
+         */
+        @JvmStatic
+        @JvmOverloads
+        fun fromJson(json: String, resolveInclude: ((String) -> String?)? = null): VsCodeTheme {
+            val contents = Contents()
+            val theme = read(json, resolveInclude, contents, depth = 0)
+
+            val type = theme["type"] as? String
+            val isDark = if (type != null) "light" !in type.lowercase() else contents.colors["editor.background"]?.isDark() ?: true
+            return VsCodeTheme(theme["name"] as? String ?: "Untitled", isDark, contents.colors, contents.rules, contents.semanticRules)
+        }
+
+        @JvmStatic
+        @JvmOverloads
+        fun fromStream(stream: InputStream, resolveInclude: ((String) -> String?)? = null): VsCodeTheme {
+            return fromJson(stream.bufferedReader().use { it.readText() }, resolveInclude)
+        }
+
+        /**
+         * Reads the theme at [path] in the app's assets, e.g. `themes/monokai-color-theme.json`,
+         * along with the themes it includes.
+         */
+        @JvmStatic
+        fun fromAssets(assets: AssetManager, path: String): VsCodeTheme {
+            fun readAsset(assetPath: String) = assets.open(assetPath).bufferedReader().use { it.readText() }
+            val directory = path.substringBeforeLast('/', "")
+            return fromJson(readAsset(path)) { include ->
+                runCatching { readAsset(resolvePath(directory, include)) }.getOrNull()
+            }
+        }
+
+        // Reads [json] into [contents], after the theme it includes
+        private fun read(json: String, resolveInclude: ((String) -> String?)?, contents: Contents, depth: Int): Map<*, *> {
+            val theme = Json.parse(json) as? Map<*, *> ?: throw IllegalArgumentException("Theme must be a JSON object")
+            val include = theme["include"] as? String
+            if (include != null && resolveInclude != null && depth < MAX_INCLUDE_DEPTH) {
+                resolveInclude(include)?.let { read(it, resolveInclude, contents, depth + 1) }
+            }
+
+            (theme["colors"] as? Map<*, *>)?.entries.orEmpty().forEach { (key, value) ->
+                val color = (value as? String)?.let(::parseColor)
+                if (key is String && color != null) contents.colors[key] = color
+            }
+            // Themes converted from `.tmTheme` files call the rules `settings`
+            val tokenColors = theme["tokenColors"] as? List<*> ?: theme["settings"] as? List<*>
+            tokenColors.orEmpty().forEach { entry -> parseRule(entry as? Map<*, *> ?: return@forEach)?.let(contents.rules::add) }
+            (theme["semanticTokenColors"] as? Map<*, *>)?.entries.orEmpty().forEach { (selector, value) ->
+                parseSemanticRule(selector.toString(), value)?.let(contents.semanticRules::add)
+            }
+            return theme
+        }
+
+        // Rules for one language only, like `variable:javascript`, are left out: the theme doesn't know the language
+        private fun parseSemanticRule(selector: String, value: Any?): SemanticTokenRule? {
+            if (':' in selector) return null
+            val parts = selector.trim().split('.')
+            val (foreground, fontStyle) = when (value) {
+                is String -> parseColor(value) to null
+                is Map<*, *> -> {
+                    val flags = listOf("bold", "italic", "underline", "strikethrough").filter { value[it] == true }
+                    val fontStyle = (value["fontStyle"] as? String) ?: flags.joinToString(" ").takeIf { flags.isNotEmpty() }
+                    (value["foreground"] as? String)?.let(::parseColor) to fontStyle
+                }
+                else -> return null
+            }
+            if (foreground == null && fontStyle == null) return null
+            return SemanticTokenRule(parts[0], parts.drop(1).toSet(), foreground, fontStyle)
+        }
+
+        private fun parseRule(entry: Map<*, *>): TokenColorRule? {
+            val settings = entry["settings"] as? Map<*, *> ?: return null
+            val scopes = when (val scope = entry["scope"]) {
+                is String -> scope.split(',')
+                is List<*> -> scope.filterIsInstance<String>().flatMap { it.split(',') }
+                else -> emptyList()
+            }
+            return TokenColorRule(
+                selectors = scopes.map { it.trim() }.filter { it.isNotEmpty() && ' ' !in it },
+                foreground = (settings["foreground"] as? String)?.let(::parseColor),
+                background = (settings["background"] as? String)?.let(::parseColor),
+                fontStyle = settings["fontStyle"] as? String
+            ).takeIf { it.selectors.isNotEmpty() || entry["scope"] == null }
+        }
+
+        /**
+         * Parses `#RGB`, `#RGBA`, `#RRGGBB` and `#RRGGBBAA`; null for anything else.
+         */
+        internal fun parseColor(value: String): Color? {
+            val hex = value.trim().removePrefix("#")
+            if (hex.any { Character.digit(it, 16) < 0 }) return null
+            val full = when (hex.length) {
+                3, 4 -> hex.map { "$it$it" }.joinToString("")
+                6, 8 -> hex
+                else -> return null
+            }
+            val rgb = full.substring(0, 6).toLong(16)
+            val alpha = if (full.length == 8) full.substring(6, 8).toLong(16) else 0xFFL
+            return Color(alpha shl 24 or rgb)
+        }
+
+        private fun Color.isDark() = red * 0.299f + green * 0.587f + blue * 0.114f < 0.5f
+
+        private fun resolvePath(directory: String, relative: String): String {
+            val parts = ArrayList<String>()
+            if (directory.isNotEmpty()) parts.addAll(directory.split('/'))
+            for (part in relative.split('/')) {
+                when (part) {
+                    "", "." -> Unit
+                    ".." -> parts.removeLastOrNull()
+                    else -> parts.add(part)
+                }
+            }
+            return parts.joinToString("/")
+        }
+
+        private const val MAX_INCLUDE_DEPTH = 8
+    }
+}
@@ -0,0 +1,197 @@
+package com.itsvks.code.util
+
+/**
+ * Minimal JSON reader and writer for grammars, themes and snippets, which avoids `org.json`: that one
+ * is only a stub off the device. Objects become [Map]s that keep their key order, arrays [List]s, and
+ * numbers [Long] or [Double].
+ *
+ * Comments and trailing commas are accepted, since VS Code's own JSON files often have them.
+ */
+internal object Json {
+    class ParseException(message: String, val offset: Int) : IllegalArgumentException("$message at offset $offset")
+
+    fun parse(text: String): Any? {
+        val reader = Reader(text)
+        val value = reader.readValue()
+        reader.skipWhitespace()
+        if (reader.position < text.length) throw ParseException("Unexpected '${text[reader.position]}'", reader.position)
+        return value
+    }
+
+    fun stringify(value: Any?): String = StringBuilder().also { write(it, value) }.toString()
+
+    private fun write(out: StringBuilder, value: Any?) {
+        when (value) {
+            null -> out.append("null")
+            is String -> writeString(out, value)
+            is Boolean -> out.append(value)
+            is Double, is Float -> {
+                val number = (value as Number).toDouble()
+                if (number % 1.0 == 0.0 && !number.isInfinite()) out.append(number.toLong()) else out.append(number)
+            }
+
+            is Number -> out.append(value.toLong())
+            is Map<*, *> -> {
+                out.append('{')
+                value.entries.forEachIndexed { i, (key, item) ->
+                    if (i > 0) out.append(',')
+                    writeString(out, key.toString())
+                    out.append(':')
+                    write(out, item)
+                }
+                out.append('}')
+            }
+
+            is Iterable<*> -> {
+                out.append('[')
+                value.forEachIndexed { i, item ->
+                    if (i > 0) out.append(',')
+                    write(out, item)
+                }
+                out.append(']')
+            }
+
+            is Array<*> -> write(out, value.asList())
+            else -> writeString(out, value.toString())
+        }
+    }
+
+    private fun writeString(out: StringBuilder, value: String) {
+        out.append('"')
+        for (char in value) {
+            when (char) {
+                '"' -> out.append("\\\"")
+                '\\' -> out.append("\\\\")
+                '\n' -> out.append("\\n")
+                '\r' -> out.append("\\r")
+                '\t' -> out.append("\\t")
+                else -> if (char < ' ') out.append("\\u%04x".format(char.code)) else out.append(char)
+            }
+        }
+        out.append('"')
+    }
+
+    private class Reader(private val text: String) {
+        var position = 0
+
+        fun readValue(): Any? {
+            skipWhitespace()
+            if (position >= text.length) throw ParseException("Unexpected end of input", position)
+            return when (val char = text[position]) {
+                '{' -> readObject()
+                '[' -> readArray()
+                '"' -> readString()
+                't' -> readLiteral("true", true)
+                'f' -> readLiteral("false", false)
+                'n' -> readLiteral("null", null)
+                else -> if (char == '-' || char.isDigit()) readNumber() else throw ParseException("Unexpected '$char'", position)
+            }
+        }
+
+        private fun readObject(): Map<String, Any?> {
+            val result = LinkedHashMap<String, Any?>()
+            position++
+            while (true) {
+                skipWhitespace()
+                if (peek() == '}') break
+                if (peek() != '"') throw ParseException("Expected a key", position)
+                val key = readString()
+                skipWhitespace()
+                expect(':')
+                result[key] = readValue()
+                skipWhitespace()
+                if (peek() == ',') position++ else break
+            }
+            skipWhitespace()
+            expect('}')
+            return result
+        }
+
+        private fun readArray(): List<Any?> {
+            val result = ArrayList<Any?>()
+            position++
+            while (true) {
+                skipWhitespace()
+                if (peek() == ']') break
+                result.add(readValue())
+                skipWhitespace()
+                if (peek() == ',') position++ else break
+            }
+            skipWhitespace()
+            expect(']')
+            return result
+        }
+
+        private fun readString(): String {
+            val out = StringBuilder()
+            position++
+            while (true) {
+                if (position >= text.length) throw ParseException("Unterminated string", position)
+                when (val char = text[position++]) {
+                    '"' -> return out.toString()
+                    '\\' -> {
+                        if (position >= text.length) throw ParseException("Unterminated string", position)
+                        when (val escaped = text[position++]) {
+                            'n' -> out.append('\n')
+                            't' -> out.append('\t')
+                            'r' -> out.append('\r')
+                            'b' -> out.append('\b')
+                            'f' -> out.append('\u000C')
+                            'u' -> {
+                                val hex = text.substring(position, (position + 4).coerceAtMost(text.length))
+                                val code = hex.takeIf { it.length == 4 }?.toIntOrNull(16)
+                                    ?: throw ParseException("Invalid unicode escape", position)
+                                out.append(code.toChar())
+                                position += 4
+                            }
+
+                            else -> out.append(escaped)
+                        }
+                    }
+
+                    else -> out.append(char)
+                }
+            }
+        }
+
+        private fun readNumber(): Number {
+            val start = position
+            if (peek() == '-') position++
+            while (position < text.length && (text[position].isDigit() || text[position] in ".eE+-")) position++
+            val number = text.substring(start, position)
+            return number.toLongOrNull() ?: number.toDoubleOrNull() ?: throw ParseException("Invalid number '$number'", start)
+        }
+
+        private fun readLiteral(literal: String, value: Any?): Any? {
+            if (!text.startsWith(literal, position)) throw ParseException("Unexpected '${text[position]}'", position)
+            position += literal.length
+            return value
+        }
+
+        private fun peek(): Char? = text.getOrNull(position)
+
+        private fun expect(char: Char) {
+            if (peek() != char) throw ParseException("Expected '$char'", position)
+            position++
+        }
+
+        fun skipWhitespace() {
+            while (position < text.length) {
+                when {
+                    text[position].isWhitespace() -> position++
+                    text.startsWith("//", position) -> {
+                        val end = text.indexOf('\n', position)
+                        position = if (end < 0) text.length else end + 1
+                    }
+
+                    text.startsWith("/*", position) -> {
+                        val end = text.indexOf("*/", position + 2)
+                        position = if (end < 0) text.length else end + 2
+                    }
+
+                    else -> return
+                }
+            }
+        }
+    }
+}
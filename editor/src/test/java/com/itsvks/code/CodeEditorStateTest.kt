@@ -0,0 +1,468 @@
+package com.itsvks.code
+
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.Direction
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertFalse
+import kotlin.test.assertNotEquals
+import kotlin.test.assertNull
+import kotlin.test.assertTrue
+
+class CodeEditorStateTest {
+    @Test
+    fun testLinesFromText() {
+        val state = CodeEditorState("one\ntwo\r\nthree")
+        assertEquals(3, state.lineCount)
+        assertEquals("two", state.getLine(1))
+        assertEquals("one\ntwo\nthree", state.getText())
+    }
+
+    @Test
+    fun testEmptyStateHasOneLine() {
+        val state = CodeEditorState()
+        assertEquals(1, state.lineCount)
+        assertEquals("", state.getLine(0))
+    }
+
+    @Test
+    fun testUpdateInsertRemoveLine() {
+        val state = CodeEditorState(listOf("a", "b", "c"))
+
+        state.updateLine(1, "bb")
+        assertEquals("a\nbb\nc", state.getText())
+
+        state.insertLine(0, "start")
+        state.insertLine(10, "end")
+        assertEquals("start\na\nbb\nc\nend", state.getText())
+
+        state.removeLine(4)
+        state.removeLine(0)
+        assertEquals("a\nbb\nc", state.getText())
+    }
+
+    @Test
+    fun testUpdateLineWithLineBreakSplitsLine() {
+        val state = CodeEditorState("ab")
+        state.updateLine(0, "a\nb")
+        assertEquals(2, state.lineCount)
+        assertEquals("b", state.getLine(1))
+    }
+
+    @Test
+    fun testInsertAndDeleteAcrossLines() {
+        val state = CodeEditorState("hello\nworld")
+        state.insert(CursorPosition(1, 0), "big ")
+        assertEquals("hello\nbig world", state.getText())
+
+        state.delete(CursorPosition(0, 5)..CursorPosition(1, 4))
+        assertEquals("helloworld", state.getText())
+    }
+
+    @Test
+    fun testSnapshotsAreImmutable() {
+        val state = CodeEditorState("abc")
+        val before = state.snapshot
+        state.insert(CursorPosition(0, 3), "def")
+
+        assertEquals("abc", before.getText())
+        assertEquals("abcdef", state.snapshot.getText())
+        assertNotEquals(before.version, state.snapshot.version)
+    }
+
+    @Test
+    fun testFoldsShiftWithEdits() {
+        val state = CodeEditorState("fun a() {\n    1\n    2\n}")
+        assertEquals(0, state.foldableRanges.value.single().startLine)
+
+        state.toggleFold(0)
+        state.insertLine(0, "// header")
+
+        assertEquals(1, state.foldableRanges.value.single().startLine)
+        assertEquals(setOf(1), state.foldedLines.value)
+    }
+
+    @Test
+    fun testUndoRedoSingleEdit() {
+        val state = CodeEditorState("hello")
+        assertFalse(state.canUndo.value)
+
+        state.setCursor(CursorPosition(0, 5))
+        state.insertText("\nworld")
+        assertTrue(state.canUndo.value)
+
+        assertEquals(CursorRange.collapsed(CursorPosition(0, 5)), state.undo())
+        assertEquals("hello", state.getText())
+        assertTrue(state.canRedo.value)
+
+        state.redo()
+        assertEquals("hello\nworld", state.getText())
+        assertEquals(CursorPosition(1, 5), state.cursor)
+        assertFalse(state.canRedo.value)
+    }
+
+    @Test
+    fun testConsecutiveTypingIsMerged() {
+        val state = CodeEditorState("")
+        "abc".forEachIndexed { i, c -> state.insert(CursorPosition(0, i), c.toString()) }
+
+        state.undo()
+        assertEquals("", state.getText())
+        assertFalse(state.canUndo.value)
+    }
+
+    @Test
+    fun testTransactionIsOneStep() {
+        val state = CodeEditorState("a\nb")
+        state.transaction {
+            insertLine(0, "first")
+            updateLine(2, "changed")
+        }
+
+        assertEquals("first\na\nchanged", state.getText())
+        state.undo()
+        assertEquals("a\nb", state.getText())
+        assertNull(state.undo())
+    }
+
+    @Test
+    fun testNewEditClearsRedo() {
+        val state = CodeEditorState("x")
+        state.updateLine(0, "y")
+        state.undo()
+        state.updateLine(0, "z")
+
+        assertFalse(state.canRedo.value)
+        assertNull(state.redo())
+    }
+
+    @Test
+    fun testTypingMovesCaret() {
+        val state = CodeEditorState("")
+        "ab\nc".forEach { state.insertText(it.toString()) }
+
+        assertEquals("ab\nc", state.getText())
+        assertEquals(CursorPosition(1, 1), state.cursor)
+    }
+
+    @Test
+    fun testSelectionShiftsWithEditsBeforeIt() {
+        val state = CodeEditorState("abc\ndef")
+        state.setSelection(CursorPosition(1, 1), CursorPosition(1, 3))
+        state.insertLine(0, "new")
+
+        assertEquals(CursorRange(CursorPosition(2, 1), CursorPosition(2, 3)), state.selection.value)
+        assertEquals("ef", state.selectedText)
+    }
+
+    @Test
+    fun testInsertTextReplacesSelectionAcrossLines() {
+        val state = CodeEditorState("one\ntwo\nthree")
+        state.setSelection(CursorPosition(0, 1), CursorPosition(2, 2))
+        state.insertText("X")
+
+        assertEquals("oXree", state.getText())
+        assertTrue(state.selection.value.isCollapsed)
+        assertEquals(CursorPosition(0, 2), state.cursor)
+    }
+
+    @Test
+    fun testMoveCursorKeepsPreferredColumn() {
+        val state = CodeEditorState("long line\nab\nanother line")
+        state.setCursor(CursorPosition(0, 7))
+
+        state.moveCursor(Direction.DOWN)
+        assertEquals(CursorPosition(1, 2), state.cursor)
+        state.moveCursor(Direction.DOWN)
+        assertEquals(CursorPosition(2, 7), state.cursor)
+
+        state.moveCursor(Direction.LEFT, extend = true)
+        assertEquals(CursorRange(CursorPosition(2, 7), CursorPosition(2, 6)), state.selection.value)
+        state.moveCursor(Direction.RIGHT)
+        assertEquals(CursorPosition(2, 7), state.cursor)
+    }
+
+    @Test
+    fun testMoveCursorWrapsAcrossLines() {
+        val state = CodeEditorState("ab\ncd")
+        state.setCursor(CursorPosition(0, 2))
+        state.moveCursor(Direction.RIGHT)
+        assertEquals(CursorPosition(1, 0), state.cursor)
+        state.moveCursor(Direction.LEFT)
+        assertEquals(CursorPosition(0, 2), state.cursor)
+    }
+
+    @Test
+    fun testDeleteBackwardAndForward() {
+        val state = CodeEditorState("ab\ncd")
+        state.setCursor(CursorPosition(1, 0))
+        state.deleteBackward()
+        assertEquals("abcd", state.getText())
+        assertEquals(CursorPosition(0, 2), state.cursor)
+
+        state.deleteForward()
+        assertEquals("abd", state.getText())
+    }
+
+    @Test
+    fun testTypingAtMultipleCaretsIsOneUndoStep() {
+        val state = CodeEditorState("ab\ncd\nef")
+        state.setCursor(CursorPosition(0, 1))
+        state.addCursorBelow()
+        state.addCursorBelow()
+        assertEquals(3, state.selections.value.size)
+
+        "xy".forEach { state.typeText(it.toString()) }
+        assertEquals("axyb\ncxyd\nexyf", state.getText())
+        assertEquals(listOf(0, 1, 2).map { CursorRange.collapsed(CursorPosition(it, 3)) }, state.selections.value)
+
+        state.deleteBackward()
+        assertEquals("axb\ncxd\nexf", state.getText())
+
+        state.undo()
+        assertEquals("axyb\ncxyd\nexyf", state.getText())
+        state.undo()
+        assertEquals("ab\ncd\nef", state.getText())
+        assertEquals(3, state.selections.value.size)
+    }
+
+    @Test
+    fun testCaretsOnSamePositionAreMerged() {
+        val state = CodeEditorState("abc")
+        state.setCursor(CursorPosition(0, 1))
+        state.addCursor(CursorPosition(0, 2))
+        state.moveCursor(Direction.LEFT)
+        state.moveCursor(Direction.LEFT)
+
+        assertEquals(listOf(CursorRange.Zero), state.selections.value)
+        assertFalse(state.hasMultipleSelections)
+    }
+
+    @Test
+    fun testAddNextOccurrence() {
+        val state = CodeEditorState("foo bar foo\nfoo")
+        state.setCursor(CursorPosition(0, 1))
+
+        state.addNextOccurrence()
+        assertEquals("foo", state.selectedText)
+        state.addNextOccurrence()
+        state.addNextOccurrence()
+        assertEquals(3, state.selections.value.size)
+
+        state.insertText("baz")
+        assertEquals("baz bar baz\nbaz", state.getText())
+    }
+
+    @Test
+    fun testSplitSelectionIntoLines() {
+        val state = CodeEditorState("one\ntwo\nthree")
+        state.setSelection(CursorPosition(0, 1), CursorPosition(2, 2))
+        state.splitSelectionIntoLines()
+
+        assertEquals(
+            listOf(
+                CursorRange(CursorPosition(0, 1), CursorPosition(0, 3)),
+                CursorRange(CursorPosition(1, 0), CursorPosition(1, 3)),
+                CursorRange(CursorPosition(2, 0), CursorPosition(2, 2))
+            ),
+            state.selections.value
+        )
+    }
+
+    @Test
+    fun testPasteDistributesLinesOverCarets() {
+        val state = CodeEditorState("a\nb")
+        state.setCursor(CursorPosition(0, 1))
+        state.addCursorBelow()
+        state.paste("1\n2")
+
+        assertEquals("a1\nb2", state.getText())
+    }
+
+    @Test
+    fun testBracketAutoPairAndSkipOver() {
+        val state = CodeEditorState("")
+        state.typeText("(")
+        assertEquals("()", state.getText())
+        assertEquals(CursorPosition(0, 1), state.cursor)
+
+        state.typeText(")")
+        assertEquals("()", state.getText())
+        assertEquals(CursorPosition(0, 2), state.cursor)
+
+        state.setCursor(CursorPosition(0, 1))
+        state.deleteBackward()
+        assertEquals("", state.getText())
+
+        state.insertText("word")
+        state.setSelection(CursorPosition(0, 0), CursorPosition(0, 4))
+        state.typeText("[")
+        assertEquals("[word]", state.getText())
+        assertEquals("word", state.selectedText)
+    }
+
+    @Test
+    fun testNewLineKeepsIndentation() {
+        val state = CodeEditorState("    if (x) {}")
+        state.setCursor(CursorPosition(0, 12))
+        state.newLine()
+
+        assertEquals("    if (x) {\n        \n    }", state.getText())
+        assertEquals(CursorPosition(1, 8), state.cursor)
+
+        state.setCursor(CursorPosition(2, 5))
+        state.newLine()
+        assertEquals("    ", state.getLine(3))
+    }
+
+    @Test
+    fun testComposingTextIsReplacedUntilCommitted() {
+        val state = CodeEditorState("val ")
+        state.setCursor(CursorPosition(0, 4))
+
+        state.setComposingText("h")
+        state.setComposingText("hel")
+        assertEquals("val hel", state.getText())
+        assertEquals(CursorRange(CursorPosition(0, 4), CursorPosition(0, 7)), state.composition.value)
+
+        state.commitText("hello")
+        assertEquals("val hello", state.getText())
+        assertNull(state.composition.value)
+        assertEquals(CursorPosition(0, 9), state.cursor)
+    }
+
+    @Test
+    fun testComposingTextWithCaretOutsideDocument() {
+        val state = CodeEditorState("val ")
+        state.setCursor(CursorPosition(0, 4))
+
+        state.setComposingText("abc", 100)
+        assertEquals(CursorRange(CursorPosition(0, 4), CursorPosition(0, 7)), state.composition.value)
+        assertEquals(CursorPosition(0, 7), state.cursor)
+
+        state.setComposingText("ab", -100)
+        assertEquals("val ab", state.getText())
+        assertEquals(CursorRange(CursorPosition(0, 4), CursorPosition(0, 6)), state.composition.value)
+        assertEquals(CursorPosition(0, 0), state.cursor)
+    }
+
+    @Test
+    fun testCompositionAtEveryCaretIsOneUndoStep() {
+        val state = CodeEditorState("a\nb")
+        state.setCursor(CursorPosition(0, 1))
+        state.addCursor(CursorPosition(1, 1))
+
+        state.setComposingText("h")
+        state.setComposingText("he")
+        state.setComposingText("hey")
+        assertEquals("ahey\nbhey", state.getText())
+        assertEquals(CursorRange(CursorPosition(1, 1), CursorPosition(1, 4)), state.composition.value)
+
+        state.commitText("hello")
+        assertEquals("ahello\nbhello", state.getText())
+        state.setComposingText("x")
+        state.finishComposingText()
+        assertEquals("ahellox\nbhellox", state.getText())
+
+        state.undo()
+        assertEquals("ahello\nbhello", state.getText())
+        state.undo()
+        assertEquals("a\nb", state.getText())
+    }
+
+    @Test
+    fun testComposingRegionFollowsEdits() {
+        val state = CodeEditorState("ab word")
+        state.setComposingRegion(3, 7)
+        state.insert(CursorPosition(0, 0), "xx")
+        assertEquals(CursorRange(CursorPosition(0, 5), CursorPosition(0, 9)), state.composition.value)
+
+        state.delete(CursorPosition(0, 4)..CursorPosition(0, 9))
+        assertNull(state.composition.value)
+    }
+
+    @Test
+    fun testDeleteSurroundingText() {
+        val state = CodeEditorState("abcdef")
+        state.setCursor(CursorPosition(0, 3))
+        state.deleteSurroundingText(2, 1)
+
+        assertEquals("aef", state.getText())
+        assertEquals(CursorPosition(0, 1), state.cursor)
+    }
+
+    @Test
+    fun testMoveWord() {
+        val state = CodeEditorState("foo.bar  baz\nnext")
+        state.moveWord(Direction.RIGHT)
+        assertEquals(CursorPosition(0, 3), state.cursor)
+        state.moveWord(Direction.RIGHT)
+        assertEquals(CursorPosition(0, 4), state.cursor)
+        state.moveWord(Direction.RIGHT)
+        state.moveWord(Direction.RIGHT)
+        assertEquals(CursorPosition(0, 12), state.cursor)
+        state.moveWord(Direction.RIGHT)
+        assertEquals(CursorPosition(1, 0), state.cursor)
+
+        state.moveWord(Direction.LEFT, extend = true)
+        state.moveWord(Direction.LEFT, extend = true)
+        assertEquals("baz\n", state.selectedText)
+    }
+
+    @Test
+    fun testSmartHome() {
+        val state = CodeEditorState("    code")
+        state.setCursor(CursorPosition(0, 6))
+        state.moveToLineStart()
+        assertEquals(CursorPosition(0, 4), state.cursor)
+        state.moveToLineStart()
+        assertEquals(CursorPosition(0, 0), state.cursor)
+        state.moveToLineEnd(extend = true)
+        assertEquals("    code", state.selectedText)
+    }
+
+    @Test
+    fun testDeleteWordBackward() {
+        val state = CodeEditorState("val name = value")
+        state.moveToDocumentEnd()
+        state.deleteWordBackward()
+        assertEquals("val name = ", state.getText())
+        state.deleteWordBackward()
+        assertEquals("val name ", state.getText())
+    }
+
+    @Test
+    fun testLineOperations() {
+        val state = CodeEditorState("a\nb\nc")
+        state.setCursor(CursorPosition(0, 1))
+
+        state.moveSelectedLines(Direction.DOWN)
+        assertEquals("b\na\nc", state.getText())
+        assertEquals(CursorPosition(1, 1), state.cursor)
+        state.moveSelectedLines(Direction.DOWN)
+        assertEquals("b\nc\na", state.getText())
+        state.moveSelectedLines(Direction.DOWN)
+        assertEquals("b\nc\na", state.getText())
+
+        state.duplicateLines()
+        assertEquals("b\nc\na\na", state.getText())
+        assertEquals(CursorPosition(3, 1), state.cursor)
+
+        state.setSelection(CursorPosition(0, 0), CursorPosition(2, 0))
+        state.deleteLines()
+        assertEquals("a\na", state.getText())
+
+        state.undo()
+        assertEquals("b\nc\na\na", state.getText())
+    }
+
+    @Test
+    fun testMovePage() {
+        val state = CodeEditorState((1..20).joinToString("\n") { "line $it" })
+        state.movePage(Direction.DOWN, 5)
+        assertEquals(CursorPosition(5, 0), state.cursor)
+        state.movePage(Direction.UP, 10, extend = true)
+        assertEquals(CursorRange(CursorPosition(5, 0), CursorPosition(0, 0)), state.selection.value)
+    }
+}
@@ -0,0 +1,90 @@
+package com.itsvks.code.completion
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.core.TextEdit
+import com.itsvks.code.language.KotlinLanguage
+import kotlinx.coroutines.runBlocking
+import kotlin.test.Test
+import kotlin.test.assertContentEquals
+import kotlin.test.assertEquals
+import kotlin.test.assertNull
+import kotlin.test.assertTrue
+
+class CompletionTest {
+    @Test
+    fun testFuzzyMatchesPreferWordStartsAndRuns() {
+        assertContentEquals(intArrayOf(0, 3), FuzzyMatcher.match("fb", "fooBar")?.matchedIndices)
+        assertNull(FuzzyMatcher.match("fb", "bar"))
+
+        val ranked = FuzzyMatcher.rank(listOf(CompletionItem("substring"), CompletionItem("String"), CompletionItem("sort")), "str")
+        assertEquals(listOf("String", "substring"), ranked.map { it.label })
+    }
+
+    @Test
+    fun testTypingAWordCompletesKeywordsAndWordsOfTheDocument() = runBlocking {
+        val state = CodeEditorState("val counter = 1\n", KotlinLanguage)
+        state.setCursor(CursorPosition(1, 0))
+        state.typeText("c")
+        state.typeText("o")
+        assertEquals(CompletionRequest(CursorPosition(1, 0)), state.completion.request.value)
+
+        state.completion.refresh()
+        val labels = state.completion.items.value.map { it.label }
+        assertTrue("counter" in labels && "const" in labels && "continue" in labels)
+        assertTrue("val" !in labels)
+
+        assertTrue(state.completion.accept(labels.indexOf("counter")))
+        assertEquals("counter", state.getLine(1))
+        assertEquals(CursorPosition(1, 7), state.cursor)
+        assertNull(state.completion.request.value)
+    }
+
+    @Test
+    fun testLeavingTheWordClosesCompletion() = runBlocking {
+        val state = CodeEditorState("val counter = 1\n", KotlinLanguage)
+        state.setCursor(CursorPosition(1, 0))
+        state.typeText("c")
+        state.typeText(" ")
+        assertNull(state.completion.request.value)
+
+        state.typeText("c")
+        state.setCursor(CursorPosition(0, 0))
+        state.completion.refresh()
+        assertNull(state.completion.request.value)
+    }
+
+    @Test
+    fun testTriggerCharsOpenCompletionAndItemsMayEditElsewhere() = runBlocking {
+        val state = CodeEditorState("val s = \"\"\ns", KotlinLanguage)
+        state.completion.providers = listOf(object : CompletionProvider {
+            override val triggerCharacters = setOf('.')
+
+            override suspend fun provideCompletions(context: CompletionContext) = listOf(
+                CompletionItem("length", CompletionItemKind.PROPERTY),
+                CompletionItem(
+                    "trimIndent",
+                    CompletionItemKind.METHOD,
+                    insertText = "trimIndent()",
+                    additionalEdits = listOf(TextEdit(CursorRange(CursorPosition(0, 0), CursorPosition(0, 0)), "import x\n"))
+                )
+            )
+        })
+        state.setCursor(CursorPosition(1, 1))
+        state.typeText(".")
+        state.completion.refresh()
+        assertEquals(listOf("length", "trimIndent"), state.completion.items.value.map { it.label })
+
+        state.typeText("t")
+        state.completion.refresh()
+        assertEquals("trimIndent", state.completion.items.value.first().label)
+
+        assertTrue(state.completion.accept())
+        assertEquals("import x\nval s = \"\"\ns.trimIndent()", state.getText())
+        assertEquals(CursorPosition(2, 14), state.cursor)
+
+        state.undo()
+        assertEquals("val s = \"\"\ns.t", state.getText())
+    }
+}
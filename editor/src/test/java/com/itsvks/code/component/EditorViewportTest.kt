@@ -0,0 +1,45 @@
+package com.itsvks.code.component
+
+import kotlin.test.Test
+import kotlin.test.assertEquals
+
+class EditorViewportTest {
+    private val rowHeight = { _: Int -> 10f }
+
+    @Test
+    fun testScrollByMovesAcrossRows() {
+        val viewport = EditorViewport()
+        assertEquals(25f, viewport.scrollBy(25f, 100, rowHeight))
+        assertEquals(2, viewport.firstVisibleRow)
+        assertEquals(5f, viewport.firstRowOffset)
+
+        viewport.scrollBy(-17f, 100, rowHeight)
+        assertEquals(0, viewport.firstVisibleRow)
+        assertEquals(8f, viewport.firstRowOffset)
+    }
+
+    @Test
+    fun testScrollByStopsAtEdges() {
+        val viewport = EditorViewport()
+        assertEquals(0f, viewport.scrollBy(-10f, 100, rowHeight))
+
+        assertEquals(40f, viewport.scrollBy(1000f, 5, rowHeight))
+        assertEquals(4, viewport.firstVisibleRow)
+        assertEquals(0f, viewport.firstRowOffset)
+    }
+
+    @Test
+    fun testEnsureRowVisible() {
+        val viewport = EditorViewport()
+        viewport.ensureRowVisible(12, 100, 50f, rowHeight)
+        // Row 12 ends at 130, so the viewport has to start at 80
+        assertEquals(8, viewport.firstVisibleRow)
+        assertEquals(0f, viewport.firstRowOffset)
+
+        viewport.ensureRowVisible(90, 100, 50f, rowHeight)
+        assertEquals(86, viewport.firstVisibleRow)
+
+        viewport.ensureRowVisible(3, 100, 50f, rowHeight)
+        assertEquals(3, viewport.firstVisibleRow)
+    }
+}
@@ -0,0 +1,59 @@
+package com.itsvks.code.diagnostics
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertFalse
+import kotlin.test.assertTrue
+
+class EditorDiagnosticsTest {
+    private fun range(line: Int, start: Int, end: Int) = CursorRange(CursorPosition(line, start), CursorPosition(line, end))
+
+    @Test
+    fun testDiagnosticsMoveWithTheTextTheyWereReportedFor() {
+        val state = CodeEditorState("val a = 1\nval b = a")
+        state.diagnostics.set(
+            listOf(
+                Diagnostic(range(1, 8, 9), DiagnosticSeverity.ERROR, "Unresolved reference"),
+                Diagnostic(range(0, 4, 5), DiagnosticSeverity.WARNING, "Unused variable", source = "kotlin", code = "UNUSED")
+            )
+        )
+        assertEquals(listOf("Unused variable", "Unresolved reference"), state.diagnostics.items.value.map { it.message })
+
+        state.insert(CursorPosition(0, 0), "// c\n")
+        assertEquals(listOf(range(1, 4, 5), range(2, 8, 9)), state.diagnostics.items.value.map { it.range })
+
+        // Typing at the end of a range doesn't extend it
+        state.insert(CursorPosition(1, 5), "z")
+        assertEquals(range(1, 4, 5), state.diagnostics.items.value[0].range)
+
+        state.delete(range(2, 8, 9))
+        assertEquals(range(2, 8, 8), state.diagnostics.items.value[1].range)
+        assertEquals(listOf("Unused variable"), state.diagnostics.diagnosticsOnLine(1).map { it.message })
+
+        state.setText("x")
+        assertTrue(state.diagnostics.items.value.isEmpty())
+    }
+
+    @Test
+    fun testProblemsAreVisitedInOrderAndShown() {
+        val state = CodeEditorState("val a = 1\nval b = a")
+        val warning = Diagnostic(range(0, 4, 5), DiagnosticSeverity.WARNING, "Unused variable")
+        val error = Diagnostic(range(1, 8, 9), DiagnosticSeverity.ERROR, "Unresolved reference")
+        state.diagnostics.set(listOf(error, warning))
+
+        assertEquals(warning, state.diagnostics.goToNext())
+        assertEquals(CursorPosition(0, 4), state.cursor)
+        assertEquals(listOf(warning), state.diagnostics.shown.value)
+        assertEquals(error, state.diagnostics.goToNext())
+        assertEquals(warning, state.diagnostics.goToNext())
+        assertEquals(error, state.diagnostics.goToPrevious())
+
+        assertFalse(state.diagnostics.showAt(CursorPosition(1, 0)))
+        assertTrue(state.diagnostics.shown.value.isEmpty())
+        assertTrue(state.diagnostics.showAt(CursorPosition(1, 9)))
+        assertEquals(listOf(error), state.diagnostics.shown.value)
+    }
+}
@@ -0,0 +1,178 @@
+package com.itsvks.code.folding
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.FoldableRange
+import com.itsvks.code.core.Rope
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.language.JavaScriptLanguage
+import com.itsvks.code.language.KotlinLanguage
+import com.itsvks.code.language.PythonLanguage
+import com.itsvks.code.syntax.LanguageBasedSyntaxHighlighter
+import com.itsvks.code.syntax.LexerState
+import com.itsvks.code.syntax.LineHighlight
+import com.itsvks.code.syntax.SyntaxHighlighter
+import kotlinx.coroutines.runBlocking
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertFalse
+import kotlin.test.assertTrue
+
+class FoldingTest {
+    @Test
+    fun testBracketsInStringsAndCommentsAreNotFolded() {
+        val text = listOf(
+            "function a() {",
+            "  const s = \"{\";",
+            "  // }",
+            "  return [",
+            "    1,",
+            "    2",
+            "  ];",
+            "}"
+        ).joinToString("\n")
+        val expected = listOf(FoldableRange(0, 7), FoldableRange(3, 6, FoldableRange.BRACKETS))
+
+        // By lexing, and from the syntax tree
+        val context = FoldingContext(TextSnapshot(Rope.fromString(text), 0), JavaScriptLanguage)
+        assertEquals(expected, BraceFoldingProvider().provideFoldingRanges(context).sortedBy { it.startLine })
+        assertEquals(expected, CodeEditorState(text, JavaScriptLanguage).foldableRanges.value)
+    }
+
+    @Test
+    fun testLinesAreLexedFromKnownStates() {
+        val lines = List(100) { if (it == 50) "/* {" else "val x$it = 1" }
+        val snapshot = TextSnapshot(Rope.fromString(lines.joinToString("\n")), 0)
+        val highlighter = LanguageBasedSyntaxHighlighter(KotlinLanguage)
+        val states = lines.runningFold(highlighter.initialState) { state, line -> highlighter.highlightLine(line, state).endState }.drop(1)
+
+        var lexed = 0
+        val counting = object : SyntaxHighlighter by highlighter {
+            override fun highlightLine(text: String, state: LexerState): LineHighlight {
+                lexed++
+                return highlighter.highlightLine(text, state)
+            }
+        }
+        // Only the line itself is lexed, starting in the state the editor knows line 49 ends in
+        val context = FoldingContext(snapshot, KotlinLanguage, highlighter = counting, lineStates = states.take(50))
+        assertFalse(context.isCode(50, 3))
+        assertEquals(1, lexed)
+    }
+
+    @Test
+    fun testPythonFoldsByIndentation() {
+        val text = listOf(
+            "import os",
+            "import sys",
+            "",
+            "def a():",
+            "    if x:",
+            "        pass",
+            "",
+            "    return 1",
+            "",
+            "print(a())"
+        ).joinToString("\n")
+        assertEquals(
+            listOf(
+                FoldableRange(0, 1, FoldableRange.IMPORTS),
+                FoldableRange(3, 7, FoldableRange.INDENT),
+                FoldableRange(4, 5, FoldableRange.INDENT)
+            ),
+            CodeEditorState(text, PythonLanguage).foldableRanges.value
+        )
+    }
+
+    @Test
+    fun testCommentBlocksAndRegionsFold() {
+        val text = listOf(
+            "// region Helpers",
+            "/**",
+            " * Adds.",
+            " */",
+            "fun add(a: Int, b: Int) = a + b",
+            "// endregion",
+            "// one",
+            "// two",
+            "val s = \"\"\"",
+            "// region",
+            "\"\"\""
+        ).joinToString("\n")
+        assertEquals(
+            listOf(
+                FoldableRange(0, 5, FoldableRange.REGION),
+                FoldableRange(1, 3, FoldableRange.COMMENT),
+                FoldableRange(6, 7, FoldableRange.COMMENT)
+            ),
+            CodeEditorState(text, KotlinLanguage).foldableRanges.value
+        )
+    }
+
+    @Test
+    fun testFoldsByLevelAndRecursively() {
+        val text = listOf(
+            "function a() {",
+            "  if (x) {",
+            "    y();",
+            "    z();",
+            "  }",
+            "}",
+            "function b() {",
+            "  return [",
+            "    1,",
+            "  ];",
+            "}"
+        ).joinToString("\n")
+        val state = CodeEditorState(text, JavaScriptLanguage)
+        assertEquals(listOf(0, 1, 6, 7), state.foldableRanges.value.map { it.startLine })
+
+        state.foldLevel(2)
+        assertEquals(setOf(1, 7), state.foldedLines.value)
+
+        state.unfoldAll()
+        state.foldRecursively(0)
+        assertEquals(setOf(0, 1), state.foldedLines.value)
+        state.unfoldRecursively(1)
+        assertEquals(setOf(0), state.foldedLines.value)
+
+        state.foldAll()
+        assertEquals(setOf(0, 1, 6, 7), state.foldedLines.value)
+
+        // The block around the caret stays open
+        state.unfoldAll()
+        state.setCursor(CursorPosition(2, 0))
+        state.foldLevel(1)
+        assertEquals(setOf(6), state.foldedLines.value)
+    }
+
+    @Test
+    fun testFoldsMoveWithTheirFirstLineAndCanBeRestored() = runBlocking {
+        val text = listOf(
+            "function a() {",
+            "  if (x) {",
+            "    y();",
+            "  }",
+            "}",
+            "function b() {",
+            "  z();",
+            "}"
+        ).joinToString("\n")
+        val state = CodeEditorState(text, JavaScriptLanguage)
+        state.toggleFold(1)
+        state.toggleFold(5)
+
+        // Above, splitting the first line before its end, and inside
+        state.insert(CursorPosition(0, 0), "// a\n")
+        state.insert(CursorPosition(2, 2), "\n")
+        state.insert(CursorPosition(4, 0), "w();\n")
+        assertEquals(setOf(3, 8), state.foldedLines.value)
+        assertTrue(state.foldableRanges.value.map { it.startLine }.containsAll(listOf(3, 8)))
+        state.refreshFoldableRanges()
+        assertEquals(setOf(3, 8), state.foldedLines.value)
+
+        val saved = FoldState.fromJson(state.saveFolds().toJson())
+        val reopened = CodeEditorState("// more\n" + state.getText(), JavaScriptLanguage)
+        reopened.restoreFolds(saved)
+        assertEquals(setOf(4, 9), reopened.foldedLines.value)
+    }
+}
@@ -0,0 +1,68 @@
+package com.itsvks.code.hover
+
+import androidx.compose.ui.text.font.FontWeight
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.theme.VsCodeDarkTheme
+import kotlinx.coroutines.runBlocking
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertIs
+import kotlin.test.assertNull
+import kotlin.test.assertTrue
+
+class HoverTest {
+    @Test
+    fun testMarkdownIsSplitIntoBlocksWithInlineStyles() {
+        val blocks = parseMarkdown(
+            """
+            ```kotlin
+            val a = 1
+            ```
+            ---
+            **Returns** the `max` of
+            snake_case values
+            - first
+            """.trimIndent(),
+            VsCodeDarkTheme
+        )
+        assertEquals(4, blocks.size)
+
+        val code = assertIs<MarkdownBlock.Code>(blocks[0])
+        assertEquals("val a = 1", code.text.text)
+        assertTrue(code.text.spanStyles.isNotEmpty())
+        assertEquals(MarkdownBlock.Rule, blocks[1])
+
+        val paragraph = assertIs<MarkdownBlock.Text>(blocks[2])
+        assertEquals("Returns the max of snake_case values", paragraph.text.text)
+        assertTrue(paragraph.text.spanStyles.any { it.item.fontWeight == FontWeight.Bold && it.start == 0 && it.end == 7 })
+        assertEquals("• first", assertIs<MarkdownBlock.Text>(blocks[3]).text.text)
+    }
+
+    @Test
+    fun testHoversAreAskedForAndClosedByMovingTheCaret() = runBlocking {
+        val state = CodeEditorState("val count = 1\ncount")
+        state.hover.providers = listOf(
+            object : HoverProvider {
+                override suspend fun provideHover(context: HoverContext): Hover? {
+                    val word = context.snapshot.wordRangeAt(context.position) ?: return null
+                    return Hover("`${context.snapshot.getText(word)}`: Int", word)
+                }
+            },
+            object : HoverProvider {
+                override suspend fun provideHover(context: HoverContext): Hover = error("Server crashed")
+            }
+        )
+        state.hover.show(CursorPosition(1, 2))
+        state.hover.refresh()
+        val word = CursorRange(CursorPosition(1, 0), CursorPosition(1, 5))
+        assertEquals(Hover("`count`: Int", word), state.hover.hover.value)
+        assertTrue(state.hover.isShowingAt(CursorPosition(1, 4)))
+
+        state.setCursor(CursorPosition(0, 0))
+        state.hover.refresh()
+        assertNull(state.hover.hover.value)
+        assertNull(state.hover.request.value)
+    }
+}
@@ -0,0 +1,78 @@
+package com.itsvks.code.input
+
+import androidx.compose.ui.input.key.Key
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertNull
+
+class KeymapTest {
+    private class FakeClipboard(var text: String? = null) : EditorClipboard {
+        override fun getText() = text
+
+        override fun setText(text: String) {
+            this.text = text
+        }
+    }
+
+    private fun CodeEditorState.press(shortcut: KeyShortcut, clipboard: EditorClipboard) {
+        Keymap.Default[shortcut]!!.execute(EditorCommandContext(this, 10, clipboard))
+    }
+
+    @Test
+    fun testCopyCutPaste() {
+        val state = CodeEditorState("hello world")
+        val clipboard = FakeClipboard()
+
+        // Nothing selected leaves the clipboard alone
+        state.press(KeyShortcut(Key.C, ctrl = true), clipboard)
+        assertNull(clipboard.text)
+
+        state.setSelection(CursorPosition(0, 0), CursorPosition(0, 5))
+        state.press(KeyShortcut(Key.C, ctrl = true), clipboard)
+        assertEquals("hello", clipboard.text)
+        assertEquals("hello world", state.getText())
+
+        state.setSelection(CursorPosition(0, 5), CursorPosition(0, 11))
+        state.press(KeyShortcut(Key.X, ctrl = true), clipboard)
+        assertEquals(" world", clipboard.text)
+        assertEquals("hello", state.getText())
+
+        state.setCursor(CursorPosition(0, 0))
+        state.press(KeyShortcut(Key.V, ctrl = true), clipboard)
+        assertEquals(" worldhello", state.getText())
+        assertEquals(CursorPosition(0, 6), state.cursor)
+
+        state.undo()
+        assertEquals("hello", state.getText())
+    }
+
+    @Test
+    fun testCopyAndPasteWithMultipleCursors() {
+        val state = CodeEditorState("one\none")
+        val clipboard = FakeClipboard()
+        state.setSelection(CursorPosition(0, 0), CursorPosition(0, 3))
+        state.addNextOccurrence()
+        state.press(KeyShortcut(Key.C, ctrl = true), clipboard)
+        assertEquals("one\none", clipboard.text)
+
+        // One line per caret
+        state.press(KeyShortcut(Key.V, ctrl = true), FakeClipboard("1\n2"))
+        assertEquals("1\n2", state.getText())
+    }
+
+    @Test
+    fun testTabIndentsSelectedLines() {
+        val state = CodeEditorState("if (x) {\n\ny()\n}")
+        val clipboard = FakeClipboard()
+
+        state.setSelection(CursorPosition(0, 2), CursorPosition(2, 1))
+        state.press(KeyShortcut(Key.Tab), clipboard)
+        assertEquals("    if (x) {\n\n    y()\n}", state.getText())
+
+        state.setCursor(CursorPosition(3, 0))
+        state.press(KeyShortcut(Key.Tab), clipboard)
+        assertEquals("    if (x) {\n\n    y()\n    }", state.getText())
+    }
+}
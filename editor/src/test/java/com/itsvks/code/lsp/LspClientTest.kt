@@ -0,0 +1,160 @@
+package com.itsvks.code.lsp
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.diagnostics.DiagnosticSeverity
+import com.itsvks.code.hover.Hover
+import com.itsvks.code.syntax.SemanticToken
+import kotlinx.coroutines.CoroutineScope
+import kotlinx.coroutines.SupervisorJob
+import kotlinx.coroutines.asCoroutineDispatcher
+import kotlinx.coroutines.cancel
+import kotlinx.coroutines.channels.Channel
+import kotlinx.coroutines.flow.first
+import kotlinx.coroutines.runBlocking
+import kotlinx.coroutines.withTimeout
+import java.nio.channels.Channels
+import java.nio.channels.Pipe
+import java.util.concurrent.Executors
+import kotlin.test.AfterTest
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertTrue
+
+class LspClientTest {
+    private val toServer = Pipe.open()
+    private val toClient = Pipe.open()
+
+    // One thread, so notifications are handled in the order they were sent
+    private val executor = Executors.newSingleThreadExecutor()
+    private val scope = CoroutineScope(SupervisorJob() + executor.asCoroutineDispatcher())
+
+    private val server = JsonRpcConnection(Channels.newInputStream(toServer.source()), Channels.newOutputStream(toClient.sink()), scope)
+    private val client = LspClient(Channels.newInputStream(toClient.source()), Channels.newOutputStream(toServer.sink()), scope)
+
+    private val changes = Channel<Any?>(Channel.UNLIMITED)
+
+    init {
+        server.onRequest("initialize") {
+            mapOf(
+                "capabilities" to mapOf(
+                    "textDocumentSync" to mapOf("openClose" to true, "change" to 2),
+                    "documentFormattingProvider" to true,
+                    "hoverProvider" to true,
+                    "semanticTokensProvider" to mapOf(
+                        "legend" to mapOf("tokenTypes" to listOf("variable", "parameter"), "tokenModifiers" to listOf("readonly", "static")),
+                        "full" to true
+                    )
+                )
+            )
+        }
+        server.onNotification("textDocument/didChange") { changes.send(it) }
+        server.onRequest("textDocument/hover") {
+            mapOf("contents" to mapOf("kind" to "markdown", "value" to "**a**: Int"))
+        }
+        server.onRequest("textDocument/semanticTokens/full") {
+            // "a" on line 0 as a readonly variable, "b" on line 1 as a static parameter
+            mapOf("data" to listOf(0, 4, 1, 0, 1, 1, 4, 1, 1, 2))
+        }
+        server.onRequest("textDocument/formatting") {
+            listOf(
+                mapOf("range" to CursorRange(CursorPosition(0, 5), CursorPosition(0, 6)).toJson(), "newText" to " = "),
+                mapOf("range" to CursorRange(CursorPosition(0, 0), CursorPosition(0, 0)).toJson(), "newText" to "// f\n")
+            )
+        }
+    }
+
+    @AfterTest
+    fun tearDown() {
+        client.connection.close()
+        server.close()
+        toServer.sink().close()
+        toClient.sink().close()
+        scope.cancel()
+        executor.shutdown()
+    }
+
+    private fun open(text: String): LspDocument = runBlocking {
+        withTimeout(TIMEOUT) { client.initialize("file:///project") }
+        client.open(CodeEditorState(text), "file:///project/a.kt", "kotlin")
+    }
+
+    @Test
+    fun testEditsAreSentAsIncrementalChanges() = runBlocking {
+        val document = open("val a = 1\nval b = a")
+
+        document.state.replace(CursorRange(CursorPosition(0, 8), CursorPosition(1, 3)), "2\nvar")
+        val params = withTimeout(TIMEOUT) { changes.receive() }
+
+        val change = (params.field("contentChanges") as List<*>).single()
+        assertEquals("2\nvar", change.string("text"))
+        assertEquals(CursorRange(CursorPosition(0, 8), CursorPosition(1, 3)), parseRange(change.field("range")))
+        assertEquals(document.state.snapshot.version, (params.field("textDocument").field("version") as Number).toLong())
+    }
+
+    @Test
+    fun testDiagnosticsAreRoutedToTheirDocument() = runBlocking {
+        val document = open("val a = 1")
+        server.notify(
+            "textDocument/publishDiagnostics",
+            mapOf(
+                "uri" to document.uri,
+                "diagnostics" to listOf(
+                    mapOf("range" to CursorRange(CursorPosition(0, 4), CursorPosition(0, 5)).toJson(), "severity" to 2, "message" to "Unused", "code" to 7)
+                )
+            )
+        )
+
+        val diagnostic = withTimeout(TIMEOUT) { document.diagnostics.first { it.isNotEmpty() } }.single()
+        assertEquals(DiagnosticSeverity.WARNING, diagnostic.severity)
+        assertEquals("Unused", diagnostic.message)
+        assertEquals("7", diagnostic.code)
+        assertEquals(listOf(diagnostic), document.state.diagnostics.items.value)
+    }
+
+    @Test
+    fun testDiagnosticsForAnotherVersionAreDropped() = runBlocking {
+        val document = open("val a = 1")
+        fun publish(version: Long, message: String) = server.notify(
+            "textDocument/publishDiagnostics",
+            mapOf(
+                "uri" to document.uri,
+                "version" to version,
+                "diagnostics" to listOf(mapOf("range" to CursorRange(CursorPosition(0, 4), CursorPosition(0, 5)).toJson(), "message" to message))
+            )
+        )
+        val version = document.state.snapshot.version
+        publish(version - 1, "Stale")
+        publish(version, "Current")
+
+        val diagnostic = withTimeout(TIMEOUT) { document.diagnostics.first { it.isNotEmpty() } }.single()
+        assertEquals("Current", diagnostic.message)
+    }
+
+    @Test
+    fun testRequestsSurfaceResultsInEditorTerms() = runBlocking {
+        val document = open("val a = 1\nval b = a")
+
+        assertEquals(Hover("**a**: Int"), withTimeout(TIMEOUT) { document.hover(CursorPosition(1, 8)) })
+
+        withTimeout(TIMEOUT) { document.refreshSemanticTokens() }
+        assertEquals(listOf(SemanticToken(0, 4, 1, "variable", setOf("readonly"))), document.state.semanticTokens.tokensOn(0))
+        assertEquals(listOf(SemanticToken(1, 4, 1, "parameter", setOf("static"))), document.state.semanticTokens.tokensOn(1))
+    }
+
+    @Test
+    fun testFormattingAppliesEveryEditAsOneUndoStep() = runBlocking {
+        val document = open("val a=1")
+
+        assertTrue(withTimeout(TIMEOUT) { document.format() })
+        assertEquals("// f\nval a = 1", document.state.getText())
+
+        document.state.undo()
+        assertEquals("val a=1", document.state.getText())
+    }
+
+    private companion object {
+        const val TIMEOUT = 5_000L
+    }
+}
@@ -0,0 +1,103 @@
+package com.itsvks.code.marker
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import kotlin.random.Random
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertFalse
+import kotlin.test.assertTrue
+
+class MarkerTest {
+    private fun range(line: Int, start: Int, end: Int) = CursorRange(CursorPosition(line, start), CursorPosition(line, end))
+
+    @Test
+    fun testGravityDecidesWhichSideOfInsertedTextAMarkerEndsUp() {
+        val state = CodeEditorState("hello world")
+        val left = state.markers.create(CursorPosition(0, 5), MarkerGravity.LEFT)
+        val right = state.markers.create(CursorPosition(0, 5), MarkerGravity.RIGHT)
+
+        state.insert(CursorPosition(0, 5), ",")
+        assertEquals(CursorPosition(0, 5), left.start)
+        assertEquals(CursorPosition(0, 6), right.start)
+
+        state.insert(CursorPosition(0, 0), "say\n")
+        assertEquals(CursorPosition(1, 5), left.start)
+        assertEquals(CursorPosition(1, 6), right.start)
+    }
+
+    @Test
+    fun testRangeMarkersGrowInsideAndAreDeletedWithTheirText() {
+        val state = CodeEditorState("hello world")
+        val deleted = ArrayList<Marker>()
+        val marker = state.markers.create(range(0, 6, 11)) { deleted += it }
+
+        // Typing at the edges stays outside, typing inside grows it
+        state.insert(CursorPosition(0, 6), "big ")
+        state.insert(CursorPosition(0, 15), "!")
+        assertEquals(range(0, 10, 15), marker.range)
+        state.insert(CursorPosition(0, 12), "-")
+        assertEquals(range(0, 10, 16), marker.range)
+        assertEquals(listOf(marker), state.markers.markersIn(range(0, 0, 10)))
+
+        state.replace(8, 17, "")
+        assertFalse(marker.isValid)
+        assertEquals(listOf(marker), deleted)
+        assertEquals(CursorPosition(0, 8), marker.start)
+        assertTrue(state.markers.markersIn(range(0, 0, 8)).isEmpty())
+    }
+
+    @Test
+    fun testManyMarkersFollowRandomEdits() {
+        val random = Random(7)
+        val state = CodeEditorState("a".repeat(2000))
+        val expected = HashMap<Marker, Int>()
+        repeat(500) {
+            val offset = random.nextInt(state.snapshot.length + 1)
+            expected[state.markers.create(state.snapshot.positionOf(offset))] = offset
+        }
+
+        repeat(300) {
+            val length = state.snapshot.length
+            val start = random.nextInt(length + 1)
+            if (random.nextBoolean()) {
+                val text = "b\n".repeat(random.nextInt(1, 4))
+                state.replace(start, start, text)
+                expected.replaceAll { _, offset -> if (offset >= start) offset + text.length else offset }
+            } else {
+                val end = minOf(length, start + random.nextInt(1, 20))
+                state.replace(start, end, "")
+                // Markers strictly inside deleted text go away
+                expected.entries.removeIf { (_, offset) -> offset in start + 1 until end }
+                expected.replaceAll { _, offset -> if (offset >= end) offset - (end - start) else offset }
+            }
+        }
+
+        assertEquals(expected.size, state.markers.size)
+        for ((marker, offset) in expected) {
+            assertTrue(marker.isValid)
+            assertEquals(offset, marker.startOffset)
+        }
+        val all = state.markers.markersIn(CursorRange(CursorPosition.Zero, state.snapshot.lastPosition))
+        assertEquals(expected.values.sorted(), all.map { it.startOffset })
+    }
+
+    @Test
+    fun testFoldsAreKeptByMarkersUntilTheirFirstLineIsDeleted() {
+        val state = CodeEditorState("fun a() {\n    1\n    2\n}\nfun b() {\n    3\n    4\n}")
+        state.toggleFold(0)
+        state.toggleFold(4)
+        // Fold markers are the editor's own; the host neither sees nor clears them
+        assertEquals(0, state.markers.size)
+        state.markers.clear()
+        state.insert(CursorPosition(0, 9), "\n")
+        assertEquals(setOf(0, 5), state.foldedLines.value)
+
+        // Removing the second header takes its fold with it
+        state.replace(state.snapshot.offsetOf(CursorPosition(4, 1)), state.snapshot.offsetOf(CursorPosition(6, 0)), "")
+        assertEquals(setOf(0), state.foldedLines.value)
+        state.setText("")
+        assertEquals(emptySet(), state.foldedLines.value)
+    }
+}
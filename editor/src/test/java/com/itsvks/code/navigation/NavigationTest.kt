@@ -0,0 +1,79 @@
+package com.itsvks.code.navigation
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.language.KotlinLanguage
+import kotlinx.coroutines.runBlocking
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertFalse
+import kotlin.test.assertTrue
+
+class NavigationTest {
+    private fun range(line: Int, start: Int, end: Int) = CursorRange(CursorPosition(line, start), CursorPosition(line, end))
+
+    private fun definitionAt(location: Location) = object : DefinitionProvider {
+        override suspend fun provideDefinitions(context: NavigationContext) = listOf(location)
+    }
+
+    @Test
+    fun testJumpsToDefinitionsAndBackAndForwardThroughEdits() = runBlocking {
+        val state = CodeEditorState("fun greet() {}\n\ngreet()", KotlinLanguage)
+        state.navigation.definitionProviders = listOf(definitionAt(Location(null, range(0, 4, 9))))
+        assertTrue(state.navigation.isNavigable(CursorPosition(2, 3)))
+        assertFalse(state.navigation.isNavigable(CursorPosition(0, 1)))
+
+        state.setCursor(CursorPosition(2, 2))
+        assertTrue(state.navigation.goToDefinition())
+        assertEquals(range(0, 4, 9), state.selection.value)
+        assertTrue(state.navigation.canGoBack.value)
+
+        // Where the jump came from moves with the text
+        state.insert(CursorPosition(0, 0), "// hi\n")
+        assertTrue(state.navigation.goBack())
+        assertEquals(CursorPosition(3, 2), state.cursor)
+        assertFalse(state.navigation.canGoBack.value)
+
+        assertTrue(state.navigation.goForward())
+        assertEquals(range(1, 4, 9), state.selection.value)
+        assertFalse(state.navigation.canGoForward.value)
+    }
+
+    @Test
+    fun testOtherFilesAreOpenedByTheHost() = runBlocking {
+        val state = CodeEditorState("greet()", KotlinLanguage)
+        val target = Location("/src/Greeter.kt", range(3, 4, 9))
+        state.navigation.definitionProviders = listOf(definitionAt(target))
+        assertFalse(state.navigation.goToDefinition(CursorPosition(0, 1)))
+        assertFalse(state.navigation.canGoBack.value)
+
+        val opened = ArrayList<Location>()
+        state.navigation.onOpenLocation = { opened += it; true }
+        assertTrue(state.navigation.goToDefinition(CursorPosition(0, 1)))
+        assertEquals(listOf(target), opened)
+        assertTrue(state.navigation.canGoBack.value)
+    }
+
+    @Test
+    fun testReferencesOfAllProvidersAreListedInOrder() = runBlocking {
+        val state = CodeEditorState("val a = 1\nprintln(a + a)", KotlinLanguage)
+        state.navigation.referenceProviders = listOf(
+            object : ReferenceProvider {
+                override suspend fun provideReferences(context: NavigationContext, includeDeclaration: Boolean) =
+                    listOf(Location(null, range(1, 12, 13)), Location(null, range(1, 8, 9)))
+            },
+            object : ReferenceProvider {
+                override suspend fun provideReferences(context: NavigationContext, includeDeclaration: Boolean): List<Location> =
+                    error("Server crashed")
+            },
+            object : ReferenceProvider {
+                override suspend fun provideReferences(context: NavigationContext, includeDeclaration: Boolean) =
+                    listOf(Location(null, range(1, 8, 9)), Location(null, range(0, 4, 5)))
+            }
+        )
+        state.navigation.requestReferences(CursorPosition(0, 4))
+        state.navigation.refresh()
+        assertEquals(listOf(range(0, 4, 5), range(1, 8, 9), range(1, 12, 13)), state.navigation.locations.value.map { it.range })
+    }
+}
@@ -0,0 +1,161 @@
+package com.itsvks.code.parser
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.FoldableRange
+import com.itsvks.code.core.Rope
+import com.itsvks.code.core.TextSnapshot
+import com.itsvks.code.language.JsonLanguage
+import com.itsvks.code.language.KotlinLanguage
+import com.itsvks.code.syntax.TokenType
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertNotNull
+import kotlin.test.assertNull
+import kotlin.test.assertSame
+
+class SyntaxTreeTest {
+    private val json = """
+        {
+          "name": "editor",
+          "tags": ["a", "}"],
+          "nested": {
+            "deep": [1, 2]
+          }
+        }
+    """.trimIndent()
+
+    private fun parse(text: String, parser: LanguageParser) = parser.parse(TextSnapshot(Rope.fromString(text), 0))
+
+    @Test
+    fun testJsonNodesFoldAndMatchBracketsOutsideStrings() {
+        val tree = parse(json, JsonParser())
+
+        assertEquals(listOf(FoldableRange(0, 6), FoldableRange(3, 5)), tree.foldableRanges())
+
+        val close = json.indexOf("\"}\"") + 1
+        assertNull(tree.bracketPairAt(close))
+        val open = json.indexOf('[')
+        assertEquals(open to json.indexOf(']'), tree.bracketPairAt(open))
+        assertEquals(0 to json.lastIndexOf('}'), tree.bracketPairAt(json.length))
+    }
+
+    @Test
+    fun testJsonKeysAreAttributes() {
+        val tree = parse(json, JsonParser())
+
+        val tokens = tree.tokensOn(1)
+        assertEquals(listOf(TokenType.ATTRIBUTE, TokenType.PUNCTUATION, TokenType.STRING, TokenType.PUNCTUATION), tokens.map { it.type })
+        assertEquals(2, tokens.first().start)
+    }
+
+    @Test
+    fun testTreeTokensStayOnLinesEditsDidNotTouch() {
+        val state = CodeEditorState(json, JsonLanguage)
+        state.syntax.treeFor(state.snapshot)
+
+        // A new line after the first one; the name is edited
+        state.insert(CursorPosition(0, 1), "\n")
+        state.insert(CursorPosition(2, 10), "s")
+        val tokens = assertNotNull(state.syntax.currentTokens())
+        assertNull(tokens.tokensOn(0))
+        assertNull(tokens.tokensOn(1))
+        assertNull(tokens.tokensOn(2))
+        assertEquals(TokenType.ATTRIBUTE, tokens.tokensOn(3)?.first()?.type)
+        assertEquals(2, tokens.tokensOn(3)?.first()?.start)
+    }
+
+    @Test
+    fun testEditsReuseUntouchedNodes() {
+        val state = CodeEditorState(json, JsonLanguage)
+        val before = assertNotNull(state.syntax.treeFor(state.snapshot))
+        val nested = assertNotNull(before.findNode(json.indexOf("{\n    \"deep\""), JsonParser.OBJECT))
+
+        state.replace(json.indexOf("editor"), json.indexOf("editor") + 6, "code editor")
+        val after = assertNotNull(state.syntax.treeFor(state.snapshot))
+
+        val moved = assertNotNull(after.findNode(nested.start + 5, JsonParser.OBJECT))
+        assertSame(nested.green, moved.green)
+        assertEquals(dump(parse(state.getText(), JsonParser()).root), dump(after.root))
+    }
+
+    @Test
+    fun testMultiLineCommentEditsReLexTheLinesAfterThem() {
+        val text = "fun a() {\n}\n\nfun b() {\n}"
+        val state = CodeEditorState(text, KotlinLanguage)
+        assertNotNull(state.syntax.treeFor(state.snapshot))
+
+        state.replace(text.indexOf("\n\n") + 1, text.indexOf("\n\n") + 1, "/*")
+        val tree = assertNotNull(state.syntax.treeFor(state.snapshot))
+        assertEquals(listOf("a"), tree.outline().map { it.name })
+        assertEquals(dump(parse(state.getText(), CLikeParser(KotlinLanguage)).root), dump(tree.root))
+    }
+
+    @Test
+    fun testSelectionExpandsFromBracketsContentsToTheEnclosingNode() {
+        val tree = parse(json, JsonParser())
+        val digit = json.indexOf('1')
+
+        val contents = assertNotNull(tree.expandSelection(digit, digit + 1))
+        assertEquals("1, 2", json.substring(contents.first, contents.second))
+        val array = assertNotNull(tree.expandSelection(contents.first, contents.second))
+        assertEquals("[1, 2]", json.substring(array.first, array.second))
+        val pair = assertNotNull(tree.expandSelection(array.first, array.second))
+        assertEquals("\"deep\": [1, 2]", json.substring(pair.first, pair.second))
+    }
+
+    @Test
+    fun testOutlinesListDeclarations() {
+        val properties = parse(json, JsonParser()).outline()
+        assertEquals(listOf("name", "tags", "nested"), properties.map { it.name })
+        assertEquals(listOf("deep"), properties[2].children.map { it.name })
+
+        val kotlin = """
+            class Greeter(val name: String) {
+                fun greet() {
+                    listOf(1).forEach { println(it) }
+                }
+            }
+
+            fun main() {
+                Greeter("x").greet()
+            }
+        """.trimIndent()
+        val outline = parse(kotlin, CLikeParser(KotlinLanguage)).outline()
+        assertEquals(listOf("Greeter", "main"), outline.map { it.name })
+        assertEquals(listOf(OutlineKind.CLASS, OutlineKind.FUNCTION), outline.map { it.kind })
+        assertEquals(listOf("greet"), outline[0].children.map { it.name })
+        assertEquals(CursorPosition(1, 8), outline[0].children[0].selectionRange.start)
+    }
+
+    @Test
+    fun testStateMatchesBracketsThroughTheTree() {
+        val state = CodeEditorState("val s = \"(\"\nfoo(bar)", KotlinLanguage)
+        state.syntax.treeFor(state.snapshot)
+
+        assertEquals(emptyList<CursorPosition>(), state.findMatchingBrackets(CursorPosition(0, 9)))
+        assertEquals(listOf(CursorPosition(1, 3), CursorPosition(1, 7)), state.findMatchingBrackets(CursorPosition(1, 8)))
+    }
+
+    @Test
+    fun testStateExpandsAndShrinksTheSelection() {
+        val state = CodeEditorState("foo(bar, baz)", KotlinLanguage)
+        state.setCursor(CursorPosition(0, 5))
+
+        state.expandSelection()
+        assertEquals("bar", state.selectedText)
+        state.expandSelection()
+        assertEquals("bar, baz", state.selectedText)
+        state.expandSelection()
+        assertEquals("(bar, baz)", state.selectedText)
+
+        state.shrinkSelection()
+        state.shrinkSelection()
+        assertEquals("bar", state.selectedText)
+    }
+
+    private fun dump(node: SyntaxNode): String {
+        if (node.childCount == 0) return "${node.kind}@${node.start}"
+        return node.children.joinToString(" ", "${node.kind}@${node.start}(", ")") { dump(it) }
+    }
+}
@@ -0,0 +1,78 @@
+package com.itsvks.code.search
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import kotlinx.coroutines.runBlocking
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertNotNull
+import kotlin.test.assertTrue
+
+class EditorSearchTest {
+    private fun search(text: String, query: SearchQuery): CodeEditorState {
+        val state = CodeEditorState(text)
+        state.search.setQuery(query)
+        runBlocking { state.search.refresh() }
+        return state
+    }
+
+    @Test
+    fun testPlainSearchIsCaseInsensitiveByDefault() {
+        val state = search("Foo foo\nfOO", SearchQuery("foo"))
+        val matches = state.search.matches.value
+        assertEquals(3, matches.size)
+        assertEquals(CursorRange(CursorPosition(1, 0), CursorPosition(1, 3)), matches[2].range)
+        // The first match is selected once the search finishes
+        assertEquals(0, state.search.currentIndex.value)
+        assertEquals(matches[0].range, state.selection.value)
+
+        assertEquals(1, search("Foo foo", SearchQuery("foo", caseSensitive = true)).search.matches.value.size)
+    }
+
+    @Test
+    fun testWholeWordAndRegex() {
+        assertEquals(1, search("val value = val_", SearchQuery("val", wholeWord = true)).search.matches.value.size)
+        assertEquals(2, search("a1 b22 c", SearchQuery("\\d+", isRegex = true)).search.matches.value.size)
+        // Special chars are literal unless searching by regex
+        assertEquals(1, search("a.b axb", SearchQuery("a.b")).search.matches.value.size)
+    }
+
+    @Test
+    fun testInvalidRegexReportsError() {
+        val state = search("text", SearchQuery("(", isRegex = true))
+        assertNotNull(state.search.error.value)
+        assertTrue(state.search.matches.value.isEmpty())
+    }
+
+    @Test
+    fun testFindNextWrapsAround() = runBlocking {
+        val state = search("x x x", SearchQuery("x"))
+        state.search.findNext()
+        state.search.findNext()
+        assertEquals(2, state.search.currentIndex.value)
+        state.search.findNext()
+        assertEquals(0, state.search.currentIndex.value)
+        state.search.findPrevious()
+        assertEquals(CursorRange(CursorPosition(0, 4), CursorPosition(0, 5)), state.selection.value)
+    }
+
+    @Test
+    fun testReplaceAllIsOneUndoableEdit() = runBlocking {
+        val state = search("let a = 1;\nlet b = 2;", SearchQuery("let (\\w) = (\\d)", isRegex = true))
+        assertEquals(2, state.search.replaceAll("const $1 = \${2}0"))
+        assertEquals("const a = 10;\nconst b = 20;", state.getText())
+
+        state.undo()
+        assertEquals("let a = 1;\nlet b = 2;", state.getText())
+    }
+
+    @Test
+    fun testReplaceCurrentSearchesAgainAfterEdits() = runBlocking {
+        val state = search("cat cat cat", SearchQuery("cat"))
+        assertTrue(state.search.replaceCurrent("dog"))
+        // The matches are stale now; replacing again must not use the old ranges
+        assertTrue(state.search.replaceCurrent("dog"))
+        assertEquals("dog dog cat", state.getText())
+    }
+}
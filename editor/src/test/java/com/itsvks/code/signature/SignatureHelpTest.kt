@@ -0,0 +1,66 @@
+package com.itsvks.code.signature
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import kotlinx.coroutines.runBlocking
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertNull
+
+class SignatureHelpTest {
+    // Knows `max(a, b)`; the parameter is the number of commas since the open parenthesis
+    private val provider = object : SignatureHelpProvider {
+        override suspend fun provideSignatureHelp(context: SignatureHelpContext): SignatureHelp? {
+            val line = context.snapshot.getLine(context.position.line).take(context.position.column)
+            val open = line.lastIndexOf("max(")
+            if (open < 0 || ')' in line.substring(open)) return null
+            val signature = SignatureInformation(
+                "max(a: Int, b: Int): Int",
+                parameters = listOf(ParameterInformation("a: Int"), ParameterInformation("b: Int"))
+            )
+            return SignatureHelp(listOf(signature), activeParameter = line.substring(open).count { it == ',' })
+        }
+    }
+
+    @Test
+    fun testParametersAreFoundInOrderWithinTheLabel() {
+        val signature = SignatureInformation(
+            "pair(x: Int, x: Int)",
+            parameters = listOf(ParameterInformation("x: Int"), ParameterInformation("x: Int"), ParameterInformation("y"))
+        )
+        assertEquals(listOf(5..10, 13..18, null), signature.parameterRanges())
+    }
+
+    @Test
+    fun testTypingACallShowsItsSignatureAndFollowsTheArguments() = runBlocking {
+        val state = CodeEditorState("")
+        state.signatureHelp.providers = listOf(provider)
+        state.typeText("max")
+        state.typeText("(")
+        state.signatureHelp.refresh()
+        assertEquals(0, state.signatureHelp.help.value?.activeParameterIndex)
+
+        state.typeText("1")
+        state.typeText(",")
+        state.signatureHelp.refresh()
+        assertEquals(1, state.signatureHelp.help.value?.activeParameterIndex)
+
+        // Moving the caret without typing closes it
+        state.setCursor(CursorPosition(0, 0))
+        state.signatureHelp.refresh()
+        assertNull(state.signatureHelp.help.value)
+    }
+
+    @Test
+    fun testLeavingTheCallClosesSignatureHelp() = runBlocking {
+        val state = CodeEditorState("")
+        state.signatureHelp.providers = listOf(provider)
+        state.typeText("max")
+        state.typeText("(")
+        state.signatureHelp.refresh()
+        state.setCursor(CursorPosition(0, 5))
+        state.typeText(" ")
+        state.signatureHelp.refresh()
+        assertNull(state.signatureHelp.request.value)
+    }
+}
@@ -0,0 +1,112 @@
+package com.itsvks.code.snippet
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.completion.CompletionItemKind
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.core.CursorRange
+import com.itsvks.code.language.KotlinLanguage
+import kotlinx.coroutines.runBlocking
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertFalse
+import kotlin.test.assertTrue
+
+class SnippetTest {
+    @Test
+    fun testExpandsTabStopsAndIndentsFollowingLines() {
+        val expanded = Snippet.parse("for (\${1:i} in \${2:items}) {\n\t\$0\n}").expand(indent = "  ")
+        assertEquals("for (i in items) {\n      \n  }", expanded.text)
+        assertEquals(
+            listOf(
+                SnippetTabStop(1, listOf(SnippetRange(5, 6))),
+                SnippetTabStop(2, listOf(SnippetRange(10, 15))),
+                SnippetTabStop(0, listOf(SnippetRange(25, 25)))
+            ),
+            expanded.tabStops
+        )
+    }
+
+    @Test
+    fun testMirrorsChoicesVariablesAndEscapes() {
+        val variables = mapOf("TM_LINE_NUMBER" to "3", "TM_FILENAME" to "Main.kt", "NAME" to "abc")
+        val expanded = Snippet.parse("\$1 = \${1:name}; \${2|a,b\\,c|} \$TM_LINE_NUMBER \${FOO:dflt} \\\$ \$UNKNOWN \$")
+            .expand(variables::get)
+        assertEquals("name = name; a 3 dflt \$ UNKNOWN \$", expanded.text)
+        // The defining placeholder comes first
+        assertEquals(listOf(SnippetRange(7, 11), SnippetRange(0, 4)), expanded.tabStops[0].ranges)
+        assertEquals(listOf("a", "b,c"), expanded.tabStops[1].choices)
+
+        val transformed = Snippet.parse("\${TM_FILENAME/(.*)\\..+\$/\$1/} \${NAME/(.*)/\${1:/upcase}/}").expand(variables::get)
+        assertEquals("Main ABC", transformed.text)
+    }
+
+    @Test
+    fun testTabMovesBetweenStopsAndTypingEditsMirrors() {
+        val state = CodeEditorState("fun main() {\n    \n}")
+        state.setCursor(CursorPosition(1, 4))
+        state.snippets.insert("for (\${1:i} in \${2:items}) {\n\t\$0\n}")
+        assertEquals("    for (i in items) {", state.getLine(1))
+        assertEquals("    }", state.getLine(3))
+        assertEquals(CursorRange(CursorPosition(1, 9), CursorPosition(1, 10)), state.selection.value)
+
+        state.typeText("x")
+        assertTrue(state.snippets.nextTabStop())
+        assertEquals(CursorRange(CursorPosition(1, 14), CursorPosition(1, 19)), state.selection.value)
+        assertTrue(state.snippets.previousTabStop())
+        assertEquals("x", state.selectedText)
+
+        assertTrue(state.snippets.nextTabStop())
+        assertTrue(state.snippets.nextTabStop())
+        assertEquals(CursorRange.collapsed(CursorPosition(2, 8)), state.selection.value)
+        assertFalse(state.snippets.isActive.value)
+        assertFalse(state.snippets.nextTabStop())
+
+        val mirrored = CodeEditorState("\n")
+        mirrored.snippets.insert("\${1:a} + \$1")
+        mirrored.typeText("bc")
+        assertEquals("bc + bc\n", mirrored.getText())
+        assertTrue(mirrored.snippets.isActive.value)
+        // Editing elsewhere ends the snippet
+        mirrored.insert(CursorPosition(1, 0), "z")
+        assertFalse(mirrored.snippets.isActive.value)
+    }
+
+    @Test
+    fun testChoicesAreOfferedInCompletion() {
+        val state = CodeEditorState("")
+        state.snippets.insert("\${1|one,two|}")
+        assertEquals(listOf("one", "two"), state.completion.items.value.map { it.label })
+        assertTrue(state.completion.accept(1))
+        assertEquals("two", state.getText())
+    }
+
+    @Test
+    fun testSnippetsLoadFromJsonAndCompleteByPrefix() = runBlocking {
+        val collection = SnippetCollection.fromJson(
+            """
+            {
+              // Comments and trailing commas are allowed
+              "For loop": { "prefix": ["for", "fori"], "body": ["for (${'$'}{1:i} in ${'$'}2) {", "\t${'$'}0", "}"], "description": "Loop" },
+              "No prefix": { "body": "x" },
+            }
+            """
+        )
+        assertEquals(listOf("For loop"), collection.definitions.map { it.name })
+        assertEquals(listOf("for", "fori"), collection.definitions[0].prefixes)
+
+        SnippetRegistry.register(KotlinLanguage, collection)
+        try {
+            val state = CodeEditorState("", KotlinLanguage)
+            state.typeText("f")
+            state.typeText("o")
+            state.typeText("r")
+            state.completion.refresh()
+            val index = state.completion.items.value.indexOfFirst { it.label == "for" && it.kind == CompletionItemKind.SNIPPET }
+            assertTrue(state.completion.accept(index))
+            assertEquals("for (i in ) {\n    \n}", state.getText())
+            assertEquals("i", state.selectedText)
+        } finally {
+            SnippetRegistry.unregisterAll(KotlinLanguage)
+        }
+    }
+}
@@ -0,0 +1,88 @@
+package com.itsvks.code.syntax
+
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.language.KotlinLanguage
+import com.itsvks.code.language.PythonLanguage
+import com.itsvks.code.language.RustLanguage
+import kotlinx.coroutines.runBlocking
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertNotNull
+import kotlin.test.assertNull
+import kotlin.test.assertTrue
+
+class DocumentHighlighterTest {
+    private fun CodeEditorState.tokensOn(line: Int): List<Token> {
+        runBlocking { highlighting.highlight(snapshot, 0 until lineCount) }
+        return assertNotNull(highlighting.tokensFor(line))
+    }
+
+    private fun CodeEditorState.typesOn(line: Int) = tokensOn(line).map { it.type }.toSet()
+
+    @Test
+    fun testBlockCommentSpansLines() {
+        val state = CodeEditorState("val a = 1 /* start\nstill comment\nend */ val b", KotlinLanguage)
+        assertEquals(setOf(TokenType.COMMENT), state.typesOn(1))
+        assertTrue(TokenType.COMMENT in state.typesOn(0))
+        assertTrue(TokenType.COMMENT in state.typesOn(2))
+        assertTrue(TokenType.COMMENT !in state.tokensOn(2).filter { it.start >= 6 }.map { it.type })
+    }
+
+    @Test
+    fun testMultiLineStrings() {
+        val python = CodeEditorState("x = \"\"\"doc\nmore doc\n\"\"\"\ny = 1", PythonLanguage)
+        assertEquals(setOf(TokenType.STRING), python.typesOn(1))
+        assertTrue(TokenType.STRING !in python.typesOn(3))
+
+        val rust = CodeEditorState("let s = r#\"raw \"quoted\"\nline\"#;\nlet t = 1;", RustLanguage)
+        assertEquals(setOf(TokenType.STRING), rust.typesOn(1).minus(TokenType.PUNCTUATION))
+        assertTrue(TokenType.STRING !in rust.typesOn(2))
+    }
+
+    @Test
+    fun testEditsRelexFollowingLines() {
+        val state = CodeEditorState("a\nb\nc", KotlinLanguage)
+        assertTrue(TokenType.COMMENT !in state.typesOn(2))
+
+        state.insert(CursorPosition(0, 0), "/* ")
+        assertEquals(setOf(TokenType.COMMENT), state.typesOn(2))
+
+        state.undo()
+        assertTrue(TokenType.COMMENT !in state.typesOn(2))
+    }
+
+    @Test
+    fun testOnlyPriorityLinesKeepTokens() {
+        val state = CodeEditorState(List(1000) { "val x$it = $it" }, KotlinLanguage)
+        runBlocking { state.highlighting.highlight(state.snapshot, 10..20) }
+        assertNotNull(state.highlighting.tokensFor(15))
+        assertNull(state.highlighting.tokensFor(500))
+        // States are known for the whole document all the same
+        assertNotNull(state.highlighting.stateAfter(999))
+    }
+
+    @Test
+    fun testRelexStopsOnceStateConverges() {
+        var lexed = 0
+        val delegate = LanguageBasedSyntaxHighlighter(KotlinLanguage)
+        val counting = object : SyntaxHighlighter {
+            override fun highlight(text: String) = delegate.highlight(text)
+
+            override fun highlightLine(text: String, state: LexerState): LineHighlight {
+                lexed++
+                return delegate.highlightLine(text, state)
+            }
+        }
+        val state = CodeEditorState(List(100) { "val x$it = $it" })
+        state.highlighting.reset(counting, state.snapshot)
+        state.tokensOn(99)
+        assertEquals(100, lexed)
+
+        lexed = 0
+        state.insert(CursorPosition(10, 0), "// ")
+        state.tokensOn(99)
+        // Only the edited line and the one after it, which ends in the same state as before
+        assertEquals(2, lexed)
+    }
+}
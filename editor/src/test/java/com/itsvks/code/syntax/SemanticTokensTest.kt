@@ -0,0 +1,55 @@
+package com.itsvks.code.syntax
+
+import androidx.compose.ui.text.style.TextDecoration
+import com.itsvks.code.CodeEditorState
+import com.itsvks.code.core.CursorPosition
+import com.itsvks.code.language.KotlinLanguage
+import com.itsvks.code.theme.VsCodeDarkTheme
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertFalse
+import kotlin.test.assertNull
+import kotlin.test.assertTrue
+
+class SemanticTokensTest {
+    private val text = "fun f(a: Int) {\n    val b = a\n    return b\n}"
+
+    @Test
+    fun testTokensForAnOlderVersionMoveWithTheEditsSince() {
+        val state = CodeEditorState(text, KotlinLanguage)
+        val version = state.snapshot.version
+        val tokens = listOf(
+            SemanticToken(0, 6, 1, SemanticTokenTypes.PARAMETER),
+            SemanticToken(1, 12, 1, SemanticTokenTypes.PARAMETER),
+            SemanticToken(2, 11, 1, SemanticTokenTypes.LOCAL, setOf(SemanticTokenModifiers.MUTABLE))
+        )
+
+        state.insert(CursorPosition(1, 0), "    // note\n")
+        assertTrue(state.semanticTokens.update(version, tokens))
+
+        assertEquals(listOf(tokens[0]), state.semanticTokens.tokensOn(0))
+        // The edited line lost its tokens, the line after it moved down
+        assertEquals(emptyList(), state.semanticTokens.tokensOn(1))
+        assertEquals(listOf(tokens[2].copy(line = 3)), state.semanticTokens.tokensOn(3))
+    }
+
+    @Test
+    fun testTokensForANewerVersionAreRejected() {
+        val state = CodeEditorState(text, KotlinLanguage)
+        assertFalse(state.semanticTokens.update(state.snapshot.version + 1, emptyList()))
+
+        state.setText("other")
+        assertFalse(state.semanticTokens.update(0, listOf(SemanticToken(0, 0, 5, SemanticTokenTypes.VARIABLE))))
+        assertEquals(emptyList(), state.semanticTokens.tokensOn(0))
+    }
+
+    @Test
+    fun testDefaultStylesFollowTheLexicalTheme() {
+        val function = VsCodeDarkTheme.getStyleForSemanticToken(SemanticTokenTypes.METHOD, emptySet())
+        assertEquals(VsCodeDarkTheme.getStyleForToken(TokenType.FUNCTION).color, function?.color)
+
+        val deprecated = VsCodeDarkTheme.getStyleForSemanticToken(SemanticTokenTypes.VARIABLE, setOf(SemanticTokenModifiers.DEPRECATED))
+        assertEquals(TextDecoration.LineThrough, deprecated?.textDecoration)
+        assertNull(VsCodeDarkTheme.getStyleForSemanticToken(SemanticTokenTypes.VARIABLE, emptySet()))
+    }
+}
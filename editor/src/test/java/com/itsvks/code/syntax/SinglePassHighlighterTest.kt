@@ -0,0 +1,124 @@
+package com.itsvks.code.syntax
+
+import com.itsvks.code.language.JavaLanguage
+import com.itsvks.code.language.KotlinLanguage
+import com.itsvks.code.language.Language
+import kotlin.test.Test
+import kotlin.test.assertContentEquals
+import kotlin.test.assertTrue
+
+/**
+ * The overlapping-regex highlighter the single-pass one replaced, kept to check that both agree.
+ * As shipped it joined keywords and types with "\b", a backspace, so none ever matched; [fixedWordBoundaries]
+ * uses real word boundaries instead.
+ */
+private class LegacyHighlighter(private val language: Language, fixedWordBoundaries: Boolean = true) {
+    private val boundary = if (fixedWordBoundaries) "\\b" else "\b"
+    private val keywordPattern = language.keywords.joinToString("|", "$boundary(?:", ")$boundary").toRegex()
+    private val typePattern = language.types.joinToString("|", "$boundary(?:", ")$boundary").toRegex()
+    private val operatorPattern = language.operators.joinToString("|") { Regex.escape(it) }.toRegex()
+    private val punctuationPattern = language.punctuation.joinToString("|") { Regex.escape(it) }.toRegex()
+
+    fun highlight(text: String): List<Token> {
+        val tokens = mutableListOf<Token>()
+        language.commentPatterns.forEach { add(text, it, TokenType.COMMENT, tokens) }
+        language.stringPatterns.forEach { add(text, it, TokenType.STRING, tokens) }
+        add(text, language.numberPattern, TokenType.NUMBER, tokens)
+        language.functionPattern?.findAll(text)?.forEach { result ->
+            if (tokens.none { result.range.first in it.range }) {
+                tokens.add(Token(TokenType.FUNCTION, result.range.first, result.range.first + result.groupValues[1].length))
+            }
+        }
+        language.annotationPattern?.let { add(text, it, TokenType.ANNOTATION, tokens) }
+        language.macroPattern?.let { add(text, it, TokenType.MACRO, tokens) }
+        language.attributePattern?.let { add(text, it, TokenType.ATTRIBUTE, tokens) }
+        language.constPattern?.let { add(text, it, TokenType.CONST, tokens) }
+        language.staticPattern?.let { add(text, it, TokenType.STATIC, tokens) }
+        add(text, keywordPattern, TokenType.KEYWORD, tokens)
+        add(text, typePattern, TokenType.TYPE, tokens)
+        add(text, operatorPattern, TokenType.OPERATOR, tokens)
+        add(text, punctuationPattern, TokenType.PUNCTUATION, tokens)
+        add(text, "\\b[a-zA-Z_][a-zA-Z0-9_]*\\b".toRegex(), TokenType.IDENTIFIER, tokens)
+        return tokens.sortedBy { it.start }
+    }
+
+    private fun add(text: String, pattern: Regex, type: TokenType, tokens: MutableList<Token>) {
+        for (result in pattern.findAll(text)) {
+            if (tokens.none { result.range.first in it.range }) tokens.add(Token(type, result.range))
+        }
+    }
+}
+
+class SinglePassHighlighterTest {
+    private val kotlinLines = listOf(
+        "package com.example.app",
+        "import kotlin.math.max",
+        "@Suppress(\"unused\")",
+        "class Counter(private val start: Int) {",
+        "    private var count = start * 2 + 0x1F",
+        "    fun increment(by: Int): Int { count += by; return max(count, MAX_COUNT) } // bump",
+        "    val label: String get() = \"count is \" + count.toString()",
+        "    /* inline */ val ratio = 1.5e3 / count",
+        "}"
+    )
+
+    private val javaLines = listOf(
+        "public final class Main {",
+        "    private static final int LIMIT = 100;",
+        "    @Override public String toString() { return \"Main\" + LIMIT; }",
+        "    public static void main(String[] args) { System.out.println(args.length); }",
+        "}"
+    )
+
+    // Type of every char, where text no token covers counts as plain
+    private fun charTypes(text: String, tokens: List<Token>): List<TokenType> {
+        val types = MutableList(text.length) { TokenType.PLAIN }
+        for (token in tokens.asReversed()) {
+            for (i in token.start until token.end.coerceAtMost(text.length)) types[i] = token.type
+        }
+        return types
+    }
+
+    private fun assertSameAsLegacy(language: Language, lines: List<String>) {
+        val legacy = LegacyHighlighter(language)
+        val highlighter = LanguageBasedSyntaxHighlighter(language)
+        for (line in lines) {
+            assertContentEquals(charTypes(line, legacy.highlight(line)), charTypes(line, highlighter.highlight(line)), line)
+        }
+    }
+
+    @Test
+    fun testMatchesLegacyOutput() {
+        assertSameAsLegacy(KotlinLanguage, kotlinLines)
+        assertSameAsLegacy(JavaLanguage, javaLines)
+    }
+
+    @Test
+    fun testDiffersFromShippedOnlyInKeywordsAndTypes() {
+        val shipped = LegacyHighlighter(KotlinLanguage, fixedWordBoundaries = false)
+        val highlighter = LanguageBasedSyntaxHighlighter(KotlinLanguage)
+        for (line in kotlinLines) {
+            val before = charTypes(line, shipped.highlight(line))
+            val after = charTypes(line, highlighter.highlight(line))
+            for (i in line.indices) {
+                val fixed = before[i] == TokenType.IDENTIFIER && after[i] in setOf(TokenType.KEYWORD, TokenType.TYPE)
+                assertTrue(before[i] == after[i] || fixed, "'$line' at $i: ${before[i]} became ${after[i]}")
+            }
+        }
+    }
+
+    @Test
+    fun testTokensNeverOverlap() {
+        val highlighter = LanguageBasedSyntaxHighlighter(KotlinLanguage)
+        // The legacy highlighter let the string overlap the comment found inside it
+        val tokens = highlighter.highlight("val url = \"http://example.com\" // link")
+        tokens.zipWithNext().forEach { (a, b) -> assertTrue(a.end <= b.start, "$a overlaps $b") }
+        assertContentEquals(listOf(TokenType.STRING), tokens.filter { it.text == "\"http://example.com\"" }.map { it.type })
+    }
+
+    @Test
+    fun testMinifiedLineMatchesLegacyOutput() {
+        val line = "val x = max(count, MAX_COUNT) + 1; ".repeat(400)
+        assertSameAsLegacy(KotlinLanguage, listOf(line))
+    }
+}
@@ -0,0 +1,112 @@
+package com.itsvks.code.syntax.textmate
+
+import com.itsvks.code.language.TextMateLanguage
+import com.itsvks.code.syntax.LexerState
+import com.itsvks.code.syntax.SyntaxHighlighterFactory
+import com.itsvks.code.syntax.Token
+import com.itsvks.code.syntax.TokenType
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertIs
+import kotlin.test.assertNotEquals
+
+class TextMateHighlighterTest {
+    private val grammar = TextMateGrammar.fromJson(
+        """
+        {
+          "scopeName": "source.demo",
+          "name": "Demo",
+          "fileTypes": ["demo"],
+          "patterns": [
+            { "include": "#comments" },
+            { "include": "#heredoc" },
+            {
+              "match": "\\b(fun)\\s+(\\w+)",
+              "captures": { "1": { "name": "storage.type.function" }, "2": { "name": "entity.name.function" } }
+            },
+            { "match": "\\b\\d+\\b", "name": "constant.numeric.demo" }
+          ],
+          "repository": {
+            "comments": {
+              "patterns": [
+                { "begin": "/\\*", "end": "\\*/", "name": "comment.block.demo" },
+                { "begin": "//", "end": "$", "name": "comment.line.demo" }
+              ]
+            },
+            "heredoc": {
+              "begin": "<<(\\w+)$",
+              "end": "^\\1$",
+              "name": "string.unquoted.heredoc.demo"
+            }
+          },
+          "injections": {
+            "L:comment": { "patterns": [{ "match": "TODO", "name": "keyword.todo" }] }
+          }
+        }
+        """.trimIndent()
+    )
+
+    private val highlighter = TextMateHighlighter(grammar)
+
+    private fun Token.typeOf(text: String) = if (this.text == text) type else null
+
+    private fun typeOf(tokens: List<Token>, text: String) = tokens.firstNotNullOf { it.typeOf(text) }
+
+    private fun highlightLines(vararg lines: String): List<List<Token>> {
+        var state: LexerState = highlighter.initialState
+        return lines.map { line ->
+            val result = highlighter.highlightLine(line, state)
+            state = result.endState
+            result.tokens
+        }
+    }
+
+    @Test
+    fun testCapturesScopeParts() {
+        val tokens = highlighter.highlight("fun main 42")
+        assertEquals(TokenType.KEYWORD, typeOf(tokens, "fun"))
+        assertEquals(TokenType.FUNCTION, typeOf(tokens, "main"))
+        assertEquals(TokenType.NUMBER, typeOf(tokens, "42"))
+    }
+
+    @Test
+    fun testRegionSpansLines() {
+        val (first, second, third) = highlightLines("1 /* open", "still", "*/ 2")
+        assertEquals(TokenType.COMMENT, typeOf(first, "/* open"))
+        assertEquals(listOf(TokenType.COMMENT), second.map { it.type })
+        assertEquals(TokenType.NUMBER, typeOf(third, "2"))
+    }
+
+    @Test
+    fun testEndRefersToBeginCaptures() {
+        val lines = highlightLines("<<END", "EOF 1", "END", "3")
+        assertEquals(listOf(TokenType.STRING), lines[1].map { it.type })
+        assertEquals(listOf(TokenType.STRING), lines[2].map { it.type })
+        assertEquals(TokenType.NUMBER, typeOf(lines[3], "3"))
+    }
+
+    @Test
+    fun testInjectionIntoMatchingScopes() {
+        val tokens = highlighter.highlight("// TODO later")
+        assertEquals(TokenType.KEYWORD, typeOf(tokens, "TODO"))
+        assertEquals(TokenType.COMMENT, tokens.first().type)
+        // Outside comments the injection doesn't apply
+        assertNotEquals(TokenType.KEYWORD, highlighter.highlight("TODO").first().type)
+    }
+
+    @Test
+    fun testOnigurumaSyntaxIsTranslated() {
+        assertEquals(true, Oniguruma.compile("^\\h{,4}$").matcher("beef").matches())
+        assertEquals(true, Oniguruma.compile("[[:alpha:]]+").matcher("abc").matches())
+        // Unsupported patterns never match rather than failing the grammar
+        assertEquals(false, Oniguruma.compile("(?~abc)").matcher("x").find())
+    }
+
+    @Test
+    fun testRegisteredLanguageUsesGrammar() {
+        val json = """{ "scopeName": "source.other", "name": "Other", "fileTypes": ["oth"], "patterns": [] }"""
+        val language = TextMateLanguage.register(json.byteInputStream())
+        assertEquals("Other", language.name)
+        assertIs<TextMateHighlighter>(SyntaxHighlighterFactory.createHighlighterForFile("main.oth"))
+    }
+}
@@ -0,0 +1,89 @@
+package com.itsvks.code.theme
+
+import androidx.compose.ui.graphics.Color
+import androidx.compose.ui.text.font.FontStyle
+import androidx.compose.ui.text.font.FontWeight
+import androidx.compose.ui.text.style.TextDecoration
+import com.itsvks.code.syntax.TokenType
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertFalse
+import kotlin.test.assertNull
+
+class VsCodeThemeTest {
+    private val base = """
+        {
+          "name": "Base",
+          "type": "light",
+          "colors": { "editor.background": "#ffffff", "editor.foreground": "#333" },
+          "tokenColors": [
+            { "scope": "comment", "settings": { "foreground": "#008000", "fontStyle": "italic" } }
+          ]
+        }
+    """.trimIndent()
+
+    private val theme = VsCodeTheme.fromJson(
+        """
+        {
+          // Comments and trailing commas, like VS Code's own theme files
+          "name": "Sample",
+          "include": "./base.json",
+          "colors": {
+            "editorCursor.foreground": "#ff000080",
+            "editorLineNumber.foreground": "#858585",
+          },
+          "tokenColors": [
+            { "scope": ["keyword", "storage"], "settings": { "foreground": "#0000FF" } },
+            { "scope": "keyword.operator, punctuation", "settings": { "foreground": "#000000" } },
+            { "scope": "keyword.control", "settings": { "fontStyle": "bold" } },
+            { "scope": "meta.embedded string", "settings": { "foreground": "#123456" } },
+            { "scope": "variable.parameter", "settings": { "foreground": "#00FF00" } },
+          ],
+          "semanticTokenColors": {
+            "property.readonly": "#aa0000",
+            "*.deprecated": { "strikethrough": true },
+            "property:java": "#bbbbbb",
+          },
+        }
+        """.trimIndent()
+    ) { include -> base.takeIf { include == "./base.json" } }
+
+    @Test
+    fun testColorsMapOntoTheEditor() {
+        assertEquals("Sample", theme.name)
+        assertFalse(theme.isDark)
+        assertEquals(Color(0xFFFFFFFF), theme.backgroundColor)
+        assertEquals(Color(0xFF333333), theme.defaultTextColor)
+        assertEquals(Color(0x80FF0000), theme.cursorColor)
+        assertEquals(Color(0xFF858585), theme.gutterTextColor)
+        assertEquals(theme.backgroundColor, theme.gutterBgColor)
+        assertEquals(AtomOneLightTheme.selectionColor, theme.selectionColor)
+    }
+
+    @Test
+    fun testTokenColorsComeFromTheMostSpecificRule() {
+        val keyword = theme.getStyleForToken(TokenType.KEYWORD)
+        assertEquals(Color(0xFF0000FF), keyword.color)
+        assertEquals(FontWeight.Bold, keyword.fontWeight)
+
+        assertEquals(Color(0xFF000000), theme.getStyleForToken(TokenType.OPERATOR).color)
+        assertEquals(FontStyle.Italic, theme.getStyleForToken(TokenType.COMMENT).fontStyle)
+        assertEquals(theme.defaultTextColor, theme.getStyleForToken(TokenType.STRING).color)
+        assertEquals(Color(0xFF0000FF), theme.getStyleForScope("storage.modifier.kotlin").color)
+    }
+
+    @Test
+    fun testSemanticTokensUseTheirOwnRulesBeforeScopes() {
+        assertEquals(Color(0xFFAA0000), theme.getStyleForSemanticToken("property", setOf("readonly", "static"))?.color)
+        assertEquals(TextDecoration.LineThrough, theme.getStyleForSemanticToken("function", setOf("deprecated"))?.textDecoration)
+        assertEquals(Color(0xFF00FF00), theme.getStyleForSemanticToken("parameter", emptySet())?.color)
+        assertEquals(theme.getStyleForToken(TokenType.STATIC).color, theme.getStyleForSemanticToken("property", emptySet())?.color)
+    }
+
+    @Test
+    fun testMalformedColorsAreIgnored() {
+        assertNull(VsCodeTheme.parseColor("red"))
+        assertNull(VsCodeTheme.parseColor("#12345"))
+        assertEquals(Color(0xFFAABBCC), VsCodeTheme.parseColor("#abc"))
+    }
+}